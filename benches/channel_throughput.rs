@@ -0,0 +1,40 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use valence::view::ChunkPos;
+use valence_playground::minecraft::world_gen::chunk_worker::WorkerMessage;
+
+fn positions(count: i32) -> Vec<ChunkPos> { (0..count).map(|i| ChunkPos::new(i, i)).collect() }
+
+pub fn channel_throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Channel Throughput");
+
+    for i in [64, 256, 1024, 4096].iter() {
+        group.bench_with_input(BenchmarkId::new("single messages", i), i, |b, i| {
+            let (sender, receiver) = flume::unbounded();
+            b.iter(|| {
+                for pos in positions(*i) {
+                    let _ = sender.try_send(WorkerMessage::Chunk(pos));
+                }
+                for msg in receiver.drain() {
+                    black_box(msg);
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("batch message", i), i, |b, i| {
+            let (sender, receiver) = flume::unbounded();
+            b.iter(|| {
+                let _ = sender.try_send(WorkerMessage::BatchChunks(positions(*i)));
+                for msg in receiver.drain() {
+                    if let WorkerMessage::BatchChunks(batch) = msg {
+                        for pos in batch {
+                            black_box(pos);
+                        }
+                    }
+                }
+            })
+        });
+    }
+    group.finish()
+}
+
+criterion_group!(benches, channel_throughput);
+criterion_main!(benches);
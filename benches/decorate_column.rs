@@ -0,0 +1,56 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use valence::{prelude::Chunk, view::ChunkPos};
+use valence_playground::minecraft::world_gen::chunk_worker::{
+    decorate_column, gen_chunk, ChunkWorkerState, TerrainSettings,
+};
+
+/// Heights of the topmost block of every terrain layer in a column.
+fn surfaces(chunk: &Chunk, offset_x: usize, offset_z: usize) -> Vec<usize> {
+    let height = chunk.section_count() * 16;
+
+    (0..height - 1)
+        .rev()
+        .filter(|y| {
+            let above = chunk.block_state(offset_x, y + 1, offset_z);
+            let block = chunk.block_state(offset_x, *y, offset_z);
+            (above.is_air() || above.is_liquid()) && !(block.is_air() || block.is_liquid())
+        })
+        .collect()
+}
+
+pub fn bench_decorate_column(c: &mut Criterion) {
+    let state = ChunkWorkerState::new(1, TerrainSettings::default());
+    let chunk = gen_chunk(&state, ChunkPos::new(1, 1));
+    let height = chunk.section_count() * 16;
+    let surfaces = surfaces(&chunk, 0, 0);
+
+    c.bench_function("decorate_column surfaces", |b| {
+        b.iter_batched_ref(
+            || chunk.clone(),
+            |chunk| decorate_column(&state, chunk, 16, 16, 0, 0, black_box(surfaces.clone())),
+            BatchSize::SmallInput,
+        );
+    });
+
+    // Scans every block of the column, like the decoration pass used to.
+    c.bench_function("decorate_column full column", |b| {
+        b.iter_batched_ref(
+            || chunk.clone(),
+            |chunk| {
+                decorate_column(
+                    &state,
+                    chunk,
+                    16,
+                    16,
+                    0,
+                    0,
+                    black_box((0..height - 1).rev()),
+                )
+            },
+            BatchSize::SmallInput,
+        );
+    });
+}
+
+criterion_group!(benches, bench_decorate_column);
+criterion_main!(benches);
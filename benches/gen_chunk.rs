@@ -1,19 +1,11 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use noise::SuperSimplex;
 use valence::view::ChunkPos;
 use valence_playground::minecraft::world_gen::chunk_worker::{
     gen_chunk, gen_chunk_fors, ChunkWorkerState, TerrainSettings,
 };
 
 fn create_state(seed: u32) -> ChunkWorkerState {
-    ChunkWorkerState {
-        density: SuperSimplex::new(seed),
-        hilly: SuperSimplex::new(seed.wrapping_add(1)),
-        stone: SuperSimplex::new(seed.wrapping_add(2)),
-        gravel: SuperSimplex::new(seed.wrapping_add(3)),
-        grass: SuperSimplex::new(seed.wrapping_add(4)),
-        settings: TerrainSettings::default(),
-    }
+    ChunkWorkerState::new(seed, TerrainSettings::default())
 }
 
 pub fn bench_gen_chunk(c: &mut Criterion) {
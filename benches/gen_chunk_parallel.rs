@@ -0,0 +1,28 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use valence::view::ChunkPos;
+use valence_playground::minecraft::world_gen::chunk_worker::{
+    gen_chunk_parallel, gen_chunk_sequential, ChunkWorkerState, TerrainSettings,
+};
+
+fn create_state(seed: u32) -> ChunkWorkerState {
+    ChunkWorkerState::new(seed, TerrainSettings::default())
+}
+
+pub fn bench_gen_chunk(c: &mut Criterion) {
+    let state = create_state(1);
+
+    c.bench_function("gen_chunk_sequential (1, 1)", move |b| {
+        b.iter(|| gen_chunk_sequential(black_box(&state), black_box(ChunkPos::new(1, 1))));
+    });
+}
+
+pub fn bench_gen_chunk_parallel(c: &mut Criterion) {
+    let state = create_state(1);
+
+    c.bench_function("gen_chunk_parallel (1, 1)", move |b| {
+        b.iter(|| gen_chunk_parallel(black_box(&state), black_box(ChunkPos::new(1, 1))));
+    });
+}
+
+criterion_group!(benches, bench_gen_chunk, bench_gen_chunk_parallel);
+criterion_main!(benches);
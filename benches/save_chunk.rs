@@ -0,0 +1,37 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use valence::{prelude::Chunk, view::ChunkPos};
+use valence_playground::minecraft::{
+    save::SaveChunk,
+    world_gen::chunk_worker::{gen_chunk, ChunkWorkerState, TerrainSettings},
+};
+
+fn create_chunk(seed: u32) -> Chunk {
+    let state = ChunkWorkerState::new(seed, TerrainSettings::default());
+    gen_chunk(&state, ChunkPos::new(1, 1))
+}
+
+pub fn bench_save_chunk(c: &mut Criterion) {
+    let chunk = create_chunk(1);
+    let rle = SaveChunk::from(&chunk);
+    let raw = rle.to_raw();
+
+    let rle_bytes = bincode::serialize(&rle).unwrap();
+    let raw_bytes = bincode::serialize(&raw).unwrap();
+
+    c.bench_function("deserialize SaveChunk raw", |b| {
+        b.iter(|| {
+            let save_chunk: SaveChunk = bincode::deserialize(black_box(&raw_bytes)).unwrap();
+            Chunk::from(save_chunk)
+        });
+    });
+
+    c.bench_function("deserialize SaveChunk rle", |b| {
+        b.iter(|| {
+            let save_chunk: SaveChunk = bincode::deserialize(black_box(&rle_bytes)).unwrap();
+            Chunk::from(save_chunk)
+        });
+    });
+}
+
+criterion_group!(benches, bench_save_chunk);
+criterion_main!(benches);
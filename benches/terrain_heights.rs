@@ -0,0 +1,61 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use valence::{prelude::Chunk, view::ChunkPos};
+use valence_playground::{
+    minecraft::world_gen::chunk_worker::{
+        gen_block, gen_block_with_height, terrain_heights, ChunkWorkerState, TerrainSettings,
+    },
+    SECTION_COUNT,
+};
+
+pub fn bench_terrain_heights(c: &mut Criterion) {
+    let state = ChunkWorkerState::new(1, TerrainSettings::default());
+    let pos = ChunkPos::new(10, 10);
+
+    // Samples the noise for every block of every column.
+    c.bench_function("gen columns without heights", |b| {
+        b.iter_batched_ref(
+            || Chunk::new(SECTION_COUNT),
+            |chunk| {
+                for offset_z in 0..16 {
+                    for offset_x in 0..16 {
+                        let x = offset_x as i32 + pos.x * 16;
+                        let z = offset_z as i32 + pos.z * 16;
+
+                        gen_block(black_box(&state), chunk, x, z, offset_x, offset_z);
+                    }
+                }
+            },
+            BatchSize::SmallInput,
+        );
+    });
+
+    c.bench_function("gen columns with heights", |b| {
+        b.iter_batched_ref(
+            || Chunk::new(SECTION_COUNT),
+            |chunk| {
+                let heights = terrain_heights(black_box(&state), pos, SECTION_COUNT * 16);
+
+                for offset_z in 0..16 {
+                    for offset_x in 0..16 {
+                        let x = offset_x as i32 + pos.x * 16;
+                        let z = offset_z as i32 + pos.z * 16;
+
+                        gen_block_with_height(
+                            black_box(&state),
+                            chunk,
+                            x,
+                            z,
+                            offset_x,
+                            offset_z,
+                            heights[offset_z][offset_x],
+                        );
+                    }
+                }
+            },
+            BatchSize::SmallInput,
+        );
+    });
+}
+
+criterion_group!(benches, bench_terrain_heights);
+criterion_main!(benches);
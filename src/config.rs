@@ -1,24 +1,39 @@
+mod logging;
+mod metrics;
 mod server;
+#[cfg(feature = "hot-reload")]
+pub mod watcher;
 mod world;
 
 use std::{
     env,
     fs::File,
-    io::{Read, Write},
-    path::PathBuf,
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
 };
 
-use anyhow::{Ok, Result};
+use anyhow::{Context, Ok, Result};
 use cfg_if::cfg_if;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-pub use self::{server::*, world::*};
+pub use self::{logging::*, metrics::*, server::*, world::*};
 
 #[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
 pub struct Config {
     pub gui: bool,
     pub world: WorldConfig,
     pub server: ServerConfig,
+    pub metrics: MetricsConfig,
+    pub logging: LoggingConfig,
+    /// Fields set by `Config.local.toml`, logged once logging is set up.
+    #[serde(skip)]
+    pub overrides: Vec<String>,
+    /// The table the config was parsed from, tells [`Config::merge`] which
+    /// fields a `Config.local.toml` sets.
+    #[serde(skip)]
+    pub source: Option<toml::Table>,
 }
 
 impl Default for Config {
@@ -38,6 +53,10 @@ impl Default for Config {
             gui,
             world: WorldConfig::default(),
             server: ServerConfig::default(),
+            metrics: MetricsConfig::default(),
+            logging: LoggingConfig::default(),
+            overrides: vec![],
+            source: None,
         }
     }
 }
@@ -108,7 +127,20 @@ impl Config {
         let mut buf = String::new();
         f.read_to_string(&mut buf)?;
 
-        Ok(toml::from_str(&buf)?)
+        Self::from_toml_str(&buf)
+    }
+
+    /// Parses a TOML config, including ones written by older versions.
+    ///
+    /// # Errors
+    /// - the config is invalid TOML or has invalid values
+    pub fn from_toml_str(toml: &str) -> Result<Self> {
+        let mut table: toml::Table = toml::from_str(toml)?;
+        migrate_legacy_toml(&mut table);
+
+        let mut config: Config = toml::Value::Table(table.clone()).try_into()?;
+        config.source = Some(table);
+        Ok(config)
     }
 
     /// # Errors
@@ -121,8 +153,11 @@ impl Config {
         Ok(ron::from_str(&buf)?)
     }
 
+    /// Writes the default config if `Config.toml` doesn't exist.
+    ///
     /// # Errors
     /// - File Permissions missing
+    /// - `Config.toml` is invalid, it is left untouched
     pub fn from_current_dir_toml() -> Result<Self> {
         let current_dir = env::current_dir()?;
         let path = current_dir.join("Config.toml");
@@ -131,14 +166,18 @@ impl Config {
             Result::Ok(mut f) => {
                 let mut buf = String::new();
                 f.read_to_string(&mut buf)?;
-                Ok(toml::from_str(&buf)?)
+                Self::from_toml_str(&buf)
             }
-            Result::Err(_) => Config::write_toml_default(path),
+            Result::Err(e) if e.kind() == ErrorKind::NotFound => Config::write_toml_default(path),
+            Result::Err(e) => Err(e.into()),
         }
     }
 
+    /// Writes the default config if `Config.ron` doesn't exist.
+    ///
     /// # Errors
     /// - File Permissions missing
+    /// - `Config.ron` is invalid, it is left untouched
     pub fn from_current_dir_ron() -> Result<Self> {
         let current_dir = env::current_dir()?;
         let path = current_dir.join("Config.ron");
@@ -149,25 +188,383 @@ impl Config {
                 f.read_to_string(&mut buf)?;
                 Ok(ron::from_str(&buf)?)
             }
-            Result::Err(_) => Config::write_ron_default(path),
+            Result::Err(e) if e.kind() == ErrorKind::NotFound => Config::write_ron_default(path),
+            Result::Err(e) => Err(e.into()),
         }
     }
 
+    /// Paths of the fields that differ from the default, like `world.seed`.
+    /// Tables are compared field by field, everything else as a whole.
+    ///
+    /// # Panics
+    /// - the config can't be represented as JSON
+    #[must_use]
+    pub fn non_default_fields(&self) -> Vec<String> {
+        let mut fields = vec![];
+        collect_non_default(
+            "",
+            &to_value(&Config::default()),
+            &to_value(self),
+            &mut fields,
+        );
+        fields
+    }
+
+    /// Whether the field at the path, like `world.seed`, has its default
+    /// value. Unknown paths are treated as default.
+    #[must_use]
+    pub fn is_default_field(&self, path: &str) -> bool {
+        !self
+            .non_default_fields()
+            .iter()
+            .any(|field| field == path || field.starts_with(&format!("{path}.")))
+    }
+
+    /// Overwrites the fields of `base` that `override_config` sets. The TOML
+    /// tables of both are merged field by field before the result is
+    /// deserialized, values like lists or enums are replaced as a whole.
+    ///
+    /// A config parsed from TOML sets the fields of its file, even the ones set
+    /// to their default value. Any other config sets its
+    /// [non-default fields](Config::non_default_fields).
+    ///
+    /// # Panics
+    /// - one of the configs can't be represented as TOML
+    #[must_use]
+    pub fn merge(base: Config, override_config: Config) -> Config {
+        let fields = match &override_config.source {
+            Some(table) => set_fields(table),
+            None => {
+                let mut fields = override_config
+                    .non_default_fields()
+                    .iter()
+                    .map(|path| path.splitn(3, '.').take(2).collect::<Vec<_>>().join("."))
+                    .collect::<Vec<_>>();
+                fields.dedup();
+                fields
+            }
+        };
+        let values = to_table(&override_config);
+        let mut merged = to_table(&base);
+
+        let mut overrides = vec![];
+        for field in fields {
+            if let Some(value) = get_field(&values, &field) {
+                set_field(&mut merged, &field, value.clone());
+                overrides.push(field);
+            }
+        }
+
+        let mut config: Config = toml::Value::Table(merged)
+            .try_into()
+            .expect("fields of two valid configs should form a valid config");
+        config.overrides = overrides;
+        config
+    }
+
+    /// # Errors
+    /// - one of the values is out of range
+    pub fn validate(&self) -> Result<()> {
+        self.server.validate()?;
+        self.logging.validate()
+    }
+
+    /// See [`Config::from_dir`].
+    ///
     /// # Errors
     /// - File Permissions missing
-    pub fn from_current_dir() -> Result<Self> {
-        let current_dir = env::current_dir()?;
-        let path_ron = current_dir.join("Config.ron");
-        let path_toml = current_dir.join("Config.toml");
-
-        let c = match Self::from_ron(path_ron) {
-            Result::Ok(c) => c,
-            Result::Err(_) => match Self::from_toml(path_toml.clone()) {
-                Result::Ok(c) => c,
-                Result::Err(_) => Self::write_toml_default(path_toml)?,
-            },
+    /// - one of the config files exists but is invalid
+    /// - the config is invalid, see [`Config::validate`]
+    pub fn from_current_dir() -> Result<Self> { Self::from_dir(&env::current_dir()?) }
+
+    /// Loads `Config.ron`, or `Config.toml` if there is no `Config.ron`. The
+    /// default config is written to `Config.toml` only if neither exists, an
+    /// invalid config file is never overwritten. Fields set in the optional
+    /// `Config.local.toml` override the loaded ones, see [`Config::merge`].
+    ///
+    /// # Errors
+    /// - File Permissions missing
+    /// - one of the config files exists but is invalid
+    /// - the config is invalid, see [`Config::validate`]
+    pub fn from_dir(dir: &Path) -> Result<Self> {
+        let path_ron = dir.join("Config.ron");
+        let path_toml = dir.join("Config.toml");
+        let path_local = dir.join("Config.local.toml");
+
+        let mut c = if path_ron.exists() {
+            Self::from_ron(path_ron).context("Invalid Config.ron")?
+        } else if path_toml.exists() {
+            Self::from_toml(path_toml).context("Invalid Config.toml")?
+        } else {
+            Self::write_toml_default(path_toml)?
         };
+        if path_local.exists() {
+            c = Self::merge(
+                c,
+                Self::from_toml(path_local).context("Invalid Config.local.toml")?,
+            );
+        }
+        c.validate()?;
 
         Ok(c)
     }
 }
+
+/// Replaces the `world.pregen_chunks` range of configs written before
+/// [`PregenShape`] existed with the square that covers it.
+fn migrate_legacy_toml(config: &mut toml::Table) {
+    let Some(toml::Value::Table(world)) = config.get_mut("world") else {
+        return;
+    };
+    let Some(toml::Value::Table(range)) = world.get("pregen_chunks") else {
+        return;
+    };
+    let (Some(start), Some(end)) = (
+        range.get("start").and_then(toml::Value::as_integer),
+        range.get("end").and_then(toml::Value::as_integer),
+    ) else {
+        return;
+    };
+
+    let shape = PregenShape::from_legacy_range(start as i32, end as i32);
+    world.insert(
+        "pregen_chunks".to_string(),
+        toml::Value::try_from(shape).expect("pregen shape can't be represented as TOML"),
+    );
+}
+
+fn to_table(config: &Config) -> toml::Table {
+    match toml::Value::try_from(config).expect("config can't be represented as TOML") {
+        toml::Value::Table(table) => table,
+        _ => unreachable!("configs are serialized as tables"),
+    }
+}
+
+/// The fields set in the table of a config, `<section>.<field>` for the fields
+/// of sections like `[world]`.
+fn set_fields(table: &toml::Table) -> Vec<String> {
+    table
+        .iter()
+        .flat_map(|(key, value)| match value {
+            toml::Value::Table(section) => section
+                .keys()
+                .map(|field| format!("{key}.{field}"))
+                .collect(),
+            _ => vec![key.clone()],
+        })
+        .collect()
+}
+
+fn get_field<'a>(table: &'a toml::Table, field: &str) -> Option<&'a toml::Value> {
+    match field.split_once('.') {
+        Some((section, field)) => table.get(section)?.get(field),
+        None => table.get(field),
+    }
+}
+
+fn set_field(table: &mut toml::Table, field: &str, value: toml::Value) {
+    match field.split_once('.') {
+        Some((section, field)) => {
+            if let Some(toml::Value::Table(section)) = table.get_mut(section) {
+                section.insert(field.to_string(), value);
+            } else {
+                let mut new = toml::Table::new();
+                new.insert(field.to_string(), value);
+                table.insert(section.to_string(), toml::Value::Table(new));
+            }
+        }
+        None => {
+            table.insert(field.to_string(), value);
+        }
+    }
+}
+
+fn to_value(config: &Config) -> Value {
+    serde_json::to_value(config).expect("config can't be represented as JSON")
+}
+
+/// Adds the paths of the leaves of `value` that differ from `default`.
+fn collect_non_default(path: &str, default: &Value, value: &Value, fields: &mut Vec<String>) {
+    match (default, value) {
+        (Value::Object(default), Value::Object(value)) => {
+            for (key, value) in value {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+
+                match default.get(key) {
+                    Some(default) => collect_non_default(&path, default, value, fields),
+                    None => fields.push(path),
+                }
+            }
+        }
+        (default, value) if default != value => fields.push(path.to_string()),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Config {
+        Config {
+            world: WorldConfig {
+                water_height: 90,
+                chunks_cached: 500,
+                ..Default::default()
+            },
+            server: ServerConfig {
+                max_connections: 10,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn local(toml: &str) -> Config { Config::from_toml_str(toml).unwrap() }
+
+    #[test]
+    fn partial_override_keeps_the_other_fields() {
+        let merged = Config::merge(base(), local("[world]\nwater_height = 64\n"));
+
+        assert_eq!(merged.world.water_height, 64);
+        assert_eq!(merged.world.chunks_cached, 500);
+        assert_eq!(merged.server.max_connections, 10);
+        assert_eq!(merged.overrides, ["world.water_height"]);
+    }
+
+    #[test]
+    fn overrides_of_several_tables_are_merged() {
+        let merged = Config::merge(
+            base(),
+            local("[world]\nspawn = [1.0, 2.0, 3.0]\n\n[server]\nmax_connections = 30\n"),
+        );
+
+        assert_eq!(merged.world.spawn, Some([1.0, 2.0, 3.0]));
+        assert_eq!(merged.world.water_height, 90);
+        assert_eq!(merged.server.max_connections, 30);
+        let mut overrides = merged.overrides;
+        overrides.sort();
+        assert_eq!(overrides, ["server.max_connections", "world.spawn"]);
+    }
+
+    #[test]
+    fn overrides_can_set_default_values() {
+        let default_height = WorldConfig::default().water_height;
+        let merged = Config::merge(
+            base(),
+            local(&format!("[world]\nwater_height = {default_height}\n")),
+        );
+
+        assert_eq!(merged.world.water_height, default_height);
+        assert_eq!(merged.world.chunks_cached, 500);
+        assert_eq!(merged.overrides, ["world.water_height"]);
+    }
+
+    #[test]
+    fn enum_overrides_replace_the_whole_value() {
+        let square = Config {
+            world: WorldConfig {
+                pregen_chunks: PregenShape::Square { radius: 8 },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let merged = Config::merge(
+            square,
+            local("[world]\npregen_chunks = { Circle = { radius = 5 } }\n"),
+        );
+
+        assert_eq!(
+            merged.world.pregen_chunks,
+            PregenShape::Circle { radius: 5 }
+        );
+    }
+
+    #[test]
+    fn configs_that_werent_parsed_override_their_non_default_fields() {
+        let override_config = Config {
+            world: WorldConfig {
+                water_height: 64,
+                pregen_chunks: PregenShape::Circle { radius: 5 },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let merged = Config::merge(base(), override_config);
+
+        assert_eq!(merged.world.water_height, 64);
+        assert_eq!(
+            merged.world.pregen_chunks,
+            PregenShape::Circle { radius: 5 }
+        );
+        assert_eq!(merged.world.chunks_cached, 500);
+        assert_eq!(merged.server.max_connections, 10);
+    }
+
+    #[test]
+    fn legacy_pregen_ranges_are_read_as_squares() {
+        let config =
+            Config::from_toml_str("[world]\npregen_chunks = { start = -22, end = 22 }\n").unwrap();
+        assert_eq!(
+            config.world.pregen_chunks,
+            PregenShape::Square { radius: 22 }
+        );
+
+        let config =
+            Config::from_toml_str("[world.pregen_chunks]\nstart = -4\nend = 10\n").unwrap();
+        assert_eq!(
+            config.world.pregen_chunks,
+            PregenShape::Square { radius: 10 }
+        );
+
+        let config =
+            Config::from_toml_str("[world]\npregen_chunks = { Circle = { radius = 5 } }\n")
+                .unwrap();
+        assert_eq!(
+            config.world.pregen_chunks,
+            PregenShape::Circle { radius: 5 }
+        );
+    }
+
+    #[test]
+    fn invalid_config_files_are_not_overwritten() {
+        let dir = std::env::temp_dir().join(format!("vp-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Config.toml");
+        let invalid = "[world]\nwater_height = \"high\"\n";
+        std::fs::write(&path, invalid).unwrap();
+
+        let result = Config::from_dir(&dir);
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(content, invalid);
+    }
+
+    #[test]
+    fn default_config_is_written_if_there_is_none() {
+        let dir = std::env::temp_dir().join(format!("vp-config-default-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let config = Config::from_dir(&dir).unwrap();
+        let written = Config::from_toml(dir.join("Config.toml"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(config.world, WorldConfig::default());
+        assert_eq!(written.unwrap().world, WorldConfig::default());
+    }
+
+    #[test]
+    fn empty_override_keeps_the_base() {
+        let merged = Config::merge(base(), local(""));
+
+        assert_eq!(merged.world, base().world);
+        assert_eq!(merged.server.max_connections, 10);
+        assert!(merged.overrides.is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::filter::Directive;
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Filter used if `RUST_LOG` isn't set, in the same syntax.
+    pub default_filter: String,
+    /// Directives like `minecraft::save=warn`, added to the filter of
+    /// `RUST_LOG` or the default filter.
+    pub filters: Vec<String>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            default_filter: "info".to_string(),
+            filters: vec![],
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// The value of `RUST_LOG` if it is set and not empty, the configured
+    /// default filter otherwise.
+    #[must_use]
+    pub fn effective_filter(&self, env: Option<&str>) -> String {
+        match env.map(str::trim) {
+            Some(filter) if !filter.is_empty() => filter.to_string(),
+            _ => self.default_filter.clone(),
+        }
+    }
+
+    /// The parsed `filters`.
+    ///
+    /// # Errors
+    /// - a filter isn't a valid directive
+    pub fn directives(&self) -> Result<Vec<Directive>> {
+        self.filters
+            .iter()
+            .map(|filter| match Directive::from_str(filter) {
+                Ok(directive) => Ok(directive),
+                Err(e) => bail!("Invalid logging filter {filter:?}: {e}"),
+            })
+            .collect()
+    }
+
+    /// # Errors
+    /// - a filter isn't a valid directive
+    pub fn validate(&self) -> Result<()> { self.directives().map(|_| ()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn filters(filters: &[&str]) -> LoggingConfig {
+        LoggingConfig {
+            filters: filters.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid_filters_are_parsed() {
+        let directives = filters(&["minecraft::save=warn", "valence=debug", "trace"])
+            .directives()
+            .unwrap();
+
+        assert_eq!(
+            directives
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["minecraft::save=warn", "valence=debug", "trace"]
+        );
+    }
+
+    #[test]
+    fn invalid_filters_fail_validation_with_their_name() {
+        let config = Config {
+            logging: filters(&["minecraft::save=warn", "valence=loud"]),
+            ..Default::default()
+        };
+
+        let error = config.validate().unwrap_err().to_string();
+        assert!(
+            error.starts_with("Invalid logging filter \"valence=loud\""),
+            "{error}"
+        );
+    }
+}
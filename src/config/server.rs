@@ -1,13 +1,39 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 use valence::prelude::ConnectionMode as ValenceConnectionMode;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
 pub struct ServerConfig {
     pub max_connections: usize,
     pub max_view_distance: u8,
     pub connection_mode: ConnectionMode,
+    /// Lines of the server description, with `&` color codes or as JSON text.
+    pub motd: Vec<String>,
+    /// Number of chat messages kept in the chat history.
+    pub chat_history: usize,
+    /// Chat messages are cut off after this many characters.
+    pub max_chat_length: usize,
+    /// Allows all players to change their own nickname, otherwise only
+    /// operators can use the `nick` command.
+    pub allow_nicknames: bool,
+    /// Seconds between saves of the data of all connected players, `0` only
+    /// saves when a player leaves.
+    pub player_save_interval: u64,
+    /// Tells players when the held item can't be placed as a block.
+    pub placement_feedback: bool,
+    pub name_colors: NameColors,
+    /// Ticks per second, between 1 and 120.
+    pub tick_rate: u32,
+    /// Only players on the whitelist can join.
+    pub whitelist_enabled: bool,
+    /// Shown to players that aren't on the whitelist when they try to join.
+    pub whitelist_message: String,
+    /// Op levels of players by uuid or username, everyone else has level `0`.
+    pub ops: BTreeMap<String, u8>,
 }
 
 impl Default for ServerConfig {
@@ -16,10 +42,63 @@ impl Default for ServerConfig {
             max_connections: 20,
             max_view_distance: 20,
             connection_mode: ConnectionMode::default(),
+            motd: vec![],
+            chat_history: 1000,
+            max_chat_length: 256,
+            allow_nicknames: false,
+            player_save_interval: 60,
+            placement_feedback: true,
+            name_colors: NameColors::default(),
+            tick_rate: DEFAULT_TICK_RATE,
+            whitelist_enabled: false,
+            whitelist_message: "You are not whitelisted on this server.".to_string(),
+            ops: BTreeMap::new(),
         }
     }
 }
 
+/// Tick rate of a vanilla server.
+pub const DEFAULT_TICK_RATE: u32 = 20;
+
+/// Highest op level, the same as in vanilla.
+pub const MAX_OP_LEVEL: u8 = 4;
+
+impl ServerConfig {
+    /// # Errors
+    /// - the tick rate is not between 1 and 120
+    /// - an op level is higher than [`MAX_OP_LEVEL`]
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=120).contains(&self.tick_rate) {
+            bail!(
+                "tick_rate must be between 1 and 120, got {}",
+                self.tick_rate
+            );
+        }
+
+        if let Some((player, level)) = self.ops.iter().find(|(_, l)| **l > MAX_OP_LEVEL) {
+            bail!("op level of {player} must be at most {MAX_OP_LEVEL}, got {level}");
+        }
+
+        Ok(())
+    }
+
+    /// Op level of a player from [`ops`](Self::ops), looked up by uuid first
+    /// and then by username, both ignoring case. `0` if they aren't an
+    /// operator.
+    #[must_use]
+    pub fn op_level(&self, uuid: Uuid, username: &str) -> u8 {
+        let uuid = uuid.to_string();
+        let find = |key: &str| {
+            self.ops
+                .iter()
+                .find(|(player, _)| player.eq_ignore_ascii_case(key))
+                .map(|(_, level)| *level)
+        };
+
+        find(&uuid).or_else(|| find(username)).unwrap_or(0)
+    }
+}
+
 #[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub enum ConnectionMode {
     #[default]
@@ -32,6 +111,16 @@ pub enum ConnectionMode {
     },
 }
 
+/// How the color of player names is picked.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum NameColors {
+    /// Derived from the uuid, players keep their color between sessions.
+    #[default]
+    Uuid,
+    /// A new random color every time a player joins.
+    Random,
+}
+
 impl From<ConnectionMode> for ValenceConnectionMode {
     fn from(val: ConnectionMode) -> Self {
         match val {
@@ -0,0 +1,216 @@
+use std::{env, path::Path};
+
+use flume::{Receiver, Sender};
+use notify::{recommended_watcher, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use valence::{bevy_app::Plugin, prelude::*};
+
+use super::{Config, WorldConfig};
+use crate::{
+    minecraft::world_gen::{chunk_worker::TerrainSettings, UpdateTerrainSettings},
+    CONFIG,
+};
+
+/// Files the config can be loaded from, see [`Config::from_current_dir`].
+const CONFIG_FILES: [&str; 3] = ["Config.ron", "Config.toml", "Config.local.toml"];
+
+/// Watches the config files in the current directory and applies changes to
+/// the terrain settings, the seed and the water height, at runtime.
+pub struct ConfigWatcherPlugin;
+
+impl Plugin for ConfigWatcherPlugin {
+    fn build(&self, app: &mut App) {
+        match ConfigWatcher::new() {
+            Ok(watcher) => {
+                app.insert_non_send_resource(watcher)
+                    .insert_resource(LoadedConfig(CONFIG.world.clone()))
+                    .add_system(watch_config_file);
+            }
+            Err(e) => {
+                warn!(target: "config::watcher", "Unable to watch the config file: {e}");
+            }
+        }
+    }
+}
+
+/// Keeps the file watcher alive and receives its events.
+struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+    receiver: Receiver<notify::Result<Event>>,
+}
+
+impl ConfigWatcher {
+    fn new() -> anyhow::Result<Self> {
+        let (sender, receiver): (Sender<_>, _) = flume::unbounded();
+        let mut watcher = recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })?;
+
+        // Editors often replace the file instead of writing to it, so the
+        // directory is watched instead of the files themselves.
+        watcher.watch(&env::current_dir()?, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            receiver,
+        })
+    }
+}
+
+/// The world config the current terrain settings are based on.
+#[derive(Resource)]
+struct LoadedConfig(WorldConfig);
+
+fn is_config_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map_or(false, |name| CONFIG_FILES.contains(&name))
+}
+
+fn watch_config_file(
+    watcher: NonSend<ConfigWatcher>,
+    mut loaded: ResMut<LoadedConfig>,
+    mut settings: ResMut<TerrainSettings>,
+    mut update: ResMut<UpdateTerrainSettings>,
+) {
+    let mut changed = false;
+    for event in watcher.receiver.try_iter() {
+        match event {
+            Ok(event) if event.kind.is_modify() || event.kind.is_create() => {
+                changed |= event.paths.iter().any(|path| is_config_file(path));
+            }
+            Ok(_) => {}
+            Err(e) => warn!(target: "config::watcher", "Error while watching the config: {e}"),
+        }
+    }
+
+    if !changed {
+        return;
+    }
+
+    let config = match reload_config() {
+        Ok(config) => config,
+        Err(e) => {
+            warn!(target: "config::watcher", "Unable to reload the config: {e}");
+            return;
+        }
+    };
+
+    if config.world == loaded.0 {
+        return;
+    }
+
+    if let Some(new_settings) = changed_terrain_settings(&loaded.0, &config.world, settings.seed) {
+        info!(target: "config::watcher",
+            "Terrain settings changed (seed {}), regenerating the terrain", new_settings.seed
+        );
+        *settings = new_settings;
+        update.0 = true;
+    }
+
+    let others_changed = WorldConfig {
+        seed: loaded.0.seed,
+        water_height: loaded.0.water_height,
+        ..config.world.clone()
+    } != loaded.0;
+    if others_changed {
+        info!(target: "config::watcher",
+            "Other changes to the world config are applied after a restart"
+        );
+    }
+
+    loaded.0 = config.world;
+}
+
+/// The terrain settings of the `reloaded` config if they differ from the ones
+/// of the `loaded` config. The current seed is kept if the seed in the config
+/// didn't change, so a random seed isn't rolled again.
+fn changed_terrain_settings(
+    loaded: &WorldConfig,
+    reloaded: &WorldConfig,
+    current_seed: u32,
+) -> Option<TerrainSettings> {
+    let old = TerrainSettings {
+        seed: current_seed,
+        ..TerrainSettings::from_config(loaded)
+    };
+    let mut new = TerrainSettings::from_config(reloaded);
+    if reloaded.seed == loaded.seed {
+        new.seed = current_seed;
+    }
+
+    (new != old).then_some(new)
+}
+
+/// Like [`Config::from_current_dir`], but never writes the default config, the
+/// config files might be in the middle of being replaced.
+fn reload_config() -> anyhow::Result<Config> {
+    let current_dir = env::current_dir()?;
+
+    let config = Config::from_ron(current_dir.join("Config.ron"))
+        .or_else(|_| Config::from_toml(current_dir.join("Config.toml")))?;
+
+    let path_local = current_dir.join("Config.local.toml");
+    if path_local.exists() {
+        return Ok(Config::merge(config, Config::from_toml(path_local)?));
+    }
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Seed;
+
+    fn config(seed: Seed, water_height: i32) -> WorldConfig {
+        WorldConfig {
+            seed,
+            water_height,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unchanged_terrain_config_keeps_the_settings() {
+        let loaded = config(Seed::Set(1), 64);
+        let reloaded = WorldConfig {
+            max_pending_chunks: 16,
+            ..loaded.clone()
+        };
+
+        assert_eq!(changed_terrain_settings(&loaded, &reloaded, 1), None);
+    }
+
+    #[test]
+    fn changed_seed_and_water_height_are_sent() {
+        let loaded = config(Seed::Set(1), 64);
+
+        let settings = changed_terrain_settings(&loaded, &config(Seed::Set(2), 64), 1).unwrap();
+        assert_eq!(
+            settings,
+            TerrainSettings::from_config(&config(Seed::Set(2), 64))
+        );
+
+        let settings = changed_terrain_settings(&loaded, &config(Seed::Set(1), 50), 1).unwrap();
+        assert_eq!(settings.seed, 1);
+        assert_eq!(settings.water_height, 50);
+        assert_eq!(
+            settings,
+            TerrainSettings::from_config(&config(Seed::Set(1), 50))
+        );
+    }
+
+    #[test]
+    fn random_seeds_are_only_rolled_when_the_seed_changes() {
+        let loaded = config(Seed::Random, 64);
+
+        assert_eq!(
+            changed_terrain_settings(&loaded, &config(Seed::Random, 64), 1234),
+            None
+        );
+
+        let settings = changed_terrain_settings(&loaded, &config(Seed::Random, 40), 1234).unwrap();
+        assert_eq!(settings.seed, 1234);
+        assert_eq!(settings.water_height, 40);
+    }
+}
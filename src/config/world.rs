@@ -1,26 +1,124 @@
-use std::ops::RangeInclusive;
-
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
 pub struct WorldConfig {
     pub seed: Seed,
+    /// Default sea level of the terrain settings.
+    pub water_height: i32,
     pub chunks_cached: usize,
     pub spawn: Option<[f64; 3]>,
-    pub pregen_chunks: RangeInclusive<i32>,
+    /// Players below op level 2 can't break or place blocks closer to the spawn
+    /// than this, in blocks.
+    pub spawn_protection_radius: u32,
+    pub pregen_chunks: PregenShape,
+    pub read_only_world: ReadOnlyWorld,
+    pub compression: CompressionLevel,
+    pub chunk_format: ChunkFormat,
+    /// Ticks an unviewed chunk stays loaded before it is removed.
+    pub chunk_unload_delay: u32,
+    /// Seconds players wait for a terrain regeneration before they are moved
+    /// back anyway.
+    pub regeneration_timeout: u64,
+    pub regeneration_progress: RegenerationProgress,
+    pub points_of_interest: Vec<PointOfInterest>,
+    /// Maximum number of chunks sent to the chunk workers that haven't been
+    /// generated yet.
+    pub max_chunk_queue_depth: usize,
+    /// Maximum number of chunks waiting to be sent to the chunk workers, chunks
+    /// of player views are rejected until there is room again.
+    pub max_pending_chunks: usize,
+    /// Maximum number of chunks of a single player's view queued per tick, the
+    /// rest is queued in the following ticks.
+    pub max_view_chunks_per_tick: usize,
+    /// View distances aren't lowered below this while the server is overloaded.
+    pub min_view_distance: u8,
+    /// Chunks in front of a player are generated before the ones behind them.
+    pub look_bias: bool,
+    /// Number of chunk workers, at most the available parallelism. Uses as
+    /// many workers as possible if not set.
+    pub chunk_workers: Option<usize>,
+    /// Inserts the pregenerated chunks into the world at startup and keeps them
+    /// loaded, instead of generating them again when a player sees them.
+    pub load_pregen_chunks: bool,
+    /// Resets the terrain settings to the defaults if they generate an empty
+    /// spawn chunk.
+    pub revert_empty_terrain: bool,
+    /// Half the side length of the square around the origin players can
+    /// explore, in blocks. Chunks outside of it are never saved.
+    pub world_border_radius: Option<f64>,
+    /// Damage per tick for every block a player is outside the world border,
+    /// as a fraction of the border radius.
+    pub border_damage: f64,
+    /// Maximum distance to the origin of the locations `rtp` teleports to, in
+    /// blocks.
+    pub rtp_radius: f64,
+    /// Seconds a player has to wait between two uses of `rtp`.
+    pub rtp_cooldown: u64,
+    /// Saves only the blocks players changed, the rest of a chunk is generated
+    /// again when it's loaded.
+    pub save_diffs: bool,
+    /// What happens to region files of other terrain settings when the server
+    /// starts or on `cleanregions`.
+    pub stale_regions: StaleRegions,
+    /// Maximum number of blocks of a schematic pasted with `paste`.
+    pub max_schematic_volume: u64,
+    /// Maximum number of blocks a single `fill` can place.
+    pub max_fill_volume: u64,
+    /// Additional instances next to the generated terrain.
+    pub dimensions: Vec<DimensionConfig>,
 }
 
 impl Default for WorldConfig {
     fn default() -> Self {
         Self {
             seed: Seed::default(),
+            water_height: 120,
             chunks_cached: 4000,
             spawn: None,
-            pregen_chunks: -22..=22,
+            spawn_protection_radius: 16,
+            pregen_chunks: PregenShape::default(),
+            read_only_world: ReadOnlyWorld::default(),
+            compression: CompressionLevel::default(),
+            chunk_format: ChunkFormat::default(),
+            chunk_unload_delay: 100,
+            regeneration_timeout: 60,
+            regeneration_progress: RegenerationProgress::default(),
+            points_of_interest: vec![],
+            max_chunk_queue_depth: 256,
+            max_pending_chunks: 2048,
+            max_view_chunks_per_tick: 256,
+            look_bias: true,
+            load_pregen_chunks: true,
+            chunk_workers: None,
+            min_view_distance: 4,
+            revert_empty_terrain: false,
+            world_border_radius: None,
+            border_damage: 0.2,
+            rtp_radius: 5000.0,
+            rtp_cooldown: 60,
+            save_diffs: false,
+            stale_regions: StaleRegions::default(),
+            max_schematic_volume: 10_000_000,
+            max_fill_volume: 1_000_000,
+            dimensions: vec![],
         }
     }
 }
 
+impl WorldConfig {
+    /// Whether any part of the chunk at `x` `z` is inside the world border.
+    #[must_use]
+    pub fn is_chunk_inside_border(&self, x: i32, z: i32) -> bool {
+        let Some(radius) = self.world_border_radius else {
+            return true;
+        };
+
+        let inside = |c: i32| f64::from(c * 16) < radius && f64::from((c + 1) * 16) > -radius;
+        inside(x) && inside(z)
+    }
+}
+
 #[derive(
     Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
 )]
@@ -38,3 +136,127 @@ impl From<Seed> for u32 {
         }
     }
 }
+
+/// Area of chunks around the origin that is generated on startup.
+///
+/// Configs written before the shapes existed contain a range like
+/// `{ start = -22, end = 22 }` instead, see [`PregenShape::from_legacy_range`].
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum PregenShape {
+    /// All chunks with `-radius <= x, z <= radius`.
+    Square { radius: i32 },
+    /// All chunks whose euclidean distance to the origin is at most `radius`.
+    Circle { radius: i32 },
+}
+
+impl Default for PregenShape {
+    fn default() -> Self { PregenShape::Square { radius: 22 } }
+}
+
+impl PregenShape {
+    /// The square that covers `start..=end` on both axes.
+    #[must_use]
+    pub fn from_legacy_range(start: i32, end: i32) -> Self {
+        PregenShape::Square {
+            radius: start.saturating_abs().max(end.saturating_abs()),
+        }
+    }
+
+    #[must_use]
+    pub fn radius(&self) -> i32 {
+        match self {
+            PregenShape::Square { radius } | PregenShape::Circle { radius } => *radius,
+        }
+    }
+
+    /// Whether the chunk at `x` `z` is part of the shape.
+    #[must_use]
+    pub fn contains(&self, x: i32, z: i32) -> bool {
+        match self {
+            PregenShape::Square { radius } => x.abs() <= *radius && z.abs() <= *radius,
+            PregenShape::Circle { radius } => {
+                let (x, z, radius) = (i64::from(x), i64::from(z), i64::from(*radius));
+                x * x + z * z <= radius * radius
+            }
+        }
+    }
+}
+
+/// What to do if the `world` directory isn't writable.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ReadOnlyWorld {
+    /// Keep running, but don't save any chunks.
+    #[default]
+    DisableSaving,
+    /// Refuse to start the server.
+    Exit,
+}
+
+/// Compression level used for region files.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionLevel {
+    Fast,
+    #[default]
+    Default,
+    Best,
+}
+
+/// What to do with region files that were generated with other terrain
+/// settings, they can't be loaded anymore.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum StaleRegions {
+    #[default]
+    Keep,
+    Delete,
+    /// Move them to `world_archive/<settings hash>/`.
+    Archive,
+}
+
+/// How the blocks of saved chunks are stored, region files can contain chunks
+/// of both formats.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkFormat {
+    /// Consecutive identical blocks stored as a single run.
+    #[default]
+    Rle,
+    /// Every block, positions are derived from the order of the blocks.
+    Indexed,
+}
+
+/// Where players see how many chunks are left during a terrain regeneration.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum RegenerationProgress {
+    #[default]
+    ActionBar,
+    Chat,
+    Off,
+}
+
+/// An additional instance players can switch to with `terrain <name>`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DimensionConfig {
+    pub name: String,
+    /// Seed of the terrain, the seed of the world if not set.
+    #[serde(default)]
+    pub seed: Option<u32>,
+    #[serde(default)]
+    pub dimension: DimensionType,
+}
+
+/// How the client renders a dimension, e.g. the sky and the fog.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum DimensionType {
+    #[default]
+    Overworld,
+    Nether,
+    End,
+}
+
+/// A named location players can teleport to with `poi tp <name>`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PointOfInterest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub position: [f64; 3],
+}
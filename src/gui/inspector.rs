@@ -1,9 +1,19 @@
 use bevy::prelude::*;
 
-use crate::minecraft::world_gen::inspector_ui as terrain_ui;
+use crate::minecraft::world_gen::{
+    cache_stats_window, inspector_ui as terrain_ui, minimap_ui, pending_chunks_ui,
+};
 
 pub struct InspectorPlugin;
 
 impl Plugin for InspectorPlugin {
-    fn build(&self, app: &mut App) { app.add_system(terrain_ui); }
+    fn build(&self, app: &mut App) {
+        app.add_system(terrain_ui)
+            .add_system(pending_chunks_ui)
+            .add_system(minimap_ui)
+            .add_system(cache_stats_window);
+
+        #[cfg(feature = "profiling")]
+        app.add_system(crate::minecraft::world_gen::timing_ui);
+    }
 }
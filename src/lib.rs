@@ -19,17 +19,12 @@ pub mod util;
 #[macro_use]
 extern crate tracing;
 
-use std::sync::Mutex;
-
 use config::Config;
 use lazy_static::lazy_static;
-use valence::prelude::*;
 
 pub const SECTION_COUNT: usize = 24;
 pub const REGION_SIZE: f64 = 16.0;
 
 lazy_static! {
-    pub static ref PLAYER_COUNT: Mutex<usize> = Mutex::new(0);
-    pub static ref SPAWN_POS: Mutex<DVec3> = Mutex::new(DVec3::new(0.0, 200.0, 0.0));
     pub static ref CONFIG: Config = Config::from_current_dir().unwrap();
 }
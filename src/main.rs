@@ -11,12 +11,33 @@ use std::io;
 use bevy::prelude::App;
 use chrono::Local;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
-use valence_playground::{gui::GuiPlugin, minecraft::MinecraftPlugin};
+use valence_playground::{gui::GuiPlugin, minecraft::MinecraftPlugin, CONFIG};
 
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
 
+    let filter = CONFIG
+        .logging
+        .effective_filter(std::env::var("RUST_LOG").ok().as_deref());
+    // Checked when the config was loaded.
+    let env_filter = || {
+        CONFIG
+            .logging
+            .directives()
+            .expect("logging filters should be valid")
+            .into_iter()
+            .fold(EnvFilter::new(&filter), EnvFilter::add_directive)
+    };
+    let log_overrides = || {
+        if !CONFIG.overrides.is_empty() {
+            tracing::info!(
+                "Config.local.toml overrides: {}",
+                CONFIG.overrides.join(", ")
+            );
+        }
+    };
+
     if let Ok(path) = std::env::var("RUST_LOG_PATH") {
         let appender = tracing_appender::rolling::never(
             &path,
@@ -25,7 +46,7 @@ async fn main() {
         let (non_blocking, _guard) = tracing_appender::non_blocking(appender);
 
         tracing_subscriber::registry()
-            .with(EnvFilter::from_default_env())
+            .with(env_filter())
             .with(fmt::layer().with_writer(io::stdout))
             .with(
                 fmt::layer()
@@ -36,6 +57,7 @@ async fn main() {
             .init();
 
         tracing::info!("Logfiles are located at: {path}");
+        log_overrides();
 
         App::new()
             .add_plugin(MinecraftPlugin)
@@ -43,9 +65,10 @@ async fn main() {
             .run();
     } else {
         tracing_subscriber::registry()
-            .with(EnvFilter::from_default_env())
+            .with(env_filter())
             .with(fmt::layer().with_writer(io::stdout))
             .init();
+        log_overrides();
 
         App::new()
             .add_plugin(MinecraftPlugin)
@@ -1,14 +1,52 @@
+pub mod ban;
+pub mod border;
 pub mod building;
 mod callbacks;
 pub mod chat;
 pub mod client;
+pub mod command;
+pub mod debug;
+pub mod maprender;
+pub mod metrics;
+pub mod player_data;
+pub mod poi;
 pub mod save;
+pub mod schematic;
+pub mod spawn;
+pub mod structure;
+pub mod time;
+pub mod weather;
+pub mod whitelist;
 pub mod world_gen;
 
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
 use bevy::prelude::Plugin;
 use valence::{client::event::default_event_handler, prelude::*};
 
-use self::{building::BuildingPlugin, chat::ChatPlugin, world_gen::WorldGenPlugin};
+use self::{
+    ban::{BanList, BanPlugin},
+    border::BorderPlugin,
+    building::{BlockChangedEvent, BuildingPlugin},
+    chat::ChatPlugin,
+    command::CommandPlugin,
+    debug::DebugPlugin,
+    metrics::MetricsPlugin,
+    poi::PoiPlugin,
+    schematic::SchematicPlugin,
+    spawn::SpawnPlugin,
+    structure::StructurePlugin,
+    time::TimePlugin,
+    weather::WeatherPlugin,
+    whitelist::{Whitelist, WhitelistPlugin},
+    world_gen::{biome::biomes, dimension::dimensions, WorldGenPlugin},
+};
 use crate::{
     minecraft::{callbacks::VPCallbacks, client::ClientPlugin},
     CONFIG,
@@ -16,16 +54,72 @@ use crate::{
 
 pub struct MinecraftPlugin;
 
+/// Time at which the server was started.
+#[derive(Resource)]
+pub struct StartTime(pub Instant);
+
+/// Position new players spawn at, set by the world generation on startup.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct SpawnPos(pub DVec3);
+
+impl Default for SpawnPos {
+    fn default() -> Self { Self(DVec3::new(0.0, 200.0, 0.0)) }
+}
+
+/// Number of players currently online.
+///
+/// The count is shared with [`VPCallbacks`], which runs outside of the ECS and
+/// needs it to answer server list pings and reject logins to a full server.
+#[derive(Resource, Clone, Default, Debug)]
+pub struct PlayerCount(Arc<AtomicUsize>);
+
+impl PlayerCount {
+    #[must_use]
+    pub fn get(&self) -> usize { self.0.load(Ordering::Relaxed) }
+
+    pub fn increment(&self) { self.0.fetch_add(1, Ordering::Relaxed); }
+
+    pub fn decrement(&self) { self.0.fetch_sub(1, Ordering::Relaxed); }
+}
+
 impl Plugin for MinecraftPlugin {
     #[cfg(feature = "minecraft")]
     fn build(&self, app: &mut bevy::prelude::App) {
         let connection_mode = CONFIG.server.connection_mode.clone().into();
+        let player_count = PlayerCount::default();
+        let ban_list = BanList::load();
+        let whitelist = Whitelist::load();
 
-        app.add_plugin(ServerPlugin::new(VPCallbacks).with_connection_mode(connection_mode))
+        app.insert_resource(StartTime(Instant::now()))
+            .insert_resource(player_count.clone())
+            .add_event::<BlockChangedEvent>()
+            .add_plugin(
+                ServerPlugin::new(VPCallbacks {
+                    player_count,
+                    ban_list: ban_list.clone(),
+                    whitelist: whitelist.clone(),
+                })
+                .with_connection_mode(connection_mode)
+                .with_tick_rate(CONFIG.server.tick_rate)
+                .with_biomes(biomes())
+                .with_dimensions(dimensions()),
+            )
+            .add_plugin(CommandPlugin)
+            .add_plugin(BanPlugin { ban_list })
+            .add_plugin(BorderPlugin)
+            .add_plugin(WhitelistPlugin { whitelist })
             .add_plugin(BuildingPlugin)
+            .add_plugin(DebugPlugin)
             .add_plugin(ChatPlugin)
             .add_plugin(ClientPlugin)
+            .add_plugin(StructurePlugin)
+            .add_plugin(SchematicPlugin)
+            .add_plugin(SpawnPlugin)
+            .add_plugin(PoiPlugin)
+            .add_plugin(TimePlugin)
+            .add_plugin(WeatherPlugin)
             .add_plugin(WorldGenPlugin)
+            .add_plugin(MetricsPlugin)
             .add_system(default_event_handler.in_schedule(EventLoopSchedule));
     }
 
@@ -0,0 +1,227 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::Result;
+use bevy::prelude::Plugin;
+use valence::prelude::*;
+
+use super::command::{Command, CommandEvent, RegisterCommandExt};
+use crate::util::{read_json, write_json, LockResultExt};
+
+pub struct BanPlugin {
+    pub ban_list: BanList,
+}
+
+impl Plugin for BanPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.ban_list.clone())
+            .register_command(
+                Command::new("ban")
+                    .with_op_level(3)
+                    .with_usage("ban <player> [reason]"),
+            )
+            .register_command(
+                Command::new("unban")
+                    .with_op_level(3)
+                    .with_usage("unban <player>"),
+            )
+            .add_system(ban)
+            .add_system(unban);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct BanEntry {
+    pub uuid: Uuid,
+    pub username: String,
+    pub reason: String,
+}
+
+/// Players that aren't allowed to join, stored in `world/banned_players.json`.
+///
+/// The list is shared with [`VPCallbacks`], which checks it when a player
+/// logs in.
+///
+/// [`VPCallbacks`]: super::callbacks::VPCallbacks
+#[derive(Resource, Clone, Default, Debug)]
+pub struct BanList(Arc<RwLock<Vec<BanEntry>>>);
+
+impl BanList {
+    /// Loads the ban list, starts with an empty list if there is none.
+    #[must_use]
+    pub fn load() -> Self {
+        let entries = match read_ban_list() {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!(target: "minecraft::ban", "Unable to load the ban list: {e}");
+                vec![]
+            }
+        };
+
+        Self(Arc::new(RwLock::new(entries)))
+    }
+
+    /// Bans a player, replaces the reason if they are already banned.
+    ///
+    /// # Errors
+    /// - the ban list can't be written
+    pub fn add(&self, uuid: Uuid, username: String, reason: String) -> Result<()> {
+        let mut entries = self.0.write().ignore_poison();
+        entries.retain(|e| e.uuid != uuid);
+        entries.push(BanEntry {
+            uuid,
+            username,
+            reason,
+        });
+
+        write_ban_list(&entries)
+    }
+
+    /// Removes the ban of a player, returns the removed entry.
+    ///
+    /// # Errors
+    /// - the ban list can't be written
+    pub fn remove(&self, uuid: Uuid) -> Result<Option<BanEntry>> {
+        let mut entries = self.0.write().ignore_poison();
+        let Some(index) = entries.iter().position(|e| e.uuid == uuid) else {
+            return Ok(None);
+        };
+        let entry = entries.remove(index);
+
+        write_ban_list(&entries)?;
+        Ok(Some(entry))
+    }
+
+    #[must_use]
+    pub fn contains(&self, uuid: Uuid) -> bool { self.get(uuid).is_some() }
+
+    #[must_use]
+    pub fn get(&self, uuid: Uuid) -> Option<BanEntry> {
+        self.0
+            .read()
+            .ignore_poison()
+            .iter()
+            .find(|e| e.uuid == uuid)
+            .cloned()
+    }
+
+    /// Finds a banned player by the username they had when they were banned.
+    #[must_use]
+    pub fn find_by_username(&self, username: &str) -> Option<BanEntry> {
+        self.0
+            .read()
+            .ignore_poison()
+            .iter()
+            .find(|e| e.username.eq_ignore_ascii_case(username))
+            .cloned()
+    }
+}
+
+/// # Errors
+/// - the current directory can't be accessed
+pub fn ban_list_path() -> Result<PathBuf> {
+    Ok(std::env::current_dir()?
+        .join("world")
+        .join("banned_players.json"))
+}
+
+fn read_ban_list() -> Result<Vec<BanEntry>> {
+    Ok(read_json(&ban_list_path()?)?.unwrap_or_default())
+}
+
+fn write_ban_list(entries: &[BanEntry]) -> Result<()> { write_json(&ban_list_path()?, entries) }
+
+fn ban(
+    mut clients: Query<(Entity, &mut Client)>,
+    mut events: EventReader<CommandEvent>,
+    ban_list: Res<BanList>,
+) {
+    for event in events.iter().filter(|e| e.is("ban")) {
+        let Ok((_, sender)) = clients.get(event.client) else {
+            continue;
+        };
+        let operator = sender.username().to_string();
+
+        let Some(username) = event.arg(0) else {
+            if let Ok((_, mut sender)) = clients.get_mut(event.client) {
+                sender.send_message("Usage: ban <player> [reason]".color(Color::RED));
+            }
+            continue;
+        };
+
+        let reason = if event.args.len() > 1 {
+            event.args[1..].join(" ")
+        } else {
+            "Banned by an operator".to_string()
+        };
+
+        let Some((target, uuid, username)) = clients
+            .iter()
+            .find(|(_, c)| c.username().to_string() == username)
+            .map(|(entity, c)| (entity, c.uuid(), c.username().to_string()))
+        else {
+            if let Ok((_, mut sender)) = clients.get_mut(event.client) {
+                sender.send_message(format!("Player {username} is not online.").color(Color::RED));
+            }
+            continue;
+        };
+
+        if let Err(e) = ban_list.add(uuid, username.clone(), reason.clone()) {
+            error!(target: "minecraft::ban", "Unable to save the ban list: {e}");
+            if let Ok((_, mut sender)) = clients.get_mut(event.client) {
+                sender.send_message(format!("Unable to save the ban list: {e}").color(Color::RED));
+            }
+            continue;
+        }
+
+        if let Ok((_, mut target)) = clients.get_mut(target) {
+            target.kick(format!("You are banned: {reason}").color(Color::RED));
+        }
+        info!(target: "minecraft::ban", "{operator} banned {username}: {reason}");
+
+        for (_, mut client) in &mut clients {
+            if !client.is_disconnected() {
+                client.send_message(
+                    format!("{username} was banned by {operator}: {reason}").color(Color::YELLOW),
+                );
+            }
+        }
+    }
+}
+
+fn unban(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    ban_list: Res<BanList>,
+) {
+    for event in events.iter().filter(|e| e.is("unban")) {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let Some(username) = event.arg(0) else {
+            client.send_message("Usage: unban <player>".color(Color::RED));
+            continue;
+        };
+
+        let Some(entry) = ban_list.find_by_username(username) else {
+            client.send_message(format!("Player {username} is not banned.").color(Color::RED));
+            continue;
+        };
+
+        match ban_list.remove(entry.uuid) {
+            Ok(_) => {
+                info!(target: "minecraft::ban",
+                    "{} unbanned {}", client.username().to_string(), entry.username
+                );
+                client.send_message(format!("Unbanned {}.", entry.username).italic());
+            }
+            Err(e) => {
+                error!(target: "minecraft::ban", "Unable to save the ban list: {e}");
+                client.send_message(format!("Unable to save the ban list: {e}").color(Color::RED));
+            }
+        }
+    }
+}
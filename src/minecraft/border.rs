@@ -0,0 +1,401 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use bevy::prelude::Plugin;
+use valence::{
+    prelude::*,
+    protocol::{
+        packet::s2c::play::{
+            HealthUpdateS2c, WorldBorderCenterChangedS2c, WorldBorderInitializeS2c,
+            WorldBorderInterpolateSizeS2c,
+        },
+        var_int::VarInt,
+        var_long::VarLong,
+    },
+};
+
+use super::{
+    command::{builtin::parse_coordinate, Command, CommandEvent, RegisterCommandExt},
+    world_gen::Instances,
+    SpawnPos,
+};
+use crate::{
+    util::{read_json, write_json},
+    CONFIG,
+};
+
+/// Maximum health of a player.
+pub const MAX_HEALTH: f32 = 20.0;
+
+/// Milliseconds the client takes to move the border to a new radius.
+const RESIZE_MILLIS: i64 = 2000;
+
+/// Diameter of the vanilla world border, sent when the border is disabled.
+const VANILLA_DIAMETER: f64 = 59_999_968.0;
+
+const BORDER_USAGE: &str = "border | border set <radius> | border center <x> <z>";
+
+pub struct BorderPlugin;
+
+impl Plugin for BorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(WorldBorder::load())
+            .register_command(
+                Command::new("border")
+                    .with_op_level(3)
+                    .with_usage(BORDER_USAGE)
+                    .with_description(
+                        "Shows or changes the center and radius of the world border.",
+                    ),
+            )
+            .add_system(border_command)
+            .add_system(init_health)
+            .add_system(sync_border.after(border_command))
+            .add_system(enforce_border.after(border_command))
+            .add_system(sync_health.after(enforce_border));
+    }
+}
+
+/// The world border players in the terrain instance can't leave, stored in
+/// `world/border.json`.
+///
+/// Starts with the configured `world_border_radius` around `x=0` `z=0` if there
+/// is no saved border. Only the configured radius decides which chunks are
+/// saved.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct WorldBorder {
+    /// `x` and `z` of the center.
+    pub center: [f64; 2],
+    /// Half the side length of the border, in blocks, `None` if there is no
+    /// border.
+    pub radius: Option<f64>,
+}
+
+impl Default for WorldBorder {
+    fn default() -> Self {
+        Self {
+            center: [0.0, 0.0],
+            radius: CONFIG.world.world_border_radius,
+        }
+    }
+}
+
+impl WorldBorder {
+    /// Loads the saved border, falls back to the configured one.
+    #[must_use]
+    pub fn load() -> Self {
+        match border_path().and_then(|path| read_json(&path)) {
+            Ok(Some(border)) => border,
+            Ok(None) => Self::default(),
+            Err(e) => {
+                warn!(target: "minecraft::border", "Unable to load the world border: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    /// Distance of the column at `x` `z` to the border, `0.0` if it is inside.
+    #[must_use]
+    pub fn distance_outside(&self, x: f64, z: f64) -> f64 {
+        let Some(radius) = self.radius else {
+            return 0.0;
+        };
+
+        let dx = (x - self.center[0]).abs() - radius;
+        let dz = (z - self.center[1]).abs() - radius;
+        dx.max(dz).max(0.0)
+    }
+
+    /// Moves the position along the axes it is outside of the border on, until
+    /// it is half a block inside.
+    #[must_use]
+    pub fn clamp_inside(&self, pos: DVec3) -> DVec3 {
+        let Some(radius) = self.radius else {
+            return pos;
+        };
+
+        let limit = (radius - 0.5).max(0.0);
+        let clamp = |v: f64, center: f64| v.clamp(center - limit, center + limit);
+
+        DVec3::new(
+            clamp(pos.x, self.center[0]),
+            pos.y,
+            clamp(pos.z, self.center[1]),
+        )
+    }
+
+    /// Damage per tick of a player at `x` `z`, `factor` times the radius for
+    /// every block they are outside of the border.
+    #[must_use]
+    pub fn damage(&self, x: f64, z: f64, factor: f64) -> f64 {
+        self.radius
+            .map_or(0.0, |radius| radius * factor * self.distance_outside(x, z))
+    }
+
+    #[must_use]
+    pub fn diameter(&self) -> f64 { self.radius.map_or(VANILLA_DIAMETER, |r| r * 2.0) }
+
+    #[must_use]
+    pub fn packet(&self) -> WorldBorderInitializeS2c {
+        WorldBorderInitializeS2c {
+            x: self.center[0],
+            z: self.center[1],
+            old_diameter: self.diameter(),
+            new_diameter: self.diameter(),
+            speed: VarLong(0),
+            portal_teleport_boundary: VarInt(29_999_984),
+            warning_blocks: VarInt(5),
+            warning_time: VarInt(15),
+        }
+    }
+
+    /// # Errors
+    /// - the border can't be written
+    pub fn save(&self) -> Result<()> { write_json(&border_path()?, self) }
+}
+
+/// Health of a player, only changed by the world border.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Health(pub f32);
+
+impl Default for Health {
+    fn default() -> Self { Self(MAX_HEALTH) }
+}
+
+/// # Errors
+/// - the current directory can't be accessed
+pub fn border_path() -> Result<PathBuf> {
+    Ok(std::env::current_dir()?.join("world").join("border.json"))
+}
+
+fn border_command(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    mut border: ResMut<WorldBorder>,
+) {
+    for event in events.iter().filter(|e| e.is("border")) {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let args = event.args.iter().map(String::as_str).collect::<Vec<_>>();
+        let new_border = match args.as_slice() {
+            [] => {
+                let msg = match border.radius {
+                    Some(radius) => format!(
+                        "The world border has a radius of {radius} blocks around {} {}.",
+                        border.center[0], border.center[1]
+                    ),
+                    None => "There is no world border.".to_string(),
+                };
+                client.send_message(msg.italic());
+                continue;
+            }
+            ["set", radius] => match radius.parse::<f64>() {
+                Ok(radius) if radius.is_finite() && radius >= 1.0 => WorldBorder {
+                    radius: Some(radius),
+                    ..*border
+                },
+                _ => {
+                    client.send_message("The radius has to be at least 1.".color(Color::RED));
+                    continue;
+                }
+            },
+            ["center", x, z] => {
+                let pos = client.position();
+                match (parse_coordinate(x, pos.x), parse_coordinate(z, pos.z)) {
+                    (Some(x), Some(z)) => WorldBorder {
+                        center: [x, z],
+                        ..*border
+                    },
+                    _ => {
+                        client.send_message("Invalid coordinates.".color(Color::RED));
+                        continue;
+                    }
+                }
+            }
+            _ => {
+                client.send_message(format!("Usage: {BORDER_USAGE}").color(Color::RED));
+                continue;
+            }
+        };
+
+        if let Err(e) = new_border.save() {
+            error!(target: "minecraft::border", "Unable to save the world border: {e}");
+            client.send_message(format!("Unable to save the world border: {e}").color(Color::RED));
+            continue;
+        }
+
+        *border = new_border;
+        info!(target: "minecraft::border",
+            "{} moved the world border to {:?} with a radius of {:?}",
+            client.username().to_string(), border.center, border.radius
+        );
+        client.send_message("Changed the world border.".italic());
+    }
+}
+
+/// Sends the border to new players and changes of it to everyone.
+fn sync_border(
+    border: Res<WorldBorder>,
+    mut last: Local<Option<WorldBorder>>,
+    mut clients: Query<&mut Client>,
+) {
+    let previous = last.replace(*border);
+
+    for mut client in &mut clients {
+        if client.is_added() {
+            client.write_packet(&border.packet());
+            continue;
+        }
+
+        let Some(previous) = previous.filter(|p| p != &*border) else {
+            continue;
+        };
+
+        if previous.center != border.center {
+            client.write_packet(&WorldBorderCenterChangedS2c {
+                x_pos: border.center[0],
+                z_pos: border.center[1],
+            });
+        }
+        if previous.radius != border.radius {
+            client.write_packet(&WorldBorderInterpolateSizeS2c {
+                old_diameter: previous.diameter(),
+                new_diameter: border.diameter(),
+                speed: VarLong(RESIZE_MILLIS),
+            });
+        }
+    }
+}
+
+fn init_health(mut commands: Commands, clients: Query<Entity, Added<Client>>) {
+    for entity in &clients {
+        commands.entity(entity).insert(Health::default());
+    }
+}
+
+/// Pushes players outside of the border back in. Players in survival and
+/// adventure mode take `border_damage` times the radius as damage per tick for
+/// every block they were outside, and respawn once they run out of health.
+fn enforce_border(
+    mut clients: Query<(&mut Client, &mut Health)>,
+    border: Res<WorldBorder>,
+    instances_list: Res<Instances>,
+    spawn: Res<SpawnPos>,
+) {
+    if border.radius.is_none() {
+        return;
+    }
+
+    for (mut client, mut health) in &mut clients {
+        if client.instance() != instances_list.terrain()
+            || client.game_mode() == GameMode::Spectator
+        {
+            continue;
+        }
+
+        let pos = client.position();
+        let distance = border.distance_outside(pos.x, pos.z);
+        if distance <= 0.0 {
+            continue;
+        }
+
+        client.set_position(border.clamp_inside(pos));
+
+        if !matches!(client.game_mode(), GameMode::Survival | GameMode::Adventure) {
+            continue;
+        }
+
+        health.0 -= border.damage(pos.x, pos.z, CONFIG.world.border_damage) as f32;
+        if health.0 <= 0.0 {
+            health.0 = MAX_HEALTH;
+            client.set_position(spawn.0);
+            client.send_message("You died outside of the world border.".color(Color::RED));
+        }
+    }
+}
+
+fn sync_health(mut clients: Query<(&mut Client, &Health), Changed<Health>>) {
+    for (mut client, health) in &mut clients {
+        client.write_packet(&HealthUpdateS2c {
+            health: health.0,
+            food: VarInt(20),
+            food_saturation: 5.0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn border(radius: Option<f64>) -> WorldBorder {
+        WorldBorder {
+            center: [100.0, -50.0],
+            radius,
+        }
+    }
+
+    #[test]
+    fn distance_outside_is_measured_along_the_farthest_axis() {
+        let border = border(Some(10.0));
+
+        assert_eq!(border.distance_outside(100.0, -50.0), 0.0);
+        // On the border is still inside.
+        assert_eq!(border.distance_outside(110.0, -40.0), 0.0);
+        assert_eq!(border.distance_outside(112.5, -50.0), 2.5);
+        assert_eq!(border.distance_outside(100.0, -63.0), 3.0);
+        assert_eq!(border.distance_outside(85.0, -64.0), 5.0);
+    }
+
+    #[test]
+    fn without_a_radius_everything_is_inside() {
+        let border = border(None);
+        let pos = DVec3::new(1e7, 64.0, -1e7);
+
+        assert_eq!(border.distance_outside(pos.x, pos.z), 0.0);
+        assert_eq!(border.clamp_inside(pos), pos);
+        assert_eq!(border.damage(pos.x, pos.z, 0.2), 0.0);
+        assert_eq!(border.diameter(), VANILLA_DIAMETER);
+    }
+
+    #[test]
+    fn positions_outside_are_clamped_half_a_block_inside() {
+        let border = border(Some(10.0));
+
+        assert_eq!(
+            border.clamp_inside(DVec3::new(120.0, 64.0, -50.0)),
+            DVec3::new(109.5, 64.0, -50.0)
+        );
+        assert_eq!(
+            border.clamp_inside(DVec3::new(80.0, 70.0, -80.0)),
+            DVec3::new(90.5, 70.0, -59.5)
+        );
+        // Positions inside don't move.
+        let inside = DVec3::new(105.0, 64.0, -45.0);
+        assert_eq!(border.clamp_inside(inside), inside);
+
+        let clamped = border.clamp_inside(DVec3::new(1e9, 0.0, -1e9));
+        assert_eq!(border.distance_outside(clamped.x, clamped.z), 0.0);
+    }
+
+    #[test]
+    fn tiny_borders_clamp_to_the_center() {
+        assert_eq!(
+            border(Some(0.25)).clamp_inside(DVec3::new(0.0, 64.0, 0.0)),
+            DVec3::new(100.0, 64.0, -50.0)
+        );
+    }
+
+    #[test]
+    fn damage_grows_linearly_with_the_distance_outside() {
+        let border = border(Some(10.0));
+
+        assert_eq!(border.damage(105.0, -50.0, 0.2), 0.0);
+        assert_eq!(border.damage(111.0, -50.0, 0.2), 2.0);
+        assert_eq!(border.damage(113.0, -50.0, 0.2), 6.0);
+        // The damage scales with the radius of the border.
+        assert_eq!(border(Some(20.0)).damage(123.0, -50.0, 0.2), 12.0);
+    }
+}
@@ -2,67 +2,379 @@ use bevy::prelude::Plugin;
 use valence::{
     client::event::{PlayerInteractBlock, StartDigging, StopDestroyBlock},
     prelude::*,
-    protocol::types::Hand,
+    protocol::{packet::s2c::play::BlockUpdateS2c, types::Hand, var_int::VarInt},
 };
 
-use super::world_gen::Instances;
+use super::{
+    client::PlayerStats,
+    command::{builtin::parse_coordinate, Command, CommandEvent, RegisterCommandExt},
+    world_gen::{BlockEntities, Instances, ModifiedChunks},
+    SpawnPos,
+};
+use crate::CONFIG;
+
+/// Number of blocks a fill operation places per tick.
+pub const FILL_BLOCKS_PER_TICK: u64 = 4096;
+
+/// A progress message is sent every time this many blocks were placed.
+const FILL_PROGRESS_INTERVAL: u64 = 1000;
 
 pub struct BuildingPlugin;
 
 impl Plugin for BuildingPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_system(digging_creative_mode.in_schedule(EventLoopSchedule))
-            .add_system(digging_survival_mode.in_schedule(EventLoopSchedule))
-            .add_system(place_blocks.in_schedule(EventLoopSchedule));
+        app.register_command(
+            Command::new("fill")
+                .with_op_level(3)
+                .with_usage("fill <x1> <y1> <z1> <x2> <y2> <z2> <block> | fill cancel"),
+        )
+        .add_system(digging_creative_mode.in_schedule(EventLoopSchedule))
+        .add_system(digging_survival_mode.in_schedule(EventLoopSchedule))
+        .add_event::<UseBlockEvent>()
+        .add_system(place_blocks.in_schedule(EventLoopSchedule))
+        .add_system(fill)
+        .add_system(process_fill_operations.after(fill));
+    }
+}
+
+/// Sent when a player changes a block of the terrain. Generated chunks are
+/// reported as a whole with a
+/// [`ChunkGeneratedEvent`](super::world_gen::ChunkGeneratedEvent).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockChangedEvent {
+    pub pos: BlockPos,
+    pub old_state: BlockState,
+    pub new_state: BlockState,
+    pub cause: BlockChangeCause,
+}
+
+/// What changed the block of a [`BlockChangedEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockChangeCause {
+    /// The player placed the block.
+    PlayerPlace(Entity),
+    /// The player broke the block.
+    PlayerBreak(Entity),
+}
+
+/// Fills a cuboid with a block, spread over multiple ticks. Attached to the
+/// player that started it.
+#[derive(Component, Debug)]
+pub struct FillOperation {
+    pub min: BlockPos,
+    pub max: BlockPos,
+    pub block: BlockState,
+    /// Index of the next block to place, blocks are placed x first, then z,
+    /// then y.
+    pub next: u64,
+}
+
+impl FillOperation {
+    #[must_use]
+    pub fn new(a: BlockPos, b: BlockPos, block: BlockState) -> Self {
+        Self {
+            min: BlockPos::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: BlockPos::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+            block,
+            next: 0,
+        }
+    }
+
+    /// Like [`FillOperation::new`], but refuses cuboids with more than
+    /// `max_volume` blocks.
+    ///
+    /// # Errors
+    /// - the cuboid contains more than `max_volume` blocks
+    pub fn with_max_volume(
+        a: BlockPos,
+        b: BlockPos,
+        block: BlockState,
+        max_volume: u64,
+    ) -> Result<Self, String> {
+        let operation = Self::new(a, b, block);
+        match operation.volume() {
+            Some(volume) if volume <= max_volume => Ok(operation),
+            Some(volume) => Err(format!(
+                "Can't fill {volume} blocks, the maximum is {max_volume}."
+            )),
+            None => Err(format!("Can't fill more than {max_volume} blocks.")),
+        }
+    }
+
+    fn size(&self) -> [u64; 3] {
+        [
+            (i64::from(self.max.x) - i64::from(self.min.x) + 1) as u64,
+            (i64::from(self.max.y) - i64::from(self.min.y) + 1) as u64,
+            (i64::from(self.max.z) - i64::from(self.min.z) + 1) as u64,
+        ]
+    }
+
+    /// Number of blocks in the cuboid, `None` if it doesn't fit into a `u64`.
+    #[must_use]
+    pub fn volume(&self) -> Option<u64> {
+        let [x, y, z] = self.size();
+        x.checked_mul(y)?.checked_mul(z)
+    }
+
+    #[must_use]
+    pub fn total(&self) -> u64 { self.volume().unwrap_or(u64::MAX) }
+
+    #[must_use]
+    pub fn is_done(&self) -> bool { self.next >= self.total() }
+
+    /// Position of the block with the given index.
+    #[must_use]
+    pub fn pos(&self, index: u64) -> BlockPos {
+        let [size_x, _, size_z] = self.size();
+
+        BlockPos::new(
+            self.min.x + (index % size_x) as i32,
+            self.min.y + (index / (size_x * size_z)) as i32,
+            self.min.z + (index / size_x % size_z) as i32,
+        )
+    }
+
+    /// Places up to `max_blocks` blocks, returns the number of placed blocks.
+    pub fn step(&mut self, instance: &mut Instance, max_blocks: u64) -> u64 {
+        let end = (self.next + max_blocks).min(self.total());
+        let placed = end - self.next;
+
+        for index in self.next..end {
+            instance.set_block(self.pos(index), self.block);
+        }
+        self.next = end;
+
+        placed
+    }
+}
+
+/// Blocks within `radius` blocks of the spawn, only operators can change them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpawnProtection {
+    pub spawn: DVec3,
+    pub radius: u32,
+}
+
+impl SpawnProtection {
+    /// Protects the blocks within `spawn_protection_radius` of the spawn.
+    #[must_use]
+    pub fn new(spawn: &SpawnPos) -> Self {
+        Self {
+            spawn: spawn.0,
+            radius: CONFIG.world.spawn_protection_radius,
+        }
     }
 }
 
+/// Whether a player with the given op level isn't allowed to change the block,
+/// because it is within the spawn protection. Players with op level 2 or
+/// higher can change every block.
+#[must_use]
+pub fn is_spawn_protected(pos: BlockPos, protection: SpawnProtection, op_level: u8) -> bool {
+    let radius = f64::from(protection.radius);
+    if op_level >= 2 || radius == 0.0 {
+        return false;
+    }
+
+    let dx = f64::from(pos.x) + 0.5 - protection.spawn.x;
+    let dz = f64::from(pos.z) + 0.5 - protection.spawn.z;
+    dx * dx + dz * dz <= radius * radius
+}
+
+/// The change of a player with the given op level replacing `old_state` at
+/// `pos` with `new_state`, `None` if the block is protected.
+#[must_use]
+pub fn player_block_change(
+    pos: BlockPos,
+    old_state: BlockState,
+    new_state: BlockState,
+    cause: BlockChangeCause,
+    protection: SpawnProtection,
+    op_level: u8,
+) -> Option<BlockChangedEvent> {
+    if is_spawn_protected(pos, protection, op_level) {
+        return None;
+    }
+
+    Some(BlockChangedEvent {
+        pos,
+        old_state,
+        new_state,
+        cause,
+    })
+}
+
+fn block_state(instance: &Instance, pos: BlockPos) -> BlockState {
+    instance
+        .block(pos)
+        .map_or(BlockState::AIR, |block| block.state())
+}
+
+/// Sends the actual block at `pos` to the client, undoing the change it
+/// already shows.
+fn deny_protected(client: &mut Client, instance: &Instance, pos: BlockPos) {
+    client.write_packet(&BlockUpdateS2c {
+        position: pos,
+        block_id: VarInt(i32::from(block_state(instance, pos).to_raw())),
+    });
+    client.send_message("That block is protected.".color(Color::RED));
+}
+
 fn digging_creative_mode(
-    clients: Query<&Client>,
+    mut clients: Query<(&mut Client, &mut PlayerStats)>,
     mut instances: Query<&mut Instance>,
     instances_list: Res<Instances>,
     mut events: EventReader<StartDigging>,
+    mut modified: ResMut<ModifiedChunks>,
+    mut block_entities: ResMut<BlockEntities>,
+    mut changed: EventWriter<BlockChangedEvent>,
+    spawn: Res<SpawnPos>,
 ) {
-    let mut instance = instances.get_mut(instances_list.terrain).unwrap();
+    let mut instance = instances.get_mut(instances_list.terrain()).unwrap();
 
     for event in events.iter() {
-        let Ok(client) = clients.get_component::<Client>(event.client) else {
+        let Ok((mut client, mut stats)) = clients.get_mut(event.client) else {
             continue;
         };
         if client.game_mode() == GameMode::Creative {
-            instance.set_block(event.position, BlockState::AIR);
+            let Some(change) = player_block_change(
+                event.position,
+                block_state(&instance, event.position),
+                BlockState::AIR,
+                BlockChangeCause::PlayerBreak(event.client),
+                SpawnProtection::new(&spawn),
+                client.op_level(),
+            ) else {
+                deny_protected(&mut client, &instance, event.position);
+                continue;
+            };
+
+            instance.set_block(change.pos, change.new_state);
+            modified.mark(change.pos);
+            block_entities.remove(change.pos);
+            changed.send(change);
+            stats.blocks_broken += 1;
         }
     }
 }
 
 fn digging_survival_mode(
-    clients: Query<&Client>,
+    mut clients: Query<(&mut Client, &mut PlayerStats)>,
     mut instances: Query<&mut Instance>,
     instances_list: Res<Instances>,
     mut events: EventReader<StopDestroyBlock>,
+    mut modified: ResMut<ModifiedChunks>,
+    mut block_entities: ResMut<BlockEntities>,
+    mut changed: EventWriter<BlockChangedEvent>,
+    spawn: Res<SpawnPos>,
 ) {
-    let mut instance = instances.get_mut(instances_list.terrain).unwrap();
+    let mut instance = instances.get_mut(instances_list.terrain()).unwrap();
 
     for event in events.iter() {
-        let Ok(client) = clients.get_component::<Client>(event.client) else {
+        let Ok((mut client, mut stats)) = clients.get_mut(event.client) else {
             continue;
         };
         if client.game_mode() == GameMode::Survival {
-            instance.set_block(event.position, BlockState::AIR);
+            let Some(change) = player_block_change(
+                event.position,
+                block_state(&instance, event.position),
+                BlockState::AIR,
+                BlockChangeCause::PlayerBreak(event.client),
+                SpawnProtection::new(&spawn),
+                client.op_level(),
+            ) else {
+                deny_protected(&mut client, &instance, event.position);
+                continue;
+            };
+
+            instance.set_block(change.pos, change.new_state);
+            modified.mark(change.pos);
+            block_entities.remove(change.pos);
+            changed.send(change);
+            stats.blocks_broken += 1;
         }
     }
 }
 
+/// Sent when a player right clicks an interactable block, like a chest or a
+/// door, instead of placing a block against it.
+#[derive(Debug, Clone, Copy)]
+pub struct UseBlockEvent {
+    pub client: Entity,
+    pub position: BlockPos,
+    pub block: BlockState,
+}
+
+/// What right clicking a block does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interaction {
+    /// Places the held block against the clicked block.
+    Place(BlockKind),
+    /// Uses the clicked block, e.g. opens a chest.
+    Use,
+    /// The held item can't be placed and the clicked block can't be used.
+    NotPlaceable,
+    /// The player doesn't hold anything and the clicked block can't be used.
+    Ignore,
+}
+
+/// Decides what right clicking `target` with `held` in the main hand does.
+/// Like in vanilla, using a block takes precedence over placing one.
+#[must_use]
+pub fn classify_interaction(held: Option<ItemKind>, target: BlockState) -> Interaction {
+    if is_interactable(target.to_kind()) {
+        return Interaction::Use;
+    }
+
+    match held {
+        Some(item) => item
+            .to_block_kind()
+            .map_or(Interaction::NotPlaceable, Interaction::Place),
+        None => Interaction::Ignore,
+    }
+}
+
+/// Blocks that do something when they are right clicked.
+#[must_use]
+pub fn is_interactable(kind: BlockKind) -> bool {
+    matches!(
+        kind,
+        BlockKind::Chest
+            | BlockKind::TrappedChest
+            | BlockKind::EnderChest
+            | BlockKind::Barrel
+            | BlockKind::CraftingTable
+            | BlockKind::Furnace
+            | BlockKind::BlastFurnace
+            | BlockKind::Smoker
+            | BlockKind::Anvil
+            | BlockKind::EnchantingTable
+            | BlockKind::Hopper
+            | BlockKind::Dispenser
+            | BlockKind::Dropper
+            | BlockKind::Lever
+            | BlockKind::StoneButton
+            | BlockKind::OakButton
+            | BlockKind::OakDoor
+            | BlockKind::OakTrapdoor
+            | BlockKind::OakFenceGate
+    )
+}
+
 fn place_blocks(
-    mut clients: Query<(&Client, &mut Inventory)>,
+    mut clients: Query<(&mut Client, &mut Inventory, &mut PlayerStats)>,
     mut instances: Query<&mut Instance>,
     instances_list: Res<Instances>,
     mut events: EventReader<PlayerInteractBlock>,
+    mut use_events: EventWriter<UseBlockEvent>,
+    mut modified: ResMut<ModifiedChunks>,
+    mut changed: EventWriter<BlockChangedEvent>,
+    spawn: Res<SpawnPos>,
 ) {
-    let mut instance = instances.get_mut(instances_list.terrain).unwrap();
+    let mut instance = instances.get_mut(instances_list.terrain()).unwrap();
 
     for event in events.iter() {
-        let Ok((client, mut inventory)) = clients.get_mut(event.client) else {
+        let Ok((mut client, mut inventory, mut stats)) = clients.get_mut(event.client) else {
             continue;
         };
         if event.hand != Hand::Main {
@@ -71,13 +383,41 @@ fn place_blocks(
 
         // get the held item
         let slot_id = client.held_item_slot();
-        let Some(stack) = inventory.slot(slot_id) else {
-            // no item in the slot
+        let stack = inventory.slot(slot_id);
+        let target = block_state(&instance, event.position);
+
+        let block_kind = match classify_interaction(stack.map(|s| s.item), target) {
+            Interaction::Place(block_kind) => block_kind,
+            Interaction::Use => {
+                use_events.send(UseBlockEvent {
+                    client: event.client,
+                    position: event.position,
+                    block: target,
+                });
+                continue;
+            }
+            Interaction::NotPlaceable => {
+                if CONFIG.server.placement_feedback {
+                    client.set_action_bar("This item can't be placed.".color(Color::GRAY));
+                }
+                continue;
+            }
+            Interaction::Ignore => continue,
+        };
+        let Some(stack) = stack else {
             continue;
         };
 
-        let Some(block_kind) = stack.item.to_block_kind() else {
-            // can't place this item as a block
+        let real_pos = event.position.get_in_direction(event.direction);
+        let Some(change) = player_block_change(
+            real_pos,
+            block_state(&instance, real_pos),
+            block_kind.to_state(),
+            BlockChangeCause::PlayerPlace(event.client),
+            SpawnProtection::new(&spawn),
+            client.op_level(),
+        ) else {
+            deny_protected(&mut client, &instance, real_pos);
             continue;
         };
 
@@ -93,7 +433,325 @@ fn place_blocks(
             };
             let _ = inventory.replace_slot(slot_id, slot);
         }
-        let real_pos = event.position.get_in_direction(event.direction);
-        instance.set_block(real_pos, block_kind.to_state());
+        instance.set_block(change.pos, change.new_state);
+        modified.mark(change.pos);
+        changed.send(change);
+        stats.blocks_placed += 1;
+    }
+}
+
+/// Parses a block name like `stone` or `minecraft:stone`.
+#[must_use]
+pub fn parse_block(name: &str) -> Option<BlockState> {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    BlockKind::from_str(name).map(BlockKind::to_state)
+}
+
+fn fill(
+    mut commands: Commands,
+    mut clients: Query<(&mut Client, Option<&FillOperation>)>,
+    mut events: EventReader<CommandEvent>,
+) {
+    for event in events.iter().filter(|e| e.is("fill")) {
+        let Ok((mut client, running)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        if event.arg(0) == Some("cancel") {
+            if let Some(operation) = running {
+                commands.entity(event.client).remove::<FillOperation>();
+                client.send_message(
+                    format!(
+                        "Cancelled fill after {}/{} blocks.",
+                        operation.next,
+                        operation.total()
+                    )
+                    .italic(),
+                );
+            } else {
+                client.send_message("No fill operation is running.".italic());
+            }
+            continue;
+        }
+
+        if running.is_some() {
+            client.send_message(
+                "A fill operation is already running, cancel it with fill cancel."
+                    .color(Color::RED),
+            );
+            continue;
+        }
+
+        let [x1, y1, z1, x2, y2, z2, block] = event.args.as_slice() else {
+            client.send_message(
+                "Usage: fill <x1> <y1> <z1> <x2> <y2> <z2> <block> | fill cancel".italic(),
+            );
+            continue;
+        };
+
+        let current = client.position();
+        let corner = |x: &str, y: &str, z: &str| {
+            Some(BlockPos::new(
+                parse_coordinate(x, current.x)?.floor() as i32,
+                parse_coordinate(y, current.y)?.floor() as i32,
+                parse_coordinate(z, current.z)?.floor() as i32,
+            ))
+        };
+
+        let (Some(a), Some(b)) = (corner(x1, y1, z1), corner(x2, y2, z2)) else {
+            client.send_message("Invalid coordinates.".color(Color::RED));
+            continue;
+        };
+
+        let Some(block) = parse_block(block) else {
+            client.send_message(format!("Unknown block: {block}").color(Color::RED));
+            continue;
+        };
+
+        let operation =
+            match FillOperation::with_max_volume(a, b, block, CONFIG.world.max_fill_volume) {
+                Ok(operation) => operation,
+                Err(msg) => {
+                    client.send_message(msg.color(Color::RED));
+                    continue;
+                }
+            };
+        client.send_message(format!("Filling {} blocks...", operation.total()).italic());
+        commands.entity(event.client).insert(operation);
+    }
+}
+
+fn process_fill_operations(
+    mut commands: Commands,
+    mut clients: Query<(Entity, &mut Client, &mut FillOperation)>,
+    mut instances: Query<&mut Instance>,
+    instances_list: Res<Instances>,
+    mut modified: ResMut<ModifiedChunks>,
+) {
+    let mut instance = instances.get_mut(instances_list.terrain()).unwrap();
+
+    for (entity, mut client, mut operation) in &mut clients {
+        let before = operation.next;
+        operation.step(&mut instance, FILL_BLOCKS_PER_TICK);
+        modified.mark_area(operation.min, operation.max);
+
+        if operation.is_done() {
+            client.send_message(format!("Filled {} blocks.", operation.total()).italic());
+            commands.entity(entity).remove::<FillOperation>();
+        } else if operation.next / FILL_PROGRESS_INTERVAL > before / FILL_PROGRESS_INTERVAL {
+            client.send_message(
+                format!("Filled {}/{} blocks.", operation.next, operation.total())
+                    .color(Color::GRAY),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+
+    const PLAYER: Entity = Entity::from_raw(1);
+
+    fn protection() -> SpawnProtection {
+        SpawnProtection {
+            spawn: DVec3::new(0.5, 64.0, 0.5),
+            radius: 16,
+        }
+    }
+
+    /// Op level of Bob, who isn't in the ops list.
+    fn not_an_op() -> u8 { ServerConfig::default().op_level(Uuid::from_u128(2), "Bob") }
+
+    fn op() -> u8 {
+        let config = ServerConfig {
+            ops: [("Bob".to_string(), 2)].into(),
+            ..Default::default()
+        };
+        config.op_level(Uuid::from_u128(2), "Bob")
+    }
+
+    #[test]
+    fn players_that_are_not_ops_cant_change_blocks_near_the_spawn() {
+        let near = BlockPos::new(10, 64, -10);
+
+        assert_eq!(
+            player_block_change(
+                near,
+                BlockState::STONE,
+                BlockState::AIR,
+                BlockChangeCause::PlayerBreak(PLAYER),
+                protection(),
+                not_an_op(),
+            ),
+            None
+        );
+        assert_eq!(
+            player_block_change(
+                near,
+                BlockState::AIR,
+                BlockState::DIRT,
+                BlockChangeCause::PlayerPlace(PLAYER),
+                protection(),
+                not_an_op(),
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn players_that_are_not_ops_can_change_blocks_outside_the_protection() {
+        let far = BlockPos::new(17, 64, 0);
+
+        assert!(player_block_change(
+            far,
+            BlockState::STONE,
+            BlockState::AIR,
+            BlockChangeCause::PlayerBreak(PLAYER),
+            protection(),
+            not_an_op(),
+        )
+        .is_some());
+
+        // Without a radius nothing is protected.
+        let disabled = SpawnProtection {
+            radius: 0,
+            ..protection()
+        };
+        assert!(!is_spawn_protected(
+            BlockPos::new(0, 64, 0),
+            disabled,
+            not_an_op()
+        ));
+    }
+
+    #[test]
+    fn ops_can_change_blocks_near_the_spawn() {
+        assert!(player_block_change(
+            BlockPos::new(0, 64, 0),
+            BlockState::STONE,
+            BlockState::AIR,
+            BlockChangeCause::PlayerBreak(PLAYER),
+            protection(),
+            op(),
+        )
+        .is_some());
+    }
+
+    #[test]
+    fn block_changes_contain_the_old_and_new_state() {
+        let pos = BlockPos::new(20, 64, 0);
+
+        assert_eq!(
+            player_block_change(
+                pos,
+                BlockState::STONE,
+                BlockState::AIR,
+                BlockChangeCause::PlayerBreak(PLAYER),
+                protection(),
+                not_an_op(),
+            ),
+            Some(BlockChangedEvent {
+                pos,
+                old_state: BlockState::STONE,
+                new_state: BlockState::AIR,
+                cause: BlockChangeCause::PlayerBreak(PLAYER),
+            })
+        );
+        assert_eq!(
+            player_block_change(
+                pos,
+                BlockState::AIR,
+                BlockState::OAK_PLANKS,
+                BlockChangeCause::PlayerPlace(PLAYER),
+                protection(),
+                not_an_op(),
+            ),
+            Some(BlockChangedEvent {
+                pos,
+                old_state: BlockState::AIR,
+                new_state: BlockState::OAK_PLANKS,
+                cause: BlockChangeCause::PlayerPlace(PLAYER),
+            })
+        );
+    }
+
+    #[test]
+    fn blocks_are_placed_against_non_interactable_blocks() {
+        for target in [BlockState::STONE, BlockState::GRASS_BLOCK, BlockState::AIR] {
+            assert_eq!(
+                classify_interaction(Some(ItemKind::Stone), target),
+                Interaction::Place(BlockKind::Stone)
+            );
+            // Interactable blocks can be placed too.
+            assert_eq!(
+                classify_interaction(Some(ItemKind::Chest), target),
+                Interaction::Place(BlockKind::Chest)
+            );
+        }
+    }
+
+    #[test]
+    fn using_interactable_blocks_takes_precedence_over_placing() {
+        for target in [
+            BlockState::CHEST,
+            BlockState::CRAFTING_TABLE,
+            BlockState::OAK_DOOR,
+            BlockState::LEVER,
+        ] {
+            assert_eq!(
+                classify_interaction(Some(ItemKind::Stone), target),
+                Interaction::Use
+            );
+            assert_eq!(
+                classify_interaction(Some(ItemKind::Stick), target),
+                Interaction::Use
+            );
+            assert_eq!(classify_interaction(None, target), Interaction::Use);
+        }
+    }
+
+    #[test]
+    fn fills_larger_than_the_maximum_volume_are_refused() {
+        let a = BlockPos::new(0, 0, 0);
+        let b = BlockPos::new(9, 9, 9);
+
+        let operation = FillOperation::with_max_volume(a, b, BlockState::STONE, 1000).unwrap();
+        assert_eq!(operation.total(), 1000);
+        assert_eq!(
+            FillOperation::with_max_volume(a, b, BlockState::STONE, 999).unwrap_err(),
+            "Can't fill 1000 blocks, the maximum is 999."
+        );
+    }
+
+    #[test]
+    fn fill_volumes_near_the_world_edge_dont_overflow() {
+        let edge = FillOperation::new(
+            BlockPos::new(-30_000_000, -64, -30_000_000),
+            BlockPos::new(30_000_000, 319, 30_000_000),
+            BlockState::STONE,
+        );
+        assert_eq!(edge.volume(), Some(60_000_001 * 60_000_001 * 384));
+
+        let a = BlockPos::new(i32::MIN, i32::MIN, i32::MIN);
+        let b = BlockPos::new(i32::MAX, i32::MAX, i32::MAX);
+        assert_eq!(FillOperation::new(a, b, BlockState::STONE).volume(), None);
+        assert_eq!(
+            FillOperation::with_max_volume(a, b, BlockState::STONE, 1_000_000).unwrap_err(),
+            "Can't fill more than 1000000 blocks."
+        );
+    }
+
+    #[test]
+    fn items_without_a_block_are_not_placeable() {
+        assert_eq!(
+            classify_interaction(Some(ItemKind::Stick), BlockState::STONE),
+            Interaction::NotPlaceable
+        );
+        assert_eq!(
+            classify_interaction(None, BlockState::STONE),
+            Interaction::Ignore
+        );
     }
 }
@@ -2,10 +2,15 @@ use std::net::SocketAddr;
 
 use valence::prelude::*;
 
-use crate::{CONFIG, PLAYER_COUNT};
+use super::{ban::BanList, whitelist::Whitelist, PlayerCount};
+use crate::{config::DEFAULT_TICK_RATE, CONFIG};
 
 #[derive(Default)]
-pub struct VPCallbacks;
+pub struct VPCallbacks {
+    pub player_count: PlayerCount,
+    pub ban_list: BanList,
+    pub whitelist: Whitelist,
+}
 
 #[async_trait]
 impl AsyncCallbacks for VPCallbacks {
@@ -18,20 +23,285 @@ impl AsyncCallbacks for VPCallbacks {
         _protocol_version: i32,
     ) -> ServerListPing {
         ServerListPing::Respond {
-            online_players: *PLAYER_COUNT.lock().unwrap() as i32,
+            online_players: self.player_count.get() as i32,
             max_players: CONFIG.server.max_connections as i32,
             player_sample: vec![],
-            description: "Just a minecraft server".color(Color::WHITE),
+            description: describe_server(),
             favicon_png: include_bytes!("../../assets/logo-64x64.png"),
         }
     }
 
-    async fn login(&self, _shared: &SharedServer, _info: &NewClientInfo) -> Result<(), Text> {
+    async fn login(&self, _shared: &SharedServer, info: &NewClientInfo) -> Result<(), Text> {
         // return Err("You are not meant to join this example".color(Color::RED));
 
-        if CONFIG.server.max_connections > *PLAYER_COUNT.lock().unwrap() {
+        if let Some(ban) = self.ban_list.get(info.uuid) {
+            return Err(format!("You are banned: {}", ban.reason).color(Color::RED));
+        }
+
+        if !self.whitelist.allows(info.uuid, &info.username) {
+            return Err(CONFIG.server.whitelist_message.clone().color(Color::RED));
+        }
+
+        if CONFIG.server.max_connections > self.player_count.get() {
             return Ok(());
         }
         return Err("Server full".color(Color::RED));
     }
 }
+
+/// The configured description, with the tick rate appended if it isn't the
+/// vanilla one.
+fn describe_server() -> Text {
+    let motd = parse_motd(&CONFIG.server.motd);
+
+    if CONFIG.server.tick_rate == DEFAULT_TICK_RATE {
+        return motd;
+    }
+
+    motd + format!("\n{} TPS", CONFIG.server.tick_rate).color(Color::GRAY)
+}
+
+/// Builds the server description from the configured lines, falls back to a
+/// default description if there are none.
+///
+/// Lines starting with `{` or `[` are parsed as JSON text, all other lines can
+/// contain `&` color (`&0`-`&f`) and formatting codes (`&k`-`&o`, `&r` to
+/// reset).
+#[must_use]
+pub fn parse_motd(lines: &[String]) -> Text {
+    if lines.is_empty() {
+        return "Just a minecraft server".color(Color::WHITE);
+    }
+
+    let mut motd = Text::default();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            motd += "\n";
+        }
+
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('{') || trimmed.starts_with('[') {
+            if let Ok(text) = serde_json::from_str::<Text>(trimmed) {
+                motd += text;
+                continue;
+            }
+        }
+
+        motd += parse_color_codes(line);
+    }
+
+    motd
+}
+
+#[derive(Default, Clone, Copy)]
+struct Style {
+    color: Option<Color>,
+    obfuscated: bool,
+    bold: bool,
+    strikethrough: bool,
+    underlined: bool,
+    italic: bool,
+}
+
+impl Style {
+    fn apply(self, text: String) -> Text {
+        let mut text = text.into_text();
+
+        if let Some(color) = self.color {
+            text = text.color(color);
+        }
+        if self.obfuscated {
+            text = text.obfuscated();
+        }
+        if self.bold {
+            text = text.bold();
+        }
+        if self.strikethrough {
+            text = text.strikethrough();
+        }
+        if self.underlined {
+            text = text.underlined();
+        }
+        if self.italic {
+            text = text.italic();
+        }
+
+        text
+    }
+}
+
+fn parse_color_codes(line: &str) -> Text {
+    let mut text = Text::default();
+    let mut style = Style::default();
+    let mut segment = String::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let code = match chars.peek() {
+            Some(code) if c == '&' => code.to_ascii_lowercase(),
+            _ => {
+                segment.push(c);
+                continue;
+            }
+        };
+
+        let mut new_style = style;
+        match code {
+            '0'..='9' | 'a'..='f' => {
+                // Colors reset the formatting, like in vanilla.
+                new_style = Style {
+                    color: Some(color_code(code)),
+                    ..Style::default()
+                };
+            }
+            'k' => new_style.obfuscated = true,
+            'l' => new_style.bold = true,
+            'm' => new_style.strikethrough = true,
+            'n' => new_style.underlined = true,
+            'o' => new_style.italic = true,
+            'r' => new_style = Style::default(),
+            // Not a code, keep the `&`.
+            _ => {
+                segment.push(c);
+                continue;
+            }
+        }
+        chars.next();
+
+        if !segment.is_empty() {
+            text += style.apply(std::mem::take(&mut segment));
+        }
+        style = new_style;
+    }
+
+    if !segment.is_empty() {
+        text += style.apply(segment);
+    }
+
+    text
+}
+
+fn color_code(code: char) -> Color {
+    match code {
+        '0' => Color::BLACK,
+        '1' => Color::DARK_BLUE,
+        '2' => Color::DARK_GREEN,
+        '3' => Color::DARK_AQUA,
+        '4' => Color::DARK_RED,
+        '5' => Color::DARK_PURPLE,
+        '6' => Color::GOLD,
+        '7' => Color::GRAY,
+        '8' => Color::DARK_GRAY,
+        '9' => Color::BLUE,
+        'a' => Color::GREEN,
+        'b' => Color::AQUA,
+        'c' => Color::RED,
+        'd' => Color::LIGHT_PURPLE,
+        'e' => Color::YELLOW,
+        _ => Color::WHITE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(segments: impl IntoIterator<Item = Text>) -> Text {
+        let mut text = Text::default();
+        for segment in segments {
+            text += segment;
+        }
+        text
+    }
+
+    #[test]
+    fn lines_without_codes_are_plain_text() {
+        assert_eq!(parse_color_codes("Hello"), text(["Hello".into_text()]));
+        assert_eq!(parse_color_codes(""), Text::default());
+    }
+
+    #[test]
+    fn codes_style_the_following_text() {
+        assert_eq!(
+            parse_color_codes("&cHello &lWorld"),
+            text(["Hello ".color(Color::RED), "World".color(Color::RED).bold()])
+        );
+        // Codes are case insensitive.
+        assert_eq!(parse_color_codes("&CHello"), parse_color_codes("&cHello"));
+    }
+
+    #[test]
+    fn colors_and_resets_clear_the_formatting() {
+        assert_eq!(
+            parse_color_codes("&l&oA&9B&m&nC&rD"),
+            text([
+                "A".into_text().bold().italic(),
+                "B".color(Color::BLUE),
+                "C".color(Color::BLUE).strikethrough().underlined(),
+                "D".into_text(),
+            ])
+        );
+        assert_eq!(
+            parse_color_codes("&kX"),
+            text(["X".into_text().obfuscated()])
+        );
+    }
+
+    #[test]
+    fn ampersands_without_a_code_are_kept() {
+        assert_eq!(
+            parse_color_codes("Tom & Jerry &z 100%&"),
+            text(["Tom & Jerry &z 100%&".into_text()])
+        );
+    }
+
+    #[test]
+    fn every_color_code_has_its_color() {
+        let colors = "0123456789abcdef"
+            .chars()
+            .map(color_code)
+            .collect::<Vec<_>>();
+
+        assert_eq!(colors[0], Color::BLACK);
+        assert_eq!(colors[6], Color::GOLD);
+        assert_eq!(colors[15], Color::WHITE);
+        for (i, color) in colors.iter().enumerate() {
+            assert!(!colors[..i].contains(color), "code {i:x}");
+        }
+    }
+
+    #[test]
+    fn motd_lines_are_joined() {
+        let lines = ["&6Valence".to_string(), "Playground".to_string()];
+
+        let mut expected = Text::default();
+        expected += parse_color_codes("&6Valence");
+        expected += "\n";
+        expected += parse_color_codes("Playground");
+        assert_eq!(parse_motd(&lines), expected);
+    }
+
+    #[test]
+    fn empty_motd_uses_the_default_description() {
+        assert_eq!(
+            parse_motd(&[]),
+            "Just a minecraft server".color(Color::WHITE)
+        );
+    }
+
+    #[test]
+    fn json_motd_lines_are_parsed() {
+        let json = r#"{"text":"Hi","color":"gold"}"#;
+        let lines = [format!("  {json}"), "{not json &cat all".to_string()];
+
+        let mut expected = Text::default();
+        expected += serde_json::from_str::<Text>(json).unwrap();
+        expected += "\n";
+        expected += parse_color_codes("{not json &cat all");
+        assert_eq!(parse_motd(&lines), expected);
+
+        let mut gold = Text::default();
+        gold += "Hi".color(Color::GOLD);
+        assert_eq!(parse_motd(&[json.to_string()]), gold);
+    }
+}
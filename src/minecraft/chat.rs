@@ -1,26 +1,83 @@
+use std::collections::VecDeque;
+
 use bevy::{
     prelude::{Plugin, Query},
 };
-use valence::{
-    client::event::{ChatMessage, CommandExecution},
-    prelude::*,
-    server::EventLoopSchedule,
-};
+use valence::{client::event::ChatMessage, prelude::*, server::EventLoopSchedule};
+
+use super::client::{init_clients, DisplayName, PlayerStats};
+use crate::CONFIG;
 
-use super::world_gen::Instances;
-use crate::SPAWN_POS;
+/// Number of the latest chat messages shown to players when they join.
+pub const CHAT_REPLAY_MESSAGES: usize = 10;
 
 #[allow(dead_code)]
 pub enum Message {
-    ChatMessage(ChatMessage),
+    /// A message of a player, with the name it was shown with.
+    ChatMessage(ChatMessage, Text),
     ServerMessage(Text),
 }
 
-#[derive(Resource, Default)]
-pub struct ChatMessages(pub Vec<Message>);
+impl Message {
+    /// The message as it is shown in the chat.
+    #[must_use]
+    pub fn text(&self) -> Text {
+        match self {
+            Message::ChatMessage(msg, sender) => {
+                sender.clone() + ": ".into_text() + msg.message.to_string().color(Color::WHITE)
+            }
+            Message::ServerMessage(msg) => "[Server]: ".color(Color::GRAY) + msg.clone(),
+        }
+    }
+}
+
+/// History of the latest chat messages, once `capacity` messages are stored
+/// the oldest message is dropped for every new one.
+#[derive(Resource)]
+pub struct ChatMessages {
+    messages: VecDeque<Message>,
+    capacity: usize,
+}
+
+impl Default for ChatMessages {
+    fn default() -> Self { Self::with_capacity(CONFIG.server.chat_history) }
+}
 
 impl ChatMessages {
-    pub fn add(&mut self, msg: Message) { self.0.push(msg) }
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            messages: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn add(&mut self, msg: Message) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.messages.len() >= self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(msg);
+    }
+
+    /// All stored messages, from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &Message> { self.messages.iter() }
+
+    /// The last `n` messages, from oldest to newest.
+    pub fn recent(&self, n: usize) -> impl Iterator<Item = &Message> {
+        self.messages
+            .iter()
+            .skip(self.messages.len().saturating_sub(n))
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize { self.messages.len() }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool { self.messages.is_empty() }
 }
 
 pub struct ChatPlugin;
@@ -29,13 +86,15 @@ impl Plugin for ChatPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.insert_resource(ChatMessages::default())
             .add_system(chat_message.in_schedule(EventLoopSchedule))
-            .add_system(interpret_command.in_schedule(EventLoopSchedule));
+            .add_system(replay_chat.before(init_clients));
     }
 }
 
 fn chat_message(
     mut clients: Query<&mut Client>,
+    display_names: Query<&DisplayName>,
     mut events: EventReader<ChatMessage>,
+    mut stats: Query<&mut PlayerStats>,
     mut messages: ResMut<ChatMessages>,
 ) {
     for event in events.iter() {
@@ -44,67 +103,76 @@ fn chat_message(
             continue;
         };
 
-        let message = event.message.to_string();
+        let Some(message) = chat_text(
+            &event.message,
+            sender.op_level(),
+            CONFIG.server.max_chat_length,
+        ) else {
+            continue;
+        };
+
+        let username = display_names.get(event.client).map_or_else(
+            |_| Text::from(sender.username().to_string()),
+            |d| d.text(sender),
+        );
 
-        let username = Text::from(sender.username().to_string());
+        info!(target: "minecraft::chat", "{}: {}", sender.username().to_string(), message);
 
-        info!(target: "minecraft::chat", "{username}: {}", message);
+        if let Ok(mut stats) = stats.get_mut(event.client) {
+            stats.messages_sent += 1;
+        }
 
-        let formatted = username + ": ".into_text() + message.color(Color::WHITE);
+        let mut event = event.clone();
+        event.message = message.into();
+        let message = Message::ChatMessage(event, username);
+        let formatted = message.text();
 
         clients.par_iter_mut().for_each_mut(|mut client| {
             client.send_message(formatted.clone());
         });
 
-        messages.add(Message::ChatMessage(event.clone()));
+        messages.add(message);
     }
 }
 
-fn interpret_command(
-    mut clients: Query<&mut Client>,
-    mut events: EventReader<CommandExecution>,
-    instances_list: Res<Instances>,
-) {
-    for event in events.iter() {
-        let Ok(mut client) = clients.get_component_mut::<Client>(event.client) else {
-            continue;
-        };
+/// Shows the latest chat messages to players that just joined, before they are
+/// told who joined.
+fn replay_chat(mut clients: Query<&mut Client, Added<Client>>, messages: Res<ChatMessages>) {
+    for mut client in &mut clients {
+        for message in messages.recent(CHAT_REPLAY_MESSAGES) {
+            client.send_message(message.text());
+        }
+    }
+}
 
-        let mut args = event.command.split_whitespace();
-        let command = args.next().unwrap_or_default();
+/// The sanitized message a player with the given op level sent, `None` if
+/// nothing is left to send. Only players with op level 2 or higher can use
+/// formatting codes.
+#[must_use]
+pub fn chat_text(message: &str, op_level: u8, max_length: usize) -> Option<String> {
+    let message = sanitize_chat(message, op_level >= 2, max_length);
+    (!message.trim().is_empty()).then_some(message)
+}
 
-        if command == "gamemode" {
-            if client.op_level() < 2 {
-                // not enough permissions to use gamemode command
-                client.send_message("Not enough permissions to use gamemode command.".italic());
-                continue;
-            }
+/// Removes control characters and, unless `allow_formatting` is set, `§`
+/// formatting codes from a chat message and cuts it off after `max_length`
+/// characters.
+#[must_use]
+pub fn sanitize_chat(message: &str, allow_formatting: bool, max_length: usize) -> String {
+    let mut sanitized = String::with_capacity(message.len().min(max_length));
+    let mut chars = message.chars().filter(|c| !c.is_control());
 
-            let mode = args.next().unwrap_or_default();
-            let mode = match mode {
-                "adventure" => GameMode::Adventure,
-                "creative" => GameMode::Creative,
-                "survival" => GameMode::Survival,
-                "spectator" => GameMode::Spectator,
-                _ => {
-                    client.send_message("Invalid gamemode.".italic());
-                    continue;
-                }
-            };
-
-            client.set_game_mode(mode);
-            client.send_message(format!("Set gamemode to {mode:?}.").italic());
-        } else if command == "terrain" {
-            client.set_instance(instances_list.terrain);
-            let spawn = *SPAWN_POS.lock().unwrap();
-            client.set_position([spawn.x, spawn.y, spawn.z]);
-        } else if command == "wait" {
-            client.set_instance(instances_list.wait);
-            client.set_position([0., 203., 0.]);
-        } else {
-            client.send_message("Invalid command.".italic());
+    while let Some(c) = chars.next() {
+        if c == '§' && !allow_formatting {
+            // Skip the formatting code as well.
+            chars.next();
+            continue;
         }
+
+        sanitized.push(c);
     }
+
+    sanitized.chars().take(max_length).collect()
 }
 
 #[cfg(feature = "gui")]
@@ -113,31 +181,16 @@ pub fn gui_chat_window(
     mut messages: ResMut<ChatMessages>,
     mut clients: Query<(&mut Client, Option<&mut McEntity>)>,
     mut send_message_content: Local<String>,
-    mut display_messages: Local<Vec<(String, String)>>,
 ) {
     use bevy_egui::egui;
 
-    messages.0.iter().for_each(|m| match m {
-        Message::ChatMessage(m) => {
-            let Ok(sender) = clients.get_component::<Client>(m.client) else {return;};
-
-            let username = sender.username().to_string();
-            display_messages.push((username, m.message.to_string()));
-        }
-        Message::ServerMessage(msg) => {
-            display_messages.push(("Server".to_string(), msg.to_string()));
-        }
-    });
-
-    messages.0.clear();
-
     egui::Window::new("Chat")
         .resizable(true)
         .collapsible(true)
         .show(egui_context.ctx_mut(), |ui| {
             ui.horizontal(|row| {
                 row.label("Total amount of messages:");
-                row.label(format!("{}", messages.0.len()));
+                row.label(format!("{}", messages.len()));
             });
 
             ui.horizontal(|row| {
@@ -150,10 +203,11 @@ pub fn gui_chat_window(
                 if row.input(|i| i.key_pressed(egui::Key::Enter)) || button.clicked() {
                     let text = send_message_content.clone();
 
+                    let message = Message::ServerMessage(text.into());
                     for (mut c, _) in clients.iter_mut() {
-                        c.send_message("[Server]: ".color(Color::GRAY) + text.clone());
-                        messages.add(Message::ServerMessage(text.clone().into()));
+                        c.send_message(message.text());
                     }
+                    messages.add(message);
 
                     *send_message_content = String::new();
                 }
@@ -161,13 +215,151 @@ pub fn gui_chat_window(
 
             ui.group(|group| {
                 egui::ScrollArea::vertical().show(group, |g| {
-                    display_messages.iter().for_each(|(from, msg)| {
+                    for message in messages.iter() {
+                        let (from, msg) = match message {
+                            Message::ChatMessage(m, _) => {
+                                let from = clients
+                                    .get_component::<Client>(m.client)
+                                    .map_or_else(|_| "?".to_string(), |c| c.username().to_string());
+                                (from, m.message.to_string())
+                            }
+                            Message::ServerMessage(msg) => ("Server".to_string(), msg.to_string()),
+                        };
+
                         g.horizontal(|row| {
                             row.label(format!("[{from}]"));
                             row.label(msg);
                         });
-                    });
+                    }
                 });
             });
         });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+
+    fn history(capacity: usize, messages: &[&str]) -> ChatMessages {
+        let mut history = ChatMessages::with_capacity(capacity);
+        for msg in messages {
+            history.add(Message::ServerMessage((*msg).into_text()));
+        }
+        history
+    }
+
+    fn texts<'a>(messages: impl Iterator<Item = &'a Message>) -> Vec<Text> {
+        messages.map(Message::text).collect()
+    }
+
+    fn server(msg: &str) -> Text { "[Server]: ".color(Color::GRAY) + msg.into_text() }
+
+    #[test]
+    fn oldest_message_is_dropped_at_capacity() {
+        let history = history(3, &["a", "b", "c", "d", "e"]);
+
+        assert_eq!(history.len(), 3);
+        assert_eq!(
+            texts(history.iter()),
+            [server("c"), server("d"), server("e")]
+        );
+    }
+
+    #[test]
+    fn capacity_zero_keeps_nothing() {
+        let history = history(0, &["a", "b"]);
+
+        assert!(history.is_empty());
+        assert_eq!(history.recent(5).count(), 0);
+    }
+
+    #[test]
+    fn recent_returns_the_latest_messages_from_oldest_to_newest() {
+        let history = history(10, &["a", "b", "c", "d"]);
+
+        assert_eq!(texts(history.recent(2)), [server("c"), server("d")]);
+        assert_eq!(history.recent(0).count(), 0);
+        // Asking for more than there are returns all of them.
+        assert_eq!(texts(history.recent(4)), texts(history.iter()));
+        assert_eq!(texts(history.recent(100)), texts(history.iter()));
+    }
+
+    #[test]
+    fn only_the_latest_messages_are_replayed() {
+        let messages = (0..CHAT_REPLAY_MESSAGES + 5)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>();
+        let history = history(
+            100,
+            &messages.iter().map(String::as_str).collect::<Vec<_>>(),
+        );
+
+        let replayed = texts(history.recent(CHAT_REPLAY_MESSAGES));
+        assert_eq!(replayed.len(), CHAT_REPLAY_MESSAGES);
+        assert_eq!(replayed[0], server("5"));
+        assert_eq!(
+            replayed.last(),
+            Some(&server(&(CHAT_REPLAY_MESSAGES + 4).to_string()))
+        );
+    }
+
+    #[test]
+    fn players_that_are_not_ops_cant_use_formatting_codes() {
+        let config = ServerConfig {
+            ops: [("Alice".to_string(), 2)].into(),
+            ..Default::default()
+        };
+        let bob = config.op_level(Uuid::from_u128(2), "Bob");
+        let alice = config.op_level(Uuid::from_u128(1), "Alice");
+
+        assert_eq!(
+            chat_text("§chello §lworld", bob, 256).as_deref(),
+            Some("hello world")
+        );
+        assert_eq!(
+            chat_text("§chello §lworld", alice, 256).as_deref(),
+            Some("§chello §lworld")
+        );
+    }
+
+    #[test]
+    fn messages_without_text_are_not_sent() {
+        assert_eq!(chat_text("", 0, 256), None);
+        assert_eq!(chat_text("  \n\t", 0, 256), None);
+        // Only formatting codes are left for players that aren't ops.
+        assert_eq!(chat_text("§a§l", 0, 256), None);
+        assert_eq!(chat_text("§a§l", 2, 256).as_deref(), Some("§a§l"));
+    }
+
+    #[test]
+    fn control_characters_are_removed() {
+        assert_eq!(
+            sanitize_chat("hel\u{7}lo\nworld\t!", false, 256),
+            "helloworld!"
+        );
+        assert_eq!(sanitize_chat("\u{1b}[31mred", true, 256), "[31mred");
+    }
+
+    #[test]
+    fn formatting_codes_are_removed_unless_allowed() {
+        assert_eq!(sanitize_chat("§chello §lworld", false, 256), "hello world");
+        assert_eq!(
+            sanitize_chat("§chello §lworld", true, 256),
+            "§chello §lworld"
+        );
+        // A trailing `§` has no code to skip.
+        assert_eq!(sanitize_chat("hello§", false, 256), "hello");
+        assert_eq!(sanitize_chat("§§a", false, 256), "a");
+    }
+
+    #[test]
+    fn messages_are_cut_off_after_max_length() {
+        assert_eq!(sanitize_chat("hello world", false, 5), "hello");
+        assert_eq!(sanitize_chat("hello", false, 0), "");
+        // The length is counted in characters, not bytes.
+        assert_eq!(sanitize_chat("äöüß", false, 3), "äöü");
+        // Removed characters don't count towards the length.
+        assert_eq!(sanitize_chat("§ah\u{0}ey", false, 3), "hey");
+    }
+}
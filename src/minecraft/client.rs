@@ -1,47 +1,234 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
 use bevy::prelude::Plugin;
 use rand::Rng;
 use valence::{client::despawn_disconnected_clients, prelude::*};
 
-use super::world_gen::Instances;
-use crate::{CONFIG, PLAYER_COUNT, SPAWN_POS};
+use super::{
+    command::{Command, CommandEvent, RegisterCommandExt},
+    player_data::{self, detect_hotbar_changes, sync_hotbar, HotbarSlotChanged, HotbarState},
+    world_gen::Instances,
+    PlayerCount, SpawnPos,
+};
+use crate::{config::NameColors, CONFIG};
+
+/// Maximum length of a nickname, same as the maximum length of a username.
+pub const MAX_NICKNAME_LENGTH: usize = 16;
+
+/// Number of ticks [`Tps`] averages over, view distances are adjusted at most
+/// once per window.
+pub const TPS_WINDOW: usize = 20;
 
 pub struct ClientPlugin;
 
 impl Plugin for ClientPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(PlayerList::default_systems())
+        let nick_op_level = if CONFIG.server.allow_nicknames { 0 } else { 2 };
+
+        app.init_resource::<Tps>()
+            .add_event::<HotbarSlotChanged>()
+            .add_systems(PlayerList::default_systems())
+            .register_command(
+                Command::new("nick")
+                    .with_op_level(nick_op_level)
+                    .with_usage("nick [name] | nick <player> <name>"),
+            )
+            .register_command(
+                Command::new("stats")
+                    .with_usage("stats [player]")
+                    .with_description("Shows the statistics of you or another online player."),
+            )
             .add_system(init_clients)
+            .add_system(nick)
+            .add_system(stats)
+            .add_system(count_time_online)
+            .add_system(detect_hotbar_changes)
+            .add_system(sync_hotbar.after(detect_hotbar_changes))
             .add_system(update_player_list)
-            .add_system(player_left)
+            .add_system(
+                player_left
+                    .after(sync_hotbar)
+                    .before(despawn_disconnected_clients),
+            )
+            .add_system(flush_player_data.after(sync_hotbar))
             .add_system(despawn_disconnected_clients)
-            .add_system(set_view_distance);
+            .add_system(measure_tps)
+            .add_system(set_view_distance.after(measure_tps));
+    }
+}
+
+/// Name of a player shown in the player list and in chat.
+#[derive(Component, Clone, Debug)]
+pub struct DisplayName {
+    pub nickname: Option<String>,
+    pub color: Color,
+}
+
+impl DisplayName {
+    /// The nickname of the player, or the username if no nickname is set.
+    #[must_use]
+    pub fn name(&self, client: &Client) -> String {
+        self.nickname
+            .clone()
+            .unwrap_or_else(|| client.username().to_string())
+    }
+
+    #[must_use]
+    pub fn text(&self, client: &Client) -> Text { self.name(client).color(self.color) }
+}
+
+/// Statistics of a player, kept between sessions.
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct PlayerStats {
+    pub blocks_broken: u64,
+    pub blocks_placed: u64,
+    pub messages_sent: u64,
+    pub time_online_ticks: u64,
+}
+
+impl PlayerStats {
+    /// Time online formatted like `1h 2m 3s`, based on the configured tick
+    /// rate.
+    #[must_use]
+    pub fn time_online(&self) -> String {
+        let seconds = self.time_online_ticks / u64::from(CONFIG.server.tick_rate);
+        format!(
+            "{}h {}m {}s",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
+
+/// Ticks per second the server actually reaches, averaged over the last
+/// [`TPS_WINDOW`] ticks.
+#[derive(Resource, Clone, Debug)]
+pub struct Tps {
+    pub current: f64,
+    durations: VecDeque<Duration>,
+    last_tick: Option<Instant>,
+}
+
+impl Default for Tps {
+    fn default() -> Self {
+        Self {
+            current: f64::from(CONFIG.server.tick_rate),
+            durations: VecDeque::with_capacity(TPS_WINDOW),
+            last_tick: None,
+        }
     }
 }
 
+impl Tps {
+    /// Records the start of a tick.
+    pub fn tick(&mut self, now: Instant) {
+        if let Some(last) = self.last_tick.replace(now) {
+            if self.durations.len() == TPS_WINDOW {
+                self.durations.pop_front();
+            }
+            self.durations.push_back(now - last);
+        }
+
+        let total = self.durations.iter().sum::<Duration>().as_secs_f64();
+        if total > 0.0 {
+            self.current = self.durations.len() as f64 / total;
+        }
+    }
+}
+
+/// Checks that a nickname is not empty, at most [`MAX_NICKNAME_LENGTH`]
+/// characters long and only consists of letters, digits and underscores.
+///
+/// # Errors
+/// - the nickname is invalid, with a message explaining why
+pub fn validate_nickname(nickname: &str) -> Result<(), String> {
+    if nickname.is_empty() {
+        return Err("Nickname can't be empty.".to_string());
+    }
+
+    if nickname.chars().count() > MAX_NICKNAME_LENGTH {
+        return Err(format!(
+            "Nickname can't be longer than {MAX_NICKNAME_LENGTH} characters."
+        ));
+    }
+
+    if !nickname
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err("Nickname can only contain letters, digits and underscores.".to_string());
+    }
+
+    Ok(())
+}
+
+/// Name color derived from the uuid of a player, so every player keeps the same
+/// color between sessions.
+#[must_use]
+pub fn uuid_color(uuid: Uuid) -> Color {
+    // FNV-1a, `DefaultHasher` isn't guaranteed to be stable between releases.
+    let hash = uuid
+        .as_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    let [r, g, b, ..] = hash.to_le_bytes();
+
+    Color::new(r, g, b)
+}
+
 pub fn init_clients(
-    mut clients: Query<&mut Client, Added<Client>>,
+    mut commands: Commands,
+    mut clients: Query<(Entity, &mut Client, &mut Inventory), Added<Client>>,
     instances: Query<Entity, With<Instance>>,
     instances_list: Res<Instances>,
     mut player_list: ResMut<PlayerList>,
+    spawn: Res<SpawnPos>,
+    player_count: Res<PlayerCount>,
 ) {
-    let instance = instances.get(instances_list.terrain).unwrap();
-    let spawn = *SPAWN_POS.lock().unwrap();
+    let instance = instances.get(instances_list.terrain()).unwrap();
     let mut new_players = vec![];
 
-    for mut client in &mut clients {
-        client.set_position([spawn.x, spawn.y, spawn.z]);
+    for (entity, mut client, mut inventory) in &mut clients {
+        client.set_position(spawn.0);
         client.set_instance(instance);
         client.set_game_mode(GameMode::Creative);
-        client.set_op_level(2);
+        let op_level = CONFIG
+            .server
+            .op_level(client.uuid(), &client.username().to_string());
+        client.set_op_level(op_level);
 
-        let mut rng = rand::thread_rng();
-        let name_color = Color::new(
-            rng.gen_range(0..=255),
-            rng.gen_range(0..=255),
-            rng.gen_range(0..=255),
-        );
+        let mut nickname = None;
+        let mut stats = PlayerStats::default();
+        let mut hotbar = HotbarState::from_inventory(&inventory);
+        if let Some(data) = player_data::load(client.uuid()) {
+            hotbar = data.restore(&mut client, &mut inventory);
+            nickname = data.nickname;
+            stats = data.stats;
+        }
+
+        let name_color = match CONFIG.server.name_colors {
+            NameColors::Uuid => uuid_color(client.uuid()),
+            NameColors::Random => {
+                let mut rng = rand::thread_rng();
+                Color::new(
+                    rng.gen_range(0..=255),
+                    rng.gen_range(0..=255),
+                    rng.gen_range(0..=255),
+                )
+            }
+        };
 
-        let username = client.username().to_owned_username().color(name_color);
+        let display_name = DisplayName {
+            nickname,
+            color: name_color,
+        };
+        let username = display_name.text(&client);
 
         client.set_view_distance(CONFIG.server.max_view_distance);
 
@@ -50,55 +237,410 @@ pub fn init_clients(
             .with_properties(client.properties()) // For the player's skin and cape.
             .with_game_mode(client.game_mode())
             .with_ping(client.ping()) // Use negative values to indicate missing.
-            .with_display_name(Some(
-                client.username().to_owned_username().color(name_color),
-            ));
+            .with_display_name(Some(username.clone()));
 
         info!(target: "minecraft", "{} joined", client.username().to_string());
         new_players.push(username);
+        commands
+            .entity(entity)
+            .insert((display_name, stats, hotbar));
         player_list.insert(client.uuid(), entry);
-        *PLAYER_COUNT.lock().unwrap() += 1;
+        player_count.increment();
     }
 
-    clients.par_iter_mut().for_each_mut(|mut c| {
+    clients.par_iter_mut().for_each_mut(|(_, mut c, _)| {
         for name in &new_players {
             c.send_message(name.clone() + " joined".to_string().color(Color::YELLOW));
         }
     });
 }
 
-fn update_player_list(mut player_list: ResMut<PlayerList>) {
+fn update_player_list(mut player_list: ResMut<PlayerList>, player_count: Res<PlayerCount>) {
     player_list.set_header("Just a normal minecraft server".into_text());
     player_list.set_footer(format!(
         "{}/{}",
-        *PLAYER_COUNT.lock().unwrap(),
+        player_count.get(),
         CONFIG.server.max_connections
     ));
 }
 
-fn player_left(mut clients: Query<&mut Client>) {
+fn player_left(
+    mut clients: Query<(
+        &mut Client,
+        &Inventory,
+        Option<&DisplayName>,
+        Option<&PlayerStats>,
+        Option<&HotbarState>,
+    )>,
+    player_count: Res<PlayerCount>,
+) {
     let mut players = vec![];
 
-    for client in &clients {
+    for (client, inventory, display_name, stats, hotbar) in &clients {
         if client.is_disconnected() {
-            let username = client.username().to_string().into_text();
+            let nickname = display_name.and_then(|d| d.nickname.as_deref());
+            let stats = stats.cloned().unwrap_or_default();
+            let hotbar = hotbar
+                .cloned()
+                .unwrap_or_else(|| HotbarState::from_inventory(inventory));
+            if let Err(e) =
+                player_data::save(client.uuid(), client, inventory, nickname, &stats, &hotbar)
+            {
+                error!(target: "minecraft::player_data", "unable to save player data: {e}");
+            }
+
+            let username = display_name.map_or_else(
+                || client.username().to_string().into_text(),
+                |d| d.text(client),
+            );
             players.push(username.clone());
             info!(target: "minecraft", "{} left", client.username().to_string());
-            *PLAYER_COUNT.lock().unwrap() -= 1;
+            player_count.decrement();
         }
     }
 
-    clients.par_iter_mut().for_each_mut(|mut c| {
+    clients.par_iter_mut().for_each_mut(|(mut c, ..)| {
         for name in &players {
             c.send_message(name.clone() + " left".to_string().color(Color::YELLOW));
         }
     });
 }
 
-fn set_view_distance(mut clients: Query<&mut Client>) {
+/// Saves the data of all connected players every `player_save_interval`
+/// seconds, so a crash only loses the changes since the last save.
+fn flush_player_data(
+    clients: Query<(
+        &Client,
+        &Inventory,
+        Option<&DisplayName>,
+        Option<&PlayerStats>,
+        Option<&HotbarState>,
+    )>,
+    mut last_flush: Local<Option<Instant>>,
+) {
+    if !is_save_due(
+        &mut last_flush,
+        Instant::now(),
+        CONFIG.server.player_save_interval,
+    ) {
+        return;
+    }
+
+    let mut saved = 0;
+    for (client, inventory, display_name, stats, hotbar) in &clients {
+        // Disconnected players are saved by `player_left`.
+        if client.is_disconnected() {
+            continue;
+        }
+
+        let nickname = display_name.and_then(|d| d.nickname.as_deref());
+        let stats = stats.cloned().unwrap_or_default();
+        let hotbar = hotbar
+            .cloned()
+            .unwrap_or_else(|| HotbarState::from_inventory(inventory));
+        match player_data::save(client.uuid(), client, inventory, nickname, &stats, &hotbar) {
+            Ok(()) => saved += 1,
+            Err(e) => error!(target: "minecraft::player_data", "unable to save player data: {e}"),
+        }
+    }
+
+    debug!(target: "minecraft::player_data", "saved the data of {saved} players");
+}
+
+/// Whether the players should be saved at `now` if they are saved every
+/// `interval` seconds, `last` is updated to the time of the last save.
+fn is_save_due(last: &mut Option<Instant>, now: Instant, interval: u64) -> bool {
+    if interval == 0 {
+        return false;
+    }
+
+    let last = last.get_or_insert(now);
+    if now.duration_since(*last) < Duration::from_secs(interval) {
+        return false;
+    }
+    *last = now;
+
+    true
+}
+
+fn nick(
+    mut clients: Query<(Entity, &mut Client, &mut DisplayName)>,
+    mut events: EventReader<CommandEvent>,
+    mut player_list: ResMut<PlayerList>,
+) {
+    for event in events.iter().filter(|e| e.is("nick")) {
+        let Ok((_, sender, _)) = clients.get(event.client) else {
+            continue;
+        };
+
+        let target = match event.args.as_slice() {
+            [] | [_] => Ok(event.client),
+            [username, _] if sender.op_level() >= 2 => clients
+                .iter()
+                .find(|(_, c, _)| c.username().to_string() == *username)
+                .map(|(entity, ..)| entity)
+                .ok_or_else(|| format!("Player {username} is not online.")),
+            [_, _] => {
+                Err("Not enough permissions to change the nickname of other players.".to_string())
+            }
+            _ => Err("Usage: nick [name] | nick <player> <name>".to_string()),
+        };
+
+        // Without a name the nickname is reset to the username.
+        let nickname = event.args.last().cloned();
+
+        let result = target.and_then(|target| {
+            if let Some(nickname) = &nickname {
+                validate_nickname(nickname)?;
+            }
+            Ok(target)
+        });
+
+        let target = match result {
+            Ok(target) => target,
+            Err(msg) => {
+                if let Ok((_, mut sender, _)) = clients.get_mut(event.client) {
+                    sender.send_message(msg.color(Color::RED));
+                }
+                continue;
+            }
+        };
+
+        let Ok((_, mut client, mut display_name)) = clients.get_mut(target) else {
+            continue;
+        };
+
+        display_name.nickname = nickname;
+        let name = display_name.text(&client);
+
+        if let Some(entry) = player_list.get_mut(client.uuid()) {
+            entry.set_display_name(Some(name.clone()));
+        }
+
+        info!(
+            target: "minecraft",
+            "{} is now called {}",
+            client.username().to_string(),
+            display_name.name(&client)
+        );
+        client.send_message("You are now called ".italic() + name);
+    }
+}
+
+fn count_time_online(mut stats: Query<&mut PlayerStats>) {
+    for mut stats in &mut stats {
+        stats.time_online_ticks += 1;
+    }
+}
+
+fn stats(mut clients: Query<(&mut Client, &PlayerStats)>, mut events: EventReader<CommandEvent>) {
+    for event in events.iter().filter(|e| e.is("stats")) {
+        let target = match event.arg(0) {
+            Some(username) => clients
+                .iter()
+                .find(|(c, _)| c.username().to_string() == username),
+            None => clients.get(event.client).ok(),
+        };
+
+        let msg = match target {
+            Some((client, stats)) => format!(
+                "Statistics of {}:\n  Blocks broken: {}\n  Blocks placed: {}\n  Messages sent: \
+                 {}\n  Time online: {}",
+                client.username(),
+                stats.blocks_broken,
+                stats.blocks_placed,
+                stats.messages_sent,
+                stats.time_online()
+            )
+            .italic(),
+            None => format!("Player {} is not online.", event.arg(0).unwrap_or_default())
+                .color(Color::RED),
+        };
+
+        if let Ok((mut client, _)) = clients.get_mut(event.client) {
+            client.send_message(msg);
+        }
+    }
+}
+
+fn measure_tps(mut tps: ResMut<Tps>) { tps.tick(Instant::now()); }
+
+/// Keeps the view distances below the maximum, and lowers them while the
+/// server can't keep up with the tick rate. They are raised again once the
+/// server is back at full speed.
+fn set_view_distance(mut clients: Query<&mut Client>, tps: Res<Tps>, mut ticks: Local<usize>) {
+    let max = CONFIG.server.max_view_distance;
+    let min = CONFIG.world.min_view_distance.min(max);
+
+    // Waits a full window so the average reflects the last change.
+    *ticks += 1;
+    let adjust = *ticks % TPS_WINDOW == 0;
+
+    let tick_rate = f64::from(CONFIG.server.tick_rate);
+    let overloaded = adjust && tps.current < tick_rate * 0.75;
+    let recovered = adjust && tps.current > tick_rate * 0.975;
+
     clients.par_iter_mut().for_each_mut(|mut c| {
-        if c.view_distance() > CONFIG.server.max_view_distance {
-            c.set_view_distance(CONFIG.server.max_view_distance);
+        let distance = c.view_distance();
+
+        if distance > max {
+            c.set_view_distance(max);
+        } else if overloaded && distance > min {
+            c.set_view_distance(distance - 1);
+            c.set_action_bar(
+                format!(
+                    "The server is overloaded ({:.1} TPS), view distance lowered to {}",
+                    tps.current,
+                    distance - 1
+                )
+                .color(Color::GRAY),
+            );
+        } else if recovered && distance < max {
+            c.set_view_distance(distance + 1);
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{
+        config::ServerConfig,
+        minecraft::command::{builtin::BuiltinCommandsPlugin, CommandRegistry},
+    };
+
+    const ALICE: Uuid = Uuid::from_u128(0x0a11_ce00_0000_4000_8000_0000_0000_0001);
+
+    fn ops(ops: &[(&str, u8)]) -> ServerConfig {
+        ServerConfig {
+            ops: ops
+                .iter()
+                .map(|(player, level)| ((*player).to_string(), *level))
+                .collect::<BTreeMap<_, _>>(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn players_are_not_ops_by_default() {
+        assert_eq!(ServerConfig::default().op_level(ALICE, "Alice"), 0);
+        assert_eq!(ops(&[("Bob", 4)]).op_level(ALICE, "Alice"), 0);
+    }
+
+    #[test]
+    fn ops_are_found_by_uuid_or_username() {
+        assert_eq!(ops(&[("alice", 3)]).op_level(ALICE, "Alice"), 3);
+        assert_eq!(
+            ops(&[(&ALICE.to_string().to_uppercase(), 2)]).op_level(ALICE, "Alice"),
+            2
+        );
+        // The uuid wins, the username can be taken by someone else later.
+        assert_eq!(
+            ops(&[("Alice", 4), (&ALICE.to_string(), 1)]).op_level(ALICE, "Alice"),
+            1
+        );
+    }
+
+    #[test]
+    fn op_levels_above_the_maximum_are_invalid() {
+        assert!(ops(&[("Alice", 4)]).validate().is_ok());
+        assert_eq!(
+            ops(&[("Alice", 5)]).validate().unwrap_err().to_string(),
+            "op level of Alice must be at most 4, got 5"
+        );
+    }
+
+    #[test]
+    fn players_that_are_not_ops_are_refused_op_3_commands() {
+        let mut app = App::new();
+        BuiltinCommandsPlugin.build(&mut app);
+        let registry = app.world.resource::<CommandRegistry>();
+        let config = ops(&[("Alice", 3)]);
+
+        let bob = config.op_level(Uuid::from_u128(2), "Bob");
+        assert_eq!(
+            registry.parse("kick Alice", bob).unwrap_err(),
+            "Not enough permissions to use kick command."
+        );
+        assert!(registry
+            .parse("kick Bob", config.op_level(ALICE, "Alice"))
+            .is_ok());
+    }
+
+    #[test]
+    fn names_are_colored_by_uuid_by_default() {
+        assert_eq!(NameColors::default(), NameColors::Uuid);
+    }
+
+    #[test]
+    fn same_uuid_always_has_the_same_color() {
+        let uuid = Uuid::from_u128(0x1234_5678_9abc_def0_1234_5678_9abc_def0);
+
+        assert_eq!(uuid_color(uuid), uuid_color(uuid));
+        assert_eq!(
+            uuid_color(uuid),
+            uuid_color(Uuid::from_bytes(*uuid.as_bytes()))
+        );
+    }
+
+    #[test]
+    fn different_uuids_have_different_colors() {
+        let colors = (0..100_u128)
+            .map(|i| {
+                let color = uuid_color(Uuid::from_u128(i));
+                (color.r, color.g, color.b)
+            })
+            .collect::<std::collections::HashSet<_>>();
+
+        assert_eq!(colors.len(), 100);
+    }
+
+    #[test]
+    fn players_are_saved_every_interval() {
+        let start = Instant::now();
+        let mut last = None;
+
+        // The first save is one interval after the start.
+        assert!(!is_save_due(&mut last, start, 60));
+        assert!(!is_save_due(&mut last, start + Duration::from_secs(59), 60));
+        assert!(is_save_due(&mut last, start + Duration::from_secs(60), 60));
+        assert_eq!(last, Some(start + Duration::from_secs(60)));
+
+        assert!(!is_save_due(
+            &mut last,
+            start + Duration::from_secs(119),
+            60
+        ));
+        assert!(is_save_due(&mut last, start + Duration::from_secs(125), 60));
+        assert!(!is_save_due(
+            &mut last,
+            start + Duration::from_secs(184),
+            60
+        ));
+    }
+
+    #[test]
+    fn players_are_only_saved_when_leaving_with_interval_0() {
+        let start = Instant::now();
+        let mut last = None;
+
+        for secs in [0, 60, 3600] {
+            assert!(!is_save_due(
+                &mut last,
+                start + Duration::from_secs(secs),
+                0
+            ));
+        }
+        assert_eq!(last, None);
+    }
+
+    #[test]
+    fn player_save_interval_is_configurable() {
+        assert_eq!(ServerConfig::default().player_save_interval, 60);
+
+        let config: ServerConfig = toml::from_str("player_save_interval = 0").unwrap();
+        assert_eq!(config.player_save_interval, 0);
+    }
+}
@@ -0,0 +1,640 @@
+pub mod builtin;
+
+use std::collections::BTreeMap;
+
+use bevy::{ecs::system::SystemParam, prelude::Plugin};
+use valence::{
+    client::event::{CommandExecution, RequestCommandCompletions},
+    entity::TrackedData,
+    prelude::*,
+    protocol::{
+        packet::s2c::play::{
+            command_suggestions::Match,
+            command_tree::{Node, NodeData, Parser, StringArg, Suggestion},
+            CommandSuggestionsS2c, CommandTreeS2c,
+        },
+        var_int::VarInt,
+    },
+};
+
+use self::builtin::BuiltinCommandsPlugin;
+use super::{
+    client::init_clients,
+    time::WorldTime,
+    weather::{spawn_lightning, Weather, WeatherTimer},
+};
+
+/// Handles command execution for all commands registered in the
+/// [`CommandRegistry`].
+///
+/// Plugins add their own commands by registering them with
+/// [`RegisterCommandExt::register_command`] and adding a system that reads the
+/// [`CommandEvent`]s of their command:
+///
+/// ```ignore
+/// app.register_command(Command::new("hello").with_usage("hello"))
+///     .add_system(hello);
+///
+/// fn hello(mut clients: Query<&mut Client>, mut events: EventReader<CommandEvent>) {
+///     for event in events.iter().filter(|e| e.is("hello")) {
+///         if let Ok(mut client) = clients.get_mut(event.client) {
+///             client.send_message("Hello!");
+///         }
+///     }
+/// }
+/// ```
+pub struct CommandPlugin;
+
+impl Plugin for CommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CommandRegistry>()
+            .add_event::<CommandEvent>()
+            .add_system(dispatch_commands.in_schedule(EventLoopSchedule))
+            .add_system(complete_commands.in_schedule(EventLoopSchedule))
+            .add_system(send_command_tree.after(init_clients))
+            .add_plugin(BuiltinCommandsPlugin);
+    }
+}
+
+/// A command players can execute.
+#[derive(Clone, Debug)]
+pub struct Command {
+    pub name: String,
+    /// Minimum op level needed to execute the command.
+    pub op_level: u8,
+    pub usage: String,
+    /// Shown by `help <command>` below the usage.
+    pub description: String,
+    /// Suggestions for the first argument that start with the typed text,
+    /// clients ask for them while the command is typed.
+    pub completions: Option<fn(&str) -> Vec<String>>,
+}
+
+impl Command {
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+
+        Self {
+            usage: name.clone(),
+            name,
+            op_level: 0,
+            description: String::new(),
+            completions: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_op_level(mut self, op_level: u8) -> Self {
+        self.op_level = op_level;
+        self
+    }
+
+    #[must_use]
+    pub fn with_usage(mut self, usage: impl Into<String>) -> Self {
+        self.usage = usage.into();
+        self
+    }
+
+    #[must_use]
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    #[must_use]
+    pub fn with_completions(mut self, completions: fn(&str) -> Vec<String>) -> Self {
+        self.completions = Some(completions);
+        self
+    }
+}
+
+/// All commands known to the server.
+#[derive(Resource, Default, Debug)]
+pub struct CommandRegistry {
+    commands: BTreeMap<String, Command>,
+}
+
+impl CommandRegistry {
+    /// Registers a command, replacing any command with the same name.
+    pub fn register(&mut self, command: Command) {
+        self.commands.insert(command.name.clone(), command);
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Command> { self.commands.get(name) }
+
+    /// All registered commands, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = &Command> { self.commands.values() }
+
+    /// Splits a command line into the registered command and its arguments.
+    ///
+    /// # Errors
+    /// - the command isn't registered
+    /// - `op_level` is lower than the op level of the command
+    pub fn parse(&self, line: &str, op_level: u8) -> Result<(&Command, Vec<String>), String> {
+        let mut args = line.split_whitespace();
+        let name = args.next().unwrap_or_default();
+
+        let Some(command) = self.get(name) else {
+            return Err("Invalid command.".to_string());
+        };
+
+        if op_level < command.op_level {
+            return Err(format!("Not enough permissions to use {name} command."));
+        }
+
+        Ok((command, args.map(ToString::to_string).collect()))
+    }
+
+    /// Completions of the first argument of a partially typed command line
+    /// like `/give dia`, with the byte offset the argument starts at. `None`
+    /// if the command has no completions, can't be used with `op_level` or
+    /// the first argument is already complete.
+    #[must_use]
+    pub fn complete(&self, text: &str, op_level: u8) -> Option<(usize, Vec<String>)> {
+        let line = text.strip_prefix('/').unwrap_or(text);
+        let (name, prefix) = line.split_once(' ')?;
+        if prefix.contains(' ') {
+            return None;
+        }
+
+        let command = self.get(name).filter(|c| op_level >= c.op_level)?;
+        let completions = command.completions?;
+
+        Some((text.len() - prefix.len(), completions(prefix)))
+    }
+
+    /// The commands a client with the given op level can use, so it can
+    /// highlight them and ask for completions. Every command takes any
+    /// arguments, they are checked when the command is executed.
+    #[must_use]
+    pub fn tree(&self, op_level: u8) -> CommandTreeS2c {
+        let mut nodes = vec![Node {
+            children: vec![],
+            data: NodeData::Root,
+            executable: false,
+            redirect_node: None,
+        }];
+
+        for command in self.iter().filter(|c| op_level >= c.op_level) {
+            let literal = nodes.len() as i32;
+            nodes[0].children.push(VarInt(literal));
+            nodes.push(Node {
+                children: vec![VarInt(literal + 1)],
+                data: NodeData::Literal {
+                    name: &command.name,
+                },
+                executable: true,
+                redirect_node: None,
+            });
+            nodes.push(Node {
+                children: vec![],
+                data: NodeData::Argument {
+                    name: "args",
+                    parser: Parser::String(StringArg::GreedyPhrase),
+                    suggestion: command.completions.map(|_| Suggestion::AskServer),
+                },
+                executable: true,
+                redirect_node: None,
+            });
+        }
+
+        CommandTreeS2c {
+            commands: nodes,
+            root_index: VarInt(0),
+        }
+    }
+}
+
+pub trait RegisterCommandExt {
+    fn register_command(&mut self, command: Command) -> &mut Self;
+}
+
+impl RegisterCommandExt for App {
+    fn register_command(&mut self, command: Command) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(CommandRegistry::default)
+            .register(command);
+        self
+    }
+}
+
+/// The client that executed a command, copied out of the ECS so commands can
+/// be interpreted without queries.
+#[derive(Clone, Debug)]
+pub struct Caller {
+    pub username: String,
+    pub op_level: u8,
+    pub position: DVec3,
+    pub instance: Entity,
+}
+
+impl Caller {
+    #[must_use]
+    pub fn new(client: &Client) -> Self {
+        Self {
+            username: client.username().to_string(),
+            op_level: client.op_level(),
+            position: client.position(),
+            instance: client.instance(),
+        }
+    }
+}
+
+/// A change a command makes to the client that executed it, to other players
+/// or to the world.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Effect {
+    Teleport(DVec3),
+    SetGameMode(GameMode),
+    SendMessage(Text),
+    SetInstance(Entity),
+    /// Disconnects the online player with the username.
+    Kick {
+        username: String,
+        reason: String,
+    },
+    /// Sends a message to every online player.
+    Broadcast(Text),
+    /// Puts the stack into the slot of the caller's inventory, or drops it in
+    /// front of them if there is no free slot.
+    GiveItem {
+        slot: Option<u16>,
+        stack: ItemStack,
+    },
+    /// Replaces the world time, the clients are updated right away.
+    SetTime(WorldTime),
+    /// Changes the weather, it clears up again after `duration` ticks.
+    SetWeather {
+        weather: Weather,
+        duration: Option<u32>,
+    },
+}
+
+/// Everything the [`Effect`]s of a command can change.
+#[derive(SystemParam)]
+pub struct EffectTargets<'w, 's> {
+    pub clients: Query<'w, 's, (&'static mut Client, Option<&'static mut Inventory>)>,
+    pub commands: Commands<'w, 's>,
+    pub time: Option<ResMut<'w, WorldTime>>,
+    pub weather: Option<ResMut<'w, Weather>>,
+    pub weather_timer: Option<ResMut<'w, WeatherTimer>>,
+}
+
+/// Applies the effects of a command `caller` executed in order.
+pub fn apply_effects(targets: &mut EffectTargets, caller: Entity, effects: Vec<Effect>) {
+    for effect in effects {
+        match effect {
+            Effect::Kick { username, reason } => {
+                let operator = targets
+                    .clients
+                    .get(caller)
+                    .map(|(c, _)| c.username().to_string())
+                    .unwrap_or_default();
+                let Some((mut target, _)) = targets
+                    .clients
+                    .iter_mut()
+                    .find(|(c, _)| c.username().to_string() == username)
+                else {
+                    continue;
+                };
+
+                target.kick(reason.clone().color(Color::RED));
+                info!(target: "minecraft", "{operator} kicked {username}: {reason}");
+            }
+            Effect::Broadcast(msg) => {
+                for (mut client, _) in &mut targets.clients {
+                    if !client.is_disconnected() {
+                        client.send_message(msg.clone());
+                    }
+                }
+            }
+            Effect::GiveItem { slot, stack } => give_item(targets, caller, slot, stack),
+            Effect::SetTime(time) => {
+                let Some(current) = &mut targets.time else {
+                    continue;
+                };
+                **current = time;
+
+                // Sent right away instead of waiting for the next sync.
+                for (mut client, _) in &mut targets.clients {
+                    client.write_packet(&current.packet());
+                }
+            }
+            Effect::SetWeather { weather, duration } => {
+                if let (Some(current), Some(timer)) =
+                    (&mut targets.weather, &mut targets.weather_timer)
+                {
+                    **current = weather;
+                    timer.0 = duration;
+                }
+
+                if weather == Weather::Thunder {
+                    let clients = targets.clients.iter().map(|(client, _)| client);
+                    spawn_lightning(&mut targets.commands, clients);
+                }
+            }
+            effect => {
+                let Ok((mut client, _)) = targets.clients.get_mut(caller) else {
+                    continue;
+                };
+                apply_to_caller(&mut client, effect);
+            }
+        }
+    }
+}
+
+fn give_item(targets: &mut EffectTargets, caller: Entity, slot: Option<u16>, stack: ItemStack) {
+    let Ok((client, inventory)) = targets.clients.get_mut(caller) else {
+        return;
+    };
+    info!(target: "minecraft",
+        "{} gave themselves {}x minecraft:{}",
+        client.username().to_string(), stack.count(), stack.item.to_str()
+    );
+
+    if let (Some(slot), Some(mut inventory)) = (slot, inventory) {
+        let _ = inventory.replace_slot(slot, Some(stack));
+        return;
+    }
+
+    // Dropped in front of the player, like items thrown by them.
+    let mut entity = McEntity::new(EntityKind::Item, client.instance());
+    entity.set_position(client.position() + DVec3::new(0.0, 1.3, 0.0));
+    if let TrackedData::Item(data) = entity.data_mut() {
+        data.set_stack(Some(stack));
+    }
+    targets.commands.spawn(entity);
+}
+
+fn apply_to_caller(client: &mut Client, effect: Effect) {
+    match effect {
+        Effect::Teleport(pos) => client.set_position(pos),
+        Effect::SetGameMode(mode) => client.set_game_mode(mode),
+        Effect::SendMessage(msg) => client.send_message(msg),
+        Effect::SetInstance(instance) => client.set_instance(instance),
+        // Handled by `apply_effects`, they aren't limited to the caller.
+        Effect::Kick { .. }
+        | Effect::Broadcast(_)
+        | Effect::GiveItem { .. }
+        | Effect::SetTime(_)
+        | Effect::SetWeather { .. } => {}
+    }
+}
+
+/// A registered command was executed by a client with enough permissions.
+#[derive(Clone, Debug)]
+pub struct CommandEvent {
+    pub client: Entity,
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+impl CommandEvent {
+    #[must_use]
+    pub fn new(client: Entity, command: &Command, args: Vec<String>) -> Self {
+        Self {
+            client,
+            name: command.name.clone(),
+            args,
+        }
+    }
+
+    #[must_use]
+    pub fn is(&self, name: &str) -> bool { self.name == name }
+
+    #[must_use]
+    pub fn arg(&self, index: usize) -> Option<&str> { self.args.get(index).map(String::as_str) }
+}
+
+fn dispatch_commands(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandExecution>,
+    mut commands: EventWriter<CommandEvent>,
+    registry: Res<CommandRegistry>,
+) {
+    for event in events.iter() {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        match registry.parse(&event.command, client.op_level()) {
+            Ok((command, args)) => commands.send(CommandEvent::new(event.client, command, args)),
+            Err(msg) => client.send_message(msg.italic()),
+        }
+    }
+}
+
+/// Tells joining players which commands they can use.
+fn send_command_tree(
+    mut clients: Query<&mut Client, Added<Client>>,
+    registry: Res<CommandRegistry>,
+) {
+    for mut client in &mut clients {
+        let tree = registry.tree(client.op_level());
+        client.write_packet(&tree);
+    }
+}
+
+fn complete_commands(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<RequestCommandCompletions>,
+    registry: Res<CommandRegistry>,
+) {
+    for event in events.iter() {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+        let Some((start, completions)) = registry.complete(&event.text, client.op_level()) else {
+            continue;
+        };
+
+        client.write_packet(&CommandSuggestionsS2c {
+            id: VarInt(event.transaction_id),
+            start: VarInt(start as i32),
+            length: VarInt((event.text.len() - start) as i32),
+            matches: completions
+                .iter()
+                .map(|id| Match {
+                    suggested_match: id,
+                    tooltip: None,
+                })
+                .collect(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct HelloPlugin;
+
+    impl Plugin for HelloPlugin {
+        fn build(&self, app: &mut App) {
+            app.init_resource::<Greeted>()
+                .register_command(
+                    Command::new("hello")
+                        .with_op_level(1)
+                        .with_usage("hello <name>"),
+                )
+                .add_system(hello);
+        }
+    }
+
+    #[derive(Resource, Default)]
+    struct Greeted(Vec<String>);
+
+    fn hello(mut events: EventReader<CommandEvent>, mut greeted: ResMut<Greeted>) {
+        for event in events.iter().filter(|e| e.is("hello")) {
+            greeted.0.push(event.arg(0).unwrap_or_default().to_string());
+        }
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_event::<CommandEvent>().add_plugin(HelloPlugin);
+        app
+    }
+
+    #[test]
+    fn commands_of_other_plugins_are_registered() {
+        let app = app();
+        let command = app
+            .world
+            .resource::<CommandRegistry>()
+            .get("hello")
+            .unwrap();
+
+        assert_eq!(command.op_level, 1);
+        assert_eq!(command.usage, "hello <name>");
+    }
+
+    #[test]
+    fn commands_of_other_plugins_are_dispatched() {
+        let mut app = app();
+        let client = Entity::from_raw(1);
+
+        let registry = app.world.resource::<CommandRegistry>();
+        let (command, args) = registry.parse("hello  world", 1).unwrap();
+        let event = CommandEvent::new(client, command, args);
+        app.world.send_event(event);
+        app.update();
+
+        assert_eq!(app.world.resource::<Greeted>().0, vec!["world".to_string()]);
+    }
+
+    #[test]
+    fn unknown_commands_are_rejected() {
+        let app = app();
+        let registry = app.world.resource::<CommandRegistry>();
+
+        assert_eq!(registry.parse("bye", 4).unwrap_err(), "Invalid command.");
+        assert_eq!(registry.parse("", 4).unwrap_err(), "Invalid command.");
+    }
+
+    #[test]
+    fn commands_check_the_op_level() {
+        let app = app();
+        let registry = app.world.resource::<CommandRegistry>();
+
+        assert_eq!(
+            registry.parse("hello world", 0).unwrap_err(),
+            "Not enough permissions to use hello command."
+        );
+        assert!(registry.parse("hello world", 1).is_ok());
+    }
+
+    fn complete_colors(prefix: &str) -> Vec<String> {
+        ["red", "green", "blue"]
+            .into_iter()
+            .filter(|color| color.starts_with(prefix))
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    fn color_registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::default();
+        registry.register(
+            Command::new("paint")
+                .with_op_level(2)
+                .with_completions(complete_colors),
+        );
+        registry.register(Command::new("hello"));
+        registry
+    }
+
+    #[test]
+    fn first_arguments_are_completed() {
+        let registry = color_registry();
+
+        assert_eq!(
+            registry.complete("/paint r", 2),
+            Some((7, vec!["red".to_string()]))
+        );
+        assert_eq!(
+            registry.complete("paint ", 2),
+            Some((6, vec!["red".into(), "green".into(), "blue".into()]))
+        );
+        assert_eq!(registry.complete("/paint x", 2), Some((7, vec![])));
+    }
+
+    #[test]
+    fn only_usable_commands_with_completions_are_completed() {
+        let registry = color_registry();
+
+        assert_eq!(registry.complete("/paint r", 1), None);
+        assert_eq!(registry.complete("/hello w", 2), None);
+        assert_eq!(registry.complete("/unknown r", 4), None);
+        // Only the first argument is completed.
+        assert_eq!(registry.complete("/paint red g", 2), None);
+        assert_eq!(registry.complete("/paint", 2), None);
+    }
+
+    #[test]
+    fn tree_only_contains_usable_commands() {
+        let registry = color_registry();
+
+        let names = |op_level| {
+            registry
+                .tree(op_level)
+                .commands
+                .iter()
+                .filter_map(|node| match node.data {
+                    NodeData::Literal { name } => Some(name.to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(0), ["hello"]);
+        assert_eq!(names(2), ["hello", "paint"]);
+
+        // The server is asked for the arguments of commands with completions.
+        let asks_server = registry
+            .tree(2)
+            .commands
+            .iter()
+            .filter(|node| {
+                matches!(
+                    node.data,
+                    NodeData::Argument {
+                        suggestion: Some(Suggestion::AskServer),
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(asks_server, 1);
+    }
+
+    #[test]
+    fn registering_a_command_again_replaces_it() {
+        let mut registry = CommandRegistry::default();
+        registry.register(Command::new("hello"));
+        registry.register(Command::new("hello").with_op_level(3));
+
+        assert_eq!(registry.iter().count(), 1);
+        assert_eq!(registry.get("hello").unwrap().op_level, 3);
+    }
+}
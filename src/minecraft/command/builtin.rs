@@ -0,0 +1,910 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use bevy::prelude::Plugin;
+use valence::prelude::*;
+
+use super::{
+    apply_effects, Caller, Command, CommandEvent, CommandRegistry, Effect, EffectTargets,
+    RegisterCommandExt,
+};
+use crate::{
+    minecraft::{
+        time::{time_effects, WorldTime},
+        weather::weather_effects,
+        world_gen::{chunk_worker::TerrainSettings, find_spawn, Instances, TERRAIN_INSTANCE},
+        SpawnPos, StartTime,
+    },
+    util::format_duration,
+};
+
+pub struct BuiltinCommandsPlugin;
+
+impl Plugin for BuiltinCommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_command(
+            Command::new("gamemode")
+                .with_op_level(2)
+                .with_usage("gamemode <adventure|creative|survival|spectator>"),
+        )
+        .register_command(Command::new("terrain").with_usage("terrain [show | <dimension>]"))
+        .register_command(Command::new("wait"))
+        .register_command(
+            Command::new("tp")
+                .with_op_level(2)
+                .with_usage("tp <x> <y> <z> | tp <player>"),
+        )
+        .register_command(
+            Command::new("kick")
+                .with_op_level(3)
+                .with_usage("kick <player> [reason]"),
+        )
+        .register_command(
+            Command::new("give")
+                .with_op_level(2)
+                .with_usage("give <item> [count]")
+                .with_completions(item_completions)
+                .with_description(
+                    "Puts items into your inventory, like give minecraft:diamond 64.",
+                ),
+        )
+        .register_command(Command::new("help").with_usage("help [command]"))
+        .register_command(Command::new("uptime"))
+        .register_command(Command::new("version"))
+        .add_system(player_commands);
+    }
+}
+
+/// Commands that are interpreted by [`interpret`], including the `time` and
+/// `weather` commands of their plugins.
+pub const INTERPRETED_COMMANDS: [&str; 11] = [
+    "gamemode", "terrain", "wait", "tp", "kick", "give", "help", "uptime", "version", "time",
+    "weather",
+];
+
+/// Online players with their instance and position, the targets of `tp` and
+/// `kick`.
+pub type Players = [(String, Entity, DVec3)];
+
+/// The state of the server the commands are interpreted with, copied out of
+/// the ECS.
+pub struct Context<'a> {
+    pub players: &'a Players,
+    pub instances: &'a Instances,
+    /// Spawn of the terrain.
+    pub spawn: DVec3,
+    /// Spawns of the other instances, only known if their spawn chunk is
+    /// loaded.
+    pub instance_spawns: &'a HashMap<Entity, DVec3>,
+    pub terrain_settings: &'a TerrainSettings,
+    pub registry: &'a CommandRegistry,
+    pub uptime: Duration,
+    pub time: &'a WorldTime,
+    /// The inventory slot of the caller `give` puts items into, `None` if the
+    /// inventory is full.
+    pub free_slot: Option<u16>,
+}
+
+/// The effects of the [`INTERPRETED_COMMANDS`].
+#[must_use]
+pub fn interpret(name: &str, args: &[String], caller: &Caller, ctx: &Context) -> Vec<Effect> {
+    let error = |msg: String| vec![Effect::SendMessage(msg.color(Color::RED))];
+
+    match name {
+        "gamemode" => {
+            let mode = match args.first().map(String::as_str).unwrap_or_default() {
+                "adventure" => GameMode::Adventure,
+                "creative" => GameMode::Creative,
+                "survival" => GameMode::Survival,
+                "spectator" => GameMode::Spectator,
+                _ => return vec![Effect::SendMessage("Invalid gamemode.".italic())],
+            };
+
+            vec![
+                Effect::SetGameMode(mode),
+                Effect::SendMessage(format!("Set gamemode to {mode:?}.").italic()),
+            ]
+        }
+        "terrain" => match args.first().map(String::as_str) {
+            Some("show") => {
+                let mut effects = vec![Effect::SendMessage("Terrain settings:".italic())];
+                effects.extend(
+                    ctx.terrain_settings
+                        .summary()
+                        .into_iter()
+                        .map(|line| Effect::SendMessage(line.color(Color::GRAY))),
+                );
+                effects
+            }
+            None | Some(TERRAIN_INSTANCE) => vec![
+                Effect::SetInstance(ctx.instances.terrain()),
+                Effect::Teleport(ctx.spawn),
+            ],
+            Some(name) => {
+                let Some(entity) = ctx.instances.get(name) else {
+                    return error(format!("Unknown dimension: {name}"));
+                };
+                let spawn = ctx
+                    .instance_spawns
+                    .get(&entity)
+                    .copied()
+                    .unwrap_or(ctx.spawn);
+
+                vec![
+                    Effect::SetInstance(entity),
+                    Effect::Teleport(spawn),
+                    Effect::SendMessage(format!("Moved to {name}.").italic()),
+                ]
+            }
+        },
+        "wait" => vec![
+            Effect::SetInstance(ctx.instances.wait()),
+            Effect::Teleport(DVec3::new(0., 203., 0.)),
+        ],
+        "tp" => {
+            let target = match args {
+                [username] => ctx
+                    .players
+                    .iter()
+                    .find(|(name, ..)| name == username)
+                    .map(|(_, instance, pos)| (*instance, *pos))
+                    .ok_or_else(|| format!("Player {username} is not online.")),
+                [x, y, z] => {
+                    let current = caller.position;
+                    match (
+                        parse_coordinate(x, current.x),
+                        parse_coordinate(y, current.y),
+                        parse_coordinate(z, current.z),
+                    ) {
+                        (Some(x), Some(y), Some(z)) => Ok((caller.instance, DVec3::new(x, y, z))),
+                        _ => Err("Invalid coordinates.".to_string()),
+                    }
+                }
+                _ => Err("Usage: tp <x> <y> <z> | tp <player>".to_string()),
+            };
+
+            match target {
+                Ok((instance, pos)) => vec![
+                    Effect::SetInstance(instance),
+                    Effect::Teleport(pos),
+                    Effect::SendMessage(
+                        format!("Teleported to {:.1} {:.1} {:.1}.", pos.x, pos.y, pos.z).italic(),
+                    ),
+                ],
+                Err(msg) => error(msg),
+            }
+        }
+        "kick" => {
+            let Some(username) = args.first() else {
+                return error("Usage: kick <player> [reason]".to_string());
+            };
+
+            if !ctx.players.iter().any(|(name, ..)| name == username) {
+                return error(format!("Player {username} is not online."));
+            }
+
+            let reason = if args.len() > 1 {
+                args[1..].join(" ")
+            } else {
+                "Kicked by an operator".to_string()
+            };
+
+            // The kicked player doesn't receive the broadcast anymore.
+            vec![
+                Effect::Kick {
+                    username: username.clone(),
+                    reason: reason.clone(),
+                },
+                Effect::Broadcast(
+                    format!("{username} was kicked by {}: {reason}", caller.username)
+                        .color(Color::YELLOW),
+                ),
+            ]
+        }
+        "give" => {
+            let Some(name) = args.first() else {
+                return error("Usage: give <item> [count]".to_string());
+            };
+            let Some(item) = parse_item(name) else {
+                return error(format!("Unknown item: {name}"));
+            };
+            let count = match args.get(1).map(|arg| arg.parse::<u32>()) {
+                None => 1,
+                Some(Ok(count)) if count > 0 => count.min(u32::from(item.max_stack())) as u8,
+                Some(_) => return error("The count has to be a positive number.".to_string()),
+            };
+
+            let name = format!("{count}x minecraft:{}", item.to_str());
+            let message = match ctx.free_slot {
+                Some(slot) => format!("Put {name} into slot {slot}."),
+                None => format!("Your inventory is full, dropped {name}."),
+            };
+
+            vec![
+                Effect::GiveItem {
+                    slot: ctx.free_slot,
+                    stack: ItemStack::new(item, count, None),
+                },
+                Effect::SendMessage(message.italic()),
+            ]
+        }
+        "help" => match args.first() {
+            None => {
+                let names = ctx
+                    .registry
+                    .iter()
+                    .filter(|c| c.op_level <= caller.op_level)
+                    .map(|c| c.name.as_str())
+                    .collect::<Vec<_>>();
+
+                vec![
+                    Effect::SendMessage(format!("Commands: {}", names.join(", ")).italic()),
+                    Effect::SendMessage("Use help <command> for details.".color(Color::GRAY)),
+                ]
+            }
+            Some(name) => {
+                let Some(command) = ctx.registry.get(name) else {
+                    return error(format!("Unknown command: {name}"));
+                };
+
+                let mut effects = vec![Effect::SendMessage(
+                    format!("Usage: {}", command.usage).italic(),
+                )];
+                if !command.description.is_empty() {
+                    effects.push(Effect::SendMessage(
+                        command.description.clone().color(Color::GRAY),
+                    ));
+                }
+                effects
+            }
+        },
+        "uptime" | "version" => vec![
+            Effect::SendMessage(
+                format!("Valence Playground v{}", env!("CARGO_PKG_VERSION")).italic(),
+            ),
+            Effect::SendMessage(format!("Uptime: {}", format_duration(ctx.uptime)).italic()),
+        ],
+        "time" => time_effects(args, ctx.time),
+        "weather" => weather_effects(args),
+        _ => vec![],
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn player_commands(
+    mut targets: EffectTargets,
+    mut events: EventReader<CommandEvent>,
+    instances: Query<&Instance>,
+    instances_list: Res<Instances>,
+    settings: Res<TerrainSettings>,
+    spawn: Res<SpawnPos>,
+    registry: Res<CommandRegistry>,
+    start_time: Res<StartTime>,
+) {
+    let mut players = None;
+    let mut instance_spawns = None;
+
+    for event in events
+        .iter()
+        .filter(|e| INTERPRETED_COMMANDS.contains(&e.name.as_str()))
+    {
+        let players = players.get_or_insert_with(|| {
+            targets
+                .clients
+                .iter()
+                .map(|(c, _)| (c.username().to_string(), c.instance(), c.position()))
+                .collect::<Vec<_>>()
+        });
+        let instance_spawns = instance_spawns.get_or_insert_with(|| {
+            instances_list
+                .map
+                .values()
+                .filter_map(|entity| {
+                    let chunk = instances.get(*entity).ok()?.chunk([0, 0])?;
+                    Some((*entity, find_spawn(chunk)?))
+                })
+                .collect::<HashMap<_, _>>()
+        });
+
+        let Ok((client, inventory)) = targets.clients.get(event.client) else {
+            continue;
+        };
+        let caller = Caller::new(client);
+        let free_slot = inventory
+            .and_then(|inventory| give_slots().find(|slot| inventory.slot(*slot).is_none()));
+
+        let time = targets.time.as_deref().cloned().unwrap_or_default();
+        let ctx = Context {
+            players,
+            instances: &instances_list,
+            spawn: spawn.0,
+            instance_spawns,
+            terrain_settings: &settings,
+            registry: &registry,
+            uptime: start_time.0.elapsed(),
+            time: &time,
+            free_slot,
+        };
+
+        let effects = interpret(&event.name, &event.args, &caller, &ctx);
+        apply_effects(&mut targets, event.client, effects);
+    }
+}
+
+/// Parses an item id like `diamond` or `minecraft:diamond`.
+#[must_use]
+pub fn parse_item(name: &str) -> Option<ItemKind> {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    ItemKind::from_str(name)
+}
+
+/// Item ids starting with `prefix`, with or without `minecraft:`.
+#[must_use]
+pub fn item_completions(prefix: &str) -> Vec<String> {
+    let name = prefix.strip_prefix("minecraft:").unwrap_or(prefix);
+
+    ItemKind::ALL
+        .iter()
+        .filter(|item| **item != ItemKind::Air && item.to_str().starts_with(name))
+        .map(|item| format!("minecraft:{}", item.to_str()))
+        .collect()
+}
+
+/// Slots of the player inventory in the order `give` fills them, the hotbar
+/// first.
+fn give_slots() -> impl Iterator<Item = u16> { (36..45).chain(9..36) }
+
+/// Highest absolute coordinate players can teleport to, the size of a vanilla
+/// world.
+pub const MAX_COORDINATE: f64 = 30_000_000.0;
+
+/// Parses an absolute (`10.5`) or relative (`~`, `~-3`, `~+5`) coordinate.
+/// Coordinates that aren't finite or are further than [`MAX_COORDINATE`] from
+/// the origin are rejected.
+#[must_use]
+pub fn parse_coordinate(arg: &str, current: f64) -> Option<f64> {
+    let coordinate = match arg.strip_prefix('~') {
+        Some("") => current,
+        Some(offset) => current + offset.parse::<f64>().ok()?,
+        None => arg.parse().ok()?,
+    };
+
+    (coordinate.is_finite() && coordinate.abs() <= MAX_COORDINATE).then_some(coordinate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minecraft::{weather::Weather, world_gen::WAIT_INSTANCE};
+
+    fn args(args: &[&str]) -> Vec<String> { args.iter().map(ToString::to_string).collect() }
+
+    fn caller() -> Caller {
+        Caller {
+            username: "Alice".to_string(),
+            op_level: 2,
+            position: DVec3::new(10.0, 64.0, -5.0),
+            instance: Entity::from_raw(1),
+        }
+    }
+
+    /// Applies the effects to a client like the caller, returns its instance,
+    /// position and the messages it received.
+    fn simulate(caller: &Caller, effects: Vec<Effect>) -> (Entity, DVec3, Vec<Text>) {
+        let (mut instance, mut position, mut messages) = (caller.instance, caller.position, vec![]);
+
+        for effect in effects {
+            match effect {
+                Effect::SetInstance(entity) => instance = entity,
+                Effect::Teleport(pos) => position = pos,
+                Effect::SendMessage(msg) => messages.push(msg),
+                _ => {}
+            }
+        }
+
+        (instance, position, messages)
+    }
+
+    /// The state of a test server, with Bob online in the terrain.
+    struct Server {
+        players: Vec<(String, Entity, DVec3)>,
+        instances: Instances,
+        instance_spawns: HashMap<Entity, DVec3>,
+        settings: TerrainSettings,
+        registry: CommandRegistry,
+        time: WorldTime,
+        free_slot: Option<u16>,
+    }
+
+    impl Server {
+        fn new() -> Self {
+            let mut app = App::new();
+            BuiltinCommandsPlugin.build(&mut app);
+
+            let mut instances = Instances::default();
+            instances.insert(TERRAIN_INSTANCE.to_string(), Entity::from_raw(8));
+            instances.insert(WAIT_INSTANCE.to_string(), Entity::from_raw(9));
+            instances.insert("nether".to_string(), Entity::from_raw(10));
+            instances.insert("end".to_string(), Entity::from_raw(11));
+
+            Self {
+                players: vec![(
+                    "Bob".to_string(),
+                    Entity::from_raw(2),
+                    DVec3::new(1.0, 2.0, 3.0),
+                )],
+                instances,
+                instance_spawns: HashMap::from([(
+                    Entity::from_raw(10),
+                    DVec3::new(0.5, 40.0, 0.5),
+                )]),
+                settings: TerrainSettings::default(),
+                registry: app.world.remove_resource::<CommandRegistry>().unwrap(),
+                time: WorldTime::default(),
+                free_slot: Some(36),
+            }
+        }
+
+        fn run(&self, name: &str, command_args: &[&str]) -> Vec<Effect> {
+            self.run_as(&caller(), name, command_args)
+        }
+
+        fn run_as(&self, caller: &Caller, name: &str, command_args: &[&str]) -> Vec<Effect> {
+            let ctx = Context {
+                players: &self.players,
+                instances: &self.instances,
+                spawn: DVec3::new(0.5, 80.0, 0.5),
+                instance_spawns: &self.instance_spawns,
+                terrain_settings: &self.settings,
+                registry: &self.registry,
+                uptime: Duration::from_secs(3725),
+                time: &self.time,
+                free_slot: self.free_slot,
+            };
+
+            interpret(name, &args(command_args), caller, &ctx)
+        }
+    }
+
+    fn message(msg: &str) -> Effect { Effect::SendMessage(msg.italic()) }
+
+    fn error(msg: &str) -> Effect { Effect::SendMessage(msg.color(Color::RED)) }
+
+    fn tp(tp_args: &[&str], players: &[(String, Entity, DVec3)]) -> (Entity, DVec3, Vec<Text>) {
+        let server = Server {
+            players: players.to_vec(),
+            ..Server::new()
+        };
+
+        simulate(&caller(), server.run("tp", tp_args))
+    }
+
+    #[test]
+    fn coordinates_are_absolute_or_relative() {
+        assert_eq!(parse_coordinate("10.5", 3.0), Some(10.5));
+        assert_eq!(parse_coordinate("-7", 3.0), Some(-7.0));
+        assert_eq!(parse_coordinate("~", 3.0), Some(3.0));
+        assert_eq!(parse_coordinate("~+5", 3.0), Some(8.0));
+        assert_eq!(parse_coordinate("~-3.5", 3.0), Some(-0.5));
+    }
+
+    #[test]
+    fn invalid_coordinates_are_rejected() {
+        for arg in [
+            "", "x", "~x", "~~", "1,5", "NaN", "inf", "-inf", "~inf", "~NaN",
+        ] {
+            assert_eq!(parse_coordinate(arg, 0.0), None, "{arg:?}");
+        }
+    }
+
+    #[test]
+    fn coordinates_outside_the_world_are_rejected() {
+        assert_eq!(parse_coordinate("30000000", 0.0), Some(MAX_COORDINATE));
+        assert_eq!(parse_coordinate("30000001", 0.0), None);
+        assert_eq!(parse_coordinate("-1e300", 0.0), None);
+        assert_eq!(parse_coordinate("~1", MAX_COORDINATE), None);
+    }
+
+    #[test]
+    fn tp_moves_the_caller_to_the_coordinates() {
+        let (instance, position, messages) = tp(&["~", "~5", "100"], &[]);
+
+        assert_eq!(instance, caller().instance);
+        assert_eq!(position, DVec3::new(10.0, 69.0, 100.0));
+        assert_eq!(messages, vec!["Teleported to 10.0 69.0 100.0.".italic()]);
+    }
+
+    #[test]
+    fn tp_moves_the_caller_to_another_player() {
+        let players = [(
+            "Bob".to_string(),
+            Entity::from_raw(2),
+            DVec3::new(1.0, 2.0, 3.0),
+        )];
+        let (instance, position, _) = tp(&["Bob"], &players);
+
+        assert_eq!(instance, Entity::from_raw(2));
+        assert_eq!(position, DVec3::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn failed_tp_keeps_the_caller_in_place() {
+        let cases = [
+            (vec!["Bob"], "Player Bob is not online."),
+            (vec!["1", "NaN", "3"], "Invalid coordinates."),
+            (vec!["~inf", "0", "0"], "Invalid coordinates."),
+            (vec!["1", "2"], "Usage: tp <x> <y> <z> | tp <player>"),
+        ];
+
+        for (tp_args, error) in cases {
+            let (instance, position, messages) = tp(&tp_args, &[]);
+
+            assert_eq!(instance, caller().instance);
+            assert_eq!(position, caller().position);
+            assert_eq!(messages, vec![error.color(Color::RED)]);
+        }
+    }
+
+    #[test]
+    fn tp_requires_op_level_2() {
+        let mut app = App::new();
+        BuiltinCommandsPlugin.build(&mut app);
+        let registry = app.world.resource::<CommandRegistry>();
+
+        assert_eq!(
+            registry.parse("tp 0 64 0", 1).unwrap_err(),
+            "Not enough permissions to use tp command."
+        );
+        let (command, tp_args) = registry.parse("tp 0 64 0", 2).unwrap();
+        assert_eq!(command.name, "tp");
+        assert_eq!(tp_args, args(&["0", "64", "0"]));
+    }
+
+    fn kick(kick_args: &[&str]) -> Vec<Effect> { Server::new().run("kick", kick_args) }
+
+    #[test]
+    fn kick_disconnects_the_player_with_the_reason() {
+        assert_eq!(
+            kick(&["Bob", "spamming", "the", "chat"]),
+            vec![
+                Effect::Kick {
+                    username: "Bob".to_string(),
+                    reason: "spamming the chat".to_string(),
+                },
+                Effect::Broadcast(
+                    "Bob was kicked by Alice: spamming the chat".color(Color::YELLOW)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn kick_without_a_reason_uses_the_default() {
+        assert_eq!(
+            kick(&["Bob"])[0],
+            Effect::Kick {
+                username: "Bob".to_string(),
+                reason: "Kicked by an operator".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn kick_errors_are_only_sent_to_the_caller() {
+        assert_eq!(
+            kick(&[]),
+            vec![Effect::SendMessage(
+                "Usage: kick <player> [reason]".color(Color::RED)
+            )]
+        );
+        // Usernames are case sensitive.
+        assert_eq!(
+            kick(&["bob", "bye"]),
+            vec![Effect::SendMessage(
+                "Player bob is not online.".color(Color::RED)
+            )]
+        );
+    }
+
+    #[test]
+    fn kick_requires_op_level_3() {
+        let mut app = App::new();
+        BuiltinCommandsPlugin.build(&mut app);
+        let registry = app.world.resource::<CommandRegistry>();
+
+        assert_eq!(
+            registry.parse("kick Bob", 2).unwrap_err(),
+            "Not enough permissions to use kick command."
+        );
+        assert!(registry.parse("kick Bob", 3).is_ok());
+    }
+
+    #[test]
+    fn gamemode_sets_the_gamemode_of_the_caller() {
+        let server = Server::new();
+
+        assert_eq!(
+            server.run("gamemode", &["creative"]),
+            vec![
+                Effect::SetGameMode(GameMode::Creative),
+                message("Set gamemode to Creative."),
+            ]
+        );
+        assert_eq!(
+            server.run("gamemode", &["hardcore"]),
+            vec![message("Invalid gamemode.")]
+        );
+        assert_eq!(
+            server.run("gamemode", &[]),
+            vec![message("Invalid gamemode.")]
+        );
+    }
+
+    #[test]
+    fn wait_moves_the_caller_to_the_waiting_area() {
+        assert_eq!(
+            Server::new().run("wait", &[]),
+            vec![
+                Effect::SetInstance(Entity::from_raw(9)),
+                Effect::Teleport(DVec3::new(0.0, 203.0, 0.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn terrain_moves_the_caller_to_the_spawn_of_the_dimension() {
+        let server = Server::new();
+        let terrain = vec![
+            Effect::SetInstance(Entity::from_raw(8)),
+            Effect::Teleport(DVec3::new(0.5, 80.0, 0.5)),
+        ];
+
+        assert_eq!(server.run("terrain", &[]), terrain);
+        assert_eq!(server.run("terrain", &[TERRAIN_INSTANCE]), terrain);
+        assert_eq!(
+            server.run("terrain", &["nether"]),
+            vec![
+                Effect::SetInstance(Entity::from_raw(10)),
+                Effect::Teleport(DVec3::new(0.5, 40.0, 0.5)),
+                message("Moved to nether."),
+            ]
+        );
+        // Without a loaded spawn chunk the caller lands at the terrain spawn.
+        assert_eq!(
+            server.run("terrain", &["end"]),
+            vec![
+                Effect::SetInstance(Entity::from_raw(11)),
+                Effect::Teleport(DVec3::new(0.5, 80.0, 0.5)),
+                message("Moved to end."),
+            ]
+        );
+        assert_eq!(
+            server.run("terrain", &["moon"]),
+            vec![error("Unknown dimension: moon")]
+        );
+    }
+
+    #[test]
+    fn terrain_show_lists_the_settings() {
+        let server = Server::new();
+        let effects = server.run("terrain", &["show"]);
+
+        assert_eq!(effects[0], message("Terrain settings:"));
+        assert_eq!(
+            effects[1..],
+            server
+                .settings
+                .summary()
+                .into_iter()
+                .map(|line| Effect::SendMessage(line.color(Color::GRAY)))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn give_puts_the_items_into_the_free_slot() {
+        assert_eq!(
+            Server::new().run("give", &["minecraft:diamond", "64"]),
+            vec![
+                Effect::GiveItem {
+                    slot: Some(36),
+                    stack: ItemStack::new(ItemKind::Diamond, 64, None),
+                },
+                message("Put 64x minecraft:diamond into slot 36."),
+            ]
+        );
+    }
+
+    #[test]
+    fn give_drops_the_items_if_the_inventory_is_full() {
+        let server = Server {
+            free_slot: None,
+            ..Server::new()
+        };
+
+        assert_eq!(
+            server.run("give", &["diamond"]),
+            vec![
+                Effect::GiveItem {
+                    slot: None,
+                    stack: ItemStack::new(ItemKind::Diamond, 1, None),
+                },
+                message("Your inventory is full, dropped 1x minecraft:diamond."),
+            ]
+        );
+    }
+
+    #[test]
+    fn give_clamps_the_count_to_the_stack_size() {
+        assert_eq!(
+            Server::new().run("give", &["ender_pearl", "64"])[0],
+            Effect::GiveItem {
+                slot: Some(36),
+                stack: ItemStack::new(ItemKind::EnderPearl, 16, None),
+            }
+        );
+    }
+
+    #[test]
+    fn give_errors_are_sent_to_the_caller() {
+        let server = Server::new();
+
+        assert_eq!(
+            server.run("give", &[]),
+            vec![error("Usage: give <item> [count]")]
+        );
+        assert_eq!(
+            server.run("give", &["unobtainium"]),
+            vec![error("Unknown item: unobtainium")]
+        );
+        for count in ["0", "-1", "many"] {
+            assert_eq!(
+                server.run("give", &["diamond", count]),
+                vec![error("The count has to be a positive number.")]
+            );
+        }
+    }
+
+    #[test]
+    fn item_ids_are_completed_with_and_without_namespace() {
+        let completions = item_completions("diamond_p");
+        assert_eq!(completions, ["minecraft:diamond_pickaxe"]);
+        assert_eq!(item_completions("minecraft:diamond_p"), completions);
+
+        assert!(item_completions("dia")
+            .iter()
+            .all(|id| id.starts_with("minecraft:dia")));
+        assert!(item_completions("not_an_item").is_empty());
+        assert!(!item_completions("").contains(&"minecraft:air".to_string()));
+    }
+
+    #[test]
+    fn give_completes_item_ids() {
+        let mut app = App::new();
+        BuiltinCommandsPlugin.build(&mut app);
+        let registry = app.world.resource::<CommandRegistry>();
+
+        assert_eq!(
+            registry.complete("/give diamond_p", 2),
+            Some((6, vec!["minecraft:diamond_pickaxe".to_string()]))
+        );
+        assert_eq!(registry.complete("/give diamond_p", 0), None);
+    }
+
+    #[test]
+    fn help_lists_the_commands_of_the_op_level() {
+        let effects = Server::new().run("help", &[]);
+
+        assert_eq!(
+            effects,
+            vec![
+                message("Commands: gamemode, give, help, terrain, tp, uptime, version, wait"),
+                Effect::SendMessage("Use help <command> for details.".color(Color::GRAY)),
+            ]
+        );
+    }
+
+    #[test]
+    fn uptime_and_version_show_both() {
+        let server = Server::new();
+        let expected = vec![
+            message(&format!(
+                "Valence Playground v{}",
+                env!("CARGO_PKG_VERSION")
+            )),
+            message(&format!(
+                "Uptime: {}",
+                format_duration(Duration::from_secs(3725))
+            )),
+        ];
+
+        assert_eq!(server.run("uptime", &[]), expected);
+        assert_eq!(server.run("version", &[]), expected);
+    }
+
+    #[test]
+    fn time_sets_the_time_of_the_world() {
+        let server = Server::new();
+
+        assert_eq!(
+            server.run("time", &["set", "noon"]),
+            vec![
+                Effect::SetTime(WorldTime {
+                    time_of_day: 6000,
+                    ..WorldTime::default()
+                }),
+                message("Set the time to 6000."),
+            ]
+        );
+        // Queries don't change the time.
+        assert_eq!(
+            server.run("time", &["query", "daytime"]),
+            vec![message("The time is 0.")]
+        );
+    }
+
+    #[test]
+    fn weather_sets_the_weather() {
+        assert_eq!(
+            Server::new().run("weather", &["rain", "600"]),
+            vec![
+                Effect::SetWeather {
+                    weather: Weather::Rain,
+                    duration: Some(600),
+                },
+                message("Set the weather to Rain for 600 ticks."),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_commands_have_no_effects() {
+        assert_eq!(Server::new().run("stop", &[]), vec![]);
+    }
+
+    #[test]
+    fn help_only_lists_the_commands_of_the_callers_op_level() {
+        let player = Caller {
+            op_level: 0,
+            ..caller()
+        };
+
+        assert_eq!(
+            Server::new().run_as(&player, "help", &[])[0],
+            message("Commands: help, terrain, uptime, version, wait")
+        );
+    }
+
+    #[test]
+    fn help_shows_the_usage_and_description_of_a_command() {
+        let server = Server::new();
+
+        assert_eq!(
+            server.run("help", &["give"]),
+            vec![
+                message("Usage: give <item> [count]"),
+                Effect::SendMessage(
+                    "Puts items into your inventory, like give minecraft:diamond 64."
+                        .color(Color::GRAY)
+                ),
+            ]
+        );
+        // Commands without a description only show their usage.
+        assert_eq!(
+            server.run("help", &["terrain"]),
+            vec![message("Usage: terrain [show | <dimension>]")]
+        );
+        assert_eq!(
+            server.run("help", &["fly"]),
+            vec![error("Unknown command: fly")]
+        );
+    }
+
+    #[test]
+    fn help_uptime_and_version_need_no_op_level() {
+        let server = Server::new();
+
+        for command in ["help", "uptime", "version"] {
+            assert_eq!(server.registry.parse(command, 0).unwrap().0.name, command);
+        }
+    }
+}
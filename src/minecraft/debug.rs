@@ -0,0 +1,120 @@
+use bevy::prelude::Plugin;
+use valence::prelude::*;
+
+use super::command::{Command, CommandEvent, RegisterCommandExt};
+
+/// Ticks between two rounds of particles, about as long as they stay visible.
+const PARTICLE_INTERVAL: u64 = 10;
+
+/// Chunks on every side of the player's chunk whose borders are shown, at most
+/// the player's view distance.
+const DEBUG_CHUNK_RADIUS: i32 = 2;
+
+/// Blocks between two particles along a chunk border.
+const PARTICLE_SPACING: usize = 2;
+
+/// Height of the pillars at the corners of the player's chunk, in blocks.
+const PILLAR_HEIGHT: i32 = 16;
+
+const DEBUG_USAGE: &str = "debug chunks";
+
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_command(
+            Command::new("debug")
+                .with_op_level(2)
+                .with_usage(DEBUG_USAGE)
+                .with_description("Toggles particles along the chunk borders around you."),
+        )
+        .add_system(debug_command)
+        .add_system(visualize_chunk_boundaries.after(debug_command));
+    }
+}
+
+/// Players that see the borders of the chunks around them.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct DebugChunks;
+
+/// Points along the chunk borders within `radius` chunks of `center` at the
+/// height `y`, and pillars at the corners of `center` itself.
+#[must_use]
+pub fn chunk_border_points(center: ChunkPos, radius: i32, y: f64) -> Vec<DVec3> {
+    let min_x = (center.x - radius) * 16;
+    let max_x = (center.x + radius + 1) * 16;
+    let min_z = (center.z - radius) * 16;
+    let max_z = (center.z + radius + 1) * 16;
+
+    let mut points = vec![];
+
+    for border in (min_x..=max_x).step_by(16) {
+        for z in (min_z..=max_z).step_by(PARTICLE_SPACING) {
+            points.push(DVec3::new(f64::from(border), y, f64::from(z)));
+        }
+    }
+    for border in (min_z..=max_z).step_by(16) {
+        for x in (min_x..=max_x).step_by(PARTICLE_SPACING) {
+            points.push(DVec3::new(f64::from(x), y, f64::from(border)));
+        }
+    }
+
+    for (x, z) in [(0, 0), (16, 0), (0, 16), (16, 16)] {
+        for height in 1..=PILLAR_HEIGHT / PARTICLE_SPACING as i32 {
+            points.push(DVec3::new(
+                f64::from(center.x * 16 + x),
+                y + f64::from(height * PARTICLE_SPACING as i32),
+                f64::from(center.z * 16 + z),
+            ));
+        }
+    }
+
+    points
+}
+
+fn debug_command(
+    mut commands: Commands,
+    mut clients: Query<(&mut Client, Option<&DebugChunks>)>,
+    mut events: EventReader<CommandEvent>,
+) {
+    for event in events.iter().filter(|e| e.is("debug")) {
+        let Ok((mut client, debug)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        if event.arg(0) != Some("chunks") || event.args.len() > 1 {
+            client.send_message(format!("Usage: {DEBUG_USAGE}").color(Color::RED));
+            continue;
+        }
+
+        if debug.is_some() {
+            commands.entity(event.client).remove::<DebugChunks>();
+            client.send_message("Stopped showing chunk borders.".italic());
+        } else {
+            commands.entity(event.client).insert(DebugChunks);
+            client.send_message("Showing chunk borders.".italic());
+        }
+    }
+}
+
+/// Shows the chunk borders around every player with [`DebugChunks`], they
+/// follow the player as the particles are placed around their current chunk.
+fn visualize_chunk_boundaries(
+    mut clients: Query<&mut Client, With<DebugChunks>>,
+    mut ticks: Local<u64>,
+) {
+    *ticks = ticks.wrapping_add(1);
+    if *ticks % PARTICLE_INTERVAL != 0 {
+        return;
+    }
+
+    for mut client in &mut clients {
+        let pos = client.position();
+        let center = ChunkPos::new((pos.x / 16.0).floor() as i32, (pos.z / 16.0).floor() as i32);
+        let radius = DEBUG_CHUNK_RADIUS.min(i32::from(client.view_distance()));
+
+        for point in chunk_border_points(center, radius, pos.y.floor() + 0.1) {
+            client.play_particle(&Particle::EndRod, false, point, [0.0, 0.0, 0.0], 0.0, 1);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use image::{Rgba, RgbaImage};
+use valence::{prelude::*, view::ChunkPos};
+
+use crate::SECTION_COUNT;
+
+/// Color of columns without a generated chunk.
+const MISSING: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+/// Maximum distance in chunks from the center of a map to its edges, so a map
+/// is at most `(2 * MAP_RADIUS + 1) * 16` pixels wide and high.
+pub const MAP_RADIUS: i32 = 32;
+
+/// Whether the chunk is part of a map centered on `center`.
+#[must_use]
+pub fn in_map_area(center: ChunkPos, pos: ChunkPos) -> bool {
+    let radius = i64::from(MAP_RADIUS);
+    (i64::from(pos.x) - i64::from(center.x)).abs() <= radius
+        && (i64::from(pos.z) - i64::from(center.z)).abs() <= radius
+}
+
+/// Path of the map with the given name, maps are always written to the `maps`
+/// directory. The name has to be checked with [`is_valid_name`] first.
+///
+/// [`is_valid_name`]: super::structure::is_valid_name
+///
+/// # Errors
+/// - the current directory can't be accessed
+pub fn map_path(name: &str) -> Result<PathBuf> {
+    let base_path = std::env::current_dir()?.join("maps");
+    Ok(base_path.join(format!("{name}.png")))
+}
+
+/// Representative color of a block seen from above, blocks that aren't listed
+/// are grey like stone.
+#[must_use]
+pub fn block_to_color(state: BlockState) -> [u8; 3] {
+    match state.to_kind() {
+        BlockKind::GrassBlock | BlockKind::Grass | BlockKind::TallGrass => [95, 159, 53],
+        BlockKind::OakLeaves | BlockKind::BirchLeaves | BlockKind::SpruceLeaves => [60, 110, 35],
+        BlockKind::OakLog | BlockKind::BirchLog | BlockKind::SpruceLog => [102, 81, 51],
+        BlockKind::Water | BlockKind::Seagrass | BlockKind::TallSeagrass | BlockKind::Kelp => {
+            [52, 90, 200]
+        }
+        BlockKind::Ice | BlockKind::PackedIce => [160, 190, 250],
+        BlockKind::Sand => [219, 207, 163],
+        BlockKind::Sandstone => [200, 185, 140],
+        BlockKind::Gravel => [136, 126, 126],
+        BlockKind::Dirt | BlockKind::CoarseDirt => [134, 96, 67],
+        BlockKind::Snow | BlockKind::SnowBlock => [240, 240, 250],
+        BlockKind::Lava => [207, 92, 20],
+        BlockKind::Deepslate => [80, 80, 82],
+        BlockKind::CoalOre | BlockKind::IronOre | BlockKind::GoldOre | BlockKind::DiamondOre => {
+            [140, 130, 120]
+        }
+        _ => [125, 125, 125],
+    }
+}
+
+/// Height and state of the topmost block of the column that isn't air.
+#[must_use]
+pub fn surface(chunk: &Chunk, x: usize, z: usize) -> Option<(usize, BlockState)> {
+    (0..SECTION_COUNT * 16)
+        .rev()
+        .map(|y| (y, chunk.block_state(x, y, z)))
+        .find(|(_, state)| !state.is_air())
+}
+
+/// Top-down view of the chunks with one pixel per column, higher columns are
+/// brighter. Only chunks at most [`MAP_RADIUS`] chunks away from `center` are
+/// drawn, the image covers the smallest rectangle containing all of them and
+/// columns of missing chunks are transparent.
+///
+/// # Errors
+/// - there are no chunks around the center
+pub fn render_map<'a>(
+    chunks: impl IntoIterator<Item = (ChunkPos, &'a Chunk)>,
+    center: ChunkPos,
+) -> Result<RgbaImage> {
+    let chunks = chunks
+        .into_iter()
+        .filter(|(pos, _)| in_map_area(center, *pos))
+        .collect::<Vec<_>>();
+    let (Some(min_x), Some(max_x), Some(min_z), Some(max_z)) = (
+        chunks.iter().map(|(pos, _)| pos.x).min(),
+        chunks.iter().map(|(pos, _)| pos.x).max(),
+        chunks.iter().map(|(pos, _)| pos.z).min(),
+        chunks.iter().map(|(pos, _)| pos.z).max(),
+    ) else {
+        bail!(
+            "There are no chunks around {} {} to render.",
+            center.x,
+            center.z
+        );
+    };
+
+    // At most `2 * MAP_RADIUS + 1` chunks, so this can't overflow.
+    let width = (i64::from(max_x) - i64::from(min_x) + 1) as u32 * 16;
+    let height = (i64::from(max_z) - i64::from(min_z) + 1) as u32 * 16;
+    let mut image = RgbaImage::from_pixel(width, height, MISSING);
+
+    for (pos, chunk) in chunks {
+        let origin_x = (i64::from(pos.x) - i64::from(min_x)) as u32 * 16;
+        let origin_z = (i64::from(pos.z) - i64::from(min_z)) as u32 * 16;
+
+        for x in 0..16 {
+            for z in 0..16 {
+                let Some((y, state)) = surface(chunk, x, z) else {
+                    continue;
+                };
+
+                // Between 60% brightness at the bottom and 100% at the build limit.
+                let brightness = 0.6 + 0.4 * y as f32 / (SECTION_COUNT * 16) as f32;
+                let [r, g, b] = block_to_color(state).map(|c| (f32::from(c) * brightness) as u8);
+
+                image.put_pixel(
+                    origin_x + x as u32,
+                    origin_z + z as u32,
+                    Rgba([r, g, b, 255]),
+                );
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+/// Renders the chunks with [`render_map`] and writes them to a PNG file,
+/// returns the size of the image.
+///
+/// # Errors
+/// - there are no chunks around the center
+/// - the file can't be written
+pub fn export_map<'a>(
+    chunks: impl IntoIterator<Item = (ChunkPos, &'a Chunk)>,
+    center: ChunkPos,
+    path: &Path,
+) -> Result<(u32, u32)> {
+    let image = render_map(chunks, center)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    image.save_with_format(path, image::ImageFormat::Png)?;
+
+    Ok(image.dimensions())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_is_bounded_around_the_center() {
+        let chunk = Chunk::new(SECTION_COUNT);
+        let far = ChunkPos::new(i32::MAX, i32::MIN);
+        let near = ChunkPos::new(MAP_RADIUS, -MAP_RADIUS);
+        let chunks = [(far, &chunk), (near, &chunk), (ChunkPos::new(0, 0), &chunk)];
+
+        let image = render_map(chunks, ChunkPos::new(0, 0)).unwrap();
+        let size = (MAP_RADIUS as u32 + 1) * 16;
+        assert_eq!(image.dimensions(), (size, size));
+
+        assert!(render_map([(far, &chunk)], ChunkPos::new(0, 0)).is_err());
+    }
+}
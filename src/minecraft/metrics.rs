@@ -0,0 +1,172 @@
+use std::{net::SocketAddr, time::Duration};
+
+use anyhow::Result;
+use axum::{routing::get, Router};
+use bevy::prelude::Plugin;
+use lazy_static::lazy_static;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use valence::prelude::*;
+
+use super::PlayerCount;
+use crate::CONFIG;
+
+lazy_static! {
+    pub static ref REGISTRY: Registry = Registry::new();
+    /// Chunks handled by the chunk workers, labeled by where the chunk came
+    /// from (`generated`, `cached` or `saved`).
+    pub static ref CHUNK_GEN_TOTAL: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("chunk_gen_total", "Chunks handled by the chunk workers"),
+        &["source"],
+    ));
+    pub static ref CHUNK_GEN_DURATION: Histogram = register(Histogram::with_opts(
+        HistogramOpts::new(
+            "chunk_gen_duration_seconds",
+            "Time it took to provide a chunk"
+        )
+        .buckets(vec![0.0001, 0.0005, 0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]),
+    ));
+    pub static ref CHUNK_CACHE_HITS: IntCounter = register(IntCounter::new(
+        "chunk_cache_hits_total",
+        "Chunks found in the chunk cache"
+    ));
+    pub static ref CHUNK_CACHE_MISSES: IntCounter = register(IntCounter::new(
+        "chunk_cache_misses_total",
+        "Chunks not found in the chunk cache"
+    ));
+    pub static ref ACTIVE_PLAYERS: IntGauge = register(IntGauge::new(
+        "active_players_gauge",
+        "Players currently online"
+    ));
+}
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(metric: prometheus::Result<T>) -> T {
+    let metric = metric.expect("metric options should be valid");
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("metric should only be registered once");
+    metric
+}
+
+/// Records a chunk handled by a chunk worker.
+pub fn record_chunk(cached: bool, saved: bool, duration: Duration) {
+    let source = if cached {
+        "cached"
+    } else if saved {
+        "saved"
+    } else {
+        "generated"
+    };
+
+    CHUNK_GEN_TOTAL.with_label_values(&[source]).inc();
+    CHUNK_GEN_DURATION.observe(duration.as_secs_f64());
+
+    if cached {
+        CHUNK_CACHE_HITS.inc();
+    } else {
+        CHUNK_CACHE_MISSES.inc();
+    }
+}
+
+/// Serves the metrics at `/metrics` if enabled in the config.
+pub struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        if !CONFIG.metrics.enabled {
+            return;
+        }
+
+        let address = CONFIG.metrics.address;
+        let _ = tokio::task::Builder::new()
+            .name("Metrics")
+            .spawn(async move {
+                if let Err(e) = serve(address).await {
+                    error!(target: "minecraft::metrics", "Metrics endpoint stopped: {e}");
+                }
+            });
+
+        app.add_system(update_active_players);
+    }
+}
+
+/// # Errors
+/// - the address can't be bound
+pub async fn serve(address: SocketAddr) -> Result<()> {
+    let app = Router::new().route("/metrics", get(metrics));
+
+    info!(target: "minecraft::metrics", "Serving metrics at http://{address}/metrics");
+
+    axum::Server::try_bind(&address)?
+        .serve(app.into_make_service())
+        .await?;
+
+    Ok(())
+}
+
+async fn metrics() -> String { encode_metrics() }
+
+/// Renders every registered metric in the prometheus text format.
+fn encode_metrics() -> String {
+    let mut buf = vec![];
+    if let Err(e) = TextEncoder::new().encode(&REGISTRY.gather(), &mut buf) {
+        error!(target: "minecraft::metrics", "Unable to encode metrics: {e}");
+    }
+
+    String::from_utf8(buf).unwrap_or_default()
+}
+
+fn update_active_players(player_count: Res<PlayerCount>) {
+    ACTIVE_PLAYERS.set(player_count.get() as i64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MetricsConfig;
+
+    #[test]
+    fn metrics_are_disabled_by_default() {
+        let config = MetricsConfig::default();
+        assert!(!config.enabled);
+        assert_eq!(config.address, SocketAddr::from(([127, 0, 0, 1], 9184)));
+
+        let config: MetricsConfig = toml::from_str("enabled = true").unwrap();
+        assert!(config.enabled);
+        assert_eq!(config.address.port(), 9184);
+    }
+
+    #[test]
+    fn chunks_are_recorded_by_source() {
+        let count = |source: &str| CHUNK_GEN_TOTAL.with_label_values(&[source]).get();
+        let (generated, cached, saved) = (count("generated"), count("cached"), count("saved"));
+        let (hits, misses) = (CHUNK_CACHE_HITS.get(), CHUNK_CACHE_MISSES.get());
+        let observed = CHUNK_GEN_DURATION.get_sample_count();
+
+        record_chunk(true, false, Duration::from_millis(1));
+        record_chunk(false, true, Duration::from_millis(2));
+        record_chunk(false, false, Duration::from_millis(3));
+
+        // Other tests may generate chunks concurrently
+        assert!(count("cached") > cached);
+        assert!(count("saved") > saved);
+        assert!(count("generated") > generated);
+        assert!(CHUNK_CACHE_HITS.get() > hits);
+        assert!(CHUNK_CACHE_MISSES.get() >= misses + 2);
+        assert!(CHUNK_GEN_DURATION.get_sample_count() >= observed + 3);
+    }
+
+    #[test]
+    fn metrics_are_exported_as_text() {
+        record_chunk(false, false, Duration::from_millis(1));
+        ACTIVE_PLAYERS.set(3);
+
+        let text = encode_metrics();
+        assert!(text.contains("chunk_gen_total{source=\"generated\"}"));
+        assert!(text.contains("chunk_gen_duration_seconds_bucket"));
+        assert!(text.contains("chunk_cache_misses_total"));
+        assert!(text.contains("active_players_gauge 3"));
+    }
+}
@@ -0,0 +1,371 @@
+use std::{fs::OpenOptions, io::Read, path::PathBuf};
+
+use anyhow::Result;
+use valence::prelude::*;
+
+use super::{client::PlayerStats, save::write_atomically};
+
+/// Slot of the player inventory holding the first hotbar item.
+pub const HOTBAR_START: u16 = 36;
+
+/// Number of hotbar slots.
+pub const HOTBAR_SIZE: usize = 9;
+
+/// Data of a player that is kept between sessions, stored in
+/// `world/players/<uuid>.player`.
+#[derive(PartialEq, Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub struct PlayerData {
+    pub position: [f64; 3],
+    pub game_mode: SavedGameMode,
+    /// Every non-empty slot of the player inventory outside of the hotbar.
+    /// They are put back with `Inventory::replace_slot` when the player joins
+    /// again.
+    pub inventory: Vec<SavedItem>,
+    pub nickname: Option<String>,
+    pub stats: PlayerStats,
+    /// The hotbar, see [`HotbarState`].
+    pub hotbar: [Option<SavedItem>; HOTBAR_SIZE],
+}
+
+/// Items in the hotbar of a player, kept up to date by [`sync_hotbar`] and
+/// saved with the rest of the [`PlayerData`].
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct HotbarState(pub [Option<ItemStack>; HOTBAR_SIZE]);
+
+impl Default for HotbarState {
+    fn default() -> Self { Self(std::array::from_fn(|_| None)) }
+}
+
+impl HotbarState {
+    #[must_use]
+    pub fn from_inventory(inventory: &Inventory) -> Self {
+        Self(std::array::from_fn(|i| {
+            inventory.slot(HOTBAR_START + i as u16).cloned()
+        }))
+    }
+
+    #[must_use]
+    pub fn from_saved(saved: &[Option<SavedItem>; HOTBAR_SIZE]) -> Self {
+        Self(std::array::from_fn(|i| {
+            saved[i].and_then(SavedItem::to_stack)
+        }))
+    }
+
+    #[must_use]
+    pub fn to_saved(&self) -> [Option<SavedItem>; HOTBAR_SIZE] {
+        std::array::from_fn(|i| {
+            self.0[i]
+                .as_ref()
+                .map(|stack| SavedItem::new(HOTBAR_START + i as u16, stack))
+        })
+    }
+
+    /// Writes every slot back into the inventory.
+    pub fn restore(&self, inventory: &mut Inventory) {
+        for (i, stack) in self.0.iter().enumerate() {
+            let _ = inventory.replace_slot(HOTBAR_START + i as u16, stack.clone());
+        }
+    }
+}
+
+/// Sent when an item in the hotbar of a player changed.
+#[derive(Clone, Debug)]
+pub struct HotbarSlotChanged {
+    pub client: Entity,
+    /// Index of the hotbar slot, from 0 to 8.
+    pub slot: usize,
+    pub stack: Option<ItemStack>,
+}
+
+/// Compares the hotbar of every changed inventory with its [`HotbarState`]
+/// and sends a [`HotbarSlotChanged`] for every slot that differs.
+pub fn detect_hotbar_changes(
+    inventories: Query<(Entity, &Inventory, &HotbarState), Changed<Inventory>>,
+    mut events: EventWriter<HotbarSlotChanged>,
+) {
+    for (client, inventory, hotbar) in &inventories {
+        for (slot, saved) in hotbar.0.iter().enumerate() {
+            let stack = inventory.slot(HOTBAR_START + slot as u16);
+            if stack != saved.as_ref() {
+                events.send(HotbarSlotChanged {
+                    client,
+                    slot,
+                    stack: stack.cloned(),
+                });
+            }
+        }
+    }
+}
+
+/// Applies [`HotbarSlotChanged`] events to the [`HotbarState`] of the player.
+pub fn sync_hotbar(
+    mut hotbars: Query<&mut HotbarState>,
+    mut events: EventReader<HotbarSlotChanged>,
+) {
+    for event in events.iter() {
+        if let Ok(mut hotbar) = hotbars.get_mut(event.client) {
+            if let Some(slot) = hotbar.0.get_mut(event.slot) {
+                *slot = event.stack.clone();
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize, Clone, Copy)]
+pub enum SavedGameMode {
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl From<GameMode> for SavedGameMode {
+    fn from(value: GameMode) -> Self {
+        match value {
+            GameMode::Survival => SavedGameMode::Survival,
+            GameMode::Creative => SavedGameMode::Creative,
+            GameMode::Adventure => SavedGameMode::Adventure,
+            GameMode::Spectator => SavedGameMode::Spectator,
+        }
+    }
+}
+
+impl From<SavedGameMode> for GameMode {
+    fn from(value: SavedGameMode) -> Self {
+        match value {
+            SavedGameMode::Survival => GameMode::Survival,
+            SavedGameMode::Creative => GameMode::Creative,
+            SavedGameMode::Adventure => GameMode::Adventure,
+            SavedGameMode::Spectator => GameMode::Spectator,
+        }
+    }
+}
+
+/// A single inventory slot. NBT data of the item is not saved.
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize, Clone, Copy)]
+pub struct SavedItem {
+    pub slot: u16,
+    pub item: u16,
+    pub count: u8,
+}
+
+impl SavedItem {
+    #[must_use]
+    pub fn new(slot: u16, stack: &ItemStack) -> Self {
+        Self {
+            slot,
+            item: stack.item.to_raw(),
+            count: stack.count(),
+        }
+    }
+
+    #[must_use]
+    pub fn to_stack(self) -> Option<ItemStack> {
+        Some(ItemStack::new(
+            ItemKind::from_raw(self.item)?,
+            self.count,
+            None,
+        ))
+    }
+}
+
+impl PlayerData {
+    #[must_use]
+    pub fn new(
+        client: &Client,
+        inventory: &Inventory,
+        nickname: Option<&str>,
+        stats: &PlayerStats,
+        hotbar: &HotbarState,
+    ) -> Self {
+        let position = client.position();
+        let hotbar_slots = HOTBAR_START..HOTBAR_START + HOTBAR_SIZE as u16;
+        let inventory = (0..inventory.slot_count())
+            .filter(|slot| !hotbar_slots.contains(slot))
+            .filter_map(|slot| {
+                inventory
+                    .slot(slot)
+                    .map(|stack| SavedItem::new(slot, stack))
+            })
+            .collect();
+
+        Self {
+            position: [position.x, position.y, position.z],
+            game_mode: client.game_mode().into(),
+            inventory,
+            nickname: nickname.map(ToString::to_string),
+            stats: stats.clone(),
+            hotbar: hotbar.to_saved(),
+        }
+    }
+
+    /// Applies the saved data to a client that just joined and returns the
+    /// restored hotbar.
+    pub fn restore(&self, client: &mut Client, inventory: &mut Inventory) -> HotbarState {
+        client.set_position(self.position);
+        client.set_game_mode(self.game_mode.into());
+
+        for item in &self.inventory {
+            let _ = inventory.replace_slot(item.slot, item.to_stack());
+        }
+
+        let hotbar = HotbarState::from_saved(&self.hotbar);
+        hotbar.restore(inventory);
+        hotbar
+    }
+}
+
+/// Path of the player data file of the player with the given uuid.
+///
+/// # Errors
+/// - the current directory can't be accessed
+pub fn player_data_path(uuid: Uuid) -> Result<PathBuf> {
+    let base_path = std::env::current_dir()?.join("world").join("players");
+    Ok(base_path.join(format!("{uuid}.player")))
+}
+
+/// # Errors
+/// - the player data file can't be written
+pub fn save(
+    uuid: Uuid,
+    client: &Client,
+    inventory: &Inventory,
+    nickname: Option<&str>,
+    stats: &PlayerStats,
+    hotbar: &HotbarState,
+) -> Result<()> {
+    write(
+        uuid,
+        &PlayerData::new(client, inventory, nickname, stats, hotbar),
+    )
+}
+
+/// # Errors
+/// - the player data file can't be written
+pub fn write(uuid: Uuid, data: &PlayerData) -> Result<()> {
+    let path = player_data_path(uuid)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let encoded: Vec<u8> = bincode::serialize(data)?;
+    write_atomically(&path, &encoded)?;
+
+    trace!(target: "minecraft::player_data", "saved player data of {uuid}");
+
+    Ok(())
+}
+
+/// Positions of all players with saved player data.
+#[must_use]
+pub fn saved_positions() -> Vec<[f64; 3]> {
+    let Ok(base_path) = std::env::current_dir().map(|p| p.join("world").join("players")) else {
+        return vec![];
+    };
+    let Ok(entries) = std::fs::read_dir(base_path) else {
+        return vec![];
+    };
+
+    entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            let uuid = name.to_str()?.strip_suffix(".player")?.parse().ok()?;
+            load(uuid)
+        })
+        .map(|data| data.position)
+        .collect()
+}
+
+#[must_use]
+pub fn load(uuid: Uuid) -> Option<PlayerData> {
+    let path = player_data_path(uuid).ok()?;
+
+    let mut buf = vec![];
+    let mut file = OpenOptions::new().read(true).open(path).ok()?;
+    file.read_to_end(&mut buf).ok()?;
+
+    match bincode::deserialize(&buf) {
+        Ok(data) => Some(data),
+        Err(e) => {
+            warn!(target: "minecraft::player_data", "unable to load player data of {uuid}: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hotbar_round_trips_through_saved_items() {
+        let mut hotbar = HotbarState::default();
+        hotbar.0[0] = Some(ItemStack::new(ItemKind::Stone, 64, None));
+        hotbar.0[8] = Some(ItemStack::new(ItemKind::OakLog, 3, None));
+
+        let saved = hotbar.to_saved();
+        assert_eq!(saved[0].map(|s| s.slot), Some(HOTBAR_START));
+        assert_eq!(saved[8].map(|s| s.slot), Some(HOTBAR_START + 8));
+        assert!(saved[1..8].iter().all(Option::is_none));
+
+        assert_eq!(HotbarState::from_saved(&saved), hotbar);
+    }
+
+    #[test]
+    fn player_data_round_trips_through_bincode() {
+        let mut hotbar = [None; HOTBAR_SIZE];
+        hotbar[2] = Some(SavedItem::new(
+            HOTBAR_START + 2,
+            &ItemStack::new(ItemKind::Torch, 16, None),
+        ));
+        let data = PlayerData {
+            position: [1.5, 70.0, -3.25],
+            game_mode: SavedGameMode::Creative,
+            inventory: vec![SavedItem::new(
+                9,
+                &ItemStack::new(ItemKind::Diamond, 5, None),
+            )],
+            nickname: Some("Steve".to_string()),
+            stats: PlayerStats {
+                blocks_placed: 12,
+                ..Default::default()
+            },
+            hotbar,
+        };
+
+        let encoded = bincode::serialize(&data).unwrap();
+        assert_eq!(bincode::deserialize::<PlayerData>(&encoded).unwrap(), data);
+    }
+
+    #[test]
+    fn game_modes_round_trip() {
+        for mode in [
+            GameMode::Survival,
+            GameMode::Creative,
+            GameMode::Adventure,
+            GameMode::Spectator,
+        ] {
+            assert_eq!(GameMode::from(SavedGameMode::from(mode)), mode);
+        }
+    }
+
+    #[test]
+    fn saved_items_keep_kind_and_count() {
+        let stack = ItemStack::new(ItemKind::OakPlanks, 42, None);
+        let saved = SavedItem::new(20, &stack);
+
+        assert_eq!(saved.slot, 20);
+        assert_eq!(saved.to_stack(), Some(stack));
+    }
+
+    #[test]
+    fn unknown_items_are_not_restored() {
+        let saved = SavedItem {
+            slot: 0,
+            item: u16::MAX,
+            count: 1,
+        };
+
+        assert_eq!(saved.to_stack(), None);
+    }
+}
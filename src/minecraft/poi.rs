@@ -0,0 +1,211 @@
+use bevy::prelude::Plugin;
+use valence::prelude::*;
+
+use super::{
+    command::{Command, CommandEvent, RegisterCommandExt},
+    structure::is_valid_name,
+    world_gen::Instances,
+};
+use crate::{config::PointOfInterest, CONFIG};
+
+pub struct PoiPlugin;
+
+impl Plugin for PoiPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PointsOfInterest::new(
+            CONFIG.world.points_of_interest.clone(),
+        ))
+        .register_command(Command::new("poi").with_usage("poi list | poi tp <name>"))
+        .add_system(poi);
+    }
+}
+
+/// The valid points of interest of the config.
+#[derive(Resource, Default, Debug)]
+pub struct PointsOfInterest(Vec<PointOfInterest>);
+
+impl PointsOfInterest {
+    /// Skips points of interest with an invalid or duplicate name.
+    #[must_use]
+    pub fn new(pois: Vec<PointOfInterest>) -> Self {
+        let mut valid: Vec<PointOfInterest> = vec![];
+
+        for poi in pois {
+            // Names have to be usable as a single command argument.
+            if !is_valid_name(&poi.name) {
+                warn!(target: "minecraft::poi",
+                    "Ignoring point of interest with invalid name {:?}", poi.name
+                );
+            } else if valid.iter().any(|p| p.name.eq_ignore_ascii_case(&poi.name)) {
+                warn!(target: "minecraft::poi",
+                    "Ignoring duplicate point of interest {}", poi.name
+                );
+            } else {
+                valid.push(poi);
+            }
+        }
+
+        Self(valid)
+    }
+
+    /// Finds a point of interest by name, ignoring case.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&PointOfInterest> {
+        self.0.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Position to teleport to for `poi tp <name>`.
+    ///
+    /// # Errors
+    /// - there is no point of interest called `name`
+    pub fn teleport_target(&self, name: &str) -> Result<(&str, DVec3), String> {
+        self.get(name)
+            .map(|poi| (poi.name.as_str(), DVec3::from_array(poi.position)))
+            .ok_or_else(|| format!("Unknown point of interest: {name}"))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PointOfInterest> { self.0.iter() }
+}
+
+fn poi(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    pois: Res<PointsOfInterest>,
+    instances_list: Res<Instances>,
+) {
+    for event in events.iter().filter(|e| e.is("poi")) {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        match (event.arg(0), event.arg(1)) {
+            (Some("list"), None) => {
+                if pois.iter().next().is_none() {
+                    client.send_message("There are no points of interest.".italic());
+                }
+
+                for poi in pois.iter() {
+                    let [x, y, z] = poi.position;
+                    client.send_message(
+                        poi.name.clone().color(Color::GOLD)
+                            + format!(" ({x:.0} {y:.0} {z:.0}) {}", poi.description)
+                                .color(Color::GRAY),
+                    );
+                }
+            }
+            (Some("tp"), Some(name)) => match pois.teleport_target(name) {
+                Ok((name, position)) => {
+                    client.set_instance(instances_list.terrain());
+                    client.set_position(position);
+                    client.send_message(format!("Teleported to {name}.").italic());
+                }
+                Err(e) => client.send_message(e.color(Color::RED)),
+            },
+            _ => client.send_message("Usage: poi list | poi tp <name>".italic()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WorldConfig;
+
+    fn poi(name: &str, position: [f64; 3]) -> PointOfInterest {
+        PointOfInterest {
+            name: name.to_owned(),
+            description: String::new(),
+            position,
+        }
+    }
+
+    #[test]
+    fn points_of_interest_are_loaded_from_the_config() {
+        let config: WorldConfig = toml::from_str(
+            r#"
+            [[points_of_interest]]
+            name = "spawn"
+            description = "Where it all began"
+            position = [0.5, 80.0, 0.5]
+
+            [[points_of_interest]]
+            name = "summit"
+            position = [120.0, 190.0, -40.0]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.points_of_interest,
+            vec![
+                PointOfInterest {
+                    description: "Where it all began".to_owned(),
+                    ..poi("spawn", [0.5, 80.0, 0.5])
+                },
+                poi("summit", [120.0, 190.0, -40.0]),
+            ]
+        );
+        assert!(WorldConfig::default().points_of_interest.is_empty());
+
+        let ron = ron::to_string(&config).unwrap();
+        assert_eq!(
+            ron::from_str::<WorldConfig>(&ron)
+                .unwrap()
+                .points_of_interest,
+            config.points_of_interest
+        );
+    }
+
+    #[test]
+    fn invalid_and_duplicate_names_are_skipped() {
+        let pois = PointsOfInterest::new(vec![
+            poi("spawn", [0.0, 64.0, 0.0]),
+            poi("", [1.0, 64.0, 1.0]),
+            poi("two words", [2.0, 64.0, 2.0]),
+            poi("SPAWN", [3.0, 64.0, 3.0]),
+            poi("old-mill_2", [4.0, 64.0, 4.0]),
+        ]);
+
+        let names: Vec<_> = pois.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["spawn", "old-mill_2"]);
+        assert_eq!(pois.get("spawn").unwrap().position, [0.0, 64.0, 0.0]);
+    }
+
+    #[test]
+    fn names_are_resolved_ignoring_case() {
+        let pois = PointsOfInterest::new(vec![poi("Summit", [120.0, 190.0, -40.0])]);
+
+        assert_eq!(pois.get("summit"), pois.get("SUMMIT"));
+        assert_eq!(pois.get("summit").unwrap().name, "Summit");
+        assert!(pois.get("summi").is_none());
+        assert!(PointsOfInterest::default().get("summit").is_none());
+    }
+
+    #[test]
+    fn teleport_target_is_the_position_of_the_point() {
+        let pois = PointsOfInterest::new(vec![poi("Summit", [120.5, 190.0, -40.5])]);
+
+        assert_eq!(
+            pois.teleport_target("summit"),
+            Ok(("Summit", DVec3::new(120.5, 190.0, -40.5)))
+        );
+        assert_eq!(
+            pois.teleport_target("valley"),
+            Err("Unknown point of interest: valley".to_owned())
+        );
+    }
+
+    #[test]
+    fn poi_command_is_registered() {
+        let mut app = App::new();
+        PoiPlugin.build(&mut app);
+
+        let command = app
+            .world
+            .resource::<crate::minecraft::command::CommandRegistry>()
+            .get("poi")
+            .unwrap();
+        assert_eq!(command.usage, "poi list | poi tp <name>");
+        assert!(app.world.contains_resource::<PointsOfInterest>());
+    }
+}
@@ -1,22 +1,183 @@
 mod chunk;
+pub mod codec;
+mod json;
+mod legacy;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fmt,
     fs::OpenOptions as StdOpenOptions,
     io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
 use tokio::{
     fs::{self, OpenOptions},
     io::AsyncWriteExt,
 };
-use valence::{prelude::Chunk, view::ChunkPos};
-use walkdir::WalkDir;
+use valence::{
+    prelude::{BlockState, Chunk},
+    view::ChunkPos,
+};
+
+use self::legacy::{LegacyRegion, LegacySaveChunk};
+pub use self::{chunk::*, json::block_name};
+use super::world_gen::chunk_worker::{gen_chunk, ChunkWorkerState, TerrainSettings};
+use crate::{
+    config::{ChunkFormat, StaleRegions, WorldConfig},
+    util::LockResultExt,
+    CONFIG, REGION_SIZE, SECTION_COUNT,
+};
+
+lazy_static! {
+    /// One lock per region position, see [`region_lock`].
+    static ref REGION_LOCKS: Mutex<HashMap<(i64, i64), Arc<Mutex<()>>>> = Mutex::default();
+    /// See [`DiffBaseline`].
+    static ref DIFF_BASELINE: DiffBaseline = DiffBaseline::default();
+}
+
+/// The chunk worker state the chunks are generated with to store only their
+/// differences with `save_diffs`. Kept for the settings of the last save, so
+/// the noise functions aren't created again on every save.
+#[derive(Default)]
+struct DiffBaseline(Mutex<Option<(u64, Arc<ChunkWorkerState>)>>);
+
+impl DiffBaseline {
+    fn get(&self, settings: &TerrainSettings) -> Arc<ChunkWorkerState> {
+        let hash = settings.content_hash();
+        let mut cached = self.0.lock().ignore_poison();
+
+        match &*cached {
+            Some((cached_hash, state)) if *cached_hash == hash => state.clone(),
+            _ => {
+                let state = Arc::new(ChunkWorkerState::new(settings.seed, settings.clone()));
+                *cached = Some((hash, state.clone()));
+                state
+            }
+        }
+    }
+}
+
+/// Held while a region file is read, written or removed, so saves of the same
+/// region can't interleave and a load never sees a half written file.
+fn region_lock(pos: (i64, i64)) -> Arc<Mutex<()>> {
+    REGION_LOCKS
+        .lock()
+        .ignore_poison()
+        .entry(pos)
+        .or_default()
+        .clone()
+}
+
+/// Written in front of the encoded region, so regions generated with other
+/// terrain settings can be rejected without decoding all of their chunks.
+///
+/// Region files of older versions don't have a header.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct RegionHeader {
+    /// [`TerrainSettings::content_hash`] of the region's settings.
+    pub settings_hash: u64,
+}
+
+impl RegionHeader {
+    pub const LEN: usize = 12;
+    const MAGIC: [u8; 4] = *b"VPRG";
+
+    #[must_use]
+    pub fn to_bytes(self) -> [u8; Self::LEN] {
+        let mut buf = [0; Self::LEN];
+        buf[..4].copy_from_slice(&Self::MAGIC);
+        buf[4..].copy_from_slice(&self.settings_hash.to_le_bytes());
+        buf
+    }
+
+    /// Returns `None` if `buf` doesn't start with a header.
+    #[must_use]
+    pub fn from_bytes(buf: &[u8]) -> Option<Self> {
+        if buf.len() < Self::LEN || buf[..4] != Self::MAGIC {
+            return None;
+        }
+
+        let settings_hash = u64::from_le_bytes(buf[4..Self::LEN].try_into().ok()?);
+        Some(Self { settings_hash })
+    }
+
+    /// Fails if the region was generated with other settings.
+    fn check(self, settings: &TerrainSettings) -> Result<()> {
+        if self.settings_hash != settings.content_hash() {
+            return Err(SettingsMismatch.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Returned when loading a region that was generated with other terrain
+/// settings.
+#[derive(Debug, Clone, Copy)]
+pub struct SettingsMismatch;
+
+impl fmt::Display for SettingsMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Terrain Settings don't match")
+    }
+}
+
+impl std::error::Error for SettingsMismatch {}
+
+/// Returned when loading a region with fatal problems, the file was moved to
+/// `quarantined` so it is neither loaded nor overwritten again.
+#[derive(Debug, Clone)]
+pub struct QuarantinedRegion {
+    pub pos: (i64, i64),
+    pub quarantined: PathBuf,
+    pub problems: Vec<RegionError>,
+}
 
-pub use self::chunk::*;
-use super::world_gen::chunk_worker::TerrainSettings;
-use crate::REGION_SIZE;
+impl fmt::Display for QuarantinedRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors = self
+            .problems
+            .iter()
+            .take(5)
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "Region {:?} is corrupted and was moved to {}, {} problems like: {}",
+            self.pos,
+            self.quarantined.display(),
+            self.problems.len(),
+            errors.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for QuarantinedRegion {}
+
+/// The region to merge new chunks into when saving. Only a missing region file,
+/// one of other settings or a quarantined one is replaced, any other error is
+/// returned so the chunks of a region that can't be read are never
+/// overwritten.
+///
+/// # Errors
+/// - the region couldn't be loaded for another reason
+fn base_region(loaded: Result<Region>) -> Result<Option<Region>> {
+    match loaded {
+        Ok(region) => Result::Ok(Some(region)),
+        Err(e) if e.is::<SettingsMismatch>() || e.is::<QuarantinedRegion>() => Result::Ok(None),
+        Err(e)
+            if e.downcast_ref::<std::io::Error>()
+                .map_or(false, |e| e.kind() == std::io::ErrorKind::NotFound) =>
+        {
+            Result::Ok(None)
+        }
+        Err(e) => Result::Err(e),
+    }
+}
 
 #[derive(PartialEq, Debug, serde::Deserialize, serde::Serialize)]
 pub struct Region {
@@ -32,12 +193,147 @@ impl IntoIterator for Region {
     fn into_iter(self) -> Self::IntoIter { self.chunks.into_iter() }
 }
 
+/// A problem with the contents of a region file, see [`Region::validate`].
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum RegionError {
+    /// The chunk belongs to another region.
+    ChunkOutOfBounds { chunk: (i32, i32) },
+    /// A block lies outside of the chunk, like below `y=0` or above the build
+    /// height.
+    BlockOutOfRange {
+        chunk: (i32, i32),
+        offset: OffsetBlockPos,
+    },
+    /// The raw block state doesn't exist.
+    InvalidBlock { chunk: (i32, i32), kind: u16 },
+    /// Another chunk with the same position was stored before this one.
+    DuplicateChunk { chunk: (i32, i32) },
+    /// The header is valid, but the chunks after it can't be decoded.
+    Undecodable,
+}
+
+impl RegionError {
+    /// Whether the region can't be loaded. Chunks of other regions and
+    /// duplicates are never read, the first chunk at a position wins.
+    #[must_use]
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            RegionError::BlockOutOfRange { .. }
+                | RegionError::InvalidBlock { .. }
+                | RegionError::Undecodable
+        )
+    }
+}
+
+impl fmt::Display for RegionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegionError::ChunkOutOfBounds { chunk } => {
+                write!(f, "chunk {chunk:?} belongs to another region")
+            }
+            RegionError::BlockOutOfRange { chunk, offset } => {
+                write!(
+                    f,
+                    "block {offset:?} of chunk {chunk:?} is outside of the chunk"
+                )
+            }
+            RegionError::InvalidBlock { chunk, kind } => {
+                write!(f, "chunk {chunk:?} contains the unknown block state {kind}")
+            }
+            RegionError::DuplicateChunk { chunk } => write!(f, "chunk {chunk:?} is stored twice"),
+            RegionError::Undecodable => write!(f, "the chunks can't be decoded"),
+        }
+    }
+}
+
 impl Region {
+    /// Checks the chunks for problems a crash while writing the region could
+    /// have caused. Every invalid block state is only reported once per chunk.
+    #[must_use]
+    pub fn validate(&self) -> Vec<RegionError> {
+        let mut errors = vec![];
+        let mut positions = HashSet::new();
+
+        for chunk in &self.chunks {
+            let pos = chunk.pos;
+
+            if chunkpos_to_regionpos(&ChunkPos::new(pos.0, pos.1)) != self.pos {
+                errors.push(RegionError::ChunkOutOfBounds { chunk: pos });
+            }
+            if !positions.insert(pos) {
+                errors.push(RegionError::DuplicateChunk { chunk: pos });
+            }
+
+            let mut invalid = HashSet::new();
+            let mut check_kind = |kind: u16, errors: &mut Vec<RegionError>| {
+                if BlockState::from_raw(kind).is_none() && invalid.insert(kind) {
+                    errors.push(RegionError::InvalidBlock { chunk: pos, kind });
+                }
+            };
+            let check_offset = |offset: OffsetBlockPos, errors: &mut Vec<RegionError>| {
+                let (x, y, z) = offset;
+                if x >= 16 || z >= 16 || y >= SECTION_COUNT * 16 {
+                    errors.push(RegionError::BlockOutOfRange { chunk: pos, offset });
+                }
+            };
+
+            match &chunk.blocks {
+                SaveChunkFormat::Raw(blocks) => {
+                    for block in blocks {
+                        check_offset((block.x, block.y, block.z), &mut errors);
+                        check_kind(block.kind, &mut errors);
+                    }
+                }
+                SaveChunkFormat::Rle(runs) => {
+                    for run in runs {
+                        check_kind(run.kind, &mut errors);
+                    }
+                }
+                SaveChunkFormat::Indexed(kinds) => {
+                    for kind in kinds {
+                        check_kind(*kind, &mut errors);
+                    }
+                }
+                SaveChunkFormat::Diff(diff) => {
+                    for (offset, old, new) in &diff.changes {
+                        check_offset(*offset, &mut errors);
+                        check_kind(*old, &mut errors);
+                        check_kind(*new, &mut errors);
+                    }
+                }
+            }
+        }
+
+        errors
+    }
+
     #[must_use]
     pub fn chunk(&self, pos: ChunkPos) -> Option<&SaveChunk> {
         self.chunks.iter().find(|&c| c.pos == (pos.x, pos.z))
     }
 
+    /// Combines two regions at the same position, chunks of `overlay` replace
+    /// the chunks of `base` at the same position. The settings are taken from
+    /// `overlay`.
+    #[must_use]
+    pub fn merge(base: Region, overlay: Region) -> Region {
+        let mut chunks = overlay.chunks;
+        let replaced: HashSet<_> = chunks.iter().map(|c| c.pos).collect();
+
+        chunks.extend(
+            base.chunks
+                .into_iter()
+                .filter(|chunk| !replaced.contains(&chunk.pos)),
+        );
+
+        Region {
+            pos: overlay.pos,
+            settings: overlay.settings,
+            chunks,
+        }
+    }
+
     #[must_use]
     pub fn region(regions: &Vec<Region>, pos: (i64, i64)) -> Option<&Region> {
         regions.iter().find(|&r| r.pos == pos)
@@ -54,6 +350,26 @@ impl Region {
     }
 }
 
+/// Checks if files can be created inside of `path`, creating the directory if
+/// it doesn't exist yet.
+#[must_use]
+pub fn is_writable(path: &Path) -> bool {
+    if std::fs::create_dir_all(path).is_err() {
+        return false;
+    }
+
+    let probe = path.join(".write_test");
+    let writable = StdOpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&probe)
+        .is_ok();
+    let _ = std::fs::remove_file(probe);
+
+    writable
+}
+
 #[must_use]
 pub fn chunkpos_to_regionpos(pos: &ChunkPos) -> (i64, i64) {
     let rpos_x = (f64::from(pos.x) / REGION_SIZE).floor() as i64;
@@ -62,43 +378,46 @@ pub fn chunkpos_to_regionpos(pos: &ChunkPos) -> (i64, i64) {
     (rpos_x, rpos_z)
 }
 
-pub fn overwrite_regions(chunks: &Vec<(ChunkPos, Chunk)>, settings: TerrainSettings) -> Result<()> {
+/// Groups the chunks by the region they belong to, chunks outside of the
+/// world border are left out.
+fn group_into_regions(
+    chunks: &[(ChunkPos, Chunk)],
+    settings: TerrainSettings,
+    config: &WorldConfig,
+) -> HashMap<(i64, i64), Region> {
     let mut regions = HashMap::new();
+    // Only needed to generate the baseline of the diffs.
+    let state = config.save_diffs.then(|| DIFF_BASELINE.get(&settings));
 
-    for (pos, chunk) in chunks {
-        let (rpos_x, rpos_z) = chunkpos_to_regionpos(pos);
+    for (pos, chunk) in chunks
+        .iter()
+        .filter(|(pos, _)| config.is_chunk_inside_border(pos.x, pos.z))
+    {
+        let rpos = chunkpos_to_regionpos(pos);
 
-        let region = match regions.get_mut(&(rpos_x, rpos_z)) {
-            Some(r) => r,
-            None => {
-                let region = Region {
-                    pos: (rpos_x, rpos_z),
-                    settings: settings.clone(),
-                    chunks: vec![],
-                };
-                regions.insert((rpos_x, rpos_z), region);
-                regions.get_mut(&(rpos_x, rpos_z)).unwrap()
-            }
-        };
+        let region = regions.entry(rpos).or_insert_with(|| Region {
+            pos: rpos,
+            settings: settings.clone(),
+            chunks: vec![],
+        });
 
-        let mut save_chunk = SaveChunk::from(chunk);
+        let mut save_chunk = to_save_chunk(state.as_deref(), *pos, chunk, config.chunk_format);
         save_chunk.pos = (pos.x, pos.z);
         region.chunks.push(save_chunk);
     }
 
-    for (pos, region) in regions {
-        let base_path = std::env::current_dir()?.join("world");
-        std::fs::create_dir_all(&base_path)?;
+    regions
+}
 
-        let path = base_path.join(format!("{}_{}.region", pos.0, pos.1));
+pub fn overwrite_regions(chunks: &Vec<(ChunkPos, Chunk)>, settings: TerrainSettings) -> Result<()> {
+    let regions = group_into_regions(chunks, settings, &CONFIG.world);
 
-        let mut file = StdOpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .open(path)?;
-        let encoded: Vec<u8> = bincode::serialize(&region)?;
-        file.write_all(encoded.as_slice())?;
+    for (pos, region) in regions {
+        std::fs::create_dir_all(std::env::current_dir()?.join("world"))?;
+
+        let lock = region_lock(pos);
+        let _guard = lock.lock().ignore_poison();
+        write_region(&region)?;
 
         trace!(target: "minecraft::save", "saved {}_{}.region", pos.0, pos.1);
     }
@@ -106,49 +425,128 @@ pub fn overwrite_regions(chunks: &Vec<(ChunkPos, Chunk)>, settings: TerrainSetti
     Result::Ok(())
 }
 
-pub fn save_chunk_to_region(chunk: Chunk, pos: ChunkPos, settings: TerrainSettings) -> Result<()> {
-    let rpos = chunkpos_to_regionpos(&pos);
-    let mut region = match load_region(rpos, &settings) {
-        Ok(r) => r,
-        Err(_) => Region {
-            pos: rpos,
-            settings,
-            chunks: vec![],
+/// Async version of [`overwrite_regions`].
+///
+/// # Errors
+/// - a region file can't be written
+pub async fn overwrite_regions_async(
+    chunks: &[(ChunkPos, Chunk)],
+    settings: TerrainSettings,
+) -> Result<()> {
+    let chunks = chunks.to_vec();
+    tokio::task::spawn_blocking(move || overwrite_regions(&chunks, settings)).await?
+}
+
+/// Saves the chunks into their region files, keeping the other chunks of the
+/// regions if they were generated with the same settings. Every region file is
+/// only read and written once. Returns the number of saved chunks, regions
+/// that can't be read or written are skipped.
+pub fn save_chunks_to_regions(chunks: &[(ChunkPos, Chunk)], settings: TerrainSettings) -> usize {
+    match std::env::current_dir() {
+        Ok(dir) => save_chunks_to_regions_in(&dir.join("world"), chunks, settings, &CONFIG.world),
+        Err(e) => {
+            error!(target: "minecraft::save", "Not saving chunks, the world directory can't be found: {e}");
+            0
+        }
+    }
+}
+
+/// [`save_chunks_to_regions`] with the region files in `dir`.
+fn save_chunks_to_regions_in(
+    dir: &Path,
+    chunks: &[(ChunkPos, Chunk)],
+    settings: TerrainSettings,
+    config: &WorldConfig,
+) -> usize {
+    let mut saved = 0;
+
+    for (rpos, overlay) in group_into_regions(chunks, settings, config) {
+        let count = overlay.chunks.len();
+        let path = region_path_in(dir, rpos);
+
+        let lock = region_lock(rpos);
+        let _guard = lock.lock().ignore_poison();
+        let region = match base_region(load_region_file(&path, &overlay.settings)) {
+            Ok(Some(base)) => Region::merge(base, overlay),
+            Ok(None) => overlay,
+            Err(e) => {
+                error!(target: "minecraft::save",
+                    "Not saving region {} {}, it can't be read: {e}", rpos.0, rpos.1
+                );
+                continue;
+            }
+        };
+
+        match write_region_file(&region, &path) {
+            Ok(()) => saved += count,
+            Err(e) => error!(target: "minecraft::save",
+                "Failed to save region {} {}: {e}", rpos.0, rpos.1
+            ),
+        }
+    }
+
+    saved
+}
+
+/// Stores only the blocks that differ from the generated chunk if `state` is
+/// given, the whole chunk in the given `format` otherwise.
+fn to_save_chunk(
+    state: Option<&ChunkWorkerState>,
+    pos: ChunkPos,
+    chunk: &Chunk,
+    format: ChunkFormat,
+) -> SaveChunk {
+    match state {
+        Some(state) => SaveChunk {
+            pos: (pos.x, pos.z),
+            blocks: SaveChunkFormat::Diff(SaveChunk::diff(&gen_chunk(state, pos), chunk)),
         },
-    };
+        None => match format {
+            ChunkFormat::Rle => SaveChunk::from(chunk),
+            ChunkFormat::Indexed => SaveChunk::from(chunk).to_indexed(),
+        },
+    }
+}
 
-    let mut save_chunk: SaveChunk = chunk.into();
+/// Region containing only the given chunk.
+fn single_chunk_region(chunk: Chunk, pos: ChunkPos, settings: TerrainSettings) -> Region {
+    let state = CONFIG
+        .world
+        .save_diffs
+        .then(|| DIFF_BASELINE.get(&settings));
+    let mut save_chunk = to_save_chunk(state.as_deref(), pos, &chunk, CONFIG.world.chunk_format);
     save_chunk.pos = (pos.x, pos.z);
 
-    let _ = region
-        .chunks
-        .iter()
-        .map(|c| {
-            if c.pos == save_chunk.pos {
-                &save_chunk
-            } else {
-                c
-            }
-        })
-        .collect::<Vec<_>>();
+    Region {
+        pos: chunkpos_to_regionpos(&pos),
+        settings,
+        chunks: vec![save_chunk],
+    }
+}
 
-    if let Some(mut c) = region.chunks.iter_mut().find(|c| c.pos == save_chunk.pos) {
-        std::mem::swap(&mut c, &mut &mut save_chunk);
-    } else {
-        region.chunks.push(save_chunk);
+/// Saves the chunk into its region file, keeping the other chunks of the
+/// region if they were generated with the same settings. Chunks outside of the
+/// world border are not saved.
+///
+/// # Errors
+/// - the existing region file can't be read
+/// - the region file can't be written
+pub fn save_chunk_to_region(chunk: Chunk, pos: ChunkPos, settings: TerrainSettings) -> Result<()> {
+    if !CONFIG.world.is_chunk_inside_border(pos.x, pos.z) {
+        return Result::Ok(());
     }
 
-    let base_path = std::env::current_dir()?.join("world");
+    let rpos = chunkpos_to_regionpos(&pos);
+    let overlay = single_chunk_region(chunk, pos, settings);
 
-    let path = base_path.join(format!("{}_{}.region", rpos.0, rpos.1));
+    let lock = region_lock(rpos);
+    let _guard = lock.lock().ignore_poison();
+    let region = match base_region(load_region_locked(rpos, &overlay.settings))? {
+        Some(base) => Region::merge(base, overlay),
+        None => overlay,
+    };
 
-    let mut file = StdOpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(path)?;
-    let encoded: Vec<u8> = bincode::serialize(&region)?;
-    file.write_all(encoded.as_slice())?;
+    write_region(&region)?;
 
     trace!(
         "saved chunk ({}, {}) to region {} {}",
@@ -161,39 +559,566 @@ pub fn save_chunk_to_region(chunk: Chunk, pos: ChunkPos, settings: TerrainSettin
     Result::Ok(())
 }
 
-pub fn load_region(pos: (i64, i64), settings: &TerrainSettings) -> Result<Region> {
+/// Async version of [`save_chunk_to_region`], the region is saved on a
+/// blocking task.
+///
+/// # Errors
+/// - the existing region file can't be read
+/// - the region file can't be written
+pub async fn save_chunk_to_region_async(
+    chunk: Chunk,
+    pos: ChunkPos,
+    settings: TerrainSettings,
+) -> Result<()> {
+    tokio::task::spawn_blocking(move || save_chunk_to_region(chunk, pos, settings)).await?
+}
+
+/// Path of the region file at the given region position.
+///
+/// # Errors
+/// - the current directory can't be accessed
+pub fn region_path(pos: (i64, i64)) -> Result<PathBuf> {
+    Result::Ok(region_path_in(&std::env::current_dir()?.join("world"), pos))
+}
+
+fn region_path_in(dir: &Path, pos: (i64, i64)) -> PathBuf {
+    dir.join(format!("{}_{}.region", pos.0, pos.1))
+}
+
+/// The block entities of the chunks of a region, stored in
+/// `world/<x>_<z>_nbt.bin` next to the region file.
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+struct NbtSidecar {
+    /// [`TerrainSettings::content_hash`] of the terrain the block entities are
+    /// in.
+    settings_hash: u64,
+    chunks: Vec<SaveChunkNbt>,
+}
+
+/// Path of the block entity file of the region at the given position.
+///
+/// # Errors
+/// - the current directory can't be accessed
+pub fn nbt_sidecar_path(pos: (i64, i64)) -> Result<PathBuf> {
     let base_path = std::env::current_dir()?.join("world");
-    let path = base_path.join(format!("{}_{}.region", pos.0, pos.1));
+    Result::Ok(base_path.join(format!("{}_{}_nbt.bin", pos.0, pos.1)))
+}
 
-    let mut buf = vec![];
+/// Replaces the block entities of the chunks in their region's sidecar file,
+/// files without any block entities left are removed.
+///
+/// # Errors
+/// - a sidecar file can't be written
+pub fn write_nbt_sidecars(settings_hash: u64, chunks: Vec<SaveChunkNbt>) -> Result<()> {
+    let mut regions: HashMap<(i64, i64), Vec<SaveChunkNbt>> = HashMap::new();
+    for chunk in chunks {
+        let rpos = chunkpos_to_regionpos(&ChunkPos::new(chunk.pos.0, chunk.pos.1));
+        regions.entry(rpos).or_default().push(chunk);
+    }
+
+    for (rpos, chunks) in regions {
+        let path = nbt_sidecar_path(rpos)?;
+
+        let mut sidecar = match std::fs::read(&path) {
+            Ok(buf) => bincode::deserialize::<NbtSidecar>(&buf)
+                .ok()
+                .filter(|s| s.settings_hash == settings_hash)
+                .unwrap_or_default(),
+            Err(_) => NbtSidecar::default(),
+        };
+        sidecar.settings_hash = settings_hash;
+        sidecar
+            .chunks
+            .retain(|c| !chunks.iter().any(|new| new.pos == c.pos));
+        sidecar
+            .chunks
+            .extend(chunks.into_iter().filter(|c| !c.is_empty()));
+
+        if sidecar.chunks.is_empty() {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, bincode::serialize(&sidecar)?)?;
+    }
+
+    Result::Ok(())
+}
+
+/// Block entities of all chunks generated with the settings of
+/// `settings_hash`.
+///
+/// # Errors
+/// - the `world` directory can't be read
+pub fn read_nbt_sidecars(settings_hash: u64) -> Result<Vec<SaveChunkNbt>> {
+    let base_path = std::env::current_dir()?.join("world");
+    if !base_path.exists() {
+        return Result::Ok(vec![]);
+    }
+
+    let mut chunks = vec![];
+    for entry in std::fs::read_dir(base_path)? {
+        let path = entry?.path();
+        if !path.to_string_lossy().ends_with("_nbt.bin") {
+            continue;
+        }
+
+        match bincode::deserialize::<NbtSidecar>(&std::fs::read(&path)?) {
+            Ok(sidecar) if sidecar.settings_hash == settings_hash => chunks.extend(sidecar.chunks),
+            Ok(_) => {}
+            Err(e) => warn!(target: "minecraft::save", "Unable to read {}: {e}", path.display()),
+        }
+    }
+
+    Result::Ok(chunks)
+}
+
+/// Parses the region position from a file name like `-1_2.region`.
+#[must_use]
+pub fn parse_region_file_name(name: &str) -> Option<(i64, i64)> {
+    let (x, z) = name.strip_suffix(".region")?.split_once('_')?;
+    Some((x.parse().ok()?, z.parse().ok()?))
+}
+
+/// Positions and paths of all region files in the `world` directory.
+///
+/// # Errors
+/// - the `world` directory can't be read
+pub fn region_files() -> Result<Vec<((i64, i64), PathBuf)>> {
+    region_files_in(&std::env::current_dir()?.join("world"))
+}
+
+/// Positions and paths of all region files in the directory.
+fn region_files_in(dir: &Path) -> Result<Vec<((i64, i64), PathBuf)>> {
+    if !dir.exists() {
+        return Result::Ok(vec![]);
+    }
+
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if let Some(pos) = name.to_str().and_then(parse_region_file_name) {
+            files.push((pos, entry.path()));
+        }
+    }
+
+    Result::Ok(files)
+}
+
+/// Chebyshev distance in chunks from `pos` to the closest chunk of the region.
+#[must_use]
+pub fn region_distance(region: (i64, i64), pos: ChunkPos) -> i64 {
+    let size = REGION_SIZE as i64;
+    let axis = |region: i64, chunk: i32| {
+        let (min, max) = (region * size, region * size + size - 1);
+        let chunk = i64::from(chunk);
+        (min - chunk).max(chunk - max).max(0)
+    };
+
+    axis(region.0, pos.x).max(axis(region.1, pos.z))
+}
+
+/// Regions that are further away than the distance of every anchor, given as
+/// the chunk and the distance in chunks around it to keep.
+#[must_use]
+pub fn regions_to_prune(regions: &[(i64, i64)], anchors: &[(ChunkPos, i64)]) -> Vec<(i64, i64)> {
+    regions
+        .iter()
+        .filter(|region| {
+            anchors
+                .iter()
+                .all(|(pos, distance)| region_distance(**region, *pos) > *distance)
+        })
+        .copied()
+        .collect()
+}
+
+/// Deletes the region files selected by [`regions_to_prune`] and returns how
+/// many files were removed and how many bytes that freed.
+///
+/// # Errors
+/// - the `world` directory can't be read
+pub fn prune_regions(anchors: &[(ChunkPos, i64)]) -> Result<(usize, u64)> {
+    let files = region_files()?;
+    let regions = files.iter().map(|(pos, _)| *pos).collect::<Vec<_>>();
+    let prune = regions_to_prune(&regions, anchors);
+
+    let (mut removed, mut freed) = (0, 0);
+    for (pos, path) in files.iter().filter(|(pos, _)| prune.contains(pos)) {
+        let lock = region_lock(*pos);
+        let _guard = lock.lock().ignore_poison();
+
+        let size = std::fs::metadata(path).map_or(0, |m| m.len());
+        match std::fs::remove_file(path) {
+            Ok(()) => {
+                if let Ok(sidecar) = nbt_sidecar_path(*pos) {
+                    let _ = std::fs::remove_file(sidecar);
+                }
+                trace!(target: "minecraft::save", "pruned region {pos:?}");
+                removed += 1;
+                freed += size;
+            }
+            Err(e) => {
+                error!(target: "minecraft::save", "Unable to remove region {pos:?}: {e}");
+            }
+        }
+    }
+
+    Result::Ok((removed, freed))
+}
+
+/// [`TerrainSettings::content_hash`] of the settings a region file was
+/// generated with, region files without a header are decoded.
+///
+/// # Errors
+/// - the region file can't be read or decoded
+pub fn region_settings_hash(path: &Path) -> Result<u64> {
     let mut file = StdOpenOptions::new().read(true).open(path)?;
-    let _ = file.read_to_end(&mut buf);
+    let mut buf = vec![];
+    file.read_to_end(&mut buf)?;
+
+    match RegionHeader::from_bytes(&buf) {
+        Some(header) => Result::Ok(header.settings_hash),
+        None => Result::Ok(decode_region(&buf)?.0.settings.content_hash()),
+    }
+}
+
+/// Deletes or archives the region files that weren't generated with the
+/// settings of `settings_hash` and returns how many files were handled.
+///
+/// # Errors
+/// - the `world` directory can't be read
+pub fn clean_stale_regions(settings_hash: u64, handling: StaleRegions) -> Result<usize> {
+    let dir = std::env::current_dir()?;
+    clean_stale_regions_in(
+        &dir.join("world"),
+        &dir.join("world_archive"),
+        settings_hash,
+        handling,
+    )
+}
+
+/// [`clean_stale_regions`] for the regions in `world`, archived regions are
+/// moved to `archive/<settings hash>/`.
+fn clean_stale_regions_in(
+    world: &Path,
+    archive: &Path,
+    settings_hash: u64,
+    handling: StaleRegions,
+) -> Result<usize> {
+    let archive = match handling {
+        StaleRegions::Keep => return Result::Ok(0),
+        StaleRegions::Delete => None,
+        StaleRegions::Archive => Some(archive),
+    };
+
+    let mut cleaned = 0;
+    for (pos, path) in region_files_in(world)? {
+        let lock = region_lock(pos);
+        let _guard = lock.lock().ignore_poison();
+
+        let hash = match region_settings_hash(&path) {
+            Ok(hash) if hash == settings_hash => continue,
+            Ok(hash) => hash,
+            Err(e) => {
+                warn!(target: "minecraft::save", "Unable to read region {pos:?}: {e}");
+                continue;
+            }
+        };
+
+        let result = match archive {
+            None => std::fs::remove_file(&path),
+            Some(archive) => {
+                // Archived regions are moved out of the `world` directory, so they are
+                // never loaded again.
+                let archived = archive.join(format!("{hash:016x}"));
+                std::fs::create_dir_all(&archived).and_then(|()| {
+                    std::fs::rename(&path, archived.join(path.file_name().unwrap_or_default()))
+                })
+            }
+        };
+
+        match result {
+            Ok(()) => {
+                trace!(target: "minecraft::save", "cleaned stale region {pos:?}");
+                cleaned += 1;
+            }
+            Err(e) => {
+                error!(target: "minecraft::save", "Unable to clean stale region {pos:?}: {e}");
+            }
+        }
+    }
+
+    Result::Ok(cleaned)
+}
+
+/// Header and encoded region, compressed with the configured
+/// [`CompressionLevel`](crate::config::CompressionLevel).
+fn encode_region(region: &Region) -> Result<Vec<u8>> {
+    let header = RegionHeader {
+        settings_hash: region.settings.content_hash(),
+    };
+
+    let mut buf = header.to_bytes().to_vec();
+    buf.extend(codec::encode(region, CONFIG.world.compression)?);
 
-    let region: Region = bincode::deserialize(&buf)?;
+    Result::Ok(buf)
+}
+
+/// Writes the region to its region file.
+///
+/// # Errors
+/// - the region file can't be written
+pub fn write_region(region: &Region) -> Result<()> {
+    write_region_file(region, &region_path(region.pos)?)
+}
+
+/// [`write_region`] with the path of the region file.
+fn write_region_file(region: &Region, path: &Path) -> Result<()> {
+    write_atomically(path, &encode_region(region)?)?;
+
+    Result::Ok(())
+}
+
+/// Writes `bytes` to `<path>.tmp` and renames it to `path`, so a crash while
+/// writing leaves either the old or the new file behind, never a truncated
+/// one.
+///
+/// # Errors
+/// - the temporary file can't be written or renamed
+pub fn write_atomically(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let tmp = tmp_path(path);
+    let mut file = StdOpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+
+    std::fs::rename(tmp, path)
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    PathBuf::from(tmp)
+}
+
+/// Decodes the content of a region file. Returns whether the file was written
+/// by an older version without a header or compression and should be
+/// rewritten.
+///
+/// Files without a header that can't be decoded are read as a
+/// [`LegacyRegion`], written before chunks had a [`SaveChunkFormat`].
+fn decode_region(buf: &[u8]) -> Result<(Region, bool)> {
+    let (header, body) = match RegionHeader::from_bytes(buf) {
+        Some(header) => (Some(header), &buf[RegionHeader::LEN..]),
+        None => (None, buf),
+    };
+
+    let region: Region = match codec::decode(body) {
+        Ok(region) => region,
+        Err(e) if header.is_none() && !codec::is_compressed(body) => {
+            match bincode::deserialize::<LegacyRegion>(body) {
+                Ok(legacy) => legacy.into(),
+                Err(_) => return Err(e),
+            }
+        }
+        Err(e) => return Err(e),
+    };
+    let outdated = header.is_none() || !codec::is_compressed(body);
+
+    Result::Ok((region, outdated))
+}
+
+/// Reads a region file without migrating it, for tools that inspect the files
+/// of a stopped server.
+///
+/// # Errors
+/// - the file can't be read or decoded
+pub fn read_region_file(path: &Path) -> Result<Region> {
+    let buf = std::fs::read(path)?;
+    let (region, _) = decode_region(&buf)?;
+
+    Result::Ok(region)
+}
+
+/// Decodes the content of the region file at `path`, region files of older
+/// versions are rewritten in the current format.
+fn read_region(buf: &[u8], path: &Path) -> Result<Region> {
+    let (region, outdated) = decode_region(buf)?;
+
+    if outdated {
+        match write_region_file(&region, path) {
+            Ok(()) => {
+                debug!(target: "minecraft::save", "migrated {} to the current format", path.display())
+            }
+            Err(e) => warn!(target: "minecraft::save", "unable to migrate {}: {e}", path.display()),
+        }
+    }
+
+    Result::Ok(region)
+}
+
+/// Loads the region at `pos` if it was generated with `settings`. Only the
+/// header is read if the settings don't match.
+///
+/// # Errors
+/// - the region file can't be read
+/// - the region was generated with different terrain settings
+pub fn load_region(pos: (i64, i64), settings: &TerrainSettings) -> Result<Region> {
+    let lock = region_lock(pos);
+    let _guard = lock.lock().ignore_poison();
+
+    load_region_locked(pos, settings)
+}
+
+/// [`load_region`] for callers that already hold the [`region_lock`].
+fn load_region_locked(pos: (i64, i64), settings: &TerrainSettings) -> Result<Region> {
+    load_region_file(&region_path(pos)?, settings)
+}
+
+/// [`load_region`] with the path of the region file.
+fn load_region_file(path: &Path, settings: &TerrainSettings) -> Result<Region> {
+    let mut header = [0; RegionHeader::LEN];
+    StdOpenOptions::new()
+        .read(true)
+        .open(path)?
+        .read_exact(&mut header)?;
+    let has_header = match RegionHeader::from_bytes(&header) {
+        Some(header) => {
+            header.check(settings)?;
+            true
+        }
+        None => false,
+    };
+
+    let buf = std::fs::read(path)?;
+    let region = match read_region(&buf, path) {
+        Ok(region) => region,
+        // The header is only written together with the chunks, so chunks that
+        // can't be decoded after a valid header were corrupted.
+        Err(e) if has_header => {
+            warn!(target: "minecraft::save", "Unable to decode {}: {e}", path.display());
+            let pos = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(parse_region_file_name)
+                .unwrap_or_default();
+            return Result::Err(quarantine(pos, path, vec![RegionError::Undecodable]));
+        }
+        Err(e) => return Result::Err(e),
+    };
     if &region.settings == settings {
-        Result::Ok(region)
+        check_region(region, path)
     } else {
-        Result::Err(anyhow::anyhow!("Terrain Settings don't match"))
+        Result::Err(SettingsMismatch.into())
     }
 }
 
-pub fn load_regions() -> Result<Vec<Region>> {
-    let mut regions = vec![];
+/// Logs the problems [`Region::validate`] finds, regions with fatal problems
+/// are moved out of the way with [`quarantine_region`].
+///
+/// # Errors
+/// - the region has a fatal problem, see [`RegionError::is_fatal`]
+fn check_region(region: Region, path: &Path) -> Result<Region> {
+    let (fatal, other): (Vec<_>, Vec<_>) = region
+        .validate()
+        .into_iter()
+        .partition(RegionError::is_fatal);
 
-    let base_path = std::env::current_dir()?.join("world");
-    for entry in WalkDir::new(base_path) {
-        let entry = entry?;
-        let _file = entry.path().display();
+    for error in &other {
+        warn!(target: "minecraft::save", "Region {:?}: {error}", region.pos);
+    }
+
+    if !fatal.is_empty() {
+        return Result::Err(quarantine(region.pos, path, fatal));
+    }
+
+    Result::Ok(region)
+}
+
+/// Moves the region file out of the way with [`quarantine_region`] and returns
+/// the [`QuarantinedRegion`] error.
+fn quarantine(pos: (i64, i64), path: &Path, problems: Vec<RegionError>) -> anyhow::Error {
+    let quarantined = match quarantine_region(path) {
+        Ok(quarantined) => quarantined,
+        Err(e) => {
+            return anyhow!("Region {pos:?} is corrupted and can't be moved out of the way: {e}")
+        }
+    };
+
+    let error = QuarantinedRegion {
+        pos,
+        quarantined,
+        problems,
+    };
+    error!(target: "minecraft::save", "{error}");
+    error.into()
+}
+
+/// Renames a corrupted region file to `<x>_<z>.region.corrupt`, numbered if
+/// that file already exists, and returns the new path. The region is
+/// generated again the next time it is needed.
+fn quarantine_region(path: &Path) -> std::io::Result<PathBuf> {
+    let mut quarantined = path.with_extension("region.corrupt");
+    let mut n = 1;
+    while quarantined.exists() {
+        quarantined = path.with_extension(format!("region.corrupt.{n}"));
+        n += 1;
+    }
 
-        if entry.file_type().is_file() && entry.path().extension().unwrap() == "region" {
-            let mut buf = vec![];
-            let mut file = StdOpenOptions::new().read(true).open(entry.path())?;
-            let _ = file.read_to_end(&mut buf);
+    std::fs::rename(path, &quarantined)?;
+    Result::Ok(quarantined)
+}
+
+/// Async version of [`load_region`], the region is loaded on a blocking task.
+///
+/// # Errors
+/// - the region file can't be read
+/// - the region was generated with different terrain settings
+pub async fn load_region_async(pos: (i64, i64), settings: &TerrainSettings) -> Result<Region> {
+    let settings = settings.clone();
+    tokio::task::spawn_blocking(move || load_region(pos, &settings)).await?
+}
 
-            let region: Region = bincode::deserialize(&buf)?;
-            trace!(target: "minecraft::save", "loaded region {:?}", region.pos);
+/// Loads every region of the world that was generated with `settings`, the
+/// regions of other settings are skipped. Regions that can't be read are
+/// skipped with a warning.
+///
+/// # Errors
+/// - the current directory or the world directory can't be accessed
+pub fn load_regions(settings: &TerrainSettings) -> Result<Vec<Region>> {
+    load_regions_in(&std::env::current_dir()?.join("world"), settings)
+}
 
-            regions.push(region);
+fn load_regions_in(dir: &Path, settings: &TerrainSettings) -> Result<Vec<Region>> {
+    let mut regions = vec![];
+
+    for (pos, path) in region_files_in(dir)? {
+        let lock = region_lock(pos);
+        let _guard = lock.lock().ignore_poison();
+
+        match load_region_file(&path, settings) {
+            Ok(region) => {
+                trace!(target: "minecraft::save", "loaded region {:?}", region.pos);
+                regions.push(region);
+            }
+            Err(e) if e.is::<SettingsMismatch>() => {
+                trace!(target: "minecraft::save",
+                    "skipped region {} {} of other settings", pos.0, pos.1
+                );
+            }
+            Err(e) => warn!(target: "minecraft::save",
+                "Unable to load region {} {}: {e}", pos.0, pos.1
+            ),
         }
     }
 
@@ -206,17 +1131,20 @@ pub async fn save_chunk(chunk: Chunk, pos: ChunkPos) -> Result<()> {
 
     let path = base_path.join(format!("{}_{}.chunk", pos.x, pos.z));
 
+    let tmp = tmp_path(&path);
     let mut file = OpenOptions::new()
         .create(true)
         .truncate(true)
         .write(true)
-        .open(path)
+        .open(&tmp)
         .await?;
     let mut save_chunk: SaveChunk = chunk.into();
     save_chunk.pos = (pos.x, pos.z);
 
     let encoded: Vec<u8> = bincode::serialize(&save_chunk)?;
     file.write_all(encoded.as_slice()).await?;
+    file.sync_all().await?;
+    fs::rename(tmp, path).await?;
 
     Result::Ok(())
 }
@@ -229,7 +1157,716 @@ pub fn load_chunk(pos: &ChunkPos) -> Result<Chunk> {
     let mut file = StdOpenOptions::new().read(true).open(path)?;
     let _ = file.read_to_end(&mut buf);
 
-    let save_chunk: SaveChunk = bincode::deserialize(&buf)?;
+    let save_chunk: SaveChunk = match bincode::deserialize(&buf) {
+        Ok(chunk) => chunk,
+        Err(e) => bincode::deserialize::<LegacySaveChunk>(&buf)
+            .map(SaveChunk::from)
+            .map_err(|_| e)?,
+    };
 
     Result::Ok(Chunk::from(save_chunk))
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::btree_set, prelude::*};
+
+    use super::*;
+    use crate::minecraft::world_gen::chunk_worker::FBMSettings;
+
+    /// Region at `(0, 0)` with a chunk at every position, the blocks of every
+    /// chunk are filled with `kind` so chunks of different regions differ.
+    fn region(positions: impl IntoIterator<Item = (i32, i32)>, kind: u16, seed: u32) -> Region {
+        Region {
+            pos: (0, 0),
+            settings: TerrainSettings {
+                seed,
+                ..Default::default()
+            },
+            chunks: positions
+                .into_iter()
+                .map(|pos| SaveChunk {
+                    pos,
+                    blocks: SaveChunkFormat::Indexed(vec![kind; 4]),
+                })
+                .collect(),
+        }
+    }
+
+    fn positions() -> impl Strategy<Value = std::collections::BTreeSet<(i32, i32)>> {
+        let size = REGION_SIZE as i32;
+        btree_set((0..size, 0..size), 0..16)
+    }
+
+    proptest! {
+        #[test]
+        fn merge_is_idempotent(base in positions(), overlay in positions()) {
+            let merged = Region::merge(region(base.clone(), 1, 1), region(overlay.clone(), 2, 2));
+            let again = Region::merge(
+                Region::merge(region(base, 1, 1), region(overlay.clone(), 2, 2)),
+                region(overlay, 2, 2),
+            );
+
+            prop_assert_eq!(merged, again);
+        }
+
+        #[test]
+        fn merge_keeps_base_chunks_not_in_overlay(base in positions(), overlay in positions()) {
+            let merged = Region::merge(region(base.clone(), 1, 1), region(overlay.clone(), 2, 2));
+
+            prop_assert_eq!(merged.settings.seed, 2);
+            prop_assert_eq!(merged.chunks.len(), base.union(&overlay).count());
+            for chunk in &merged.chunks {
+                let kind = if overlay.contains(&chunk.pos) { 2 } else { 1 };
+                prop_assert_eq!(&chunk.blocks, &SaveChunkFormat::Indexed(vec![kind; 4]));
+            }
+        }
+    }
+
+    fn raw_chunk(pos: (i32, i32), blocks: Vec<Block>) -> SaveChunk {
+        SaveChunk {
+            pos,
+            blocks: SaveChunkFormat::Raw(blocks),
+        }
+    }
+
+    fn stone(y: usize) -> Block {
+        Block {
+            x: 0,
+            y,
+            z: 0,
+            kind: BlockState::STONE.to_raw(),
+        }
+    }
+
+    #[test]
+    fn valid_region_has_no_errors() {
+        let region = Region {
+            pos: (0, 0),
+            settings: TerrainSettings::default(),
+            chunks: vec![raw_chunk((0, 0), vec![stone(0)]), raw_chunk((1, 0), vec![])],
+        };
+
+        assert!(region.validate().is_empty());
+    }
+
+    #[test]
+    fn chunk_of_another_region_is_not_fatal() {
+        let region = Region {
+            pos: (0, 0),
+            settings: TerrainSettings::default(),
+            chunks: vec![raw_chunk((REGION_SIZE as i32, 0), vec![])],
+        };
+
+        let errors = region.validate();
+        assert_eq!(
+            errors,
+            vec![RegionError::ChunkOutOfBounds {
+                chunk: (REGION_SIZE as i32, 0)
+            }]
+        );
+        assert!(!errors[0].is_fatal());
+    }
+
+    #[test]
+    fn duplicate_chunk_is_not_fatal() {
+        let region = Region {
+            pos: (0, 0),
+            settings: TerrainSettings::default(),
+            chunks: vec![raw_chunk((0, 0), vec![]), raw_chunk((0, 0), vec![])],
+        };
+
+        let errors = region.validate();
+        assert_eq!(errors, vec![RegionError::DuplicateChunk { chunk: (0, 0) }]);
+        assert!(!errors[0].is_fatal());
+    }
+
+    #[test]
+    fn block_out_of_range_is_fatal() {
+        let y = SECTION_COUNT * 16;
+        let region = Region {
+            pos: (0, 0),
+            settings: TerrainSettings::default(),
+            chunks: vec![raw_chunk((0, 0), vec![stone(y)])],
+        };
+
+        let errors = region.validate();
+        assert_eq!(
+            errors,
+            vec![RegionError::BlockOutOfRange {
+                chunk: (0, 0),
+                offset: (0, y, 0)
+            }]
+        );
+        assert!(errors[0].is_fatal());
+    }
+
+    #[test]
+    fn invalid_block_is_fatal_and_reported_once() {
+        let invalid = Block {
+            kind: u16::MAX,
+            ..stone(0)
+        };
+        let region = Region {
+            pos: (0, 0),
+            settings: TerrainSettings::default(),
+            chunks: vec![raw_chunk((0, 0), vec![invalid, invalid])],
+        };
+
+        let errors = region.validate();
+        assert_eq!(
+            errors,
+            vec![RegionError::InvalidBlock {
+                chunk: (0, 0),
+                kind: u16::MAX
+            }]
+        );
+        assert!(errors[0].is_fatal());
+    }
+
+    #[test]
+    fn corrupted_region_is_quarantined() {
+        let dir = std::env::temp_dir().join(format!("vp-quarantine-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("0_0.region");
+        std::fs::write(&path, b"corrupted").unwrap();
+        std::fs::write(dir.join("0_0.region.corrupt"), b"older").unwrap();
+
+        let region = Region {
+            pos: (0, 0),
+            settings: TerrainSettings::default(),
+            chunks: vec![raw_chunk((0, 0), vec![stone(SECTION_COUNT * 16)])],
+        };
+        let error = check_region(region, &path).unwrap_err();
+
+        assert!(error.is::<QuarantinedRegion>());
+        assert!(!path.exists());
+        assert_eq!(
+            std::fs::read(dir.join("0_0.region.corrupt.1")).unwrap(),
+            b"corrupted"
+        );
+        assert!(base_region(Err(error)).unwrap().is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Temporary `world` and archive directory with a region file of each
+    /// settings hash, the files only contain the header.
+    fn stale_world(name: &str, hashes: &[u64]) -> (PathBuf, PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("vp-{name}-{}", std::process::id()));
+        let world = dir.join("world");
+        std::fs::create_dir_all(&world).unwrap();
+
+        for (x, hash) in hashes.iter().enumerate() {
+            let header = RegionHeader {
+                settings_hash: *hash,
+            };
+            std::fs::write(world.join(format!("{x}_0.region")), header.to_bytes()).unwrap();
+        }
+
+        (dir.clone(), world, dir.join("world_archive"))
+    }
+
+    #[test]
+    fn stale_regions_are_deleted() {
+        let (dir, world, archive) = stale_world("stale-delete", &[1, 2, 1]);
+
+        let cleaned = clean_stale_regions_in(&world, &archive, 1, StaleRegions::Delete).unwrap();
+
+        assert_eq!(cleaned, 1);
+        assert!(world.join("0_0.region").exists());
+        assert!(!world.join("1_0.region").exists());
+        assert!(world.join("2_0.region").exists());
+        assert!(!archive.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stale_regions_are_archived() {
+        let (dir, world, archive) = stale_world("stale-archive", &[1, 2]);
+
+        let cleaned = clean_stale_regions_in(&world, &archive, 1, StaleRegions::Archive).unwrap();
+
+        assert_eq!(cleaned, 1);
+        assert!(world.join("0_0.region").exists());
+        assert!(!world.join("1_0.region").exists());
+        assert!(archive
+            .join(format!("{:016x}", 2))
+            .join("1_0.region")
+            .exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stale_regions_are_kept() {
+        let (dir, world, archive) = stale_world("stale-keep", &[1, 2]);
+
+        let cleaned = clean_stale_regions_in(&world, &archive, 1, StaleRegions::Keep).unwrap();
+
+        assert_eq!(cleaned, 0);
+        assert!(world.join("1_0.region").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn only_missing_or_stale_regions_are_replaced() {
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert!(base_region(Err(missing.into())).unwrap().is_none());
+        assert!(base_region(Err(SettingsMismatch.into())).unwrap().is_none());
+
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert!(base_region(Err(denied.into())).is_err());
+        assert!(base_region(Err(anyhow::anyhow!("corrupted"))).is_err());
+    }
+
+    #[test]
+    fn read_only_world_saving_is_disabled_by_default() {
+        assert_eq!(
+            crate::config::ReadOnlyWorld::default(),
+            crate::config::ReadOnlyWorld::DisableSaving
+        );
+    }
+
+    #[test]
+    fn directory_below_a_file_is_not_writable() {
+        let dir = std::env::temp_dir().join(format!("vp-writable-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("file"), b"").unwrap();
+
+        assert!(is_writable(&dir));
+        assert!(!dir.join(".write_test").exists());
+        assert!(!is_writable(&dir.join("file").join("world")));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_only_directory_is_not_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("vp-writable-ro-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o555)).unwrap();
+
+        // Permissions don't apply to root, the check can only be tested if the
+        // directory is actually read-only.
+        let actually_read_only = std::fs::write(dir.join("probe"), b"").is_err();
+        if actually_read_only {
+            assert!(!is_writable(&dir));
+        }
+
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn uncompressed_regions_without_a_header_are_marked_for_migration() {
+        let region = region([(0, 0), (3, 5)], 1, 7);
+
+        let (decoded, outdated) = decode_region(&encode_region(&region).unwrap()).unwrap();
+        assert_eq!(decoded, region);
+        assert!(!outdated);
+
+        // Plain bincode without a header, written before regions were compressed.
+        let legacy = bincode::serialize(&region).unwrap();
+        let (decoded, outdated) = decode_region(&legacy).unwrap();
+        assert_eq!(decoded, region);
+        assert!(outdated);
+    }
+
+    /// A region file written before chunks had a [`SaveChunkFormat`], with
+    /// the default terrain settings of that version and seed 42.
+    #[rustfmt::skip]
+    const LEGACY_REGION: &[u8] = &[
+        // pos
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        // settings.enable_gravel
+        0x01,
+        // settings.gravel_height
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x40,
+        0x03, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8, 0xbf,
+        // settings.enable_sand
+        0x01,
+        // settings.sand_offset
+        0x05, 0x00, 0x00, 0x00,
+        // settings.sand_height
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x40,
+        0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xe0, 0x3f,
+        // settings.enable_stone
+        0x01,
+        // settings.stone_point_scaleing
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2e, 0x40,
+        // settings.enable_grass, settings.enable_water
+        0x01, 0x01,
+        // settings.seed
+        0x2a, 0x00, 0x00, 0x00,
+        // number of chunks
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        // chunk pos
+        0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
+        // number of blocks
+        0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        // stone at 0 1 2
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x01, 0x00,
+        // dirt at 15 0 15
+        0x0f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x0f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x0a, 0x00,
+    ];
+
+    #[test]
+    fn legacy_region_files_are_decoded_and_marked_for_migration() {
+        let (region, outdated) = decode_region(LEGACY_REGION).unwrap();
+
+        assert!(outdated);
+        assert_eq!(region.pos, (0, 0));
+        assert_eq!(region.settings.seed, 42);
+        assert_eq!(region.settings.gravel_height, FBMSettings::default_gravel());
+        assert!(!region.settings.enable_caves);
+        assert!(region.validate().is_empty());
+        assert_eq!(
+            region.chunks,
+            [raw_chunk(
+                (1, 2),
+                vec![
+                    Block {
+                        x: 0,
+                        y: 1,
+                        z: 2,
+                        kind: BlockState::STONE.to_raw(),
+                    },
+                    Block {
+                        x: 15,
+                        y: 0,
+                        z: 15,
+                        kind: BlockState::DIRT.to_raw(),
+                    },
+                ]
+            )]
+        );
+
+        // Cut off files are still rejected.
+        assert!(decode_region(&LEGACY_REGION[..LEGACY_REGION.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn legacy_region_files_dont_block_saving() {
+        let dir = std::env::temp_dir().join(format!("vp-legacy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("0_0.region");
+        std::fs::write(&path, LEGACY_REGION).unwrap();
+
+        // The old generator had other settings, so the region is replaced.
+        let loaded = load_region_file(&path, &TerrainSettings::default());
+        assert!(matches!(base_region(loaded), Ok(None)));
+        // The file was migrated to the current format on the way.
+        let header = RegionHeader::from_bytes(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(
+            header.settings_hash,
+            decode_region(LEGACY_REGION)
+                .unwrap()
+                .0
+                .settings
+                .content_hash()
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn region_files_are_replaced_without_temporary_files_left() {
+        let dir = std::env::temp_dir().join(format!("vp-atomic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("0_0.region");
+        std::fs::write(&path, b"old").unwrap();
+
+        let region = Region {
+            pos: (0, 0),
+            settings: TerrainSettings::default(),
+            chunks: vec![raw_chunk((0, 0), vec![stone(SECTION_COUNT * 16)])],
+        };
+        write_region_file(&region, &path).unwrap();
+
+        assert_eq!(read_region_file(&path).unwrap(), region);
+        assert!(!tmp_path(&path).exists());
+        assert_eq!(region_files_in(&dir).unwrap().len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn region_of_other_settings_is_rejected_by_its_header() {
+        let dir = std::env::temp_dir().join(format!("vp-mismatch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = TerrainSettings::default();
+        let other = TerrainSettings {
+            seed: settings.seed.wrapping_add(1),
+            ..Default::default()
+        };
+
+        // The body isn't a region, so it must not be read at all.
+        let mut buf = RegionHeader {
+            settings_hash: other.content_hash(),
+        }
+        .to_bytes()
+        .to_vec();
+        buf.extend(b"definitely not a region");
+        let mismatch = dir.join("0_0.region");
+        std::fs::write(&mismatch, &buf).unwrap();
+
+        let e = load_region_file(&mismatch, &settings).unwrap_err();
+        assert!(e.is::<SettingsMismatch>(), "{e}");
+
+        // With matching settings the garbage is decoded, fails and is moved
+        // out of the way so the region can be saved again.
+        buf[4..RegionHeader::LEN].copy_from_slice(&settings.content_hash().to_le_bytes());
+        let garbage = dir.join("1_0.region");
+        std::fs::write(&garbage, &buf).unwrap();
+
+        let e = load_region_file(&garbage, &settings).unwrap_err();
+        let quarantined = e.downcast_ref::<QuarantinedRegion>().unwrap();
+        assert_eq!(quarantined.pos, (1, 0));
+        assert_eq!(quarantined.problems, [RegionError::Undecodable]);
+        assert!(!garbage.exists());
+        assert_eq!(std::fs::read(&quarantined.quarantined).unwrap(), buf);
+        assert!(base_region(Err(e)).unwrap().is_none());
+
+        // Files too short for a header can't be read.
+        let short = dir.join("2_0.region");
+        std::fs::write(&short, b"VPRG").unwrap();
+        assert!(load_region_file(&short, &settings).is_err());
+        assert!(load_region_file(&dir.join("3_0.region"), &settings).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn merge_keeps_overlay_chunks_first_in_order() {
+        let merged = Region::merge(
+            region([(0, 0), (1, 0), (2, 0), (3, 0)], 1, 1),
+            region([(2, 0), (0, 0), (5, 0)], 2, 2),
+        );
+
+        let positions: Vec<_> = merged.chunks.iter().map(|c| c.pos).collect();
+        assert_eq!(positions, [(2, 0), (0, 0), (5, 0), (1, 0), (3, 0)]);
+    }
+
+    #[test]
+    fn merge_of_full_regions_replaces_every_chunk() {
+        let size = REGION_SIZE as i32;
+        let all = || itertools::iproduct!(0..size, 0..size);
+
+        let merged = Region::merge(region(all(), 1, 1), region(all(), 2, 2));
+
+        assert_eq!(merged.chunks.len(), all().count());
+        assert!(merged
+            .chunks
+            .iter()
+            .all(|c| c.blocks == SaveChunkFormat::Indexed(vec![2; 4])));
+    }
+
+    fn bordered(radius: Option<f64>) -> WorldConfig {
+        WorldConfig {
+            world_border_radius: radius,
+            save_diffs: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn chunks_touching_the_border_are_inside() {
+        let config = bordered(Some(20.0));
+
+        // The chunks cover -32..-16, -16..0, 0..16, 16..32 and 32..48.
+        for (x, inside) in [
+            (-3, false),
+            (-2, true),
+            (-1, true),
+            (0, true),
+            (1, true),
+            (2, false),
+        ] {
+            assert_eq!(config.is_chunk_inside_border(x, 0), inside, "x {x}");
+            assert_eq!(config.is_chunk_inside_border(0, x), inside, "z {x}");
+        }
+        assert!(!config.is_chunk_inside_border(2, 2));
+        assert!(bordered(None).is_chunk_inside_border(i32::MAX / 16, i32::MIN / 16));
+    }
+
+    #[test]
+    fn diff_baseline_is_only_created_again_for_other_settings() {
+        let baseline = DiffBaseline::default();
+        let settings = TerrainSettings::default();
+        let other = TerrainSettings {
+            seed: settings.seed.wrapping_add(1),
+            ..Default::default()
+        };
+
+        let first = baseline.get(&settings);
+        assert!(Arc::ptr_eq(&first, &baseline.get(&settings)));
+
+        let changed = baseline.get(&other);
+        assert!(!Arc::ptr_eq(&first, &changed));
+        assert_eq!(changed.settings, other);
+        assert!(Arc::ptr_eq(&changed, &baseline.get(&other)));
+    }
+
+    #[test]
+    fn chunks_outside_the_border_are_not_saved() {
+        let chunks: Vec<_> = [(0, 0), (1, 1), (2, 0), (-3, 0), (0, -3)]
+            .into_iter()
+            .map(|(x, z)| (ChunkPos::new(x, z), Chunk::new(SECTION_COUNT)))
+            .collect();
+
+        let regions =
+            group_into_regions(&chunks, TerrainSettings::default(), &bordered(Some(20.0)));
+        let mut saved: Vec<_> = regions
+            .values()
+            .flat_map(|region| region.chunks.iter().map(|c| c.pos))
+            .collect();
+        saved.sort_unstable();
+        assert_eq!(saved, [(0, 0), (1, 1)]);
+
+        let regions = group_into_regions(&chunks, TerrainSettings::default(), &bordered(None));
+        let saved: usize = regions.values().map(|region| region.chunks.len()).sum();
+        assert_eq!(saved, chunks.len());
+    }
+
+    #[test]
+    fn saving_writes_no_region_files_outside_the_border() {
+        let dir = std::env::temp_dir().join(format!("vp-border-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = TerrainSettings::default();
+        let chunk = |x, z| (ChunkPos::new(x, z), Chunk::new(SECTION_COUNT));
+        let region_files = || {
+            let mut files = region_files_in(&dir)
+                .unwrap()
+                .into_iter()
+                .map(|(pos, _)| pos)
+                .collect::<Vec<_>>();
+            files.sort_unstable();
+            files
+        };
+
+        // Only chunks outside of the border, nothing is written.
+        let outside = [chunk(-3, 0), chunk(0, -3), chunk(40, 40)];
+        let saved =
+            save_chunks_to_regions_in(&dir, &outside, settings.clone(), &bordered(Some(20.0)));
+        assert_eq!(saved, 0);
+        assert!(region_files().is_empty());
+
+        // The chunks inside are saved, the region files of the others are still missing.
+        let mixed = [
+            chunk(0, 0),
+            chunk(1, 1),
+            chunk(2, 0),
+            chunk(-3, 0),
+            chunk(0, -3),
+        ];
+        let saved =
+            save_chunks_to_regions_in(&dir, &mixed, settings.clone(), &bordered(Some(20.0)));
+        assert_eq!(saved, 2);
+        assert_eq!(region_files(), [(0, 0)]);
+        let region = load_region_file(&region_path_in(&dir, (0, 0)), &settings).unwrap();
+        let mut positions = region.chunks.iter().map(|c| c.pos).collect::<Vec<_>>();
+        positions.sort_unstable();
+        assert_eq!(positions, [(0, 0), (1, 1)]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn only_regions_of_the_current_settings_are_loaded_after_a_settings_change() {
+        let dir = std::env::temp_dir().join(format!("vp-load-regions-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = TerrainSettings::default();
+        let new = TerrainSettings {
+            seed: old.seed.wrapping_add(1),
+            ..Default::default()
+        };
+
+        let size = REGION_SIZE as i32;
+        let write = |settings: &TerrainSettings, pos: ChunkPos| {
+            let chunks = [(pos, Chunk::new(SECTION_COUNT))];
+            for region in
+                group_into_regions(&chunks, settings.clone(), &bordered(None)).into_values()
+            {
+                let name = format!("{}_{}.region", region.pos.0, region.pos.1);
+                std::fs::write(dir.join(name), encode_region(&region).unwrap()).unwrap();
+            }
+        };
+        write(&old, ChunkPos::new(0, 0));
+        write(&old, ChunkPos::new(size, 0));
+        write(&new, ChunkPos::new(0, size));
+        std::fs::write(dir.join("notes.txt"), "not a region").unwrap();
+
+        let regions = load_regions_in(&dir, &new).unwrap();
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0].pos, (0, 1));
+        assert_eq!(regions[0].settings, new);
+        // Chunks of the old settings aren't taken for the new settings.
+        assert!(Region::chunk_from_regions(&regions, ChunkPos::new(0, 0)).is_none());
+        assert!(Region::chunk_from_regions(&regions, ChunkPos::new(0, size)).is_some());
+
+        let mut old_regions = load_regions_in(&dir, &old)
+            .unwrap()
+            .into_iter()
+            .map(|region| region.pos)
+            .collect::<Vec<_>>();
+        old_regions.sort_unstable();
+        assert_eq!(old_regions, [(0, 0), (1, 0)]);
+
+        assert!(load_regions_in(&dir.join("missing"), &new)
+            .unwrap()
+            .is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn saved_chunks_round_trip_in_every_format() {
+        let pos = ChunkPos::new(3, -2);
+        let state = ChunkWorkerState::new(7, TerrainSettings::default());
+        let generated = gen_chunk(&state, pos);
+        let mut modified = generated.clone();
+        for y in [0, 64, SECTION_COUNT * 16 - 1] {
+            modified.set_block_state(5, y, 11, BlockState::GLASS);
+        }
+
+        let saved = [
+            to_save_chunk(None, pos, &modified, ChunkFormat::Rle),
+            to_save_chunk(None, pos, &modified, ChunkFormat::Indexed),
+            to_save_chunk(Some(&state), pos, &modified, ChunkFormat::Rle),
+        ];
+        assert!(matches!(saved[0].blocks, SaveChunkFormat::Rle(_)));
+        assert!(matches!(saved[1].blocks, SaveChunkFormat::Indexed(_)));
+        assert!(matches!(saved[2].blocks, SaveChunkFormat::Diff(_)));
+
+        for save_chunk in saved {
+            assert_eq!(save_chunk.pos, (3, -2));
+
+            let loaded = save_chunk.to_chunk(|| gen_chunk(&state, pos));
+            for (x, y, z) in itertools::iproduct!(0..16, 0..SECTION_COUNT * 16, 0..16) {
+                assert_eq!(
+                    loaded.block_state(x, y, z),
+                    modified.block_state(x, y, z),
+                    "{:?} {x} {y} {z}",
+                    save_chunk.blocks
+                );
+            }
+        }
+    }
+}
@@ -1,15 +1,117 @@
 use itertools::iproduct;
-use valence::prelude::{BlockState, Chunk};
+use valence::{
+    prelude::{BlockPos, BlockState, Chunk},
+    view::ChunkPos,
+};
 
-use crate::SECTION_COUNT;
+use crate::{minecraft::world_gen::BlockEntities, SECTION_COUNT};
 
 pub type OffsetBlockPos = (usize, usize, usize);
 pub type SaveChunkIteratorItem = (OffsetBlockPos, BlockState);
 
+/// Number of blocks in a single column of a chunk.
+const COLUMN_HEIGHT: usize = SECTION_COUNT * 16;
+
 #[derive(PartialEq, Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct SaveChunk {
     pub pos: (i32, i32),
-    pub blocks: Vec<Block>,
+    pub blocks: SaveChunkFormat,
+}
+
+/// How the blocks of a [`SaveChunk`] are stored.
+///
+/// Blocks are ordered column by column (z, then x), every column from the top
+/// to the bottom.
+#[derive(PartialEq, Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub enum SaveChunkFormat {
+    /// Every block with its position.
+    Raw(Vec<Block>),
+    /// Consecutive identical blocks stored as a single run.
+    Rle(Vec<BlockRun>),
+    /// Only the blocks that differ from the generated chunk, the chunk has to
+    /// be generated again to load it, see [`SaveChunk::to_chunk`].
+    Diff(SaveChunkDiff),
+    /// Every block without its position, which is derived from the index.
+    Indexed(Vec<u16>),
+}
+
+impl SaveChunk {
+    /// The blocks of `modified` that differ from `generated`.
+    #[must_use]
+    pub fn diff(generated: &Chunk, modified: &Chunk) -> SaveChunkDiff {
+        let mut changes = vec![];
+
+        for (z, x) in iproduct!(0..16, 0..16) {
+            for y in (0..modified.section_count() * 16).rev() {
+                let old = generated.block_state(x, y, z).to_raw();
+                let new = modified.block_state(x, y, z).to_raw();
+
+                if old != new {
+                    changes.push(((x, y, z), old, new));
+                }
+            }
+        }
+
+        SaveChunkDiff { changes }
+    }
+
+    /// Builds the chunk, `generate` is only called for
+    /// [`SaveChunkFormat::Diff`] chunks, which are applied to the generated
+    /// chunk.
+    #[must_use]
+    pub fn to_chunk(&self, generate: impl FnOnce() -> Chunk) -> Chunk {
+        match &self.blocks {
+            SaveChunkFormat::Diff(diff) => {
+                let mut chunk = generate();
+                diff.apply(&mut chunk);
+                chunk
+            }
+            _ => self.into(),
+        }
+    }
+
+    /// Converts the chunk to the [`SaveChunkFormat::Indexed`] format.
+    /// [`SaveChunkFormat::Diff`] chunks are kept as they are.
+    #[must_use]
+    pub fn to_indexed(&self) -> SaveChunk {
+        if let SaveChunkFormat::Diff(_) = self.blocks {
+            return self.clone();
+        }
+
+        SaveChunk {
+            pos: self.pos,
+            blocks: SaveChunkFormat::Indexed(
+                self.clone()
+                    .into_iter()
+                    .map(|(_, block)| block.to_raw())
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Converts the chunk to the [`SaveChunkFormat::Raw`] format, useful for
+    /// debugging. [`SaveChunkFormat::Diff`] chunks are kept as they are.
+    #[must_use]
+    pub fn to_raw(&self) -> SaveChunk {
+        if let SaveChunkFormat::Diff(_) = self.blocks {
+            return self.clone();
+        }
+
+        SaveChunk {
+            pos: self.pos,
+            blocks: SaveChunkFormat::Raw(
+                self.clone()
+                    .into_iter()
+                    .map(|((x, y, z), block)| Block {
+                        x,
+                        y,
+                        z,
+                        kind: block.to_raw(),
+                    })
+                    .collect(),
+            ),
+        }
+    }
 }
 
 impl IntoIterator for SaveChunk {
@@ -20,15 +122,102 @@ impl IntoIterator for SaveChunk {
         SaveChunkIterator {
             blocks: self.blocks,
             next: SaveChunkId(0),
+            run: 0,
+            run_offset: 0,
+        }
+    }
+}
+
+/// Raw NBT compounds of the block entities (signs, chests, ...) in a chunk,
+/// stored in a `<region>_nbt.bin` file next to the region file.
+#[derive(PartialEq, Debug, serde::Deserialize, serde::Serialize, Clone, Default)]
+pub struct SaveChunkNbt {
+    pub pos: (i32, i32),
+    /// World position of every block entity and its NBT.
+    pub entities: Vec<([i32; 3], Vec<u8>)>,
+}
+
+impl SaveChunkNbt {
+    /// The block entities of the chunk at `pos`.
+    #[must_use]
+    pub fn from_entities(entities: &BlockEntities, pos: ChunkPos) -> Self {
+        Self {
+            pos: (pos.x, pos.z),
+            entities: entities
+                .in_chunk(pos)
+                .map(|(pos, nbt)| ([pos.x, pos.y, pos.z], nbt.to_vec()))
+                .collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool { self.entities.is_empty() }
+
+    /// Parses the NBT of every block entity, invalid NBT is skipped.
+    pub fn values(&self) -> impl Iterator<Item = (BlockPos, fastnbt::Value)> + '_ {
+        self.entities
+            .iter()
+            .filter_map(|([x, y, z], nbt)| match fastnbt::from_bytes(nbt) {
+                Ok(value) => Some((BlockPos::new(*x, *y, *z), value)),
+                Err(e) => {
+                    warn!(target: "minecraft::save", "Invalid block entity at {x} {y} {z}: {e}");
+                    None
+                }
+            })
+    }
+
+    /// Adds the block entities to `entities`, replacing the ones at the same
+    /// positions.
+    pub fn restore(&self, entities: &mut BlockEntities) {
+        for (pos, value) in self.values() {
+            entities.set(pos, &value);
+        }
+    }
+}
+
+impl SaveChunk {
+    /// The blocks of the chunk together with its block entities.
+    #[must_use]
+    pub fn with_nbt(
+        chunk: &Chunk,
+        entities: &BlockEntities,
+        pos: ChunkPos,
+    ) -> (SaveChunk, SaveChunkNbt) {
+        let mut save_chunk = SaveChunk::from(chunk);
+        save_chunk.pos = (pos.x, pos.z);
+
+        (save_chunk, SaveChunkNbt::from_entities(entities, pos))
+    }
+}
+
+/// The blocks a player changed in a generated chunk, as the position, the
+/// generated and the changed raw block state.
+#[derive(PartialEq, Debug, serde::Deserialize, serde::Serialize, Clone, Default)]
+pub struct SaveChunkDiff {
+    pub changes: Vec<(OffsetBlockPos, u16, u16)>,
+}
+
+impl SaveChunkDiff {
+    /// Sets the changed blocks in the chunk.
+    pub fn apply(&self, chunk: &mut Chunk) {
+        for ((x, y, z), _, new) in &self.changes {
+            if let Some(state) = BlockState::from_raw(*new) {
+                chunk.set_block_state(*x, *y, *z, state);
+            }
         }
     }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool { self.changes.is_empty() }
 }
 
 pub struct SaveChunkId(usize);
 
 pub struct SaveChunkIterator {
-    blocks: Vec<Block>,
+    blocks: SaveChunkFormat,
     next: SaveChunkId,
+    run: usize,
+    run_offset: u16,
 }
 
 impl Iterator for SaveChunkIterator {
@@ -36,17 +225,58 @@ impl Iterator for SaveChunkIterator {
 
     fn next(&mut self) -> Option<Self::Item> {
         let next = self.next.0;
-        let Some(block) = self.blocks.get(next) else { return None; };
 
-        self.next.0 = next + 1;
+        match &self.blocks {
+            SaveChunkFormat::Raw(blocks) => {
+                let block = blocks.get(next)?;
+
+                self.next.0 = next + 1;
+
+                Some((
+                    (block.x, block.y, block.z),
+                    BlockState::from_raw(block.kind)?,
+                ))
+            }
+            SaveChunkFormat::Rle(runs) => {
+                let mut run = runs.get(self.run)?;
+                while self.run_offset >= run.count {
+                    self.run += 1;
+                    self.run_offset = 0;
+                    run = runs.get(self.run)?;
+                }
+
+                self.run_offset += 1;
+                self.next.0 = next + 1;
+
+                Some((offset_from_index(next), BlockState::from_raw(run.kind)?))
+            }
+            SaveChunkFormat::Indexed(blocks) => {
+                let kind = blocks.get(next)?;
+
+                self.next.0 = next + 1;
+
+                Some((offset_from_index(next), BlockState::from_raw(*kind)?))
+            }
+            SaveChunkFormat::Diff(diff) => {
+                let (pos, _, new) = diff.changes.get(next)?;
 
-        Some((
-            (block.x, block.y, block.z),
-            BlockState::from_raw(block.kind)?,
-        ))
+                self.next.0 = next + 1;
+
+                Some((*pos, BlockState::from_raw(*new)?))
+            }
+        }
     }
 }
 
+/// Position of the block at `index` in the scan order of a [`SaveChunk`].
+#[must_use]
+pub fn offset_from_index(index: usize) -> OffsetBlockPos {
+    let column = index / COLUMN_HEIGHT;
+    let y = COLUMN_HEIGHT - 1 - index % COLUMN_HEIGHT;
+
+    (column % 16, y, column / 16)
+}
+
 #[derive(PartialEq, Debug, serde::Deserialize, serde::Serialize, Clone, Copy)]
 pub struct Block {
     pub x: usize,
@@ -55,6 +285,12 @@ pub struct Block {
     pub kind: u16,
 }
 
+#[derive(PartialEq, Debug, serde::Deserialize, serde::Serialize, Clone, Copy)]
+pub struct BlockRun {
+    pub count: u16,
+    pub kind: u16,
+}
+
 impl From<SaveChunk> for Chunk {
     fn from(value: SaveChunk) -> Self {
         let mut chunk = Chunk::new(SECTION_COUNT);
@@ -68,59 +304,170 @@ impl From<SaveChunk> for Chunk {
 }
 
 impl From<&SaveChunk> for Chunk {
-    fn from(value: &SaveChunk) -> Self {
-        let mut chunk = Chunk::new(SECTION_COUNT);
-
-        for (pos, block) in value.clone().into_iter() {
-            chunk.set_block_state(pos.0, pos.1, pos.2, block);
-        }
-
-        chunk
-    }
+    fn from(value: &SaveChunk) -> Self { Chunk::from(value.clone()) }
 }
 
 impl From<Chunk> for SaveChunk {
-    fn from(value: Chunk) -> Self {
-        let mut save_chunk = SaveChunk {
-            pos: (0, 0),
-            blocks: Vec::new(),
-        };
+    fn from(value: Chunk) -> Self { SaveChunk::from(&value) }
+}
+
+impl From<&Chunk> for SaveChunk {
+    fn from(value: &Chunk) -> Self {
+        let mut runs: Vec<BlockRun> = Vec::new();
 
         for (offset_z, offset_x) in iproduct!(0..16, 0..16) {
             for y in (0..value.section_count() * 16).rev() {
-                let block = value.block_state(offset_x, y, offset_z);
-                save_chunk.blocks.push(Block {
-                    x: offset_x,
-                    y,
-                    z: offset_z,
-                    kind: block.to_raw(),
-                })
+                let kind = value.block_state(offset_x, y, offset_z).to_raw();
+
+                match runs.last_mut() {
+                    Some(run) if run.kind == kind && run.count < u16::MAX => run.count += 1,
+                    _ => runs.push(BlockRun { count: 1, kind }),
+                }
             }
         }
 
-        save_chunk
+        SaveChunk {
+            pos: (0, 0),
+            blocks: SaveChunkFormat::Rle(runs),
+        }
     }
 }
 
-impl From<&Chunk> for SaveChunk {
-    fn from(value: &Chunk) -> Self {
-        let mut save_chunk = SaveChunk {
-            pos: (0, 0),
-            blocks: Vec::new(),
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        for (offset_z, offset_x) in iproduct!(0..16, 0..16) {
-            for y in (0..value.section_count() * 16).rev() {
-                let block = value.block_state(offset_x, y, offset_z);
-                save_chunk.blocks.push(Block {
-                    x: offset_x,
-                    y,
-                    z: offset_z,
-                    kind: block.to_raw(),
-                })
+    /// Stone from `bottom` to `top` in every other column, air everywhere else.
+    fn layered_chunk(bottom: usize, top: usize) -> Chunk {
+        let mut chunk = Chunk::new(SECTION_COUNT);
+        for (x, z) in iproduct!(0..16, 0..16).filter(|(x, z)| (x + z) % 2 == 0) {
+            for y in bottom..=top {
+                chunk.set_block_state(x, y, z, BlockState::STONE);
             }
         }
+        chunk
+    }
+
+    fn assert_same_blocks(a: &Chunk, b: &Chunk) {
+        for (x, y, z) in iproduct!(0..16, 0..COLUMN_HEIGHT, 0..16) {
+            assert_eq!(
+                a.block_state(x, y, z),
+                b.block_state(x, y, z),
+                "{x} {y} {z}"
+            );
+        }
+    }
+
+    fn runs(chunk: &SaveChunk) -> &[BlockRun] {
+        match &chunk.blocks {
+            SaveChunkFormat::Rle(runs) => runs,
+            blocks => panic!("expected runs, got {blocks:?}"),
+        }
+    }
+
+    #[test]
+    fn offsets_follow_the_scan_order() {
+        assert_eq!(offset_from_index(0), (0, COLUMN_HEIGHT - 1, 0));
+        assert_eq!(offset_from_index(COLUMN_HEIGHT - 1), (0, 0, 0));
+        assert_eq!(offset_from_index(COLUMN_HEIGHT), (1, COLUMN_HEIGHT - 1, 0));
+        assert_eq!(
+            offset_from_index(16 * COLUMN_HEIGHT),
+            (0, COLUMN_HEIGHT - 1, 1)
+        );
+        assert_eq!(offset_from_index(256 * COLUMN_HEIGHT - 1), (15, 0, 15));
+    }
+
+    #[test]
+    fn runs_round_trip() {
+        // The stone crosses the border between the first two sections.
+        let chunk = layered_chunk(10, 20);
+        let save_chunk = SaveChunk::from(&chunk);
+
+        assert_same_blocks(&Chunk::from(&save_chunk), &chunk);
+    }
+
+    #[test]
+    fn runs_cross_section_boundaries() {
+        let save_chunk = SaveChunk::from(&layered_chunk(10, 20));
+        let runs = runs(&save_chunk);
+
+        // Air above the stone, the stone and air below it in the first column.
+        assert_eq!(usize::from(runs[0].count), COLUMN_HEIGHT - 21);
+        assert_eq!(runs[1].count, 11);
+        assert_eq!(runs[1].kind, BlockState::STONE.to_raw());
+        assert_eq!(
+            runs.iter().map(|r| usize::from(r.count)).sum::<usize>(),
+            256 * COLUMN_HEIGHT
+        );
+    }
+
+    #[test]
+    fn runs_cross_column_boundaries_up_to_the_maximum_length() {
+        let save_chunk = SaveChunk::from(&Chunk::new(SECTION_COUNT));
+        let runs = runs(&save_chunk);
+        let total = 256 * COLUMN_HEIGHT;
+
+        assert_eq!(runs.len(), total.div_ceil(usize::from(u16::MAX)));
+        assert_eq!(runs[0].count, u16::MAX);
+        assert!(runs.iter().all(|r| r.kind == BlockState::AIR.to_raw()));
+        assert_same_blocks(&Chunk::from(&save_chunk), &Chunk::new(SECTION_COUNT));
+    }
 
-        save_chunk
+    #[test]
+    fn raw_chunks_round_trip() {
+        let chunk = layered_chunk(0, COLUMN_HEIGHT - 1);
+        let raw = SaveChunk::from(&chunk).to_raw();
+
+        assert!(matches!(raw.blocks, SaveChunkFormat::Raw(_)));
+        assert_same_blocks(&Chunk::from(&raw), &chunk);
+    }
+
+    #[test]
+    fn empty_diff_round_trips() {
+        let generated = layered_chunk(10, 20);
+        let diff = SaveChunk::diff(&generated, &generated);
+        assert!(diff.is_empty());
+
+        let save_chunk = SaveChunk {
+            pos: (0, 0),
+            blocks: SaveChunkFormat::Diff(diff),
+        };
+        assert_same_blocks(&save_chunk.to_chunk(|| generated.clone()), &generated);
+        // Diffs aren't converted to the other formats.
+        assert_eq!(save_chunk.to_indexed(), save_chunk);
+        assert_eq!(save_chunk.to_raw(), save_chunk);
+    }
+
+    #[test]
+    fn full_chunk_diff_round_trips() {
+        let generated = Chunk::new(SECTION_COUNT);
+        let modified = layered_chunk(0, COLUMN_HEIGHT - 1);
+        let diff = SaveChunk::diff(&generated, &modified);
+
+        // Every other column changed from air to stone.
+        assert_eq!(diff.changes.len(), 128 * COLUMN_HEIGHT);
+        assert!(diff.changes.iter().all(|(_, old, new)| {
+            *old == BlockState::AIR.to_raw() && *new == BlockState::STONE.to_raw()
+        }));
+
+        let save_chunk = SaveChunk {
+            pos: (0, 0),
+            blocks: SaveChunkFormat::Diff(diff),
+        };
+        assert_same_blocks(&save_chunk.to_chunk(|| generated.clone()), &modified);
+    }
+
+    #[test]
+    fn indexed_chunks_round_trip() {
+        let chunk = layered_chunk(10, 20);
+        let indexed = SaveChunk::from(&chunk).to_indexed();
+
+        let SaveChunkFormat::Indexed(kinds) = &indexed.blocks else {
+            panic!("expected indexed blocks, got {:?}", indexed.blocks);
+        };
+        assert_eq!(kinds.len(), 256 * COLUMN_HEIGHT);
+        assert_same_blocks(&Chunk::from(&indexed), &chunk);
+        // Converting back keeps the blocks.
+        assert_same_blocks(&Chunk::from(&indexed.to_raw()), &chunk);
     }
 }
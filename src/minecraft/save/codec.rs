@@ -0,0 +1,92 @@
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::config::CompressionLevel;
+
+/// Magic bytes at the start of every zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+impl CompressionLevel {
+    #[must_use]
+    pub fn zstd_level(self) -> i32 {
+        match self {
+            CompressionLevel::Fast => 1,
+            CompressionLevel::Default => zstd::DEFAULT_COMPRESSION_LEVEL,
+            CompressionLevel::Best => 19,
+        }
+    }
+}
+
+/// Returns `true` if `buf` was written by [`encode`], `false` if it is plain
+/// bincode written by an older version.
+#[must_use]
+pub fn is_compressed(buf: &[u8]) -> bool { buf.starts_with(&ZSTD_MAGIC) }
+
+/// Serializes `value` with bincode and compresses the result.
+///
+/// # Errors
+/// - `value` can't be serialized
+pub fn encode<T: Serialize>(value: &T, level: CompressionLevel) -> Result<Vec<u8>> {
+    let raw = bincode::serialize(value)?;
+
+    Ok(zstd::encode_all(raw.as_slice(), level.zstd_level())?)
+}
+
+/// Decodes a buffer written by [`encode`]. Uncompressed buffers are read as
+/// plain bincode.
+///
+/// # Errors
+/// - `buf` is not valid zstd or bincode
+pub fn decode<T: DeserializeOwned>(buf: &[u8]) -> Result<T> {
+    if is_compressed(buf) {
+        let raw = zstd::decode_all(buf)?;
+        Ok(bincode::deserialize(&raw)?)
+    } else {
+        Ok(bincode::deserialize(buf)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value() -> (String, Vec<u32>) { ("region".to_string(), (0..1000).map(|i| i % 7).collect()) }
+
+    #[test]
+    fn encoded_values_round_trip_at_every_level() {
+        for level in [
+            CompressionLevel::Fast,
+            CompressionLevel::Default,
+            CompressionLevel::Best,
+        ] {
+            let buf = encode(&value(), level).unwrap();
+
+            assert!(is_compressed(&buf));
+            assert_eq!(decode::<(String, Vec<u32>)>(&buf).unwrap(), value());
+        }
+    }
+
+    #[test]
+    fn encoded_values_are_smaller_than_bincode() {
+        let raw = bincode::serialize(&value()).unwrap();
+        let buf = encode(&value(), CompressionLevel::Default).unwrap();
+
+        assert!(buf.len() < raw.len());
+    }
+
+    #[test]
+    fn legacy_bincode_is_detected_and_decoded() {
+        let raw = bincode::serialize(&value()).unwrap();
+
+        assert!(!is_compressed(&raw));
+        assert_eq!(decode::<(String, Vec<u32>)>(&raw).unwrap(), value());
+    }
+
+    #[test]
+    fn truncated_buffers_are_rejected() {
+        let buf = encode(&value(), CompressionLevel::Default).unwrap();
+
+        assert!(decode::<(String, Vec<u32>)>(&buf[..buf.len() / 2]).is_err());
+        assert!(decode::<(String, Vec<u32>)>(&ZSTD_MAGIC).is_err());
+    }
+}
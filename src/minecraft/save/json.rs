@@ -0,0 +1,317 @@
+use std::io::{Read, Write};
+
+use anyhow::{bail, Context, Result};
+use valence::prelude::*;
+
+use super::{
+    offset_from_index, Block, BlockRun, Region, SaveChunk, SaveChunkDiff, SaveChunkFormat,
+};
+use crate::minecraft::{schematic::parse_block_state, world_gen::chunk_worker::TerrainSettings};
+
+/// Human readable version of a [`Region`], blocks are stored by their name
+/// instead of their raw id.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct JsonRegion {
+    pos: (i64, i64),
+    settings: TerrainSettings,
+    chunks: Vec<JsonChunk>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct JsonChunk {
+    pos: (i32, i32),
+    format: JsonFormat,
+    /// In the scan order of [`SaveChunk`], only the changed blocks for
+    /// [`JsonFormat::Diff`].
+    blocks: Vec<JsonBlock>,
+}
+
+/// The [`SaveChunkFormat`] the chunk is stored in, so it is restored exactly.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq)]
+enum JsonFormat {
+    Raw,
+    Rle,
+    Indexed,
+    Diff,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct JsonBlock {
+    x: usize,
+    y: usize,
+    z: usize,
+    /// Like `minecraft:oak_log[axis=y]`.
+    block: String,
+    /// The generated block a [`JsonFormat::Diff`] block replaces.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    generated: Option<String>,
+}
+
+impl Region {
+    /// Writes the region as pretty printed JSON.
+    ///
+    /// # Errors
+    /// - the JSON can't be written
+    pub fn to_json_writer<W: Write>(&self, w: W) -> Result<()> {
+        let json = JsonRegion {
+            pos: self.pos,
+            settings: self.settings.clone(),
+            chunks: self.chunks.iter().map(JsonChunk::from_chunk).collect(),
+        };
+
+        serde_json::to_writer_pretty(w, &json)?;
+        Ok(())
+    }
+
+    /// Reads a region written by [`Region::to_json_writer`].
+    ///
+    /// # Errors
+    /// - the JSON is invalid
+    /// - a block name is unknown
+    /// - the blocks of a chunk stored without positions aren't in scan order
+    pub fn from_json_reader<R: Read>(r: R) -> Result<Self> {
+        let json: JsonRegion = serde_json::from_reader(r)?;
+
+        let chunks = json
+            .chunks
+            .into_iter()
+            .map(|chunk| {
+                let pos = chunk.pos;
+                chunk
+                    .into_chunk()
+                    .with_context(|| format!("Invalid chunk {pos:?}"))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Region {
+            pos: json.pos,
+            settings: json.settings,
+            chunks,
+        })
+    }
+}
+
+impl JsonChunk {
+    fn from_chunk(chunk: &SaveChunk) -> Self {
+        let (format, blocks) = match &chunk.blocks {
+            SaveChunkFormat::Diff(diff) => {
+                let blocks = diff
+                    .changes
+                    .iter()
+                    .map(|((x, y, z), old, new)| JsonBlock {
+                        x: *x,
+                        y: *y,
+                        z: *z,
+                        block: raw_block_name(*new),
+                        generated: Some(raw_block_name(*old)),
+                    })
+                    .collect();
+
+                (JsonFormat::Diff, blocks)
+            }
+            blocks => {
+                let format = match blocks {
+                    SaveChunkFormat::Raw(_) => JsonFormat::Raw,
+                    SaveChunkFormat::Rle(_) => JsonFormat::Rle,
+                    _ => JsonFormat::Indexed,
+                };
+                let blocks = chunk
+                    .clone()
+                    .into_iter()
+                    .map(|((x, y, z), state)| JsonBlock {
+                        x,
+                        y,
+                        z,
+                        block: block_name(state),
+                        generated: None,
+                    })
+                    .collect();
+
+                (format, blocks)
+            }
+        };
+
+        Self {
+            pos: chunk.pos,
+            format,
+            blocks,
+        }
+    }
+
+    fn into_chunk(self) -> Result<SaveChunk> {
+        let blocks = match self.format {
+            JsonFormat::Raw => SaveChunkFormat::Raw(
+                self.blocks
+                    .iter()
+                    .map(|b| {
+                        Ok(Block {
+                            x: b.x,
+                            y: b.y,
+                            z: b.z,
+                            kind: parse_block_name(&b.block)?,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            JsonFormat::Rle | JsonFormat::Indexed => {
+                let mut kinds = Vec::with_capacity(self.blocks.len());
+                for (i, b) in self.blocks.iter().enumerate() {
+                    if offset_from_index(i) != (b.x, b.y, b.z) {
+                        bail!("Block {i} is at {} {} {}, not in scan order", b.x, b.y, b.z);
+                    }
+                    kinds.push(parse_block_name(&b.block)?);
+                }
+
+                if self.format == JsonFormat::Indexed {
+                    SaveChunkFormat::Indexed(kinds)
+                } else {
+                    SaveChunkFormat::Rle(to_runs(&kinds))
+                }
+            }
+            JsonFormat::Diff => SaveChunkFormat::Diff(SaveChunkDiff {
+                changes: self
+                    .blocks
+                    .iter()
+                    .map(|b| {
+                        let generated = b.generated.as_deref().unwrap_or("minecraft:air");
+                        Ok((
+                            (b.x, b.y, b.z),
+                            parse_block_name(generated)?,
+                            parse_block_name(&b.block)?,
+                        ))
+                    })
+                    .collect::<Result<_>>()?,
+            }),
+        };
+
+        Ok(SaveChunk {
+            pos: self.pos,
+            blocks,
+        })
+    }
+}
+
+/// The same runs [`SaveChunk::from`] builds for a chunk.
+fn to_runs(kinds: &[u16]) -> Vec<BlockRun> {
+    let mut runs: Vec<BlockRun> = Vec::new();
+
+    for kind in kinds {
+        match runs.last_mut() {
+            Some(run) if run.kind == *kind && run.count < u16::MAX => run.count += 1,
+            _ => runs.push(BlockRun {
+                count: 1,
+                kind: *kind,
+            }),
+        }
+    }
+
+    runs
+}
+
+/// Name of a block state with all of its properties, like
+/// `minecraft:oak_log[axis=y]`.
+#[must_use]
+pub fn block_name(state: BlockState) -> String {
+    let kind = state.to_kind();
+    let props = kind
+        .props()
+        .iter()
+        .filter_map(|name| {
+            let value = state.get(*name)?;
+            Some(format!("{}={}", name.to_str(), value.to_str()))
+        })
+        .collect::<Vec<_>>();
+
+    if props.is_empty() {
+        format!("minecraft:{}", kind.to_str())
+    } else {
+        format!("minecraft:{}[{}]", kind.to_str(), props.join(","))
+    }
+}
+
+fn raw_block_name(raw: u16) -> String {
+    BlockState::from_raw(raw).map_or_else(|| format!("unknown:{raw}"), block_name)
+}
+
+fn parse_block_name(name: &str) -> Result<u16> {
+    // Ids without a block are kept, so they survive the round trip.
+    if let Some(raw) = name.strip_prefix("unknown:") {
+        return Ok(raw.parse()?);
+    }
+
+    match parse_block_state(name) {
+        Some(state) => Ok(state.to_raw()),
+        None => bail!("Unknown block {name}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+    use crate::SECTION_COUNT;
+
+    /// Raw id of a block state, the low ids all belong to a block.
+    fn block() -> impl Strategy<Value = u16> {
+        (0..4096_u16).prop_filter_map("not a block state", |raw| {
+            BlockState::from_raw(raw).map(|_| raw)
+        })
+    }
+
+    fn offset() -> impl Strategy<Value = (usize, usize, usize)> {
+        (0..16_usize, 0..SECTION_COUNT * 16, 0..16_usize)
+    }
+
+    fn blocks() -> impl Strategy<Value = SaveChunkFormat> {
+        prop_oneof![
+            vec((offset(), block()), 0..32).prop_map(|blocks| {
+                SaveChunkFormat::Raw(
+                    blocks
+                        .into_iter()
+                        .map(|((x, y, z), kind)| Block { x, y, z, kind })
+                        .collect(),
+                )
+            }),
+            // Runs like the ones `SaveChunk::from` builds, adjacent runs never have
+            // the same block.
+            vec((block(), 1..50_usize), 0..8).prop_map(|runs| {
+                let kinds = runs
+                    .into_iter()
+                    .flat_map(|(kind, count)| std::iter::repeat(kind).take(count))
+                    .collect::<Vec<_>>();
+                SaveChunkFormat::Rle(to_runs(&kinds))
+            }),
+            vec(block(), 0..64).prop_map(SaveChunkFormat::Indexed),
+            // Ids without a block are kept in diffs.
+            vec((offset(), any::<u16>(), any::<u16>()), 0..16)
+                .prop_map(|changes| SaveChunkFormat::Diff(SaveChunkDiff { changes })),
+        ]
+    }
+
+    fn region() -> impl Strategy<Value = Region> {
+        let chunk =
+            (any::<(i32, i32)>(), blocks()).prop_map(|(pos, blocks)| SaveChunk { pos, blocks });
+
+        (any::<(i64, i64)>(), any::<u32>(), vec(chunk, 0..4)).prop_map(|(pos, seed, chunks)| {
+            Region {
+                pos,
+                settings: TerrainSettings {
+                    seed,
+                    ..Default::default()
+                },
+                chunks,
+            }
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn regions_round_trip_through_json(region in region()) {
+            let mut json = vec![];
+            region.to_json_writer(&mut json).unwrap();
+
+            prop_assert_eq!(Region::from_json_reader(json.as_slice()).unwrap(), region);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use super::{Block, Region, SaveChunk, SaveChunkFormat};
+use crate::{
+    config::WorldConfig,
+    minecraft::world_gen::chunk_worker::{FBMSettings, TerrainSettings},
+};
+
+/// Water height of the generator before it was configurable.
+const LEGACY_WATER_HEIGHT: i32 = 120;
+
+/// Layout of the region files written before chunks had a
+/// [`SaveChunkFormat`], plain bincode without a header or compression.
+#[derive(Debug, serde::Deserialize)]
+pub struct LegacyRegion {
+    pos: (i64, i64),
+    settings: LegacyTerrainSettings,
+    chunks: Vec<LegacySaveChunk>,
+}
+
+/// A chunk stored as every block with its position, the only format before
+/// [`SaveChunkFormat`] existed.
+#[derive(Debug, serde::Deserialize)]
+pub struct LegacySaveChunk {
+    pos: (i32, i32),
+    blocks: Vec<Block>,
+}
+
+/// The terrain settings before trees, ores, caves and biomes were generated.
+#[derive(Debug, serde::Deserialize)]
+struct LegacyTerrainSettings {
+    enable_gravel: bool,
+    gravel_height: FBMSettings,
+    enable_sand: bool,
+    sand_offset: i32,
+    sand_height: FBMSettings,
+    enable_stone: bool,
+    stone_point_scaleing: f64,
+    enable_grass: bool,
+    enable_water: bool,
+    seed: u32,
+}
+
+impl From<LegacySaveChunk> for SaveChunk {
+    fn from(chunk: LegacySaveChunk) -> Self {
+        SaveChunk {
+            pos: chunk.pos,
+            blocks: SaveChunkFormat::Raw(chunk.blocks),
+        }
+    }
+}
+
+impl From<LegacyTerrainSettings> for TerrainSettings {
+    fn from(settings: LegacyTerrainSettings) -> Self {
+        TerrainSettings {
+            enable_gravel: settings.enable_gravel,
+            gravel_height: settings.gravel_height,
+            enable_sand: settings.enable_sand,
+            sand_offset: settings.sand_offset,
+            sand_height: settings.sand_height,
+            enable_stone: settings.enable_stone,
+            stone_point_scaleing: settings.stone_point_scaleing,
+            enable_grass: settings.enable_grass,
+            enable_water: settings.enable_water,
+            seed: settings.seed,
+            // Features the old generator didn't have.
+            enable_trees: false,
+            enable_ores: false,
+            enable_caves: false,
+            water_height: LEGACY_WATER_HEIGHT,
+            ..TerrainSettings::from_config(&WorldConfig::default())
+        }
+    }
+}
+
+impl From<LegacyRegion> for Region {
+    fn from(region: LegacyRegion) -> Self {
+        Region {
+            pos: region.pos,
+            settings: region.settings.into(),
+            chunks: region.chunks.into_iter().map(SaveChunk::from).collect(),
+        }
+    }
+}
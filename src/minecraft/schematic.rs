@@ -0,0 +1,423 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Result};
+use bevy::prelude::Plugin;
+use flate2::read::GzDecoder;
+use valence::{prelude::*, view::ChunkPos};
+
+use super::{
+    building::FILL_BLOCKS_PER_TICK,
+    command::{Command, CommandEvent, RegisterCommandExt},
+    structure::is_valid_name,
+    world_gen::{Instances, ModifiedChunks},
+};
+use crate::CONFIG;
+
+/// Upper bound of the size of a decompressed schematic per block, a palette
+/// index takes at most 5 bytes.
+const MAX_BYTES_PER_BLOCK: u64 = 5;
+
+/// Room for the palette and the other tags of a decompressed schematic.
+const MAX_HEADER_BYTES: u64 = 1024 * 1024;
+
+pub struct SchematicPlugin;
+
+impl Plugin for SchematicPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_command(
+            Command::new("paste")
+                .with_op_level(3)
+                .with_usage("paste <name> | paste cancel")
+                .with_description("Pastes world/schematics/<name>.schem at your position."),
+        )
+        .add_system(paste)
+        .add_system(process_paste_operations.after(paste));
+    }
+}
+
+/// Blocks of a [Sponge Schematic v2](https://github.com/SpongePowered/Schematic-Specification/blob/master/versions/schematic-2.md)
+/// file.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Schematic {
+    /// Size along the x, y and z axis.
+    pub size: [u32; 3],
+    /// Block states, ordered x first, then z, then y.
+    pub blocks: Vec<BlockState>,
+}
+
+/// The parts of the NBT structure that are needed to place the blocks.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SpongeSchematic {
+    version: i32,
+    width: i16,
+    height: i16,
+    length: i16,
+    palette: HashMap<String, i32>,
+    block_data: fastnbt::ByteArray,
+}
+
+/// Loads a gzip compressed Sponge Schematic v2 file.
+///
+/// # Errors
+/// - the file can't be read or isn't a valid schematic
+/// - the schematic contains more than `max_schematic_volume` blocks
+pub fn load_schem(path: &Path) -> Result<Schematic> {
+    let max_volume = CONFIG.world.max_schematic_volume;
+    let buf = decompress(std::fs::File::open(path)?, max_volume)?;
+
+    let schem: SpongeSchematic = fastnbt::from_bytes(&buf)?;
+    if schem.version != 2 {
+        bail!(
+            "Unsupported schematic version {}, only version 2 is supported.",
+            schem.version
+        );
+    }
+
+    // The dimensions are unsigned shorts stored as signed ones.
+    let size = [schem.width, schem.height, schem.length].map(|s| u32::from(s as u16));
+    let volume = size.iter().map(|s| u64::from(*s)).product::<u64>();
+    if volume > max_volume {
+        bail!("Schematic contains {volume} blocks, the maximum is {max_volume}.");
+    }
+
+    let mut palette = HashMap::with_capacity(schem.palette.len());
+    for (name, index) in schem.palette {
+        let state = parse_block_state(&name).unwrap_or_else(|| {
+            warn!(target: "minecraft::schematic", "Unknown block {name}, replacing it with air");
+            BlockState::AIR
+        });
+        palette.insert(index, state);
+    }
+
+    let indices = decode_varints(schem.block_data.iter().map(|b| *b as u8))?;
+    if indices.len() as u64 != volume {
+        bail!(
+            "Schematic contains {} blocks, but its size is {volume}.",
+            indices.len()
+        );
+    }
+
+    let blocks = indices
+        .into_iter()
+        .map(|i| palette.get(&i).copied().unwrap_or(BlockState::AIR))
+        .collect();
+
+    Ok(Schematic { size, blocks })
+}
+
+/// Decompresses a gzip compressed schematic, but stops before the result gets
+/// larger than a schematic of `max_volume` blocks can be. This way the volume
+/// limit also holds for files that claim a small size.
+///
+/// # Errors
+/// - the data isn't gzip compressed
+/// - the decompressed data is too large
+fn decompress(reader: impl Read, max_volume: u64) -> Result<Vec<u8>> {
+    let limit = max_volume
+        .saturating_mul(MAX_BYTES_PER_BLOCK)
+        .saturating_add(MAX_HEADER_BYTES);
+
+    let mut buf = vec![];
+    GzDecoder::new(reader)
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut buf)
+        .context("Schematic is not gzip compressed")?;
+
+    if buf.len() as u64 > limit {
+        bail!("Schematic contains more than {max_volume} blocks.");
+    }
+
+    Ok(buf)
+}
+
+/// Path of the schematic with the given name, schematics are only read from
+/// the `world/schematics` directory. The name has to be checked with
+/// [`is_valid_name`] first.
+///
+/// # Errors
+/// - the current directory can't be accessed
+pub fn schematic_path(name: &str) -> Result<PathBuf> {
+    let base_path = std::env::current_dir()?.join("world").join("schematics");
+    Ok(base_path.join(format!("{name}.schem")))
+}
+
+/// Places the schematic with its lowest corner at `origin` at once. Returns
+/// the number of blocks that weren't placed because their chunk isn't loaded.
+pub fn paste_schematic(instance: &mut Instance, schematic: &Schematic, origin: BlockPos) -> usize {
+    let mut operation = PasteOperation::new(String::new(), schematic.clone(), origin);
+    operation.step(instance, schematic.blocks.len());
+    operation.skipped
+}
+
+/// Pastes a schematic, spread over multiple ticks like a
+/// [`FillOperation`](super::building::FillOperation). Attached to the player
+/// that started it.
+#[derive(Component, Debug)]
+pub struct PasteOperation {
+    pub name: String,
+    pub schematic: Schematic,
+    /// Lowest corner of the schematic.
+    pub origin: BlockPos,
+    /// Index of the next block to place.
+    pub next: usize,
+    /// Number of blocks that weren't placed because their chunk isn't loaded.
+    pub skipped: usize,
+}
+
+impl PasteOperation {
+    #[must_use]
+    pub fn new(name: String, schematic: Schematic, origin: BlockPos) -> Self {
+        Self {
+            name,
+            schematic,
+            origin,
+            next: 0,
+            skipped: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn total(&self) -> usize { self.schematic.blocks.len() }
+
+    #[must_use]
+    pub fn is_done(&self) -> bool { self.next >= self.total() }
+
+    /// Position of the block with the given index.
+    #[must_use]
+    pub fn pos(&self, index: usize) -> BlockPos {
+        let [size_x, _, size_z] = self.schematic.size.map(|s| s as usize);
+
+        BlockPos::new(
+            self.origin.x + (index % size_x) as i32,
+            self.origin.y + (index / (size_x * size_z)) as i32,
+            self.origin.z + (index / size_x % size_z) as i32,
+        )
+    }
+
+    /// Highest corner of the schematic.
+    #[must_use]
+    pub fn max(&self) -> BlockPos {
+        let [x, y, z] = self.schematic.size.map(|s| s as i32);
+        BlockPos::new(
+            self.origin.x + x - 1,
+            self.origin.y + y - 1,
+            self.origin.z + z - 1,
+        )
+    }
+
+    /// Places up to `max_blocks` blocks, blocks in chunks that aren't loaded
+    /// are counted in `skipped`. Returns the number of handled blocks.
+    pub fn step(&mut self, instance: &mut Instance, max_blocks: usize) -> usize {
+        let end = self.next.saturating_add(max_blocks).min(self.total());
+        let handled = end - self.next;
+
+        for index in self.next..end {
+            let pos = self.pos(index);
+            let chunk = ChunkPos::new(pos.x.div_euclid(16), pos.z.div_euclid(16));
+            if instance.chunk(chunk).is_none() {
+                self.skipped += 1;
+                continue;
+            }
+
+            instance.set_block(pos, self.schematic.blocks[index]);
+        }
+        self.next = end;
+
+        handled
+    }
+}
+
+/// Parses a block state like `minecraft:oak_log[axis=y]`.
+#[must_use]
+pub fn parse_block_state(s: &str) -> Option<BlockState> {
+    let (name, props) = match s.split_once('[') {
+        Some((name, props)) => (name, props.strip_suffix(']')?),
+        None => (s, ""),
+    };
+
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    let mut state = BlockKind::from_str(name)?.to_state();
+
+    for prop in props.split(',').filter(|p| !p.is_empty()) {
+        let (key, value) = prop.split_once('=')?;
+        state = state.set(PropName::from_str(key)?, PropValue::from_str(value)?);
+    }
+
+    Some(state)
+}
+
+/// Decodes the palette indices of the `BlockData`, which are stored as VarInts.
+fn decode_varints(bytes: impl Iterator<Item = u8>) -> Result<Vec<i32>> {
+    let mut values = vec![];
+    let (mut value, mut shift) = (0i32, 0);
+
+    for byte in bytes {
+        value |= i32::from(byte & 0x7f) << shift;
+
+        if byte & 0x80 == 0 {
+            values.push(value);
+            (value, shift) = (0, 0);
+        } else {
+            shift += 7;
+            if shift >= 32 {
+                bail!("VarInt in the block data is too long.");
+            }
+        }
+    }
+
+    if shift != 0 {
+        bail!("Block data ends in the middle of a VarInt.");
+    }
+
+    Ok(values)
+}
+
+fn paste(
+    mut commands: Commands,
+    mut clients: Query<(&mut Client, Option<&PasteOperation>)>,
+    mut events: EventReader<CommandEvent>,
+) {
+    for event in events.iter().filter(|e| e.is("paste")) {
+        let Ok((mut client, running)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        if event.arg(0) == Some("cancel") {
+            if let Some(operation) = running {
+                commands.entity(event.client).remove::<PasteOperation>();
+                client.send_message(
+                    format!(
+                        "Cancelled pasting {} after {}/{} blocks.",
+                        operation.name,
+                        operation.next,
+                        operation.total()
+                    )
+                    .italic(),
+                );
+            } else {
+                client.send_message("No paste operation is running.".italic());
+            }
+            continue;
+        }
+
+        if running.is_some() {
+            client.send_message(
+                "A paste operation is already running, cancel it with paste cancel."
+                    .color(Color::RED),
+            );
+            continue;
+        }
+
+        let Some(name) = event.arg(0).filter(|n| is_valid_name(n)) else {
+            client.send_message(
+                "Usage: paste <name>, names can only contain letters, digits, _ and -."
+                    .color(Color::RED),
+            );
+            continue;
+        };
+
+        let schematic = match schematic_path(name).and_then(|path| load_schem(&path)) {
+            Ok(schematic) => schematic,
+            Err(e) => {
+                client.send_message(format!("Unable to load {name}: {e}").color(Color::RED));
+                continue;
+            }
+        };
+
+        let p = client.position();
+        let origin = BlockPos::new(p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32);
+        let [x, y, z] = schematic.size;
+
+        info!(
+            target: "minecraft::schematic",
+            "{} is pasting {name} ({x}x{y}x{z}) at {} {} {}",
+            client.username().to_string(),
+            origin.x,
+            origin.y,
+            origin.z
+        );
+        client.send_message(format!("Pasting {name} ({x}x{y}x{z})...").italic());
+        commands.entity(event.client).insert(PasteOperation::new(
+            name.to_string(),
+            schematic,
+            origin,
+        ));
+    }
+}
+
+fn process_paste_operations(
+    mut commands: Commands,
+    mut clients: Query<(Entity, &mut Client, &mut PasteOperation)>,
+    mut instances: Query<&mut Instance>,
+    instances_list: Res<Instances>,
+    mut modified: ResMut<ModifiedChunks>,
+) {
+    let mut instance = instances.get_mut(instances_list.terrain()).unwrap();
+
+    for (entity, mut client, mut operation) in &mut clients {
+        operation.step(&mut instance, FILL_BLOCKS_PER_TICK as usize);
+        modified.mark_area(operation.origin, operation.max());
+
+        if operation.is_done() {
+            let [x, y, z] = operation.schematic.size;
+            client.send_message(format!("Pasted {} ({x}x{y}x{z}).", operation.name).italic());
+            if operation.skipped > 0 {
+                client.send_message(
+                    format!(
+                        "{} blocks in chunks that aren't loaded were skipped.",
+                        operation.skipped
+                    )
+                    .color(Color::YELLOW),
+                );
+            }
+            commands.entity(entity).remove::<PasteOperation>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decompression_stops_at_the_volume_limit() {
+        let limit = MAX_BYTES_PER_BLOCK + MAX_HEADER_BYTES;
+
+        let small = gzip(&vec![0; limit as usize]);
+        assert_eq!(decompress(small.as_slice(), 1).unwrap().len() as u64, limit);
+
+        let large = gzip(&vec![0; limit as usize + 1]);
+        assert!(decompress(large.as_slice(), 1).is_err());
+    }
+
+    #[test]
+    fn paste_positions_are_ordered_x_z_y() {
+        let schematic = Schematic {
+            size: [2, 2, 3],
+            blocks: vec![BlockState::STONE; 12],
+        };
+        let operation = PasteOperation::new("test".into(), schematic, BlockPos::new(10, 64, -5));
+
+        assert_eq!(operation.pos(0), BlockPos::new(10, 64, -5));
+        assert_eq!(operation.pos(1), BlockPos::new(11, 64, -5));
+        assert_eq!(operation.pos(2), BlockPos::new(10, 64, -4));
+        assert_eq!(operation.pos(6), BlockPos::new(10, 65, -5));
+        assert_eq!(operation.max(), BlockPos::new(11, 65, -3));
+    }
+}
@@ -0,0 +1,343 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use bevy::prelude::Plugin;
+use valence::prelude::*;
+
+use super::{
+    command::{Command, CommandEvent, RegisterCommandExt},
+    world_gen::{find_spawn, Instances},
+    SpawnPos,
+};
+use crate::{
+    util::{read_json, write_json},
+    CONFIG,
+};
+
+const SPAWN_USAGE: &str = "spawn | spawn set | spawn reset";
+
+pub struct SpawnPlugin;
+
+impl Plugin for SpawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_command(
+            Command::new("spawn")
+                .with_op_level(3)
+                .with_usage(SPAWN_USAGE)
+                .with_description(
+                    "Shows the spawn point, moves it to your position or detects it again.",
+                ),
+        )
+        .add_system(spawn_command);
+    }
+}
+
+/// A spawn point set with `spawn set`, stored in `world/spawn.json`. It takes
+/// precedence over the configured `spawn` and the detected one.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct SpawnConfig {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl From<DVec3> for SpawnConfig {
+    fn from(pos: DVec3) -> Self {
+        Self {
+            x: pos.x,
+            y: pos.y,
+            z: pos.z,
+        }
+    }
+}
+
+impl From<SpawnConfig> for DVec3 {
+    fn from(spawn: SpawnConfig) -> Self { DVec3::new(spawn.x, spawn.y, spawn.z) }
+}
+
+impl SpawnConfig {
+    /// The saved spawn point, `None` if there is none or it can't be read.
+    #[must_use]
+    pub fn load() -> Option<Self> { Self::load_from(&spawn_path().ok()?) }
+
+    fn load_from(path: &Path) -> Option<Self> {
+        match read_json(path) {
+            Ok(spawn) => spawn,
+            Err(e) => {
+                warn!(target: "minecraft::spawn", "Unable to load the spawn point: {e}");
+                None
+            }
+        }
+    }
+
+    /// # Errors
+    /// - the spawn point can't be written
+    pub fn save(&self) -> Result<()> { self.save_to(&spawn_path()?) }
+
+    fn save_to(&self, path: &Path) -> Result<()> { write_json(path, self) }
+
+    /// Removes the saved spawn point, returns `false` if there was none.
+    ///
+    /// # Errors
+    /// - the file can't be removed
+    pub fn remove() -> Result<bool> { Self::remove_from(&spawn_path()?) }
+
+    fn remove_from(path: &Path) -> Result<bool> {
+        if !path.exists() {
+            return Ok(false);
+        }
+
+        std::fs::remove_file(path)?;
+        Ok(true)
+    }
+}
+
+/// # Errors
+/// - the current directory can't be accessed
+pub fn spawn_path() -> Result<PathBuf> {
+    Ok(std::env::current_dir()?.join("world").join("spawn.json"))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SpawnAction {
+    Show,
+    Set,
+    Reset,
+}
+
+/// # Errors
+/// - the arguments don't match [`SPAWN_USAGE`]
+fn parse_spawn_args(args: &[String]) -> Result<SpawnAction, String> {
+    match args {
+        [] => Ok(SpawnAction::Show),
+        [arg] if arg == "set" => Ok(SpawnAction::Set),
+        [arg] if arg == "reset" => Ok(SpawnAction::Reset),
+        _ => Err(format!("Usage: {SPAWN_USAGE}")),
+    }
+}
+
+/// Saves `pos` as the spawn point at `path`.
+///
+/// # Errors
+/// - the caller isn't in the terrain instance
+/// - the spawn point can't be saved
+fn set_spawn(pos: DVec3, in_terrain: bool, path: &Path) -> Result<(), String> {
+    if !in_terrain {
+        return Err("The spawn has to be in the terrain instance.".to_string());
+    }
+
+    SpawnConfig::from(pos).save_to(path).map_err(|e| {
+        error!(target: "minecraft::spawn", "Unable to save the spawn point: {e}");
+        format!("Unable to save the spawn point: {e}")
+    })
+}
+
+/// Removes the spawn point saved at `path`.
+///
+/// # Errors
+/// - the spawn point can't be removed
+fn reset_spawn(path: &Path) -> Result<(), String> {
+    SpawnConfig::remove_from(path).map(drop).map_err(|e| {
+        error!(target: "minecraft::spawn", "Unable to remove the spawn point: {e}");
+        format!("Unable to remove the spawn point: {e}")
+    })
+}
+
+fn spawn_command(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    mut spawn: ResMut<SpawnPos>,
+    instances: Query<&Instance>,
+    instances_list: Res<Instances>,
+) {
+    for event in events.iter().filter(|e| e.is("spawn")) {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+        let operator = client.username().to_string();
+
+        let action = match parse_spawn_args(&event.args) {
+            Ok(action) => action,
+            Err(e) => {
+                client.send_message(e.color(Color::RED));
+                continue;
+            }
+        };
+
+        let new_spawn = match action {
+            SpawnAction::Show => {
+                let pos = spawn.0;
+                client.send_message(
+                    format!("The spawn is at {:.1} {:.1} {:.1}.", pos.x, pos.y, pos.z).italic(),
+                );
+                continue;
+            }
+            SpawnAction::Set => {
+                let pos = client.position();
+                let in_terrain = client.instance() == instances_list.terrain();
+                let result = spawn_path()
+                    .map_err(|e| format!("Unable to save the spawn point: {e}"))
+                    .and_then(|path| set_spawn(pos, in_terrain, &path));
+                if let Err(e) = result {
+                    client.send_message(e.color(Color::RED));
+                    continue;
+                }
+
+                pos
+            }
+            SpawnAction::Reset => {
+                let result = spawn_path()
+                    .map_err(|e| format!("Unable to remove the spawn point: {e}"))
+                    .and_then(|path| reset_spawn(&path));
+                if let Err(e) = result {
+                    client.send_message(e.color(Color::RED));
+                    continue;
+                }
+
+                // Detected the same way as on startup.
+                CONFIG
+                    .world
+                    .spawn
+                    .map(|[x, y, z]| DVec3::new(x, y, z))
+                    .or_else(|| {
+                        instances
+                            .get(instances_list.terrain())
+                            .ok()
+                            .and_then(|instance| instance.chunk([0, 0]))
+                            .and_then(find_spawn)
+                    })
+                    .unwrap_or(SpawnPos::default().0)
+            }
+        };
+
+        spawn.0 = new_spawn;
+        info!(target: "minecraft::spawn",
+            "{operator} moved the spawn to {:.1} {:.1} {:.1}", new_spawn.x, new_spawn.y, new_spawn.z
+        );
+        client.send_message(
+            format!(
+                "Moved the spawn to {:.1} {:.1} {:.1}.",
+                new_spawn.x, new_spawn.y, new_spawn.z
+            )
+            .italic(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minecraft::command::CommandRegistry;
+
+    fn spawn_file(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("vp-spawn-{name}-{}", std::process::id()))
+            .join("spawn.json")
+    }
+
+    fn remove_spawn_dir(path: &Path) { std::fs::remove_dir_all(path.parent().unwrap()).unwrap(); }
+
+    fn args(args: &[&str]) -> Vec<String> { args.iter().map(ToString::to_string).collect() }
+
+    #[test]
+    fn spawn_points_round_trip() {
+        let path = spawn_file("round-trip");
+        let pos = DVec3::new(12.5, 80.0, -300.25);
+
+        assert_eq!(SpawnConfig::load_from(&path), None);
+        set_spawn(pos, true, &path).unwrap();
+        let loaded = SpawnConfig::load_from(&path).unwrap();
+        assert_eq!(DVec3::from(loaded), pos);
+
+        // Setting it again overwrites the saved spawn point.
+        set_spawn(DVec3::new(0.5, 64.0, 0.5), true, &path).unwrap();
+        assert_eq!(
+            SpawnConfig::load_from(&path),
+            Some(SpawnConfig {
+                x: 0.5,
+                y: 64.0,
+                z: 0.5
+            })
+        );
+
+        remove_spawn_dir(&path);
+    }
+
+    #[test]
+    fn reset_removes_the_saved_spawn() {
+        let path = spawn_file("reset");
+
+        // Resetting without a saved spawn point isn't an error.
+        assert!(!SpawnConfig::remove_from(&path).unwrap());
+        assert_eq!(reset_spawn(&path), Ok(()));
+
+        set_spawn(DVec3::new(1.0, 2.0, 3.0), true, &path).unwrap();
+        assert_eq!(reset_spawn(&path), Ok(()));
+        assert!(!path.exists());
+        assert_eq!(SpawnConfig::load_from(&path), None);
+
+        remove_spawn_dir(&path);
+    }
+
+    #[test]
+    fn invalid_spawn_files_are_ignored() {
+        let path = spawn_file("invalid");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "{ \"x\": 1.0 }").unwrap();
+
+        assert_eq!(SpawnConfig::load_from(&path), None);
+
+        remove_spawn_dir(&path);
+    }
+
+    #[test]
+    fn spawn_arguments_are_parsed() {
+        assert_eq!(parse_spawn_args(&args(&[])), Ok(SpawnAction::Show));
+        assert_eq!(parse_spawn_args(&args(&["set"])), Ok(SpawnAction::Set));
+        assert_eq!(parse_spawn_args(&args(&["reset"])), Ok(SpawnAction::Reset));
+
+        let usage = Err("Usage: spawn | spawn set | spawn reset".to_string());
+        assert_eq!(parse_spawn_args(&args(&["move"])), usage);
+        assert_eq!(parse_spawn_args(&args(&["set", "0", "64", "0"])), usage);
+        assert_eq!(parse_spawn_args(&args(&["reset", "now"])), usage);
+    }
+
+    #[test]
+    fn spawn_can_only_be_set_in_the_terrain_instance() {
+        let path = spawn_file("instance");
+
+        assert_eq!(
+            set_spawn(DVec3::new(0.0, 64.0, 0.0), false, &path),
+            Err("The spawn has to be in the terrain instance.".to_string())
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn unwritable_spawn_files_are_reported() {
+        let path = spawn_file("unwritable");
+        // The parent of the spawn file is a file, so it can't be created.
+        let blocker = path.parent().unwrap();
+        std::fs::write(blocker, "").unwrap();
+
+        let e = set_spawn(DVec3::new(0.0, 64.0, 0.0), true, &path).unwrap_err();
+        assert!(e.starts_with("Unable to save the spawn point: "), "{e}");
+
+        std::fs::remove_file(blocker).unwrap();
+    }
+
+    #[test]
+    fn spawn_requires_op_level_3() {
+        let mut app = App::new();
+        SpawnPlugin.build(&mut app);
+        let registry = app.world.resource::<CommandRegistry>();
+
+        for line in ["spawn", "spawn set", "spawn reset"] {
+            assert_eq!(
+                registry.parse(line, 2).unwrap_err(),
+                "Not enough permissions to use spawn command."
+            );
+            assert!(registry.parse(line, 3).is_ok());
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use bevy::prelude::Plugin;
+use valence::prelude::*;
+
+use super::{
+    command::{Command, CommandEvent, RegisterCommandExt},
+    save::codec,
+    world_gen::Instances,
+};
+use crate::CONFIG;
+
+/// Maximum number of blocks a structure can contain.
+pub const MAX_STRUCTURE_VOLUME: u64 = 64 * 64 * 64;
+
+pub struct StructurePlugin;
+
+impl Plugin for StructurePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_command(Command::new("pos1"))
+            .register_command(Command::new("pos2"))
+            .register_command(
+                Command::new("export")
+                    .with_op_level(2)
+                    .with_usage("export <name>"),
+            )
+            .add_system(select)
+            .add_system(export);
+    }
+}
+
+/// Corners of the area a player selected with `pos1` and `pos2`.
+#[derive(Component, Default, Debug)]
+pub struct Selection {
+    pub pos1: Option<BlockPos>,
+    pub pos2: Option<BlockPos>,
+}
+
+impl Selection {
+    /// Lowest and highest corner of the selection, if both corners are set.
+    #[must_use]
+    pub fn bounds(&self) -> Option<(BlockPos, BlockPos)> {
+        let (a, b) = (self.pos1?, self.pos2?);
+
+        Some((
+            BlockPos::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            BlockPos::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        ))
+    }
+}
+
+/// Blocks of a cuboid area, stored in `world/structures/<name>.structure`.
+#[derive(PartialEq, Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub struct Structure {
+    /// Size along the x, y and z axis.
+    pub size: [u32; 3],
+    /// Raw block states, ordered x first, then z, then y.
+    pub blocks: Vec<u16>,
+}
+
+impl Structure {
+    /// Copies the blocks between the two corners (inclusive) out of the
+    /// instance.
+    ///
+    /// # Errors
+    /// - the area is bigger than [`MAX_STRUCTURE_VOLUME`]
+    /// - a block in the area is not loaded
+    pub fn capture(instance: &Instance, min: BlockPos, max: BlockPos) -> Result<Self> {
+        Self::capture_with(min, max, |pos| {
+            instance.block(pos).map(|block| block.state())
+        })
+    }
+
+    /// Like [`capture`](Self::capture), but reads the blocks with `block_at`,
+    /// which returns `None` for blocks that aren't loaded.
+    ///
+    /// # Errors
+    /// - the area is bigger than [`MAX_STRUCTURE_VOLUME`]
+    /// - a block in the area is not loaded
+    pub fn capture_with(
+        min: BlockPos,
+        max: BlockPos,
+        block_at: impl Fn(BlockPos) -> Option<BlockState>,
+    ) -> Result<Self> {
+        let size = [
+            (max.x - min.x + 1) as u32,
+            (max.y - min.y + 1) as u32,
+            (max.z - min.z + 1) as u32,
+        ];
+        let volume = size.iter().map(|s| u64::from(*s)).product::<u64>();
+
+        if volume > MAX_STRUCTURE_VOLUME {
+            bail!("Selection contains {volume} blocks, the maximum is {MAX_STRUCTURE_VOLUME}.");
+        }
+
+        let mut blocks = Vec::with_capacity(volume as usize);
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                for x in min.x..=max.x {
+                    let Some(block) = block_at(BlockPos::new(x, y, z)) else {
+                        bail!("Block at {x} {y} {z} is not loaded.");
+                    };
+                    blocks.push(block.to_raw());
+                }
+            }
+        }
+
+        Ok(Self { size, blocks })
+    }
+
+    /// Places the structure with its lowest corner at `origin`.
+    pub fn paste(&self, instance: &mut Instance, origin: BlockPos) {
+        self.paste_with(origin, |pos, state| {
+            instance.set_block(pos, state);
+        });
+    }
+
+    /// Like [`paste`](Self::paste), but places the blocks with `set_block`.
+    pub fn paste_with(&self, origin: BlockPos, mut set_block: impl FnMut(BlockPos, BlockState)) {
+        let [size_x, _, size_z] = self.size.map(|s| s as usize);
+
+        for (i, raw) in self.blocks.iter().enumerate() {
+            let Some(state) = BlockState::from_raw(*raw) else {
+                continue;
+            };
+
+            let x = origin.x + (i % size_x) as i32;
+            let z = origin.z + (i / size_x % size_z) as i32;
+            let y = origin.y + (i / (size_x * size_z)) as i32;
+
+            set_block(BlockPos::new(x, y, z), state);
+        }
+    }
+}
+
+/// Only letters, digits, `_` and `-` are allowed, so names can't point outside
+/// of the structure directory and are a single command argument.
+#[must_use]
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// # Errors
+/// - the current directory can't be accessed
+pub fn structure_path(name: &str) -> Result<PathBuf> {
+    let base_path = std::env::current_dir()?.join("world").join("structures");
+    Ok(base_path.join(format!("{name}.structure")))
+}
+
+/// # Errors
+/// - the structure file can't be written
+pub fn write(name: &str, structure: &Structure) -> Result<()> {
+    let path = structure_path(name)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::write(path, codec::encode(structure, CONFIG.world.compression)?)?;
+
+    Ok(())
+}
+
+/// # Errors
+/// - the structure file doesn't exist or can't be decoded
+pub fn load(name: &str) -> Result<Structure> {
+    let buf = std::fs::read(structure_path(name)?)?;
+    codec::decode(&buf)
+}
+
+fn select(
+    mut commands: Commands,
+    mut clients: Query<(&mut Client, Option<&mut Selection>)>,
+    mut events: EventReader<CommandEvent>,
+) {
+    for event in events.iter().filter(|e| e.is("pos1") || e.is("pos2")) {
+        let Ok((mut client, selection)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let p = client.position();
+        let pos = BlockPos::new(p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32);
+
+        let set_corner = |selection: &mut Selection| {
+            if event.is("pos1") {
+                selection.pos1 = Some(pos);
+            } else {
+                selection.pos2 = Some(pos);
+            }
+        };
+
+        match selection {
+            Some(mut selection) => set_corner(&mut selection),
+            None => {
+                let mut selection = Selection::default();
+                set_corner(&mut selection);
+                commands.entity(event.client).insert(selection);
+            }
+        }
+
+        client
+            .send_message(format!("Set {} to {} {} {}.", event.name, pos.x, pos.y, pos.z).italic());
+    }
+}
+
+fn export(
+    mut clients: Query<(&mut Client, Option<&Selection>)>,
+    mut events: EventReader<CommandEvent>,
+    instances: Query<&Instance>,
+    instances_list: Res<Instances>,
+) {
+    let instance = instances.get(instances_list.terrain()).unwrap();
+
+    for event in events.iter().filter(|e| e.is("export")) {
+        let Ok((mut client, selection)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let Some(name) = event.arg(0).filter(|n| is_valid_name(n)) else {
+            client.send_message(
+                "Usage: export <name>, names can only contain letters, digits, _ and -."
+                    .color(Color::RED),
+            );
+            continue;
+        };
+
+        let Some((min, max)) = selection.and_then(Selection::bounds) else {
+            client.send_message("Select an area with pos1 and pos2 first.".color(Color::RED));
+            continue;
+        };
+
+        match Structure::capture(instance, min, max).and_then(|s| write(name, &s).map(|_| s)) {
+            Ok(structure) => {
+                let [x, y, z] = structure.size;
+                info!(
+                    target: "minecraft::structure",
+                    "{} exported {name} ({x}x{y}x{z})",
+                    client.username().to_string()
+                );
+                client.send_message(format!("Exported {name} ({x}x{y}x{z}).").italic());
+            }
+            Err(e) => client.send_message(e.to_string().color(Color::RED)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Blocks of a small world, positions that aren't in the map aren't loaded.
+    fn world() -> HashMap<BlockPos, BlockState> {
+        let mut world = HashMap::new();
+        for (x, y, z) in itertools::iproduct!(0..4, 60..64, 0..4) {
+            let block = if y == 60 {
+                BlockState::STONE
+            } else if x == z {
+                BlockState::OAK_LOG
+            } else {
+                BlockState::AIR
+            };
+            world.insert(BlockPos::new(x, y, z), block);
+        }
+        world
+    }
+
+    #[test]
+    fn captured_structures_paste_to_the_same_blocks() {
+        let world = world();
+        let structure =
+            Structure::capture_with(BlockPos::new(1, 60, 0), BlockPos::new(3, 62, 2), |pos| {
+                world.get(&pos).copied()
+            })
+            .unwrap();
+        assert_eq!(structure.size, [3, 3, 3]);
+        assert_eq!(structure.blocks.len(), 27);
+
+        let mut pasted = HashMap::new();
+        structure.paste_with(BlockPos::new(101, 10, -50), |pos, state| {
+            pasted.insert(pos, state);
+        });
+
+        assert_eq!(pasted.len(), 27);
+        for (pos, state) in pasted {
+            let source = BlockPos::new(pos.x - 100, pos.y + 50, pos.z + 50);
+            assert_eq!(world[&source], state, "{source:?}");
+        }
+    }
+
+    #[test]
+    fn blocks_are_ordered_x_z_y() {
+        let world = world();
+        let structure =
+            Structure::capture_with(BlockPos::new(0, 60, 0), BlockPos::new(1, 61, 1), |pos| {
+                world.get(&pos).copied()
+            })
+            .unwrap();
+
+        let log = BlockState::OAK_LOG.to_raw();
+        let stone = BlockState::STONE.to_raw();
+        let air = BlockState::AIR.to_raw();
+        assert_eq!(
+            structure.blocks,
+            vec![stone, stone, stone, stone, log, air, air, log]
+        );
+    }
+
+    #[test]
+    fn unloaded_blocks_are_not_captured() {
+        let world = world();
+        let error =
+            Structure::capture_with(BlockPos::new(0, 60, 0), BlockPos::new(4, 60, 0), |pos| {
+                world.get(&pos).copied()
+            })
+            .unwrap_err();
+
+        assert_eq!(error.to_string(), "Block at 4 60 0 is not loaded.");
+    }
+
+    #[test]
+    fn structures_are_limited_in_size() {
+        let error =
+            Structure::capture_with(BlockPos::new(0, 0, 0), BlockPos::new(64, 63, 63), |_| {
+                Some(BlockState::AIR)
+            })
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            format!("Selection contains 266240 blocks, the maximum is {MAX_STRUCTURE_VOLUME}.")
+        );
+    }
+
+    #[test]
+    fn structures_round_trip_through_the_codec() {
+        let structure = Structure {
+            size: [2, 1, 1],
+            blocks: vec![BlockState::STONE.to_raw(), BlockState::AIR.to_raw()],
+        };
+        let encoded = codec::encode(&structure, CONFIG.world.compression).unwrap();
+
+        assert_eq!(codec::decode::<Structure>(&encoded).unwrap(), structure);
+    }
+
+    #[test]
+    fn selection_bounds_need_both_corners() {
+        let mut selection = Selection {
+            pos1: Some(BlockPos::new(5, 70, -3)),
+            pos2: None,
+        };
+        assert_eq!(selection.bounds(), None);
+
+        selection.pos2 = Some(BlockPos::new(-1, 80, -10));
+        assert_eq!(
+            selection.bounds(),
+            Some((BlockPos::new(-1, 70, -10), BlockPos::new(5, 80, -3)))
+        );
+    }
+
+    #[test]
+    fn names_cannot_leave_the_structure_directory() {
+        assert!(is_valid_name("house_2-b"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("../house"));
+        assert!(!is_valid_name("a/b"));
+        assert!(!is_valid_name("house.structure"));
+        assert!(!is_valid_name("my house"));
+    }
+}
@@ -0,0 +1,363 @@
+use bevy::prelude::Plugin;
+use valence::{prelude::*, protocol::packet::s2c::play::WorldTimeUpdateS2c};
+
+use super::command::{Command, Effect, RegisterCommandExt};
+
+/// Number of ticks in a full minecraft day.
+pub const DAY_LENGTH: i64 = 24000;
+
+/// How often (in ticks) the current time gets synchronised with the clients.
+const SYNC_INTERVAL: i64 = 20;
+
+const TIME_USAGE: &str = "time <freeze|resume> | time set <day|noon|night|midnight|0..24000> | \
+                          time add <ticks> | time query <daytime|gametime>";
+
+pub struct TimePlugin;
+
+impl Plugin for TimePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldTime>()
+            .register_command(Command::new("time").with_op_level(2).with_usage(TIME_USAGE))
+            .add_system(advance_time.run_if(time_not_frozen))
+            .add_system(sync_time.after(advance_time));
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorldTime {
+    /// Total amount of ticks the world has existed for.
+    pub world_age: i64,
+    /// Current time of the day, in the range `0..DAY_LENGTH`.
+    pub time_of_day: i64,
+    /// If set, the time of day is fixed and [`advance_time`] doesn't run.
+    pub frozen: bool,
+}
+
+impl WorldTime {
+    pub fn freeze(&mut self) { self.frozen = true; }
+
+    pub fn resume(&mut self) { self.frozen = false; }
+
+    pub fn set_time_of_day(&mut self, time_of_day: i64) {
+        self.time_of_day = time_of_day.rem_euclid(DAY_LENGTH);
+    }
+
+    pub fn add(&mut self, ticks: i64) { self.set_time_of_day(self.time_of_day + ticks); }
+
+    #[must_use]
+    pub fn packet(&self) -> WorldTimeUpdateS2c {
+        // A negative time of day tells the client to stop moving the sun.
+        let time_of_day = if self.frozen {
+            -self.time_of_day.max(1)
+        } else {
+            self.time_of_day
+        };
+
+        WorldTimeUpdateS2c {
+            world_age: self.world_age,
+            time_of_day,
+        }
+    }
+}
+
+/// Parses a time of day, either one of the names `day`, `noon`, `night` and
+/// `midnight` or a number of ticks, clamped to `0..=DAY_LENGTH`.
+#[must_use]
+pub fn parse_time_of_day(arg: &str) -> Option<i64> {
+    match arg {
+        "day" => Some(1000),
+        "noon" => Some(6000),
+        "night" => Some(13000),
+        "midnight" => Some(18000),
+        _ => arg
+            .parse::<i64>()
+            .ok()
+            .map(|ticks| ticks.clamp(0, DAY_LENGTH) % DAY_LENGTH),
+    }
+}
+
+fn time_not_frozen(time: Res<WorldTime>) -> bool { !time.frozen }
+
+fn advance_time(mut time: ResMut<WorldTime>) {
+    time.world_age += 1;
+    time.time_of_day = (time.time_of_day + 1) % DAY_LENGTH;
+}
+
+fn sync_time(time: Res<WorldTime>, mut clients: Query<&mut Client>) {
+    // While frozen the time only changes through commands, so every change is sent
+    // right away.
+    let send_all = time.is_changed() && (time.frozen || time.world_age % SYNC_INTERVAL == 0);
+
+    for mut client in &mut clients {
+        if send_all || client.is_added() {
+            client.write_packet(&time.packet());
+        }
+    }
+}
+
+/// Runs the `time` command with the given arguments, returns the reply and
+/// whether the time changed.
+pub fn run_time_command(args: &[String], time: &mut WorldTime) -> (String, bool) {
+    let arg = |i: usize| args.get(i).map(String::as_str);
+
+    match (arg(0).unwrap_or_default(), arg(1), args.len()) {
+        ("freeze", None, _) => {
+            time.freeze();
+            ("Time is now frozen.".to_string(), true)
+        }
+        ("resume", None, _) => {
+            time.resume();
+            ("Time is now running.".to_string(), true)
+        }
+        ("set", Some(arg), 2) => match parse_time_of_day(arg) {
+            Some(time_of_day) => {
+                time.set_time_of_day(time_of_day);
+                (format!("Set the time to {time_of_day}."), true)
+            }
+            None => (format!("Invalid time: {arg}"), false),
+        },
+        ("add", Some(arg), 2) => match arg.parse::<i64>() {
+            Ok(ticks) if ticks >= 0 => {
+                time.add(ticks);
+                (format!("Set the time to {}.", time.time_of_day), true)
+            }
+            _ => (format!("Invalid number of ticks: {arg}"), false),
+        },
+        ("query", Some("daytime"), 2) => (format!("The time is {}.", time.time_of_day), false),
+        ("query", Some("gametime"), 2) => (format!("The world age is {}.", time.world_age), false),
+        _ => (format!("Usage: {TIME_USAGE}"), false),
+    }
+}
+
+/// The effects of the `time` command, applied by the builtin commands.
+#[must_use]
+pub fn time_effects(args: &[String], time: &WorldTime) -> Vec<Effect> {
+    let mut new_time = time.clone();
+    let (message, changed) = run_time_command(args, &mut new_time);
+
+    let mut effects = vec![];
+    if changed {
+        effects.push(Effect::SetTime(new_time));
+    }
+    effects.push(Effect::SendMessage(message.italic()));
+
+    effects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minecraft::command::CommandRegistry;
+
+    fn time_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<WorldTime>()
+            .add_system(advance_time.run_if(time_not_frozen));
+        app
+    }
+
+    #[test]
+    fn frozen_time_does_not_advance_until_resumed() {
+        let mut app = time_app();
+
+        app.update();
+        app.update();
+        assert_eq!(app.world.resource::<WorldTime>().time_of_day, 2);
+
+        app.world.resource_mut::<WorldTime>().freeze();
+        for _ in 0..5 {
+            app.update();
+        }
+        let time = app.world.resource::<WorldTime>();
+        assert_eq!(time.time_of_day, 2);
+        assert_eq!(time.world_age, 2);
+
+        app.world.resource_mut::<WorldTime>().resume();
+        app.update();
+        let time = app.world.resource::<WorldTime>();
+        assert_eq!(time.time_of_day, 3);
+        assert_eq!(time.world_age, 3);
+    }
+
+    #[test]
+    fn time_of_day_wraps_around_at_day_length() {
+        let mut time = WorldTime::default();
+
+        time.set_time_of_day(DAY_LENGTH);
+        assert_eq!(time.time_of_day, 0);
+        time.set_time_of_day(DAY_LENGTH + 500);
+        assert_eq!(time.time_of_day, 500);
+        time.set_time_of_day(-1);
+        assert_eq!(time.time_of_day, DAY_LENGTH - 1);
+
+        time.set_time_of_day(23_000);
+        time.add(2000);
+        assert_eq!(time.time_of_day, 1000);
+        time.add(3 * DAY_LENGTH);
+        assert_eq!(time.time_of_day, 1000);
+    }
+
+    #[test]
+    fn advancing_wraps_the_time_of_day_but_not_the_world_age() {
+        let mut app = time_app();
+        app.world
+            .resource_mut::<WorldTime>()
+            .set_time_of_day(DAY_LENGTH - 1);
+
+        app.update();
+        let time = app.world.resource::<WorldTime>();
+        assert_eq!(time.time_of_day, 0);
+        assert_eq!(time.world_age, 1);
+    }
+
+    #[test]
+    fn frozen_packet_has_a_negative_time_of_day() {
+        let mut time = WorldTime::default();
+        time.set_time_of_day(6000);
+        assert_eq!(time.packet().time_of_day, 6000);
+
+        time.freeze();
+        assert_eq!(time.packet().time_of_day, -6000);
+
+        // -0 would still move the sun.
+        time.set_time_of_day(0);
+        assert_eq!(time.packet().time_of_day, -1);
+    }
+
+    #[test]
+    fn named_times_are_parsed() {
+        assert_eq!(parse_time_of_day("day"), Some(1000));
+        assert_eq!(parse_time_of_day("noon"), Some(6000));
+        assert_eq!(parse_time_of_day("night"), Some(13000));
+        assert_eq!(parse_time_of_day("midnight"), Some(18000));
+    }
+
+    #[test]
+    fn numeric_times_are_clamped_to_a_day() {
+        assert_eq!(parse_time_of_day("0"), Some(0));
+        assert_eq!(parse_time_of_day("12345"), Some(12345));
+        assert_eq!(parse_time_of_day("24000"), Some(0));
+        assert_eq!(parse_time_of_day("99999"), Some(0));
+        assert_eq!(parse_time_of_day("-5"), Some(0));
+    }
+
+    #[test]
+    fn invalid_times_are_rejected() {
+        assert_eq!(parse_time_of_day(""), None);
+        assert_eq!(parse_time_of_day("Day"), None);
+        assert_eq!(parse_time_of_day("evening"), None);
+        assert_eq!(parse_time_of_day("1.5"), None);
+        assert_eq!(parse_time_of_day("12ticks"), None);
+    }
+
+    fn run(args: &str, time: &mut WorldTime) -> (String, bool) {
+        let args = args
+            .split_whitespace()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        run_time_command(&args, time)
+    }
+
+    #[test]
+    fn time_can_be_frozen_and_resumed() {
+        let mut time = WorldTime::default();
+
+        assert_eq!(
+            run("freeze", &mut time),
+            ("Time is now frozen.".to_string(), true)
+        );
+        assert!(time.frozen);
+        assert_eq!(
+            run("resume", &mut time),
+            ("Time is now running.".to_string(), true)
+        );
+        assert!(!time.frozen);
+    }
+
+    #[test]
+    fn time_set_accepts_names_and_ticks() {
+        let mut time = WorldTime::default();
+
+        assert_eq!(
+            run("set night", &mut time),
+            ("Set the time to 13000.".to_string(), true)
+        );
+        assert_eq!(time.time_of_day, 13000);
+        assert_eq!(
+            run("set 500", &mut time),
+            ("Set the time to 500.".to_string(), true)
+        );
+        assert_eq!(time.time_of_day, 500);
+        assert_eq!(
+            run("set 30000", &mut time),
+            ("Set the time to 0.".to_string(), true)
+        );
+        assert_eq!(time.time_of_day, 0);
+    }
+
+    #[test]
+    fn time_add_wraps_around() {
+        let mut time = WorldTime::default();
+        time.set_time_of_day(23_500);
+
+        assert_eq!(
+            run("add 1000", &mut time),
+            ("Set the time to 500.".to_string(), true)
+        );
+        assert_eq!(time.time_of_day, 500);
+    }
+
+    #[test]
+    fn invalid_time_arguments_are_reported() {
+        let mut time = WorldTime::default();
+        time.set_time_of_day(42);
+        let usage = format!("Usage: {TIME_USAGE}");
+
+        for (args, reply) in [
+            ("set dusk", "Invalid time: dusk".to_string()),
+            ("add -5", "Invalid number of ticks: -5".to_string()),
+            ("add many", "Invalid number of ticks: many".to_string()),
+            ("", usage.clone()),
+            ("set", usage.clone()),
+            ("set day now", usage.clone()),
+            ("freeze now", usage.clone()),
+            ("query weather", usage.clone()),
+            ("rewind 5", usage.clone()),
+        ] {
+            assert_eq!(run(args, &mut time), (reply, false), "time {args}");
+        }
+        assert_eq!(time.time_of_day, 42);
+        assert!(!time.frozen);
+    }
+
+    #[test]
+    fn time_can_be_queried() {
+        let mut time = WorldTime {
+            world_age: 123_456,
+            time_of_day: 6000,
+            frozen: false,
+        };
+
+        assert_eq!(
+            run("query daytime", &mut time),
+            ("The time is 6000.".to_string(), false)
+        );
+        assert_eq!(
+            run("query gametime", &mut time),
+            ("The world age is 123456.".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn time_requires_op_level_2() {
+        let mut app = App::new();
+        TimePlugin.build(&mut app);
+        let registry = app.world.resource::<CommandRegistry>();
+
+        assert_eq!(
+            registry.parse("time set day", 1).unwrap_err(),
+            "Not enough permissions to use time command."
+        );
+        assert!(registry.parse("time set day", 2).is_ok());
+    }
+}
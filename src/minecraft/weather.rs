@@ -0,0 +1,282 @@
+use bevy::prelude::Plugin;
+use rand::{seq::IteratorRandom, Rng};
+use valence::{
+    prelude::*,
+    protocol::packet::s2c::play::{game_state_change::GameEventKind, GameStateChangeS2c},
+};
+
+use super::command::{Command, Effect, RegisterCommandExt};
+
+/// Maximum number of ticks the weather can be set for.
+pub const MAX_WEATHER_DURATION: u32 = 24000;
+
+/// Chance per tick that lightning strikes near a random player during a
+/// thunderstorm.
+const LIGHTNING_CHANCE: f64 = 1.0 / 200.0;
+
+/// Ticks until a lightning bolt is removed again.
+const LIGHTNING_TICKS: u32 = 20;
+
+const WEATHER_USAGE: &str = "weather <clear|rain|thunder> [duration]";
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Weather>()
+            .init_resource::<WeatherTimer>()
+            .register_command(
+                Command::new("weather")
+                    .with_op_level(2)
+                    .with_usage(WEATHER_USAGE)
+                    .with_description(
+                        "Changes the weather, it clears up again after duration ticks (at most \
+                         24000) if given.",
+                    ),
+            )
+            .add_system(tick_weather_timer)
+            .add_system(sync_weather.after(tick_weather_timer))
+            .add_system(thunderstorm.after(sync_weather))
+            .add_system(remove_lightning);
+    }
+}
+
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Weather {
+    #[default]
+    Clear,
+    Rain,
+    Thunder,
+}
+
+impl Weather {
+    #[must_use]
+    pub fn parse(arg: &str) -> Option<Self> {
+        match arg {
+            "clear" => Some(Weather::Clear),
+            "rain" => Some(Weather::Rain),
+            "thunder" => Some(Weather::Thunder),
+            _ => None,
+        }
+    }
+
+    /// Packets that change the weather of a client to this weather.
+    #[must_use]
+    pub fn packets(self) -> [GameStateChangeS2c; 3] {
+        let (kind, rain, thunder) = match self {
+            Weather::Clear => (GameEventKind::EndRaining, 0.0, 0.0),
+            Weather::Rain => (GameEventKind::BeginRaining, 1.0, 0.0),
+            Weather::Thunder => (GameEventKind::BeginRaining, 1.0, 1.0),
+        };
+
+        [
+            GameStateChangeS2c { kind, value: 0.0 },
+            GameStateChangeS2c {
+                kind: GameEventKind::RainLevelChange,
+                value: rain,
+            },
+            GameStateChangeS2c {
+                kind: GameEventKind::ThunderLevelChange,
+                value: thunder,
+            },
+        ]
+    }
+}
+
+/// Ticks until the weather clears up again, `None` if the weather stays.
+#[derive(Resource, Default, Debug)]
+pub struct WeatherTimer(pub Option<u32>);
+
+/// A lightning bolt entity that is removed once `ticks_left` reaches zero.
+#[derive(Component, Debug)]
+pub struct Lightning {
+    pub ticks_left: u32,
+}
+
+/// The effects of the `weather` command, applied by the builtin commands.
+#[must_use]
+pub fn weather_effects(args: &[String]) -> Vec<Effect> {
+    let arg = |i: usize| args.get(i).map(String::as_str);
+
+    let Some(weather) = arg(0).and_then(Weather::parse) else {
+        return vec![Effect::SendMessage(
+            format!("Usage: {WEATHER_USAGE}").color(Color::RED),
+        )];
+    };
+
+    let duration = match arg(1).map(str::parse::<u32>) {
+        None => None,
+        Some(Ok(ticks)) if (1..=MAX_WEATHER_DURATION).contains(&ticks) => Some(ticks),
+        Some(_) => {
+            return vec![Effect::SendMessage(
+                format!("The duration has to be between 1 and {MAX_WEATHER_DURATION} ticks.")
+                    .color(Color::RED),
+            )];
+        }
+    };
+    // Clear weather doesn't clear up.
+    let duration = duration.filter(|_| weather != Weather::Clear);
+
+    let message = match duration {
+        Some(ticks) => format!("Set the weather to {weather:?} for {ticks} ticks."),
+        None => format!("Set the weather to {weather:?}."),
+    };
+
+    vec![
+        Effect::SetWeather { weather, duration },
+        Effect::SendMessage(message.italic()),
+    ]
+}
+
+fn tick_weather_timer(mut timer: ResMut<WeatherTimer>, mut weather: ResMut<Weather>) {
+    let Some(ticks) = timer.0 else {
+        return;
+    };
+
+    if ticks <= 1 {
+        timer.0 = None;
+        *weather = Weather::Clear;
+    } else {
+        timer.0 = Some(ticks - 1);
+    }
+}
+
+fn sync_weather(weather: Res<Weather>, mut clients: Query<&mut Client>) {
+    for mut client in &mut clients {
+        if weather.is_changed() || client.is_added() {
+            for packet in weather.packets() {
+                client.write_packet(&packet);
+            }
+        }
+    }
+}
+
+fn thunderstorm(mut commands: Commands, weather: Res<Weather>, clients: Query<&mut Client>) {
+    if *weather == Weather::Thunder && rand::thread_rng().gen_bool(LIGHTNING_CHANCE) {
+        spawn_lightning(&mut commands, clients.iter());
+    }
+}
+
+/// Strikes a lightning bolt up to 16 blocks away from a random player.
+pub fn spawn_lightning<'a>(commands: &mut Commands, clients: impl Iterator<Item = &'a Client>) {
+    let mut rng = rand::thread_rng();
+    let Some(client) = clients.choose(&mut rng) else {
+        return;
+    };
+
+    let offset = DVec3::new(rng.gen_range(-16.0..16.0), 0.0, rng.gen_range(-16.0..16.0));
+    let mut bolt = McEntity::new(EntityKind::LightningBolt, client.instance());
+    bolt.set_position(client.position() + offset);
+
+    commands.spawn((
+        bolt,
+        Lightning {
+            ticks_left: LIGHTNING_TICKS,
+        },
+    ));
+}
+
+fn remove_lightning(mut commands: Commands, mut bolts: Query<(Entity, &mut Lightning)>) {
+    for (entity, mut lightning) in &mut bolts {
+        lightning.ticks_left = lightning.ticks_left.saturating_sub(1);
+
+        if lightning.ticks_left == 0 {
+            commands.entity(entity).insert(Despawned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minecraft::command::CommandRegistry;
+
+    fn weather(args: &[&str]) -> Vec<Effect> {
+        weather_effects(&args.iter().map(ToString::to_string).collect::<Vec<_>>())
+    }
+
+    fn error(msg: &str) -> Vec<Effect> { vec![Effect::SendMessage(msg.color(Color::RED))] }
+
+    #[test]
+    fn weather_is_set_for_the_duration() {
+        assert_eq!(
+            weather(&["thunder", "24000"]),
+            vec![
+                Effect::SetWeather {
+                    weather: Weather::Thunder,
+                    duration: Some(24000),
+                },
+                Effect::SendMessage("Set the weather to Thunder for 24000 ticks.".italic()),
+            ]
+        );
+        assert_eq!(
+            weather(&["rain"]),
+            vec![
+                Effect::SetWeather {
+                    weather: Weather::Rain,
+                    duration: None,
+                },
+                Effect::SendMessage("Set the weather to Rain.".italic()),
+            ]
+        );
+    }
+
+    #[test]
+    fn clear_weather_has_no_duration() {
+        assert_eq!(
+            weather(&["clear", "100"]),
+            vec![
+                Effect::SetWeather {
+                    weather: Weather::Clear,
+                    duration: None,
+                },
+                Effect::SendMessage("Set the weather to Clear.".italic()),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_weather_arguments_are_rejected() {
+        let usage = error("Usage: weather <clear|rain|thunder> [duration]");
+        assert_eq!(weather(&[]), usage);
+        assert_eq!(weather(&["snow"]), usage);
+        assert_eq!(weather(&["Rain"]), usage);
+
+        for duration in ["0", "24001", "-5", "long"] {
+            assert_eq!(
+                weather(&["rain", duration]),
+                error("The duration has to be between 1 and 24000 ticks."),
+                "{duration}"
+            );
+        }
+    }
+
+    #[test]
+    fn weather_clears_up_when_the_timer_runs_out() {
+        let mut app = App::new();
+        app.insert_resource(Weather::Rain)
+            .insert_resource(WeatherTimer(Some(2)))
+            .add_system(tick_weather_timer);
+
+        app.update();
+        assert_eq!(*app.world.resource::<Weather>(), Weather::Rain);
+        assert_eq!(app.world.resource::<WeatherTimer>().0, Some(1));
+
+        app.update();
+        assert_eq!(*app.world.resource::<Weather>(), Weather::Clear);
+        assert_eq!(app.world.resource::<WeatherTimer>().0, None);
+    }
+
+    #[test]
+    fn weather_requires_op_level_2() {
+        let mut app = App::new();
+        WeatherPlugin.build(&mut app);
+        let registry = app.world.resource::<CommandRegistry>();
+
+        assert_eq!(
+            registry.parse("weather rain", 1).unwrap_err(),
+            "Not enough permissions to use weather command."
+        );
+        assert!(registry.parse("weather rain", 2).is_ok());
+    }
+}
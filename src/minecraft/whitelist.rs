@@ -0,0 +1,368 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use anyhow::Result;
+use bevy::prelude::Plugin;
+use valence::prelude::*;
+
+use super::command::{Command, CommandEvent, RegisterCommandExt};
+use crate::{
+    util::{read_json, write_json, LockResultExt},
+    CONFIG,
+};
+
+const WHITELIST_USAGE: &str = "whitelist add <player> | whitelist remove <player> | whitelist \
+                               list | whitelist on | whitelist off";
+
+pub struct WhitelistPlugin {
+    pub whitelist: Whitelist,
+}
+
+impl Plugin for WhitelistPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.whitelist.clone())
+            .register_command(
+                Command::new("whitelist")
+                    .with_op_level(3)
+                    .with_usage(WHITELIST_USAGE)
+                    .with_description(
+                        "Changes which players are allowed to join while the whitelist is on.",
+                    ),
+            )
+            .add_system(whitelist);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct WhitelistEntry {
+    /// `None` for players that were added while they were offline, filled in
+    /// when they join for the first time.
+    pub uuid: Option<Uuid>,
+    pub username: String,
+}
+
+/// Players that are allowed to join while the whitelist is enabled, stored in
+/// `world/whitelist.json`.
+///
+/// Like the [`BanList`] the whitelist is shared with [`VPCallbacks`], which
+/// checks it when a player logs in.
+///
+/// [`BanList`]: super::ban::BanList
+/// [`VPCallbacks`]: super::callbacks::VPCallbacks
+#[derive(Resource, Clone, Debug)]
+pub struct Whitelist {
+    entries: Arc<RwLock<Vec<WhitelistEntry>>>,
+    enabled: Arc<AtomicBool>,
+    /// File the entries are saved to, see [`whitelist_path`].
+    path: Arc<PathBuf>,
+}
+
+impl Whitelist {
+    /// Loads the whitelist, starts with an empty list if there is none. It is
+    /// enabled if `whitelist_enabled` is set.
+    #[must_use]
+    pub fn load() -> Self {
+        let path = whitelist_path().unwrap_or_else(|_| PathBuf::from("world/whitelist.json"));
+        Self::load_from(path, CONFIG.server.whitelist_enabled)
+    }
+
+    fn load_from(path: PathBuf, enabled: bool) -> Self {
+        let entries = match read_json(&path) {
+            Ok(entries) => entries.unwrap_or_default(),
+            Err(e) => {
+                warn!(target: "minecraft::whitelist", "Unable to load the whitelist: {e}");
+                vec![]
+            }
+        };
+
+        Self {
+            entries: Arc::new(RwLock::new(entries)),
+            enabled: Arc::new(AtomicBool::new(enabled)),
+            path: Arc::new(path),
+        }
+    }
+
+    #[must_use]
+    pub fn is_enabled(&self) -> bool { self.enabled.load(Ordering::Relaxed) }
+
+    /// Enables or disables the whitelist until the server is restarted.
+    pub fn set_enabled(&self, enabled: bool) { self.enabled.store(enabled, Ordering::Relaxed); }
+
+    /// Adds a player, returns `false` if they are already on the whitelist.
+    ///
+    /// # Errors
+    /// - the whitelist can't be written
+    pub fn add(&self, uuid: Option<Uuid>, username: String) -> Result<bool> {
+        let mut entries = self.entries.write().ignore_poison();
+        if entries.iter().any(|e| {
+            e.username.eq_ignore_ascii_case(&username) || (uuid.is_some() && e.uuid == uuid)
+        }) {
+            return Ok(false);
+        }
+
+        entries.push(WhitelistEntry { uuid, username });
+
+        self.save(&entries)?;
+        Ok(true)
+    }
+
+    /// Removes a player by their username, returns the removed entry.
+    ///
+    /// # Errors
+    /// - the whitelist can't be written
+    pub fn remove(&self, username: &str) -> Result<Option<WhitelistEntry>> {
+        let mut entries = self.entries.write().ignore_poison();
+        let Some(index) = entries
+            .iter()
+            .position(|e| e.username.eq_ignore_ascii_case(username))
+        else {
+            return Ok(None);
+        };
+        let entry = entries.remove(index);
+
+        self.save(&entries)?;
+        Ok(Some(entry))
+    }
+
+    #[must_use]
+    pub fn entries(&self) -> Vec<WhitelistEntry> { self.entries.read().ignore_poison().clone() }
+
+    /// Whether a player is allowed to join. Players that were added by their
+    /// username get their uuid assigned, so they stay on the whitelist after a
+    /// name change.
+    #[must_use]
+    pub fn allows(&self, uuid: Uuid, username: &str) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+
+        let added_by_name =
+            |e: &WhitelistEntry| e.uuid.is_none() && e.username.eq_ignore_ascii_case(username);
+
+        {
+            let entries = self.entries.read().ignore_poison();
+            if entries.iter().any(|e| e.uuid == Some(uuid)) {
+                return true;
+            }
+            if !entries.iter().any(added_by_name) {
+                return false;
+            }
+        }
+
+        // Only the first login of players added by their username writes.
+        let mut entries = self.entries.write().ignore_poison();
+        let Some(entry) = entries.iter_mut().find(|e| added_by_name(e)) else {
+            // Recorded by another login since the read lock was released.
+            return entries.iter().any(|e| e.uuid == Some(uuid));
+        };
+        entry.uuid = Some(uuid);
+        entry.username = username.to_string();
+
+        if let Err(e) = self.save(&entries) {
+            error!(target: "minecraft::whitelist", "Unable to save the whitelist: {e}");
+        }
+
+        true
+    }
+
+    fn save(&self, entries: &[WhitelistEntry]) -> Result<()> { write_json(&self.path, entries) }
+}
+
+/// # Errors
+/// - the current directory can't be accessed
+pub fn whitelist_path() -> Result<PathBuf> {
+    Ok(std::env::current_dir()?
+        .join("world")
+        .join("whitelist.json"))
+}
+
+fn whitelist(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    whitelist: Res<Whitelist>,
+) {
+    for event in events.iter().filter(|e| e.is("whitelist")) {
+        let Ok(client) = clients.get(event.client) else {
+            continue;
+        };
+        let operator = client.username().to_string();
+
+        let msg = match (event.arg(0), event.arg(1)) {
+            (Some("add"), Some(username)) => {
+                let uuid = clients
+                    .iter()
+                    .find(|c| c.username().to_string() == username)
+                    .map(Client::uuid);
+
+                match whitelist.add(uuid, username.to_string()) {
+                    Ok(true) => {
+                        info!(target: "minecraft::whitelist", "{operator} whitelisted {username}");
+                        format!("Added {username} to the whitelist.").italic()
+                    }
+                    Ok(false) => {
+                        format!("{username} is already on the whitelist.").color(Color::RED)
+                    }
+                    Err(e) => {
+                        error!(target: "minecraft::whitelist", "Unable to save the whitelist: {e}");
+                        format!("Unable to save the whitelist: {e}").color(Color::RED)
+                    }
+                }
+            }
+            (Some("remove"), Some(username)) => match whitelist.remove(username) {
+                Ok(Some(entry)) => {
+                    info!(target: "minecraft::whitelist",
+                        "{operator} removed {} from the whitelist", entry.username
+                    );
+                    format!("Removed {} from the whitelist.", entry.username).italic()
+                }
+                Ok(None) => format!("{username} is not on the whitelist.").color(Color::RED),
+                Err(e) => {
+                    error!(target: "minecraft::whitelist", "Unable to save the whitelist: {e}");
+                    format!("Unable to save the whitelist: {e}").color(Color::RED)
+                }
+            },
+            (Some("list"), None) => {
+                let names = whitelist
+                    .entries()
+                    .into_iter()
+                    .map(|e| e.username)
+                    .collect::<Vec<_>>();
+                let state = if whitelist.is_enabled() { "on" } else { "off" };
+
+                if names.is_empty() {
+                    format!("The whitelist is {state} and empty.").italic()
+                } else {
+                    format!(
+                        "The whitelist is {state}, {} players: {}",
+                        names.len(),
+                        names.join(", ")
+                    )
+                    .italic()
+                }
+            }
+            (Some(state @ ("on" | "off")), None) => {
+                whitelist.set_enabled(state == "on");
+                info!(target: "minecraft::whitelist", "{operator} turned the whitelist {state}");
+                format!("Turned the whitelist {state}.").italic()
+            }
+            _ => format!("Usage: {WHITELIST_USAGE}").color(Color::RED),
+        };
+
+        if let Ok(mut client) = clients.get_mut(event.client) {
+            client.send_message(msg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn whitelist_file(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("vp-whitelist-{name}-{}", std::process::id()))
+            .join("whitelist.json")
+    }
+
+    fn remove_whitelist_dir(path: &Path) {
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn added_and_removed_players_are_saved() {
+        let path = whitelist_file("add-remove");
+        let whitelist = Whitelist::load_from(path.clone(), true);
+
+        assert!(whitelist.add(None, "Alice".to_string()).unwrap());
+        assert!(whitelist
+            .add(Some(Uuid::from_u128(2)), "Bob".to_string())
+            .unwrap());
+        // Usernames are case insensitive.
+        assert!(!whitelist.add(None, "alice".to_string()).unwrap());
+        assert!(!whitelist
+            .add(Some(Uuid::from_u128(2)), "Robert".to_string())
+            .unwrap());
+
+        let loaded = Whitelist::load_from(path.clone(), true);
+        assert_eq!(loaded.entries(), whitelist.entries());
+        assert_eq!(loaded.entries().len(), 2);
+
+        assert_eq!(
+            loaded.remove("ALICE").unwrap(),
+            Some(WhitelistEntry {
+                uuid: None,
+                username: "Alice".to_string(),
+            })
+        );
+        assert_eq!(loaded.remove("Carol").unwrap(), None);
+
+        let names = Whitelist::load_from(path.clone(), true)
+            .entries()
+            .into_iter()
+            .map(|e| e.username)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Bob"]);
+
+        remove_whitelist_dir(&path);
+    }
+
+    #[test]
+    fn players_added_by_name_get_their_uuid_when_they_join() {
+        let path = whitelist_file("uuid");
+        let whitelist = Whitelist::load_from(path.clone(), true);
+        let uuid = Uuid::from_u128(7);
+        whitelist.add(None, "Bob".to_string()).unwrap();
+
+        assert!(!whitelist.allows(Uuid::from_u128(8), "Carol"));
+        assert!(whitelist.allows(uuid, "bob"));
+
+        let entries = Whitelist::load_from(path.clone(), true).entries();
+        assert_eq!(
+            entries,
+            [WhitelistEntry {
+                uuid: Some(uuid),
+                username: "bob".to_string(),
+            }]
+        );
+        // They stay on the whitelist after a name change, but the old name
+        // doesn't allow others to join anymore.
+        assert!(whitelist.allows(uuid, "Robert"));
+        assert!(!whitelist.allows(Uuid::from_u128(9), "bob"));
+
+        remove_whitelist_dir(&path);
+    }
+
+    #[test]
+    fn disabled_whitelist_allows_everyone() {
+        let path = whitelist_file("enabled");
+        let whitelist = Whitelist::load_from(path.clone(), false);
+        let uuid = Uuid::from_u128(3);
+
+        assert!(!whitelist.is_enabled());
+        assert!(whitelist.allows(uuid, "Dave"));
+        // Nothing is written while the whitelist is off.
+        assert!(!path.exists());
+
+        // The login callbacks share the state with the command.
+        let shared = whitelist.clone();
+        whitelist.set_enabled(true);
+        assert!(shared.is_enabled());
+        assert!(!shared.allows(uuid, "Dave"));
+
+        whitelist.add(Some(uuid), "Dave".to_string()).unwrap();
+        assert!(shared.allows(uuid, "Dave"));
+
+        // Turning the whitelist on only lasts until the restart.
+        assert!(!Whitelist::load_from(path.clone(), false).is_enabled());
+        assert!(Whitelist::load_from(path.clone(), true).allows(uuid, "Dave"));
+
+        remove_whitelist_dir(&path);
+    }
+}
@@ -1,39 +1,75 @@
+pub mod biome;
+pub mod chunk_cache;
 pub mod chunk_worker;
+pub mod dimension;
+pub mod test_world;
 
 use std::{
-    collections::{hash_map::Entry, HashMap},
-    mem::size_of,
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    hash::Hash,
     num::NonZeroUsize,
     process,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
-use bevy::prelude::{Query, ResMut, Resource, World};
+use bevy::prelude::{Commands, Query, ResMut, Resource, World};
 use bevy_egui::egui;
 use flume::{Receiver, Sender};
 use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
 use itertools::iproduct;
 use lru::LruCache;
-use noise::SuperSimplex;
+use rand::Rng;
 use rayon::prelude::*;
 use valence::{bevy_app::Plugin, prelude::*, server::Server};
 
-use self::chunk_worker::{
-    chunk_worker, gen_chunk, ChunkWorkerState, TerrainSettings, WorkerMessage, WorkerResponse,
+#[cfg(feature = "profiling")]
+use self::chunk_worker::ChunkGenTiming;
+use self::{
+    biome::{assign_biomes, BiomeIds},
+    chunk_cache::TrackedLruCache,
+    chunk_worker::{
+        chunk_worker, chunk_worker_count, gen_chunk, ChunkInfo, ChunkWorkerState, GeologyPreset,
+        OreSettings, TerrainPreset, TerrainSettings, WorkerMessage, WorkerResponse,
+        MAX_HEIGHT_SCALE,
+    },
+    dimension::dimension_id,
+};
+use super::{
+    client::init_clients,
+    command::{Caller, Command, CommandEvent, RegisterCommandExt},
 };
-use super::client::init_clients;
 use crate::{
+    config::{
+        DimensionConfig, PregenShape, ReadOnlyWorld, RegenerationProgress, StaleRegions,
+        WorldConfig,
+    },
     minecraft::{
-        save::{load_regions, overwrite_regions, Region},
+        maprender::map_path,
+        player_data::saved_positions,
+        save::{
+            clean_stale_regions, is_writable, load_regions, overwrite_regions, prune_regions,
+            read_nbt_sidecars, write_nbt_sidecars, Region, SaveChunkNbt,
+        },
+        spawn::SpawnConfig,
+        structure::is_valid_name,
         world_gen::chunk_worker::ChunkWorker,
+        SpawnPos,
     },
-    CONFIG, SPAWN_POS,
+    util::format_duration,
+    CONFIG, SECTION_COUNT,
 };
 
 /// The order in which chunks should be processed by the thread pool. Smaller
 /// values are sent first.
 pub type Priority = u64;
 
+const CTP_USAGE: &str = "ctp <chunk x> <chunk z>";
+
+/// Distance in chunks to the spawn and the players within which `prune` keeps
+/// regions if no distance is given.
+const PRUNE_DISTANCE: i64 = 256;
+
 /// World Gen sender
 type WGSender = Sender<WorkerMessage>;
 
@@ -41,23 +77,293 @@ type WGSender = Sender<WorkerMessage>;
 type WGReceiver = Receiver<WorkerResponse>;
 
 #[derive(Resource, Clone, Debug)]
-pub struct UpdateTerrainSettings(bool);
+pub struct UpdateTerrainSettings(pub bool);
 
-#[derive(Resource, Clone, Debug)]
+/// Name of the instance with the generated terrain.
+pub const TERRAIN_INSTANCE: &str = "terrain";
+/// Name of the instance players wait in during a regeneration.
+pub const WAIT_INSTANCE: &str = "wait";
+
+/// All instances by name, always contains the [`TERRAIN_INSTANCE`] and the
+/// [`WAIT_INSTANCE`] and an instance for every dimension of the config.
+#[derive(Resource, Clone, Debug, Default)]
 pub struct Instances {
-    pub terrain: Entity,
-    pub wait: Entity,
+    pub map: HashMap<String, Entity>,
+}
+
+impl Instances {
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<Entity> { self.map.get(name).copied() }
+
+    pub fn insert(&mut self, name: String, entity: Entity) { self.map.insert(name, entity); }
+
+    /// # Panics
+    /// - the terrain instance wasn't created yet
+    #[must_use]
+    pub fn terrain(&self) -> Entity {
+        self.get(TERRAIN_INSTANCE)
+            .expect("terrain instance should exist")
+    }
+
+    /// # Panics
+    /// - the wait instance wasn't created yet
+    #[must_use]
+    pub fn wait(&self) -> Entity { self.get(WAIT_INSTANCE).expect("wait instance should exist") }
+
+    /// Names and instances of the additional dimensions of the config.
+    pub fn dimensions(&self) -> impl Iterator<Item = (&str, Entity)> {
+        self.map
+            .iter()
+            .filter(|(name, _)| *name != TERRAIN_INSTANCE && *name != WAIT_INSTANCE)
+            .map(|(name, entity)| (name.as_str(), *entity))
+    }
+
+    /// Name of the additional dimension of the instance, `None` for the
+    /// terrain and the wait instance.
+    #[must_use]
+    pub fn dimension_name(&self, instance: Entity) -> Option<&str> {
+        self.dimensions()
+            .find(|(_, entity)| *entity == instance)
+            .map(|(name, _)| name)
+    }
+}
+
+/// Number of chunks [`ChunkInfos`] keeps track of.
+const CHUNK_INFO_CAPACITY: usize = 1024;
+
+/// How the most recently received chunks were provided by the chunk workers.
+#[derive(Resource)]
+pub struct ChunkInfos(LruCache<ChunkPos, ChunkInfo>);
+
+impl Default for ChunkInfos {
+    fn default() -> Self {
+        Self(LruCache::new(
+            NonZeroUsize::new(CHUNK_INFO_CAPACITY).unwrap(),
+        ))
+    }
+}
+
+impl ChunkInfos {
+    pub fn record(&mut self, pos: ChunkPos, info: ChunkInfo) { self.0.put(pos, info); }
+
+    #[must_use]
+    pub fn get(&self, pos: ChunkPos) -> Option<&ChunkInfo> { self.0.peek(&pos) }
+}
+
+/// Seconds of cache occupancy shown in the [`cache_stats_window`].
+const CACHE_HISTORY_SECONDS: usize = 60;
+
+/// Chunk cache statistics of the chunk workers, hits and misses are counted
+/// since startup.
+#[derive(Resource, Debug)]
+pub struct CacheStats {
+    pub len: usize,
+    pub capacity: usize,
+    pub evictions: u64,
+    pub hits: u64,
+    pub misses: u64,
+    /// Number of cached chunks once per second, the oldest first.
+    pub occupancy: VecDeque<usize>,
+    last_sample: Instant,
+}
+
+impl Default for CacheStats {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            capacity: CONFIG.world.chunks_cached,
+            evictions: 0,
+            hits: 0,
+            misses: 0,
+            occupancy: VecDeque::with_capacity(CACHE_HISTORY_SECONDS),
+            last_sample: Instant::now(),
+        }
+    }
+}
+
+impl CacheStats {
+    pub fn record_lookup(&mut self, cached: bool) {
+        if cached {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+    }
+
+    /// Share of the chunks that were found in the cache, `None` before the
+    /// first chunk.
+    #[must_use]
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let total = self.hits + self.misses;
+        (total > 0).then(|| self.hits as f64 / total as f64)
+    }
+
+    /// Adds the current occupancy to the history if a second passed since the
+    /// last sample.
+    pub fn sample(&mut self, now: Instant) {
+        if now.duration_since(self.last_sample) < Duration::from_secs(1) {
+            return;
+        }
+
+        if self.occupancy.len() == CACHE_HISTORY_SECONDS {
+            self.occupancy.pop_front();
+        }
+        self.occupancy.push_back(self.len);
+        self.last_sample = now;
+    }
+}
+
+/// Phase timings of the chunks received from the chunk workers.
+#[cfg(feature = "profiling")]
+#[derive(Resource, Default, Debug)]
+pub struct ChunkTimings {
+    pub last: ChunkGenTiming,
+    /// Sum of the timings of all chunks.
+    pub total: ChunkGenTiming,
+    pub count: u64,
+}
+
+#[cfg(feature = "profiling")]
+impl ChunkTimings {
+    pub fn record(&mut self, timing: ChunkGenTiming) {
+        self.last = timing;
+        self.total.add(&timing);
+        self.count += 1;
+    }
 }
 
+/// Number of ticks each loaded chunk has not been viewed by any client.
+#[derive(Resource, Default, Debug)]
+pub struct UnviewedChunks(HashMap<ChunkPos, u32>);
+
 #[derive(Resource)]
 pub struct WorldGenState {
     /// Chunks that need to be generated. Chunks without a priority have already
     /// been sent to the thread pool.
     pending: HashMap<ChunkPos, Option<Priority>>,
+    /// Number of chunks sent to the thread pool that haven't been received yet.
+    in_flight: usize,
+    /// [`TerrainSettings::content_hash`] of the settings of every chunk in the
+    /// terrain instance.
+    chunk_hashes: HashMap<ChunkPos, u64>,
+    /// [`TerrainSettings::content_hash`] of the current settings.
+    settings_hash: u64,
+    /// Whether the last tick couldn't send all pending chunks, used to only warn
+    /// once when the queue fills up.
+    queue_full: bool,
+    /// Whether chunks in the view of a player were rejected because there were
+    /// already [`max_pending_chunks`](crate::config::WorldConfig) pending.
+    views_incomplete: bool,
+    /// Whether the last batch couldn't be sent to the thread pool, used to only
+    /// log the failure once.
+    send_failed: bool,
+    /// Chunks of the additional dimensions sent to the thread pool that
+    /// haven't been received yet, by the name of the dimension.
+    dimension_pending: HashSet<(String, ChunkPos)>,
     sender: WGSender,
     receiver: WGReceiver,
 }
 
+/// Sent when a chunk generated by the chunk workers is inserted into an
+/// instance, instead of a block change for every block of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkGeneratedEvent {
+    pub instance: Entity,
+    pub pos: ChunkPos,
+}
+
+/// A player waiting for a chunk to be generated before being teleported into
+/// it by `ctp`.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ChunkTeleport(pub ChunkPos);
+
+/// A player waiting for a random chunk to be generated before being teleported
+/// into it by `rtp`.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct RandomTeleport {
+    pub pos: ChunkPos,
+    /// Column of the chunk the player should land in, if it is safe.
+    pub column: (usize, usize),
+    /// Number of other chunks that are tried if the chunk has no safe landing
+    /// spot.
+    pub attempts_left: u8,
+}
+
+impl RandomTeleport {
+    /// Picks a random column within the `rtp_radius`.
+    #[must_use]
+    pub fn new(attempts_left: u8) -> Self {
+        let (x, z) = random_column(CONFIG.world.rtp_radius);
+
+        Self {
+            pos: ChunkPos::new(x.div_euclid(16), z.div_euclid(16)),
+            column: (x.rem_euclid(16) as usize, z.rem_euclid(16) as usize),
+            attempts_left,
+        }
+    }
+}
+
+/// When players last used `rtp`.
+#[derive(Resource, Default, Debug)]
+pub struct RandomTeleportCooldowns(HashMap<Uuid, Instant>);
+
+impl RandomTeleportCooldowns {
+    /// Time until the player can use `rtp` again, `None` if they can use it
+    /// now.
+    #[must_use]
+    pub fn remaining(&self, uuid: Uuid, now: Instant, cooldown: Duration) -> Option<Duration> {
+        let last = self.0.get(&uuid)?;
+        cooldown
+            .checked_sub(now.saturating_duration_since(*last))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    pub fn start(&mut self, uuid: Uuid, now: Instant) { self.0.insert(uuid, now); }
+}
+
+/// Progress of a terrain regeneration, players wait in the wait world until
+/// it is finished.
+#[derive(Resource, Debug)]
+pub struct Regeneration {
+    started: Instant,
+    /// Chunks in the views of the players that still need to be generated.
+    remaining: HashSet<ChunkPos>,
+    total: usize,
+    /// The chunk workers use the new terrain settings.
+    settings_set: bool,
+    /// Number of remaining chunks the players were last told about.
+    reported: Option<usize>,
+}
+
+impl Regeneration {
+    #[must_use]
+    pub fn new(chunks: impl IntoIterator<Item = ChunkPos>) -> Self {
+        let remaining: HashSet<_> = chunks.into_iter().collect();
+
+        Self {
+            started: Instant::now(),
+            total: remaining.len(),
+            remaining,
+            settings_set: false,
+            reported: None,
+        }
+    }
+
+    pub fn chunk_generated(&mut self, pos: ChunkPos) { self.remaining.remove(&pos); }
+
+    #[must_use]
+    pub fn remaining(&self) -> usize { self.remaining.len() }
+
+    #[must_use]
+    pub fn total(&self) -> usize { self.total }
+
+    #[must_use]
+    pub fn is_finished(&self) -> bool { self.settings_set && self.remaining.is_empty() }
+
+    #[must_use]
+    pub fn timed_out(&self, timeout: Duration) -> bool { self.started.elapsed() >= timeout }
+}
+
 pub struct WorldGenPlugin;
 
 impl Plugin for WorldGenPlugin {
@@ -65,11 +371,104 @@ impl Plugin for WorldGenPlugin {
         app.init_resource::<TerrainSettings>() // `ResourceInspectorPlugin` won't initialize the resource
             .register_type::<TerrainSettings>()
             .insert_resource(UpdateTerrainSettings(false)) // you need to register your type to display it
+            .init_resource::<UnviewedChunks>()
+            .init_resource::<ModifiedChunks>()
+            .init_resource::<ChunkInfos>()
+            .init_resource::<ShowPendingChunks>()
+            .init_resource::<Minimap>()
+            .init_resource::<CacheStats>()
+            .add_event::<ChunkGeneratedEvent>()
+            .register_command(Command::new("ctp").with_op_level(2).with_usage(CTP_USAGE))
+            .register_command(
+                Command::new("chunkinfo")
+                    .with_op_level(2)
+                    .with_usage("chunkinfo [<chunk x> <chunk z>]"),
+            )
+            .register_command(Command::new("save").with_op_level(3))
+            .register_command(
+                Command::new("map")
+                    .with_op_level(3)
+                    .with_usage("map export [name]")
+                    .with_description(
+                        "Writes a top-down PNG of the cached chunks around you to \
+                         maps/<name>.png, maps/map.png if no name is given.",
+                    ),
+            )
+            .register_command(
+                Command::new("prewarm")
+                    .with_op_level(3)
+                    .with_usage("prewarm <region x> <region z>")
+                    .with_description(
+                        "Loads the saved chunks of a region into the chunk cache ahead of time.",
+                    ),
+            )
+            .register_command(Command::new("rtp"))
+            .register_command(
+                Command::new("refresh")
+                    .with_op_level(2)
+                    .with_description("Sends all chunks you see again."),
+            )
+            .register_command(
+                Command::new("fingerprint")
+                    .with_op_level(2)
+                    .with_description(
+                        "Shows a hash of the terrain settings, the generator version and the \
+                         world height, worlds with the same fingerprint are identical.",
+                    ),
+            )
+            .register_command(
+                Command::new("prune")
+                    .with_op_level(3)
+                    .with_usage("prune [distance]"),
+            )
+            .register_command(
+                Command::new("cleanregions")
+                    .with_op_level(3)
+                    .with_description(
+                        "Deletes or archives the region files of other terrain settings, \
+                         depending on stale_regions.",
+                    ),
+            )
+            .register_command(
+                Command::new("chunkworker")
+                    .with_op_level(3)
+                    .with_usage("chunkworker pause | chunkworker resume"),
+            )
+            .init_resource::<ChunkWorkerStatus>()
+            .init_resource::<RandomTeleportCooldowns>()
+            .register_command(
+                Command::new("cachesize")
+                    .with_op_level(3)
+                    .with_usage("cachesize <chunks>"),
+            )
             .add_startup_system(setup)
             .add_system(set_terrain_settings)
             .add_system(remove_unviewed_chunks.after(init_clients))
             .add_system(update_client_views.after(remove_unviewed_chunks))
-            .add_system(send_recv_chunks.after(update_client_views));
+            .add_system(remove_unviewed_dimension_chunks.after(init_clients))
+            .add_system(update_dimension_views.after(remove_unviewed_dimension_chunks))
+            .add_system(enforce_view_distance_limit.after(update_client_views))
+            .add_system(send_recv_chunks.after(update_client_views))
+            .add_system(regeneration_progress.after(send_recv_chunks))
+            .add_system(chunk_teleport.after(send_recv_chunks))
+            .add_system(random_teleport.after(send_recv_chunks))
+            .add_system(check_empty_terrain.after(send_recv_chunks))
+            .add_system(cache_size)
+            .add_system(save_world)
+            .add_system(prewarm)
+            .add_system(map_command)
+            .add_system(prune)
+            .add_system(clean_regions)
+            .add_system(fingerprint)
+            .add_system(refresh)
+            .add_system(chunk_worker_command)
+            .add_system(chunk_info);
+
+        #[cfg(feature = "profiling")]
+        app.init_resource::<ChunkTimings>();
+
+        #[cfg(feature = "hot-reload")]
+        app.add_plugin(crate::config::watcher::ConfigWatcherPlugin);
     }
 }
 
@@ -81,11 +480,10 @@ fn setup(world: &mut World) {
 
     info!(target: "minecraft::world_gen", "Current seed: {seed}");
 
-    let pregen_chunks = CONFIG.world.pregen_chunks.clone();
-    let num_pregen_chunks = pregen_chunks.clone().max().unwrap() * 2 + 1;
-    let num_pregen_chunks = num_pregen_chunks * num_pregen_chunks;
+    let pregen_shape = CONFIG.world.pregen_chunks;
+    let num_pregen_chunks = pregen_positions(&pregen_shape).count();
 
-    if num_pregen_chunks > CONFIG.world.chunks_cached.try_into().unwrap() {
+    if num_pregen_chunks > CONFIG.world.chunks_cached {
         error!(target: "minecraft::world_gen",
             "Number of pregenerated chunks is higher than the chunk cache size. Please lower the \
              range of pregenerated chunks!"
@@ -93,30 +491,69 @@ fn setup(world: &mut World) {
         process::exit(0);
     }
 
+    let world_dir = std::env::current_dir().unwrap().join("world");
+    let saving = is_writable(&world_dir);
+
+    if !saving {
+        match CONFIG.world.read_only_world {
+            ReadOnlyWorld::DisableSaving => {
+                warn!(target: "minecraft::save",
+                    "World directory {} is not writable, generated chunks won't be saved!",
+                    world_dir.display()
+                );
+            }
+            ReadOnlyWorld::Exit => {
+                error!(target: "minecraft::save",
+                    "World directory {} is not writable. Fix the permissions or set \
+                     `read_only_world` to `DisableSaving` to start without saving.",
+                    world_dir.display()
+                );
+                process::exit(1);
+            }
+        }
+    }
+
     let (finished_sender, finished_receiver) = flume::unbounded();
     let (pending_sender, pending_receiver) = flume::unbounded();
-    let mut cache = LruCache::new(NonZeroUsize::new(CONFIG.world.chunks_cached).unwrap());
-    let state = ChunkWorkerState {
-        settings: TerrainSettings::default(),
-        density: SuperSimplex::new(seed),
-        hilly: SuperSimplex::new(seed.wrapping_add(1)),
-        stone: SuperSimplex::new(seed.wrapping_add(2)),
-        gravel: SuperSimplex::new(seed.wrapping_add(3)),
-        grass: SuperSimplex::new(seed.wrapping_add(4)),
-    };
+    let mut cache = TrackedLruCache::new(
+        NonZeroUsize::new(CONFIG.world.chunks_cached).unwrap(),
+        saving,
+    );
+    // The settings keep the seed, so diffs of saved chunks are applied to the same
+    // terrain they were created from.
+    let mut state = ChunkWorkerState::new(
+        seed,
+        TerrainSettings {
+            seed,
+            ..TerrainSettings::default()
+        },
+    );
+    let biome_ids = BiomeIds::from_server(world.resource::<Server>());
+    state.biome_ids = biome_ids;
+
+    let pending_chunks = pending_pregen_chunks(&CONFIG.world);
+    let settings_hash = state.settings.content_hash();
 
-    let mut pending_chunks = HashMap::new();
-    for (x, z) in iproduct!(pregen_chunks.clone(), pregen_chunks.clone()) {
-        let pos = ChunkPos::new(x, z);
-        pending_chunks.insert(pos, Some((x + z) as u64));
+    // Regions of other settings are only cleaned up on startup and with
+    // `cleanregions`, not every time the settings are changed. On startup they are
+    // cleaned before the pregenerated chunks replace them.
+    if saving && CONFIG.world.stale_regions != StaleRegions::Keep {
+        match clean_stale_regions(settings_hash, CONFIG.world.stale_regions) {
+            Ok(count) => info!(target: "minecraft::save",
+                "Cleaned up {count} regions of other terrain settings"
+            ),
+            Err(e) => error!(target: "minecraft::save", "Unable to clean up stale regions: {e}"),
+        }
     }
 
-    let regions = match load_regions() {
+    let regions = match load_regions(&state.settings) {
         Ok(r) => r,
-        Err(_) => vec![],
+        Err(e) => {
+            warn!(target: "minecraft::save", "Unable to load the regions: {e}");
+            vec![]
+        }
     };
     let regions_empty = regions.is_empty();
-    debug!("{regions_empty}");
 
     let pb = ProgressBar::new(num_pregen_chunks as u64)
         .with_message("Pregenerating chunks...".to_string());
@@ -132,7 +569,7 @@ fn setup(world: &mut World) {
     // let state = Arcstate));
     let state_clone = Arc::from(state.clone());
 
-    let chunks = iproduct!(pregen_chunks.clone(), pregen_chunks)
+    let chunks = pregen_positions(&pregen_shape)
         .progress_with(pb.clone())
         .par_bridge()
         .map(move |(x, z)| {
@@ -144,7 +581,9 @@ fn setup(world: &mut World) {
                 match Region::chunk_from_regions(&regions, pos) {
                     Some(c) => {
                         trace!(target: "minecraft::world_gen", "loaded from file: ({}, {})", pos.x, pos.z);
-                        c.into()
+                        let mut chunk = c.to_chunk(|| gen_chunk(&state_clone, pos));
+                        assign_biomes(&state_clone, &mut chunk, pos);
+                        chunk
                     }
                     None => gen_chunk(&state_clone, pos)
                 }
@@ -155,43 +594,60 @@ fn setup(world: &mut World) {
         .collect::<Vec<(ChunkPos, Chunk)>>();
 
     for (pos, chunk) in &chunks {
-        cache.push(*pos, chunk.clone());
+        cache.push(*pos, chunk.clone(), settings_hash, &state.settings);
     }
 
-    if regions_empty {
+    if regions_empty && saving {
         let _ = overwrite_regions(&chunks, state.settings.clone());
     }
 
-    drop(chunks);
-
     pb.finish_with_message("Chunks generated");
 
-    let spawn_chunk = cache
+    let spawn_chunk = &cache
         .get(&ChunkPos::new(0, 0))
-        .expect("Should be generated");
-    let mut y = spawn_chunk.section_count() * 16 - 1;
+        .expect("Should be generated")
+        .chunk;
 
-    if CONFIG.world.spawn.is_some() {
+    let spawn_pos = if let Some(spawn) = SpawnConfig::load() {
+        debug!(target: "minecraft::world_gen",
+            "Spawn at {} {} {} (world/spawn.json)", spawn.x, spawn.y, spawn.z
+        );
+        SpawnPos(spawn.into())
+    } else if CONFIG.world.spawn.is_some() {
         let spawn = CONFIG.world.spawn.unwrap();
-        *SPAWN_POS.lock().unwrap() = DVec3::new(spawn[0], spawn[1], spawn[2]);
         debug!(target: "minecraft::world_gen", "Spawn at {} {} {}", spawn[0], spawn[1], spawn[2]);
+        SpawnPos(DVec3::new(spawn[0], spawn[1], spawn[2]))
+    } else if let Some(spawn) = find_spawn(spawn_chunk) {
+        debug!(target: "minecraft::world_gen", "Spawn at {} {} {}", spawn.x, spawn.y, spawn.z);
+        SpawnPos(spawn)
     } else {
-        loop {
-            let block = spawn_chunk.block_state(0, y, 0);
-            if block.is_air() {
-                y -= 1;
-            } else if y == 0 {
-                break;
-            } else {
-                y -= 50; // Blocks below 0 are treated as a bove 0
-                *SPAWN_POS.lock().unwrap() = DVec3::new(0.0, y as f64, 0.0);
-                debug!(target: "minecraft::world_gen", "Spawn height: {y}, Spawn block: {}", block);
-                break;
-            }
+        let spawn = SpawnPos::default().0;
+        warn!(target: "minecraft::world_gen",
+            "No safe spawn found in the spawn chunk, using {} {} {}", spawn.x, spawn.y, spawn.z
+        );
+        SpawnPos(spawn)
+    };
+
+    // The additional dimensions are generated by the chunk workers like the
+    // terrain, with the settings of their dimension type.
+    let mut dimensions: Vec<(&DimensionConfig, ChunkWorkerState)> = vec![];
+    for dimension in &CONFIG.world.dimensions {
+        let name = dimension.name.as_str();
+        if name == TERRAIN_INSTANCE
+            || name == WAIT_INSTANCE
+            || dimensions.iter().any(|(d, _)| d.name == name)
+        {
+            warn!(target: "minecraft::world_gen",
+                "Ignoring dimension {name}, the name is already used"
+            );
+            continue;
         }
-    }
 
-    println!("{}", size_of::<LruCache<ChunkPos, Chunk>>());
+        let seed = dimension.seed.unwrap_or(seed);
+        let mut state = ChunkWorkerState::new(seed, dimension.dimension.terrain_settings(seed));
+        state.biome_ids = biome_ids;
+        dimensions.push((dimension, state));
+    }
 
     // Chunks are generated in a thread pool for parallelism and to avoid blocking
     // the main tick loop. You can use your thread pool of choice here (rayon,
@@ -205,9 +661,38 @@ fn setup(world: &mut World) {
         receiver: pending_receiver,
         cache,
         state,
+        settings_hash,
+        saving,
+        paused: false,
+        deferred: vec![],
+        deferred_dimensions: vec![],
+        deferred_flush: false,
+        prewarmed: HashSet::new(),
+        dimensions: dimensions
+            .iter()
+            .map(|(dimension, state)| (dimension.name.clone(), state.clone()))
+            .collect(),
     }));
-    let metrics = tokio::runtime::Handle::current().metrics();
-    for i in 0..metrics.num_workers() {
+    let runtime_workers = tokio::runtime::Handle::current().metrics().num_workers();
+    let available_parallelism = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let worker_count = chunk_worker_count(
+        runtime_workers,
+        available_parallelism,
+        CONFIG.world.chunk_workers,
+    );
+    if let Some(configured) = CONFIG.world.chunk_workers.filter(|c| *c != worker_count) {
+        warn!(target: "minecraft::world_gen",
+            "chunk_workers is set to {configured}, using {worker_count} chunk workers instead \
+             ({runtime_workers} runtime threads, {available_parallelism} cores)"
+        );
+    } else {
+        info!(target: "minecraft::world_gen",
+            "Using {worker_count} chunk workers ({runtime_workers} runtime threads, \
+             {available_parallelism} cores)"
+        );
+    }
+
+    for i in 0..worker_count {
         let worker_clone = Arc::clone(&worker);
 
         let _ = tokio::task::Builder::new()
@@ -219,17 +704,51 @@ fn setup(world: &mut World) {
 
     world.insert_resource(WorldGenState {
         pending: pending_chunks,
+        in_flight: 0,
+        chunk_hashes: if CONFIG.world.load_pregen_chunks {
+            chunks
+                .iter()
+                .map(|(pos, _)| (*pos, settings_hash))
+                .collect()
+        } else {
+            HashMap::new()
+        },
+        settings_hash,
+        queue_full: false,
+        send_failed: false,
+        views_incomplete: false,
+        dimension_pending: HashSet::new(),
         sender: pending_sender,
         receiver: finished_receiver,
     });
 
     world.insert_resource(TerrainSettings::default());
+    world.insert_resource(spawn_pos);
+    world.insert_resource(BlockEntities::load(saving, settings_hash));
 
-    let instance = world
+    let mut instance = world
         .resource::<Server>()
         .new_instance(DimensionId::default());
 
+    // The spawn area is available right away, without waiting for the first
+    // player to request it.
+    let mut pregenerated = vec![];
+    if CONFIG.world.load_pregen_chunks {
+        for (pos, chunk) in chunks {
+            instance.insert_chunk(pos, chunk);
+            pregenerated.push(pos);
+        }
+    } else {
+        drop(chunks);
+    }
+
     let terrain_id = world.spawn(instance).id();
+    for pos in pregenerated {
+        world.send_event(ChunkGeneratedEvent {
+            instance: terrain_id,
+            pos,
+        });
+    }
 
     // Creating waiting world
     let mut instance = world
@@ -250,264 +769,3454 @@ fn setup(world: &mut World) {
 
     let wait_id = world.spawn(instance).id();
 
-    world.insert_resource(Instances {
-        terrain: terrain_id,
-        wait: wait_id,
-    });
+    let mut instances = Instances::default();
+    instances.insert(TERRAIN_INSTANCE.to_string(), terrain_id);
+    instances.insert(WAIT_INSTANCE.to_string(), wait_id);
+
+    for (dimension, state) in &dimensions {
+        let instance = new_dimension(world, dimension, state);
+        let id = world.spawn(instance).id();
+        instances.insert(dimension.name.clone(), id);
+
+        info!(target: "minecraft::world_gen",
+            "Created dimension {} ({:?})", dimension.name, dimension.dimension
+        );
+    }
+
+    world.insert_resource(instances);
 
     info!(target: "minecraft::world_gen", "World generation started");
 }
 
-fn remove_unviewed_chunks(mut instances: Query<&mut Instance>, instances_list: Res<Instances>) {
-    let mut instance = instances.get_mut(instances_list.terrain).unwrap();
-    instance.retain_chunks(|_, chunk| chunk.is_viewed_mut());
+/// Creates the instance of a dimension of the config with its spawn chunk,
+/// which stays loaded so `terrain <name>` can find a spawn in it. All other
+/// chunks are generated by the chunk workers once players see them.
+fn new_dimension(world: &World, dimension: &DimensionConfig, state: &ChunkWorkerState) -> Instance {
+    let server = world.resource::<Server>();
+    let mut instance = server.new_instance(dimension_id(server, dimension.dimension));
+
+    let pos = ChunkPos::new(0, 0);
+    instance.insert_chunk(pos, gen_chunk(state, pos));
+
+    instance
 }
 
-fn update_client_views(
-    instances: Query<&mut Instance>,
-    instances_list: Res<Instances>,
-    mut clients: Query<&mut Client>,
-    mut state: ResMut<WorldGenState>,
-) {
-    let instance = instances.get(instances_list.terrain).unwrap();
+/// Positions of all chunks that are pregenerated for the given shape.
+pub fn pregen_positions(shape: &PregenShape) -> impl Iterator<Item = (i32, i32)> + Send {
+    let shape = *shape;
+    let radius = shape.radius();
 
-    for client in &mut clients {
-        let view = client.view();
-        let queue_pos = |pos| {
-            if instance.chunk(pos).is_none() {
-                match state.pending.entry(pos) {
-                    Entry::Occupied(mut oe) => {
-                        if let Some(priority) = oe.get_mut() {
-                            let dist = view.pos.distance_squared(pos);
-                            *priority = (*priority).min(dist);
-                        }
-                    }
-                    Entry::Vacant(ve) => {
-                        let dist = view.pos.distance_squared(pos);
-                        ve.insert(Some(dist));
-                    }
-                }
-            }
-        };
+    iproduct!(-radius..=radius, -radius..=radius).filter(move |(x, z)| shape.contains(*x, *z))
+}
 
-        // Queue all the new chunks in the view to be sent to the thread pool.
-        if client.is_added() {
-            view.iter().for_each(queue_pos);
-        } else {
-            let old_view = client.old_view();
-            if old_view != view {
-                view.diff(old_view).for_each(queue_pos);
-            }
-        }
+/// Finds the spawn position in the spawn chunk, starting at the column at `x=0`
+/// `z=0` and moving outwards until a column with a usable surface is found.
+#[must_use]
+pub fn find_spawn(chunk: &Chunk) -> Option<DVec3> {
+    let mut columns = iproduct!(0..16, 0..16).collect::<Vec<(usize, usize)>>();
+    columns.sort_unstable_by_key(|(x, z)| x * x + z * z);
+
+    columns.into_iter().find_map(|(x, z)| {
+        let y = surface_height(chunk, x, z)?;
+        // Blocks below 0 are treated as above 0
+        Some(DVec3::new(x as f64, y as f64 - 50.0, z as f64))
+    })
+}
+
+/// Returns the highest solid block in the column that has room for a player
+/// above it. Columns that are solid up to the build limit or don't contain any
+/// solid block are rejected.
+#[must_use]
+pub fn surface_height(chunk: &Chunk, x: usize, z: usize) -> Option<usize> {
+    let top = chunk.section_count() * 16 - 1;
+
+    if !chunk.block_state(x, top, z).is_air() {
+        return None;
     }
+
+    (0..top - 1).rev().find(|&y| {
+        let block = chunk.block_state(x, y, z);
+
+        !block.is_air()
+            && !block.is_liquid()
+            && chunk.block_state(x, y + 1, z).is_air()
+            && chunk.block_state(x, y + 2, z).is_air()
+    })
 }
 
-fn send_recv_chunks(
-    mut instances: Query<&mut Instance>,
-    instances_list: Res<Instances>,
-    state: ResMut<WorldGenState>,
-    mut clients: Query<&mut Client>,
-) {
-    let mut instance = instances.get_mut(instances_list.terrain).unwrap();
-    let state = state.into_inner();
+/// Blocks a player shouldn't land on.
+const UNSAFE_GROUND: [BlockKind; 4] = [
+    BlockKind::Cactus,
+    BlockKind::MagmaBlock,
+    BlockKind::Campfire,
+    BlockKind::SweetBerryBush,
+];
 
-    // Insert the chunks that are finished generating into the instance.
-    for response in state.receiver.drain() {
-        match response {
-            WorkerResponse::Chunk(pos, chunk) => {
-                instance.insert_chunk(pos, chunk);
-                assert!(state.pending.remove(&pos).is_some());
-            }
-            WorkerResponse::GetTerrainSettings(_) => todo!("Not yet implemented"),
-            WorkerResponse::TerrainSettingsSet => {
-                clients.par_iter_mut().for_each_mut(|mut c| {
-                    c.set_instance(instances_list.terrain);
-                    let spawn = *SPAWN_POS.lock().unwrap();
-                    c.set_position([spawn.x, spawn.y, spawn.z]);
-                    c.send_message("Terrain Regenerated".color(Color::GREEN))
-                });
+/// Finds a column with a safe place to land, starting at `x` `z` and then
+/// trying the closest other columns. Returns the column and the height of the
+/// block to stand on.
+#[must_use]
+pub fn safe_landing(chunk: &Chunk, x: usize, z: usize) -> Option<(usize, usize, usize)> {
+    let mut columns = iproduct!(0..16, 0..16).collect::<Vec<(usize, usize)>>();
+    columns.sort_by_key(|(cx, cz)| cx.abs_diff(x).pow(2) + cz.abs_diff(z).pow(2));
+
+    columns.into_iter().find_map(|(x, z)| {
+        let y = surface_height(chunk, x, z)?;
+        let ground = chunk.block_state(x, y, z).to_kind();
+
+        (!UNSAFE_GROUND.contains(&ground)).then_some((x, z, y))
+    })
+}
+
+/// Picks a random block column within `radius` blocks of the origin that is
+/// inside of the world border.
+fn random_column(radius: f64) -> (i32, i32) {
+    let radius = CONFIG
+        .world
+        .world_border_radius
+        .map_or(radius, |border| border.min(radius))
+        .max(1.0);
+
+    let mut rng = rand::thread_rng();
+    let mut coordinate = || rng.gen_range(-radius..radius).floor() as i32;
+
+    (coordinate(), coordinate())
+}
+
+/// Generates the chunk before all other pending chunks, unless it was already
+/// sent to the workers.
+fn queue_first(state: &mut WorldGenState, pos: ChunkPos) {
+    match state.pending.entry(pos) {
+        Entry::Occupied(mut oe) => {
+            if let Some(priority) = oe.get_mut() {
+                *priority = 0;
             }
         }
+        Entry::Vacant(ve) => {
+            ve.insert(Some(0));
+        }
     }
+}
 
-    // Collect all the new chunks that need to be loaded this tick.
-    let mut to_send = vec![];
+/// Switches to the settings with the content hash `hash` and forgets every
+/// chunk that was generated with other settings or isn't loaded anymore.
+fn invalidate_chunks(state: &mut WorldGenState, hash: u64, is_loaded: impl Fn(ChunkPos) -> bool) {
+    state.settings_hash = hash;
+    state
+        .chunk_hashes
+        .retain(|pos, chunk_hash| *chunk_hash == hash && is_loaded(*pos));
+}
 
-    for (pos, priority) in &mut state.pending.iter_mut() {
-        if let Some(pri) = priority.take() {
-            to_send.push((pri, pos));
-        }
+/// Block column in the center of the chunk.
+#[must_use]
+pub fn chunk_center(pos: ChunkPos) -> (i32, i32) { (pos.x * 16 + 8, pos.z * 16 + 8) }
+
+/// Parses the `<chunk x> <chunk z>` arguments of `ctp`.
+#[must_use]
+pub fn parse_chunk_pos(args: &[String]) -> Option<ChunkPos> {
+    match args {
+        [x, z] => Some(ChunkPos::new(x.parse().ok()?, z.parse().ok()?)),
+        _ => None,
     }
+}
 
-    // Sort chunks by ascending priority.
-    to_send.sort_unstable_by_key(|(pri, _)| *pri);
+/// Priority of a chunk in the view of a player, based on the squared distance
+/// to the chunk the player is in.
+///
+/// With `look_bias` chunks in front of the player, according to the yaw in
+/// degrees, are sent twice as early.
+#[must_use]
+pub fn chunk_priority(view_pos: ChunkPos, pos: ChunkPos, yaw: f32, look_bias: bool) -> Priority {
+    let dist = view_pos.distance_squared(pos);
 
-    // Send the sorted chunks to be loaded.
-    for (_, pos) in to_send {
-        let _ = state.sender.try_send(WorkerMessage::Chunk(*pos));
+    if !look_bias {
+        return dist;
+    }
+
+    // A yaw of 0 faces towards positive z, 90 towards negative x.
+    let yaw = f64::from(yaw).to_radians();
+    let (dx, dz) = (f64::from(pos.x - view_pos.x), f64::from(pos.z - view_pos.z));
+
+    if dx * -yaw.sin() + dz * yaw.cos() > 0.0 {
+        (dist as f64 * 0.5) as Priority
+    } else {
+        dist
     }
 }
 
-fn set_terrain_settings(
-    settings: ResMut<TerrainSettings>,
-    mut update: ResMut<UpdateTerrainSettings>,
-    mut state: ResMut<WorldGenState>,
-    mut instances: Query<&mut Instance>,
+/// Pregenerated chunks stay loaded if they are inserted into the instance at
+/// startup.
+fn is_kept_loaded(config: &WorldConfig, pos: ChunkPos) -> bool {
+    config.load_pregen_chunks && config.pregen_chunks.contains(pos.x, pos.z)
+}
+
+/// Pregenerated chunks that aren't inserted into the instance at startup are
+/// sent through the chunk workers like any other chunk.
+fn pending_pregen_chunks(config: &WorldConfig) -> HashMap<ChunkPos, Option<Priority>> {
+    if config.load_pregen_chunks {
+        return HashMap::new();
+    }
+
+    pregen_positions(&config.pregen_chunks)
+        .map(|(x, z)| (ChunkPos::new(x, z), Some((x + z) as u64)))
+        .collect()
+}
+
+/// Returns `true` if there is no column in the chunk a player could stand on.
+#[must_use]
+pub fn is_empty_terrain(chunk: &Chunk) -> bool {
+    iproduct!(0..16, 0..16).all(|(x, z)| surface_height(chunk, x, z).is_none())
+}
+
+/// Warns operators once per terrain settings if the spawn chunk is empty,
+/// which usually means the settings disable too many features. If
+/// `revert_empty_terrain` is set, the settings are reset to the defaults.
+fn check_empty_terrain(
+    instances: Query<&Instance>,
     instances_list: Res<Instances>,
+    state: Res<WorldGenState>,
+    spawn: Res<SpawnPos>,
+    mut settings: ResMut<TerrainSettings>,
+    mut update: ResMut<UpdateTerrainSettings>,
     mut clients: Query<&mut Client>,
+    mut checked: Local<Option<u64>>,
 ) {
-    if update.0 {
-        update.0 = false;
-        let _ = state
-            .sender
-            .try_send(WorkerMessage::SetTerrainSettings(settings.clone()));
-        let mut instance = instances.get_mut(instances_list.terrain).unwrap();
+    if *checked == Some(state.settings_hash) {
+        return;
+    }
 
-        instance.clear_chunks();
+    let pos = ChunkPos::new(
+        (spawn.0.x / 16.0).floor() as i32,
+        (spawn.0.z / 16.0).floor() as i32,
+    );
 
-        for mut client in &mut clients {
-            client.send_message("Regenerating terrain".color(Color::RED));
-            client.set_instance(instances_list.wait);
-            client.set_position([0., 203., 0.]);
+    // Only check the chunk once it was generated with the current settings.
+    if state.chunk_hashes.get(&pos) != Some(&state.settings_hash) {
+        return;
+    }
+    *checked = Some(state.settings_hash);
 
-            let view = client.view();
-            let queue_pos = |pos| {
-                if instance.chunk(pos).is_none() {
-                    match state.pending.entry(pos) {
-                        Entry::Occupied(mut oe) => {
-                            if let Some(priority) = oe.get_mut() {
-                                let dist = view.pos.distance_squared(pos);
-                                *priority = (*priority).min(dist);
-                            }
-                        }
-                        Entry::Vacant(ve) => {
-                            let dist = view.pos.distance_squared(pos);
-                            ve.insert(Some(dist));
-                        }
-                    }
-                }
-            };
+    let instance = instances.get(instances_list.terrain()).unwrap();
+    let Some(chunk) = instance.chunk(pos) else {
+        return;
+    };
+
+    if !is_empty_terrain(chunk) {
+        return;
+    }
 
-            // Queue all the new chunks in the view to be sent to the thread pool.
+    let revert = CONFIG.world.revert_empty_terrain;
+    warn!(target: "minecraft::world_gen",
+        "The spawn chunk doesn't contain any terrain, the terrain settings might disable too much{}",
+        if revert { ", reverting to the default settings" } else { "" }
+    );
 
-            view.iter().for_each(queue_pos);
+    let message = if revert {
+        "The terrain is empty, reverting to the default terrain settings."
+    } else {
+        "The terrain is empty, check the terrain settings."
+    };
+    for mut client in &mut clients {
+        if client.op_level() >= 2 {
+            client.send_message(message.color(Color::GOLD));
         }
+    }
 
-        // Collect all the new chunks that need to be loaded this tick.
-        let mut to_send = vec![];
+    if revert {
+        *settings = reverted_settings(&settings);
+        update.0 = true;
+    }
+}
 
-        for (pos, priority) in &mut state.pending.iter_mut() {
-            if let Some(pri) = priority.take() {
-                to_send.push((pri, *pos));
-            }
-        }
+/// The default terrain settings with the seed of `settings`, used if the spawn
+/// chunk is empty and `revert_empty_terrain` is set.
+fn reverted_settings(settings: &TerrainSettings) -> TerrainSettings {
+    TerrainSettings {
+        seed: settings.seed,
+        ..Default::default()
+    }
+}
+
+/// Chunks of the terrain players changed blocks in since they were loaded or
+/// last saved. They are handed back to the chunk workers when they are
+/// unloaded or the world is saved, so the changes are saved even for chunks
+/// that are never unloaded, like the spawn chunks.
+#[derive(Resource, Default, Debug)]
+pub struct ModifiedChunks(pub HashSet<ChunkPos>);
 
-        // Sort chunks by ascending priority.
-        to_send.sort_unstable_by_key(|(pri, _)| *pri);
+impl ModifiedChunks {
+    pub fn mark(&mut self, pos: BlockPos) {
+        self.0
+            .insert(ChunkPos::new(pos.x.div_euclid(16), pos.z.div_euclid(16)));
+    }
 
-        // Send the sorted chunks to be loaded.
-        for (_, pos) in to_send {
-            let _ = state.sender.try_send(WorkerMessage::Chunk(pos));
+    /// Marks all chunks between the two corners.
+    pub fn mark_area(&mut self, min: BlockPos, max: BlockPos) {
+        for (x, z) in iproduct!(
+            min.x.div_euclid(16)..=max.x.div_euclid(16),
+            min.z.div_euclid(16)..=max.z.div_euclid(16)
+        ) {
+            self.0.insert(ChunkPos::new(x, z));
         }
     }
 }
 
-pub fn inspector_ui(
-    mut egui_context: bevy_egui::EguiContexts,
-    mut settings: ResMut<TerrainSettings>,
-    mut update: ResMut<UpdateTerrainSettings>,
-) {
-    egui::Window::new("Terrain Settings").show(egui_context.ctx_mut(), |ui| {
-        egui::ScrollArea::vertical().show(ui, |ui| {
-            ui.collapsing("Gravel", |ui| {
-                egui::Grid::new("gravel_settings").show(ui, |ui| {
-                    ui.checkbox(&mut settings.enable_gravel, "Enable gravel");
-                    ui.end_row();
+/// NBT of the block entities in the terrain, like the text of signs, saved
+/// next to the region files. Changing a block entity doesn't mark its chunk in
+/// [`ModifiedChunks`], that's up to the caller.
+#[derive(Resource, Debug)]
+pub struct BlockEntities {
+    entities: HashMap<BlockPos, Vec<u8>>,
+    /// Whether the block entities are written to disk.
+    saving: bool,
+    settings_hash: u64,
+    /// The systems don't run on the runtime's threads, so the sidecar files
+    /// are written through its handle.
+    runtime: tokio::runtime::Handle,
+}
 
-                    ui.label("Point scaling");
-                    ui.add(
-                        egui::DragValue::new(&mut settings.gravel_height.point_scaleing).speed(0.1),
-                    );
-                    ui.end_row();
+impl BlockEntities {
+    /// Loads the saved block entities of the terrain generated with the
+    /// settings of `settings_hash`.
+    ///
+    /// # Panics
+    /// - not called from within the tokio runtime
+    #[must_use]
+    pub fn load(saving: bool, settings_hash: u64) -> Self {
+        let mut entities = Self {
+            entities: HashMap::new(),
+            saving,
+            settings_hash,
+            runtime: tokio::runtime::Handle::current(),
+        };
 
-                    ui.label("Octaves");
-                    ui.add(egui::DragValue::new(&mut settings.gravel_height.octaves).speed(0.1));
-                    ui.end_row();
+        match read_nbt_sidecars(settings_hash) {
+            Ok(chunks) => {
+                for chunk in chunks {
+                    chunk.restore(&mut entities);
+                }
+            }
+            Err(e) => warn!(target: "minecraft::save", "Unable to load the block entities: {e}"),
+        }
 
-                    ui.label("Lacunarity");
-                    ui.add(egui::DragValue::new(&mut settings.gravel_height.lacunarity).speed(0.1));
-                    ui.end_row();
+        entities
+    }
 
-                    ui.label("Persistence");
-                    ui.add(
-                        egui::DragValue::new(&mut settings.gravel_height.persistence).speed(0.1),
-                    );
-                    ui.end_row();
-                });
-            });
+    #[must_use]
+    pub fn get(&self, pos: BlockPos) -> Option<fastnbt::Value> {
+        fastnbt::from_bytes(self.entities.get(&pos)?).ok()
+    }
 
-            ui.collapsing("Sand", |ui| {
-                egui::Grid::new("sand_settings").show(ui, |ui| {
-                    ui.checkbox(&mut settings.enable_sand, "Enable sand");
-                    ui.end_row();
+    pub fn set(&mut self, pos: BlockPos, nbt: &fastnbt::Value) {
+        match fastnbt::to_bytes(nbt) {
+            Ok(bytes) => {
+                self.entities.insert(pos, bytes);
+            }
+            Err(e) => warn!(target: "minecraft::save", "Invalid block entity NBT: {e}"),
+        }
+    }
 
-                    ui.label("Sand offset");
-                    ui.add(egui::DragValue::new(&mut settings.sand_offset).speed(0.1));
-                    ui.end_row();
+    pub fn remove(&mut self, pos: BlockPos) -> bool { self.entities.remove(&pos).is_some() }
 
-                    ui.label("Point scaling");
-                    ui.add(
-                        egui::DragValue::new(&mut settings.sand_height.point_scaleing).speed(0.1),
-                    );
-                    ui.end_row();
+    /// The positions and raw NBT of the block entities in a chunk.
+    pub fn in_chunk(&self, pos: ChunkPos) -> impl Iterator<Item = (BlockPos, &[u8])> {
+        self.entities
+            .iter()
+            .filter(move |(p, _)| p.x.div_euclid(16) == pos.x && p.z.div_euclid(16) == pos.z)
+            .map(|(p, nbt)| (*p, nbt.as_slice()))
+    }
 
-                    ui.label("Octaves");
-                    ui.add(egui::DragValue::new(&mut settings.sand_height.octaves).speed(0.1));
-                    ui.end_row();
+    /// Writes the block entities of the chunks to their sidecar files on a
+    /// blocking task.
+    pub fn save_in_background(&self, chunks: impl IntoIterator<Item = ChunkPos>) {
+        if !self.saving {
+            return;
+        }
 
-                    ui.label("Lacunarity");
-                    ui.add(egui::DragValue::new(&mut settings.sand_height.lacunarity).speed(0.1));
-                    ui.end_row();
+        let chunks = chunks
+            .into_iter()
+            .map(|pos| SaveChunkNbt::from_entities(self, pos))
+            .collect::<Vec<_>>();
+        let settings_hash = self.settings_hash;
 
-                    ui.label("Persistence");
-                    ui.add(egui::DragValue::new(&mut settings.sand_height.persistence).speed(0.1));
-                    ui.end_row();
-                });
-            });
+        self.runtime.spawn_blocking(move || {
+            if let Err(e) = write_nbt_sidecars(settings_hash, chunks) {
+                error!(target: "minecraft::save", "Unable to save the block entities: {e}");
+            }
+        });
+    }
 
-            ui.collapsing("Stone", |ui| {
-                egui::Grid::new("stone_settings").show(ui, |ui| {
-                    ui.checkbox(&mut settings.enable_stone, "Enable stone");
-                    ui.end_row();
+    /// All chunks that contain block entities.
+    #[must_use]
+    pub fn chunks(&self) -> HashSet<ChunkPos> {
+        self.entities
+            .keys()
+            .map(|p| ChunkPos::new(p.x.div_euclid(16), p.z.div_euclid(16)))
+            .collect()
+    }
+}
 
-                    ui.label("Point scaling");
-                    ui.add(egui::DragValue::new(&mut settings.stone_point_scaleing).speed(0.1));
-                    ui.end_row();
+fn remove_unviewed_chunks(
+    mut instances: Query<&mut Instance>,
+    instances_list: Res<Instances>,
+    mut unviewed: ResMut<UnviewedChunks>,
+    mut modified: ResMut<ModifiedChunks>,
+    block_entities: Res<BlockEntities>,
+    state: Res<WorldGenState>,
+) {
+    let mut instance = instances.get_mut(instances_list.terrain()).unwrap();
+    let delay = CONFIG.world.chunk_unload_delay;
+
+    instance.retain_chunks(|pos, chunk| {
+        let viewed = chunk.is_viewed_mut() || is_kept_loaded(&CONFIG.world, pos);
+        if keep_unviewed(&mut unviewed.0, pos, viewed, delay) {
+            return true;
+        }
+
+        if modified.0.remove(&pos) {
+            block_entities.save_in_background([pos]);
+            send_modified_chunk(&state, pos, |x, y, z| chunk.block_state(x, y, z));
+        }
+
+        false
+    });
+
+    // Chunks can also be removed by clearing the instance.
+    unviewed.0.retain(|pos, _| instance.chunk(*pos).is_some());
+}
+
+/// Counts the ticks the chunk at `key` hasn't been viewed and returns whether
+/// it stays loaded. Chunks are unloaded once nobody saw them for more than
+/// `delay` ticks.
+fn keep_unviewed<K: Copy + Eq + Hash>(
+    unviewed: &mut HashMap<K, u32>,
+    key: K,
+    viewed: bool,
+    delay: u32,
+) -> bool {
+    if viewed {
+        unviewed.remove(&key);
+        return true;
+    }
+
+    let ticks = unviewed.entry(key).or_default();
+    *ticks += 1;
+
+    if *ticks <= delay {
+        return true;
+    }
+
+    unviewed.remove(&key);
+    false
+}
+
+/// Hands a copy of a chunk players modified to the chunk workers, which cache
+/// it and save it once it is evicted or the world is saved.
+fn send_modified_chunk(
+    state: &WorldGenState,
+    pos: ChunkPos,
+    block_state: impl Fn(usize, usize, usize) -> BlockState,
+) {
+    let mut copy = Chunk::new(SECTION_COUNT);
+    for (x, y, z) in iproduct!(0..16, 0..SECTION_COUNT * 16, 0..16) {
+        copy.set_block_state(x, y, z, block_state(x, y, z));
+    }
+
+    if let Err(e) = state
+        .sender
+        .try_send(WorkerMessage::ChunkModified(pos, copy))
+    {
+        error!(target: "minecraft::world_gen",
+            "Unable to send the modified chunk {pos:?} to the chunk workers: {e}"
+        );
+    }
+}
+
+/// Chunks of a player's view that weren't queued yet because the view changed
+/// by more than `max_view_chunks_per_tick` chunks, sorted by priority.
+#[derive(Component, Default, Debug)]
+pub struct ViewBacklog(Vec<ChunkPos>);
+
+/// Sorts the chunks by priority and splits them into the `limit` chunks queued
+/// this tick and the rest that is queued in the following ticks.
+#[must_use]
+pub fn split_view_chunks(
+    mut chunks: Vec<(Priority, ChunkPos)>,
+    limit: usize,
+) -> (Vec<ChunkPos>, Vec<ChunkPos>) {
+    chunks.sort_by_key(|(priority, _)| *priority);
+
+    let later = chunks.split_off(limit.min(chunks.len()));
+    (
+        chunks.into_iter().map(|(_, pos)| pos).collect(),
+        later.into_iter().map(|(_, pos)| pos).collect(),
+    )
+}
+
+fn update_client_views(
+    mut commands: Commands,
+    instances: Query<&mut Instance>,
+    instances_list: Res<Instances>,
+    mut clients: Query<(Entity, &mut Client, Option<&mut ViewBacklog>)>,
+    mut state: ResMut<WorldGenState>,
+) {
+    let instance = instances.get(instances_list.terrain()).unwrap();
+    let state = &mut *state;
+    let max_pending = CONFIG.world.max_pending_chunks;
+
+    let rescan = rescan_views(state, max_pending);
+
+    for (entity, client, backlog) in &mut clients {
+        // Chunks of the additional dimensions are requested by
+        // `update_dimension_views`.
+        if instances_list.dimension_name(client.instance()).is_some() {
+            continue;
+        }
+
+        let view = client.view();
+        let yaw = client.yaw();
+        let priority = |pos| chunk_priority(view.pos, pos, yaw, CONFIG.world.look_bias);
+
+        let chunks = new_view_chunks(
+            view,
+            client.old_view(),
+            backlog.as_ref().map(|backlog| backlog.0.as_slice()),
+            client.is_added() || rescan,
+        );
+
+        let chunks = chunks
+            .into_iter()
+            .filter(|pos| instance.chunk(*pos).is_none())
+            .map(|pos| (priority(pos), pos))
+            .collect::<Vec<_>>();
+
+        let (now, later) = split_view_chunks(chunks, CONFIG.world.max_view_chunks_per_tick);
+        match backlog {
+            Some(mut backlog) => backlog.0 = later,
+            None if !later.is_empty() => {
+                commands.entity(entity).insert(ViewBacklog(later));
+            }
+            None => {}
+        }
+
+        // Queue the chunks to be sent to the thread pool.
+        for pos in now {
+            queue_view_chunk(state, pos, priority(pos), max_pending);
+        }
+    }
+}
+
+/// Collects the new chunks of a view, including the chunks of the `backlog`
+/// that didn't fit into the previous ticks and are still in the view. The whole
+/// view is collected if it is new or has to be queued again.
+fn new_view_chunks(
+    view: ChunkView,
+    old_view: ChunkView,
+    backlog: Option<&[ChunkPos]>,
+    whole_view: bool,
+) -> Vec<ChunkPos> {
+    if whole_view {
+        return view.iter().collect();
+    }
+
+    let mut chunks = vec![];
+    if let Some(backlog) = backlog {
+        chunks.extend(backlog.iter().filter(|pos| view.contains(**pos)));
+    }
+    if old_view != view {
+        chunks.extend(view.diff(old_view));
+    }
+
+    chunks
+}
+
+/// Whether the complete views should be queued again because chunks were
+/// rejected and there is room again.
+fn rescan_views(state: &mut WorldGenState, max_pending: usize) -> bool {
+    let rescan = state.views_incomplete && state.pending.len() < max_pending;
+    if rescan {
+        state.views_incomplete = false;
+    }
+
+    rescan
+}
+
+/// Queues a chunk of a player's view, new chunks are rejected while there are
+/// already `max_pending` chunks pending.
+fn queue_view_chunk(
+    state: &mut WorldGenState,
+    pos: ChunkPos,
+    priority: Priority,
+    max_pending: usize,
+) {
+    let full = state.pending.len() >= max_pending;
+    match state.pending.entry(pos) {
+        Entry::Occupied(mut oe) => {
+            if let Some(pending) = oe.get_mut() {
+                *pending = (*pending).min(priority);
+            }
+        }
+        Entry::Vacant(_) if full => state.views_incomplete = true,
+        Entry::Vacant(ve) => {
+            ve.insert(Some(priority));
+        }
+    }
+}
+
+/// Tells players with missing chunks in their view to wait while no new chunks
+/// are accepted.
+fn enforce_view_distance_limit(
+    instances: Query<&Instance>,
+    instances_list: Res<Instances>,
+    mut clients: Query<&mut Client>,
+    state: Res<WorldGenState>,
+    mut ticks: Local<u32>,
+) {
+    if !state.views_incomplete {
+        return;
+    }
+
+    // The action bar stays visible for a few seconds, there is no need to send it
+    // every tick.
+    *ticks = ticks.wrapping_add(1);
+    if *ticks % 20 != 1 {
+        return;
+    }
+
+    let instance = instances.get(instances_list.terrain()).unwrap();
+    for mut client in &mut clients {
+        if client
+            .view()
+            .iter()
+            .any(|pos| instance.chunk(pos).is_none())
+        {
+            client.set_action_bar("Generating terrain, please wait.".color(Color::GOLD));
+        }
+    }
+}
+
+/// Removes the chunks of the additional dimensions nobody saw for
+/// `chunk_unload_delay` ticks, except for their spawn chunk.
+fn remove_unviewed_dimension_chunks(
+    mut instances: Query<&mut Instance>,
+    instances_list: Res<Instances>,
+    mut unviewed: Local<HashMap<(Entity, ChunkPos), u32>>,
+) {
+    let delay = CONFIG.world.chunk_unload_delay;
+
+    for (_, entity) in instances_list.dimensions() {
+        let Ok(mut instance) = instances.get_mut(entity) else {
+            continue;
+        };
+
+        instance.retain_chunks(|pos, chunk| {
+            let viewed = chunk.is_viewed_mut() || pos == ChunkPos::new(0, 0);
+            keep_unviewed(&mut *unviewed, (entity, pos), viewed, delay)
+        });
+    }
+}
+
+/// Requests the missing chunks in the views of the players in the additional
+/// dimensions from the chunk workers, the closest chunks first.
+fn update_dimension_views(
+    instances: Query<&Instance>,
+    instances_list: Res<Instances>,
+    clients: Query<&Client>,
+    mut state: ResMut<WorldGenState>,
+) {
+    let state = &mut *state;
+
+    for client in &clients {
+        let Some(name) = instances_list.dimension_name(client.instance()) else {
+            continue;
+        };
+        let Ok(instance) = instances.get(client.instance()) else {
+            continue;
+        };
+
+        let view = client.view();
+        let mut chunks = view
+            .iter()
+            .filter(|pos| instance.chunk(*pos).is_none())
+            .filter(|pos| !state.dimension_pending.contains(&(name.to_string(), *pos)))
+            .collect::<Vec<_>>();
+        chunks.sort_unstable_by_key(|pos| view.pos.distance_squared(*pos));
+
+        for pos in chunks {
+            if state.dimension_pending.len() >= CONFIG.world.max_chunk_queue_depth {
+                return;
+            }
+
+            // The chunks are requested again next tick.
+            if let Err(e) = state
+                .sender
+                .try_send(WorkerMessage::DimensionChunk(name.to_string(), pos))
+            {
+                error!(target: "minecraft::world_gen",
+                    "Unable to send chunk {pos:?} of {name} to the chunk workers: {e}"
+                );
+                return;
+            }
+            state.dimension_pending.insert((name.to_string(), pos));
+        }
+    }
+}
+
+fn send_recv_chunks(
+    mut instances: Query<&mut Instance>,
+    instances_list: Res<Instances>,
+    state: ResMut<WorldGenState>,
+    mut regeneration: Option<ResMut<Regeneration>>,
+    mut chunk_infos: ResMut<ChunkInfos>,
+    mut minimap: ResMut<Minimap>,
+    mut cache_stats: ResMut<CacheStats>,
+    mut clients: Query<&mut Client>,
+    mut generated: EventWriter<ChunkGeneratedEvent>,
+    #[cfg(feature = "profiling")] mut timings: ResMut<ChunkTimings>,
+) {
+    let mut instance = instances.get_mut(instances_list.terrain()).unwrap();
+    let state = state.into_inner();
+    let mut dimension_chunks = vec![];
+
+    // Insert the chunks that are finished generating into the instance.
+    for response in state.receiver.drain() {
+        match response {
+            WorkerResponse::Chunk(pos, chunk, hash) => {
+                state.in_flight = state.in_flight.saturating_sub(1);
+
+                // The chunk was requested before the settings changed, generate it again.
+                if hash != state.settings_hash {
+                    state.pending.insert(pos, Some(0));
+                    continue;
+                }
+
+                instance.insert_chunk(pos, chunk);
+                generated.send(ChunkGeneratedEvent {
+                    instance: instances_list.terrain(),
+                    pos,
                 });
-            });
+                assert!(state.pending.remove(&pos).is_some());
+                state.chunk_hashes.insert(pos, hash);
 
-            ui.checkbox(&mut settings.enable_grass, "Enable grass");
-            ui.checkbox(&mut settings.enable_water, "Enable water");
-            ui.horizontal(|ui| {
-                ui.label("Seed");
-                ui.add(egui::DragValue::new(&mut settings.seed).speed(0.1));
-                if ui.button("Random").clicked() {
-                    settings.seed = rand::random();
+                if let Some(regeneration) = &mut regeneration {
+                    regeneration.chunk_generated(pos);
                 }
-            });
+            }
+            WorkerResponse::ChunkInfo(pos, info) => {
+                cache_stats.record_lookup(info.cached);
+                chunk_infos.record(pos, info);
+            }
+            #[cfg(feature = "profiling")]
+            WorkerResponse::Timing(timing) => timings.record(timing),
+            WorkerResponse::GetTerrainSettings(_) => todo!("Not yet implemented"),
+            WorkerResponse::TerrainSettingsSet => {
+                if let Some(regeneration) = &mut regeneration {
+                    regeneration.settings_set = true;
+                }
+            }
+            WorkerResponse::FlushComplete { count } => {
+                info!(target: "minecraft::world_gen", "World saved: {count} chunks flushed");
 
-            ui.separator();
+                for mut client in &mut clients {
+                    if client.op_level() >= 2 {
+                        client
+                            .send_message(format!("World saved: {count} chunks flushed.").italic());
+                    }
+                }
+            }
+            WorkerResponse::PrewarmComplete(x, z, count) => {
+                info!(target: "minecraft::world_gen", "Prewarmed {count} chunks of region {x} {z}");
 
-            ui.horizontal(|ui| {
-                if ui.button("Update").clicked() {
-                    update.0 = true;
+                for mut client in &mut clients {
+                    if client.op_level() >= 3 {
+                        client.send_message(
+                            format!("Prewarmed {count} chunks of region {x} {z}.").italic(),
+                        );
+                    }
                 }
+            }
+            WorkerResponse::MapExported(path, result) => {
+                let msg = match result {
+                    Ok((width, height)) => {
+                        info!(target: "minecraft::world_gen",
+                            "Exported a {width}x{height} map to {}", path.display()
+                        );
+                        format!("Exported a {width}x{height} map to {}.", path.display()).italic()
+                    }
+                    Err(e) => {
+                        warn!(target: "minecraft::world_gen", "Unable to export the map: {e}");
+                        format!("Unable to export the map: {e}").color(Color::RED)
+                    }
+                };
 
-                if ui.button("Reset").clicked() {
-                    *settings = TerrainSettings::default();
-                    update.0 = true;
+                for mut client in &mut clients {
+                    if client.op_level() >= 3 {
+                        client.send_message(msg.clone());
+                    }
+                }
+            }
+            WorkerResponse::StaleRegionsCleaned(result) => {
+                let msg = match result {
+                    Ok(count) => {
+                        info!(target: "minecraft::save",
+                            "Cleaned up {count} regions of other terrain settings"
+                        );
+                        format!("Cleaned up {count} regions of other terrain settings.").italic()
+                    }
+                    Err(e) => {
+                        error!(target: "minecraft::save", "Unable to clean up stale regions: {e}");
+                        format!("Unable to clean up stale regions: {e}").color(Color::RED)
+                    }
+                };
+
+                for mut client in &mut clients {
+                    if client.op_level() >= 3 {
+                        client.send_message(msg.clone());
+                    }
                 }
+            }
+            WorkerResponse::DimensionChunk(name, pos, chunk) => {
+                state.dimension_pending.remove(&(name.clone(), pos));
+                dimension_chunks.push((name, pos, chunk));
+            }
+            WorkerResponse::HeightMap(pos, heights) => {
+                minimap.tiles.insert(pos, heights);
+            }
+            WorkerResponse::CacheStats {
+                len,
+                capacity,
+                evictions,
+            } => {
+                cache_stats.len = len;
+                cache_stats.capacity = capacity;
+                cache_stats.evictions = evictions;
+            }
+        }
+    }
+
+    for (name, pos, chunk) in dimension_chunks {
+        let Some(entity) = instances_list.get(&name) else {
+            continue;
+        };
+        if let Ok(mut dimension) = instances.get_mut(entity) {
+            dimension.insert_chunk(pos, chunk);
+            generated.send(ChunkGeneratedEvent {
+                instance: entity,
+                pos,
             });
-        });
-    });
+        }
+    }
+
+    send_pending_chunks(state, CONFIG.world.max_chunk_queue_depth);
+}
+
+/// Sends the pending chunks with a priority to the chunk workers, the most
+/// important first, without exceeding `max_queue_depth` chunks in flight.
+fn send_pending_chunks(state: &mut WorldGenState, max_queue_depth: usize) {
+    // Collect all the new chunks that need to be loaded.
+    let mut to_send = vec![];
+
+    for (pos, priority) in &state.pending {
+        if let Some(pri) = priority {
+            to_send.push((*pri, *pos));
+        }
+    }
+
+    // Sort chunks by ascending priority.
+    to_send.sort_unstable_by_key(|(pri, _)| *pri);
+
+    // Only send as many chunks as fit into the queue, the rest stays pending
+    // until the workers catch up.
+    let budget = max_queue_depth.saturating_sub(state.in_flight);
+
+    if to_send.len() > budget {
+        if !state.queue_full {
+            warn!(target: "minecraft::world_gen",
+                "Chunk queue is full ({} chunks in flight), {} chunks have to wait",
+                state.in_flight,
+                to_send.len() - budget
+            );
+        }
+        state.queue_full = true;
+        to_send.truncate(budget);
+    } else {
+        state.queue_full = false;
+    }
+
+    // Send the sorted chunks to be loaded.
+    if !to_send.is_empty() {
+        let batch: Vec<_> = to_send.into_iter().map(|(_, pos)| pos).collect();
+        let positions = batch.clone();
+
+        match state.sender.try_send(WorkerMessage::BatchChunks(batch)) {
+            Ok(()) => {
+                for pos in positions {
+                    state.pending.insert(pos, None);
+                    state.in_flight += 1;
+                }
+
+                if state.send_failed {
+                    info!(target: "minecraft::world_gen", "Chunk workers are accepting chunks again");
+                    state.send_failed = false;
+                }
+            }
+            // The chunks keep their priority and are sent again next tick.
+            Err(e) => {
+                if !state.send_failed {
+                    error!(target: "minecraft::world_gen",
+                        "Unable to send {} chunks to the chunk workers: {e}", positions.len()
+                    );
+                }
+                state.send_failed = true;
+            }
+        }
+    }
+}
+
+/// Queues the chunk requested with `ctp` and teleports the player onto its
+/// surface once it is loaded.
+fn chunk_teleport(
+    mut commands: Commands,
+    mut events: EventReader<CommandEvent>,
+    mut clients: Query<(Entity, &mut Client, Option<&ChunkTeleport>)>,
+    instances: Query<&Instance>,
+    instances_list: Res<Instances>,
+    mut state: ResMut<WorldGenState>,
+) {
+    let instance = instances.get(instances_list.terrain()).unwrap();
+
+    for event in events.iter().filter(|e| e.is("ctp")) {
+        let Ok((_, mut client, _)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let Some(pos) = parse_chunk_pos(&event.args) else {
+            client.send_message(format!("Usage: {CTP_USAGE}").color(Color::RED));
+            continue;
+        };
+
+        if instance.chunk(pos).is_none() {
+            queue_first(&mut state, pos);
+            client.send_message(format!("Loading chunk {} {}...", pos.x, pos.z).italic());
+        }
+
+        commands.entity(event.client).insert(ChunkTeleport(pos));
+    }
+
+    for (entity, mut client, teleport) in &mut clients {
+        let Some(ChunkTeleport(pos)) = teleport.copied() else {
+            continue;
+        };
+
+        let Some(chunk) = instance.chunk(pos) else {
+            continue;
+        };
+
+        commands.entity(entity).remove::<ChunkTeleport>();
+
+        let Some(y) = surface_height(chunk, 8, 8) else {
+            client.send_message(
+                format!("Chunk {} {} has no surface to stand on.", pos.x, pos.z).color(Color::RED),
+            );
+            continue;
+        };
+
+        let (x, z) = chunk_center(pos);
+        // Same offset as the spawn position, see `find_spawn`.
+        let position = DVec3::new(f64::from(x) + 0.5, y as f64 - 50.0, f64::from(z) + 0.5);
+
+        client.set_instance(instances_list.terrain());
+        client.set_position(position);
+        client.send_message(
+            format!(
+                "Teleported to chunk {} {} ({:.1} {:.1} {:.1}).",
+                pos.x, pos.z, position.x, position.y, position.z
+            )
+            .italic(),
+        );
+    }
+}
+
+/// Number of chunks `rtp` tries to find a safe landing spot in.
+const RTP_ATTEMPTS: u8 = 5;
+
+/// Teleports the player to a random location, the chunk is generated first if
+/// it isn't loaded.
+fn random_teleport(
+    mut commands: Commands,
+    mut events: EventReader<CommandEvent>,
+    mut clients: Query<(Entity, &mut Client, Option<&RandomTeleport>)>,
+    instances: Query<&Instance>,
+    instances_list: Res<Instances>,
+    mut state: ResMut<WorldGenState>,
+    mut cooldowns: ResMut<RandomTeleportCooldowns>,
+) {
+    let instance = instances.get(instances_list.terrain()).unwrap();
+    let cooldown = Duration::from_secs(CONFIG.world.rtp_cooldown);
+    let now = Instant::now();
+
+    for event in events.iter().filter(|e| e.is("rtp")) {
+        let Ok((_, mut client, running)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        if running.is_some() {
+            client.send_message("Already looking for a random location.".color(Color::RED));
+            continue;
+        }
+
+        if let Some(remaining) = cooldowns.remaining(client.uuid(), now, cooldown) {
+            client.send_message(
+                format!(
+                    "You can teleport again in {}.",
+                    format_duration(Duration::from_secs(remaining.as_secs_f64().ceil() as u64))
+                )
+                .color(Color::RED),
+            );
+            continue;
+        }
+
+        cooldowns.start(client.uuid(), now);
+        client.send_message("Looking for a random location...".italic());
+
+        let teleport = RandomTeleport::new(RTP_ATTEMPTS - 1);
+        queue_first(&mut state, teleport.pos);
+        commands.entity(event.client).insert(teleport);
+    }
+
+    for (entity, mut client, teleport) in &mut clients {
+        let Some(teleport) = teleport.copied() else {
+            continue;
+        };
+
+        let Some(chunk) = instance.chunk(teleport.pos) else {
+            continue;
+        };
+
+        let (x, z) = teleport.column;
+        let Some((x, z, y)) = safe_landing(chunk, x, z) else {
+            if teleport.attempts_left == 0 {
+                commands.entity(entity).remove::<RandomTeleport>();
+                client.send_message("Unable to find a safe location.".color(Color::RED));
+                continue;
+            }
+
+            let teleport = RandomTeleport::new(teleport.attempts_left - 1);
+            queue_first(&mut state, teleport.pos);
+            commands.entity(entity).insert(teleport);
+            continue;
+        };
+
+        commands.entity(entity).remove::<RandomTeleport>();
+
+        // Same offset as the spawn position, see `find_spawn`.
+        let position = DVec3::new(
+            f64::from(teleport.pos.x * 16) + x as f64 + 0.5,
+            y as f64 - 50.0,
+            f64::from(teleport.pos.z * 16) + z as f64 + 0.5,
+        );
+
+        client.set_instance(instances_list.terrain());
+        client.set_position(position);
+        client.send_message(
+            format!(
+                "Teleported to {:.1} {:.1} {:.1}.",
+                position.x, position.y, position.z
+            )
+            .italic(),
+        );
+    }
+}
+
+/// Shows which worker provided a chunk and how long it took, defaults to the
+/// chunk the player is in.
+fn chunk_info(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    chunk_infos: Res<ChunkInfos>,
+) {
+    for event in events.iter().filter(|e| e.is("chunkinfo")) {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        match chunk_info_message(&chunk_infos, &event.args, client.view().pos) {
+            Ok(message) => client.send_message(message.italic()),
+            Err(e) => client.send_message(e.color(Color::RED)),
+        }
+    }
+}
+
+/// The answer to `chunkinfo`, `current` is the chunk the player is in.
+///
+/// # Errors
+/// - the arguments aren't a chunk position
+fn chunk_info_message(
+    chunk_infos: &ChunkInfos,
+    args: &[String],
+    current: ChunkPos,
+) -> Result<String, String> {
+    let pos = if args.is_empty() {
+        current
+    } else {
+        parse_chunk_pos(args).ok_or("Usage: chunkinfo [<chunk x> <chunk z>]")?
+    };
+
+    let Some(info) = chunk_infos.get(pos) else {
+        return Ok(format!("No information about chunk {} {}.", pos.x, pos.z));
+    };
+
+    let source = if info.cached {
+        "cache"
+    } else if info.saved {
+        "region file"
+    } else {
+        "generator"
+    };
+
+    Ok(format!(
+        "Chunk {} {}: provided by {} from the {source} in {:?}",
+        pos.x, pos.z, info.worker, info.duration
+    ))
+}
+
+/// Resizes the chunk cache of the workers.
+fn cache_size(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    state: Res<WorldGenState>,
+) {
+    for event in events.iter().filter(|e| e.is("cachesize")) {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        match resize_cache(&state, &event.args) {
+            Ok(capacity) => {
+                info!(target: "minecraft::world_gen",
+                    "{} resized the chunk cache to {capacity} chunks", client.username().to_string()
+                );
+                client.send_message(
+                    format!("Resized the chunk cache to {capacity} chunks.").italic(),
+                );
+            }
+            Err(e) => client.send_message(e.color(Color::RED)),
+        }
+    }
+}
+
+/// Sends the capacity given to `cachesize` to the chunk workers.
+///
+/// # Errors
+/// - the argument is missing or not a size of at least 1
+/// - the chunk workers don't accept messages
+fn resize_cache(state: &WorldGenState, args: &[String]) -> Result<NonZeroUsize, String> {
+    let capacity = args
+        .first()
+        .and_then(|arg| arg.parse::<NonZeroUsize>().ok())
+        .ok_or_else(|| "Usage: cachesize <chunks>, the size has to be at least 1.".to_string())?;
+
+    state
+        .sender
+        .try_send(WorkerMessage::ResizeCache(capacity))
+        .map_err(|e| format!("Unable to resize the chunk cache: {e}"))?;
+
+    Ok(capacity)
+}
+
+/// Whether the chunk workers are paused with `chunkworker pause`.
+#[derive(Resource, Default, Debug)]
+pub struct ChunkWorkerStatus {
+    pub paused: bool,
+}
+
+/// Pauses or resumes the chunk workers, e.g. for disk maintenance.
+fn chunk_worker_command(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    state: Res<WorldGenState>,
+    mut status: ResMut<ChunkWorkerStatus>,
+) {
+    for event in events.iter().filter(|e| e.is("chunkworker")) {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let paused = match set_paused(&state, &mut status, &event.args) {
+            Ok(paused) => paused,
+            Err(e) => {
+                client.send_message(e.color(Color::RED));
+                continue;
+            }
+        };
+
+        let action = if paused { "paused" } else { "resumed" };
+        info!(target: "minecraft::world_gen",
+            "{} {action} the chunk workers", client.username().to_string()
+        );
+        client.send_message(format!("Chunk workers {action}.").italic());
+    }
+}
+
+/// Sends the `pause` or `resume` given to `chunkworker` to the chunk workers,
+/// returns whether they are paused now.
+///
+/// # Errors
+/// - the argument is neither `pause` nor `resume`
+/// - the chunk workers don't accept messages
+fn set_paused(
+    state: &WorldGenState,
+    status: &mut ChunkWorkerStatus,
+    args: &[String],
+) -> Result<bool, String> {
+    let (message, paused) = match args.first().map(String::as_str) {
+        Some("pause") => (WorkerMessage::Pause, true),
+        Some("resume") => (WorkerMessage::Resume, false),
+        _ => return Err("Usage: chunkworker pause | chunkworker resume".to_string()),
+    };
+
+    state
+        .sender
+        .try_send(message)
+        .map_err(|e| format!("Unable to send the message to the chunk workers: {e}"))?;
+
+    status.paused = paused;
+    Ok(paused)
+}
+
+/// Saves all cached chunks and the modified chunks that are still loaded, the
+/// operators are notified once the chunk workers are done.
+fn save_world(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    instances: Query<&Instance>,
+    instances_list: Res<Instances>,
+    mut modified: ResMut<ModifiedChunks>,
+    state: Res<WorldGenState>,
+    block_entities: Res<BlockEntities>,
+) {
+    for event in events.iter().filter(|e| e.is("save")) {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        block_entities.save_in_background(block_entities.chunks());
+
+        // The workers handle messages in order, so the chunks are cached before
+        // the cache is flushed.
+        let instance = instances.get(instances_list.terrain()).unwrap();
+        for pos in std::mem::take(&mut modified.0) {
+            if let Some(chunk) = instance.chunk(pos) {
+                send_modified_chunk(&state, pos, |x, y, z| chunk.block_state(x, y, z));
+            }
+        }
+
+        match state.sender.try_send(WorkerMessage::FlushAll) {
+            Ok(()) => {
+                info!(target: "minecraft::world_gen",
+                    "{} started saving the world", client.username().to_string()
+                );
+                client.send_message("Saving the world...".italic());
+            }
+            Err(e) => {
+                client.send_message(format!("Unable to save the world: {e}").color(Color::RED));
+            }
+        }
+    }
+}
+
+/// Loads a region into the cache of the chunk workers, the operators are
+/// notified once it is done.
+fn prewarm(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    state: Res<WorldGenState>,
+) {
+    for event in events.iter().filter(|e| e.is("prewarm")) {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let (Some(Ok(x)), Some(Ok(z))) = (
+            event.arg(0).map(str::parse::<i64>),
+            event.arg(1).map(str::parse::<i64>),
+        ) else {
+            client.send_message("Usage: prewarm <region x> <region z>".color(Color::RED));
+            continue;
+        };
+
+        match state.sender.try_send(WorkerMessage::PrewarmRegion(x, z)) {
+            Ok(()) => client.send_message(format!("Prewarming region {x} {z}...").italic()),
+            Err(e) => {
+                client.send_message(
+                    format!("Unable to prewarm region {x} {z}: {e}").color(Color::RED),
+                );
+            }
+        }
+    }
+}
+
+/// Lets the chunk workers render their cached chunks to a PNG, the operators
+/// are notified once it is written.
+fn map_command(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    state: Res<WorldGenState>,
+) {
+    for event in events.iter().filter(|e| e.is("map")) {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        if event.arg(0) != Some("export") || event.args.len() > 2 {
+            client.send_message("Usage: map export [name]".color(Color::RED));
+            continue;
+        }
+
+        let name = event.arg(1).unwrap_or("map");
+        let name = name.strip_suffix(".png").unwrap_or(name);
+        if !is_valid_name(name) {
+            client.send_message(
+                "Map names can only contain letters, digits, _ and -.".color(Color::RED),
+            );
+            continue;
+        }
+
+        let path = match map_path(name) {
+            Ok(path) => path,
+            Err(e) => {
+                client.send_message(e.to_string().color(Color::RED));
+                continue;
+            }
+        };
+
+        let pos = client.position();
+        let center = ChunkPos::new((pos.x / 16.0).floor() as i32, (pos.z / 16.0).floor() as i32);
+
+        match state
+            .sender
+            .try_send(WorkerMessage::ExportMap(path, center))
+        {
+            Ok(()) => {
+                client.send_message(format!("Exporting the map to maps/{name}.png...").italic())
+            }
+            Err(e) => {
+                client.send_message(format!("Unable to export the map: {e}").color(Color::RED));
+            }
+        }
+    }
+}
+
+/// Added by `refresh` after moving the player to the wait instance, so all
+/// chunks of their view are sent again when they are moved back the next tick.
+#[derive(Component, Debug, PartialEq)]
+pub struct Refresh {
+    pub instance: Entity,
+    pub position: DVec3,
+}
+
+fn refresh(
+    mut commands: Commands,
+    mut clients: Query<(Entity, &mut Client, Option<&Refresh>)>,
+    mut events: EventReader<CommandEvent>,
+    instances_list: Res<Instances>,
+) {
+    for (entity, mut client, refresh) in &mut clients {
+        if let Some(refresh) = refresh {
+            client.set_instance(refresh.instance);
+            client.set_position(refresh.position);
+            client.send_message("Refreshed your chunks.".italic());
+            commands.entity(entity).remove::<Refresh>();
+        }
+    }
+
+    for event in events.iter().filter(|e| e.is("refresh")) {
+        let Ok((_, mut client, refreshing)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        match start_refresh(&Caller::new(&client), refreshing.is_some(), &event.args) {
+            Ok(refresh) => {
+                commands.entity(event.client).insert(refresh);
+                client.set_instance(instances_list.wait());
+            }
+            Err(e) => client.send_message(e.color(Color::RED)),
+        }
+    }
+}
+
+/// Remembers where the caller is, so they can be moved back there once their
+/// chunks are unloaded.
+///
+/// # Errors
+/// - arguments are given
+/// - the chunks of the caller are already being refreshed
+fn start_refresh(caller: &Caller, refreshing: bool, args: &[String]) -> Result<Refresh, String> {
+    if !args.is_empty() {
+        return Err("Usage: refresh".to_string());
+    }
+    if refreshing {
+        return Err("Your chunks are already being refreshed.".to_string());
+    }
+
+    Ok(Refresh {
+        instance: caller.instance,
+        position: caller.position,
+    })
+}
+
+fn fingerprint(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    settings: Res<TerrainSettings>,
+) {
+    for event in events.iter().filter(|e| e.is("fingerprint")) {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        client.send_message(format!("Fingerprint: {:016x}", settings.fingerprint()).italic());
+        client.send_message(
+            format!(
+                "Settings hash: {:016x}, generator v{}, {SECTION_COUNT} sections",
+                settings.content_hash(),
+                env!("CARGO_PKG_VERSION")
+            )
+            .color(Color::GRAY),
+        );
+    }
+}
+
+/// Deletes the region files further than the given distance in chunks from the
+/// spawn, the online players and the saved player positions.
+///
+/// The regions of the pregenerated area around the spawn are always kept.
+fn prune(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    spawn: Res<SpawnPos>,
+) {
+    for event in events.iter().filter(|e| e.is("prune")) {
+        let distance = match event.arg(0).map(str::parse::<i64>) {
+            None => PRUNE_DISTANCE,
+            Some(Ok(distance)) if distance >= 0 => distance,
+            Some(_) => {
+                if let Ok(mut client) = clients.get_mut(event.client) {
+                    client.send_message("Usage: prune [distance]".color(Color::RED));
+                }
+                continue;
+            }
+        };
+
+        let chunk_at = |pos: [f64; 3]| {
+            ChunkPos::new(
+                (pos[0] / 16.0).floor() as i32,
+                (pos[2] / 16.0).floor() as i32,
+            )
+        };
+        let spawn_distance = distance.max(i64::from(CONFIG.world.pregen_chunks.radius()));
+        let mut anchors = vec![(chunk_at(spawn.0.to_array()), spawn_distance)];
+        anchors.extend(
+            clients
+                .iter()
+                .map(|c| (chunk_at(c.position().to_array()), distance)),
+        );
+        anchors.extend(
+            saved_positions()
+                .into_iter()
+                .map(|pos| (chunk_at(pos), distance)),
+        );
+
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        match prune_regions(&anchors) {
+            Ok((removed, freed)) => {
+                info!(target: "minecraft::world_gen",
+                    "{} pruned {removed} regions, freeing {freed} bytes", client.username().to_string()
+                );
+                client.send_message(
+                    format!(
+                        "Removed {removed} region files, freed {:.2} MiB.",
+                        freed as f64 / (1024.0 * 1024.0)
+                    )
+                    .italic(),
+                );
+            }
+            Err(e) => {
+                client.send_message(format!("Unable to prune the regions: {e}").color(Color::RED));
+            }
+        }
+    }
+}
+
+/// Lets the chunk workers delete or archive the region files of other terrain
+/// settings, the operators are notified once it is done.
+fn clean_regions(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<CommandEvent>,
+    state: Res<WorldGenState>,
+) {
+    for event in events.iter().filter(|e| e.is("cleanregions")) {
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        if CONFIG.world.stale_regions == StaleRegions::Keep {
+            client.send_message(
+                "stale_regions is set to Keep, set it to Delete or Archive to clean up the \
+                 regions of other terrain settings."
+                    .color(Color::RED),
+            );
+            continue;
+        }
+
+        match state.sender.try_send(WorkerMessage::CleanStaleRegions) {
+            Ok(()) => {
+                client
+                    .send_message("Cleaning up the regions of other terrain settings...".italic());
+            }
+            Err(e) => {
+                client
+                    .send_message(format!("Unable to clean up the regions: {e}").color(Color::RED));
+            }
+        }
+    }
+}
+
+/// Shows the players in the wait world how many chunks are left and moves them
+/// back once the regeneration is finished or timed out.
+fn regeneration_progress(
+    mut commands: Commands,
+    regeneration: Option<ResMut<Regeneration>>,
+    instances_list: Res<Instances>,
+    spawn: Res<SpawnPos>,
+    mut clients: Query<&mut Client>,
+) {
+    let Some(mut regeneration) = regeneration else {
+        return;
+    };
+
+    let timeout = Duration::from_secs(CONFIG.world.regeneration_timeout);
+    let finished = regeneration.is_finished();
+
+    if finished || regeneration.timed_out(timeout) {
+        let message = if finished {
+            "Terrain Regenerated".color(Color::GREEN)
+        } else {
+            warn!(target: "minecraft::world_gen",
+                "Terrain regeneration timed out with {} chunks remaining", regeneration.remaining()
+            );
+            "Terrain regeneration is taking too long, some chunks might still be missing"
+                .color(Color::GOLD)
+        };
+
+        clients.par_iter_mut().for_each_mut(|mut c| {
+            c.set_instance(instances_list.terrain());
+            c.set_position(spawn.0);
+            c.send_message(message.clone());
+        });
+
+        commands.remove_resource::<Regeneration>();
+        return;
+    }
+
+    let remaining = regeneration.remaining();
+    if regeneration.reported == Some(remaining) {
+        return;
+    }
+    regeneration.reported = Some(remaining);
+
+    let progress = format!(
+        "Regenerating terrain: {remaining}/{} chunks remaining",
+        regeneration.total()
+    );
+
+    for mut client in &mut clients {
+        match CONFIG.world.regeneration_progress {
+            RegenerationProgress::ActionBar => client.set_action_bar(progress.clone()),
+            RegenerationProgress::Chat => client.send_message(progress.clone().italic()),
+            RegenerationProgress::Off => {}
+        }
+    }
+}
+
+fn set_terrain_settings(
+    mut commands: Commands,
+    settings: ResMut<TerrainSettings>,
+    mut update: ResMut<UpdateTerrainSettings>,
+    mut state: ResMut<WorldGenState>,
+    mut instances: Query<&mut Instance>,
+    instances_list: Res<Instances>,
+    mut clients: Query<&mut Client>,
+) {
+    if update.0 {
+        update.0 = false;
+        if let Err(e) = state
+            .sender
+            .try_send(WorkerMessage::SetTerrainSettings(settings.clone()))
+        {
+            error!(target: "minecraft::world_gen",
+                "Unable to send the terrain settings to the chunk workers: {e}"
+            );
+            return;
+        }
+
+        let mut instance = instances.get_mut(instances_list.terrain()).unwrap();
+        let state = state.into_inner();
+
+        // Only remove the chunks that were generated with different settings.
+        invalidate_chunks(state, settings.content_hash(), |pos| {
+            instance.chunk(pos).is_some()
+        });
+
+        let chunk_hashes = &state.chunk_hashes;
+        instance.retain_chunks(|pos, _| chunk_hashes.contains_key(&pos));
+
+        let mut awaited = vec![];
+
+        for mut client in &mut clients {
+            let view = client.view();
+            let invalidated: Vec<_> = view
+                .iter()
+                .filter(|pos| instance.chunk(*pos).is_none())
+                .collect();
+
+            // Players that don't see any changed chunk can stay where they are.
+            if invalidated.is_empty() {
+                continue;
+            }
+
+            client.send_message("Regenerating terrain".color(Color::RED));
+            client.set_instance(instances_list.wait());
+            client.set_position([0., 203., 0.]);
+
+            for pos in &invalidated {
+                let dist = chunk_priority(view.pos, *pos, client.yaw(), CONFIG.world.look_bias);
+                match state.pending.entry(*pos) {
+                    Entry::Occupied(mut oe) => {
+                        if let Some(priority) = oe.get_mut() {
+                            *priority = (*priority).min(dist);
+                        }
+                    }
+                    Entry::Vacant(ve) => {
+                        ve.insert(Some(dist));
+                    }
+                }
+            }
+
+            awaited.extend(invalidated);
+        }
+
+        // Chunks that are kept loaded are regenerated even if nobody sees them.
+        for (x, z) in pregen_positions(&CONFIG.world.pregen_chunks) {
+            let pos = ChunkPos::new(x, z);
+            if is_kept_loaded(&CONFIG.world, pos) && instance.chunk(pos).is_none() {
+                state
+                    .pending
+                    .entry(pos)
+                    .or_insert(Some(ChunkPos::new(0, 0).distance_squared(pos)));
+            }
+        }
+
+        if !awaited.is_empty() {
+            commands.insert_resource(Regeneration::new(awaited));
+        }
+
+        // The queued chunks are sent to the thread pool by `send_recv_chunks`.
+    }
+}
+
+pub fn inspector_ui(
+    mut egui_context: bevy_egui::EguiContexts,
+    mut settings: ResMut<TerrainSettings>,
+    mut update: ResMut<UpdateTerrainSettings>,
+    mut preset: Local<TerrainPreset>,
+    mut geology: Local<GeologyPreset>,
+    mut error: Local<Option<String>>,
+    mut seed_input: Local<String>,
+    mut show_pending: ResMut<ShowPendingChunks>,
+    mut minimap: ResMut<Minimap>,
+    worker_status: Res<ChunkWorkerStatus>,
+) {
+    egui::Window::new("Terrain Settings").show(egui_context.ctx_mut(), |ui| {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            // Terrain presets replace all settings and are applied right away.
+            egui::ComboBox::from_label("Preset")
+                .selected_text(preset.name())
+                .show_ui(ui, |ui| {
+                    for option in TerrainPreset::ALL {
+                        if !ui
+                            .selectable_value(&mut *preset, option, option.name())
+                            .clicked()
+                        {
+                            continue;
+                        }
+
+                        if let Some(new_settings) = option.settings(&settings) {
+                            *settings = new_settings;
+                            *error = None;
+                            update.0 = true;
+                        }
+                    }
+                });
+
+            // Presets only set the starting values, they can still be fine-tuned below.
+            egui::ComboBox::from_label("Geology")
+                .selected_text(geology.name())
+                .show_ui(ui, |ui| {
+                    for preset in GeologyPreset::ALL {
+                        if ui
+                            .selectable_value(&mut *geology, preset, preset.name())
+                            .clicked()
+                        {
+                            preset.apply(&mut settings);
+                        }
+                    }
+                });
+
+            ui.collapsing("Gravel", |ui| {
+                egui::Grid::new("gravel_settings").show(ui, |ui| {
+                    ui.checkbox(&mut settings.enable_gravel, "Enable gravel");
+                    ui.end_row();
+
+                    ui.label("Point scaling");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.gravel_height.point_scaleing).speed(0.1),
+                    );
+                    ui.end_row();
+
+                    ui.label("Octaves");
+                    ui.add(egui::DragValue::new(&mut settings.gravel_height.octaves).speed(0.1));
+                    ui.end_row();
+
+                    ui.label("Lacunarity");
+                    ui.add(egui::DragValue::new(&mut settings.gravel_height.lacunarity).speed(0.1));
+                    ui.end_row();
+
+                    ui.label("Persistence");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.gravel_height.persistence).speed(0.1),
+                    );
+                    ui.end_row();
+                });
+            });
+
+            ui.collapsing("Sand", |ui| {
+                egui::Grid::new("sand_settings").show(ui, |ui| {
+                    ui.checkbox(&mut settings.enable_sand, "Enable sand");
+                    ui.end_row();
+
+                    ui.label("Sand offset");
+                    ui.add(egui::DragValue::new(&mut settings.sand_offset).speed(0.1));
+                    ui.end_row();
+
+                    ui.label("Point scaling");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.sand_height.point_scaleing).speed(0.1),
+                    );
+                    ui.end_row();
+
+                    ui.label("Octaves");
+                    ui.add(egui::DragValue::new(&mut settings.sand_height.octaves).speed(0.1));
+                    ui.end_row();
+
+                    ui.label("Lacunarity");
+                    ui.add(egui::DragValue::new(&mut settings.sand_height.lacunarity).speed(0.1));
+                    ui.end_row();
+
+                    ui.label("Persistence");
+                    ui.add(egui::DragValue::new(&mut settings.sand_height.persistence).speed(0.1));
+                    ui.end_row();
+                });
+            });
+
+            ui.collapsing("Stone", |ui| {
+                egui::Grid::new("stone_settings").show(ui, |ui| {
+                    ui.checkbox(&mut settings.enable_stone, "Enable stone");
+                    ui.end_row();
+
+                    ui.label("Point scaling");
+                    ui.add(egui::DragValue::new(&mut settings.stone_point_scaleing).speed(0.1));
+                    ui.end_row();
+                });
+            });
+
+            ui.collapsing("Ores", |ui| {
+                ui.checkbox(&mut settings.enable_ores, "Enable ores");
+
+                ore_settings_ui(ui, "Coal", &mut settings.coal_ore);
+                ore_settings_ui(ui, "Iron", &mut settings.iron_ore);
+                ore_settings_ui(ui, "Gold", &mut settings.gold_ore);
+                ore_settings_ui(ui, "Redstone", &mut settings.redstone_ore);
+                ore_settings_ui(ui, "Diamond", &mut settings.diamond_ore);
+            });
+
+            ui.collapsing("Caves", |ui| {
+                egui::Grid::new("cave_settings").show(ui, |ui| {
+                    ui.checkbox(&mut settings.enable_caves, "Enable caves");
+                    ui.end_row();
+
+                    ui.label("Threshold");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.cave_threshold)
+                            .speed(0.001)
+                            .clamp_range(0.0..=1.0),
+                    );
+                    ui.end_row();
+                });
+            });
+
+            ui.collapsing("Trees", |ui| {
+                egui::Grid::new("tree_settings").show(ui, |ui| {
+                    ui.checkbox(&mut settings.enable_trees, "Enable trees");
+                    ui.end_row();
+
+                    ui.label("Tree density");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.tree_density)
+                            .speed(0.001)
+                            .clamp_range(0.0..=1.0),
+                    );
+                    ui.end_row();
+                });
+            });
+
+            ui.collapsing("Vegetation", |ui| {
+                egui::Grid::new("vegetation_settings").show(ui, |ui| {
+                    for (name, thresholds) in [
+                        ("Grass", &mut settings.grass_thresholds),
+                        ("Seagrass", &mut settings.seagrass_thresholds),
+                    ] {
+                        ui.label(format!("{name} threshold"));
+                        ui.add(
+                            egui::DragValue::new(&mut thresholds.short)
+                                .speed(0.01)
+                                .clamp_range(-1.0..=1.0),
+                        );
+                        ui.end_row();
+
+                        ui.label(format!("Tall {} threshold", name.to_lowercase()));
+                        ui.add(
+                            egui::DragValue::new(&mut thresholds.tall)
+                                .speed(0.01)
+                                .clamp_range(-1.0..=1.0),
+                        );
+                        ui.end_row();
+                    }
+                });
+            });
+
+            ui.collapsing("Biomes", |ui| {
+                egui::Grid::new("biome_settings").show(ui, |ui| {
+                    ui.checkbox(&mut settings.biomes.enable_biomes, "Enable biomes");
+                    ui.end_row();
+
+                    ui.label("Climate scaling");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.biomes.temperature.point_scaleing)
+                            .speed(1.0),
+                    );
+                    ui.end_row();
+
+                    ui.label("Snow temperature");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.biomes.snow_temperature)
+                            .speed(0.01)
+                            .clamp_range(0.0..=1.0),
+                    );
+                    ui.end_row();
+
+                    ui.label("Desert temperature");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.biomes.desert_temperature)
+                            .speed(0.01)
+                            .clamp_range(0.0..=1.0),
+                    );
+                    ui.end_row();
+
+                    ui.label("Desert humidity");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.biomes.desert_humidity)
+                            .speed(0.01)
+                            .clamp_range(0.0..=1.0),
+                    );
+                    ui.end_row();
+                });
+            });
+
+            ui.checkbox(&mut settings.enable_grass, "Enable grass");
+            ui.checkbox(&mut settings.enable_water, "Enable water");
+            ui.horizontal(|ui| {
+                ui.label("Water height");
+                ui.add(egui::DragValue::new(&mut settings.water_height).clamp_range(0..=383));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Height scale");
+                ui.add(
+                    egui::DragValue::new(&mut settings.height_scale)
+                        .speed(0.01)
+                        .clamp_range(0.0..=MAX_HEIGHT_SCALE),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Seed");
+                let response = ui.text_edit_singleline(&mut *seed_input);
+                if response.changed() {
+                    *error = apply_seed_input(&seed_input, &mut settings).err();
+                } else if !response.has_focus() {
+                    sync_seed_input(&mut seed_input, settings.seed);
+                }
+
+                if ui.button("Randomize seed").clicked() {
+                    randomize_seed(&mut settings, &mut seed_input);
+                }
+            });
+
+            ui.collapsing("Feature seeds", |ui| {
+                egui::Grid::new("feature_seeds").show(ui, |ui| {
+                    let main_seed = settings.seed;
+                    for (name, seed, offset) in settings.seeds.fields_mut() {
+                        let mut custom = seed.is_some();
+                        if ui.checkbox(&mut custom, name).changed() {
+                            *seed = custom.then(|| main_seed.wrapping_add(offset));
+                        }
+
+                        match seed {
+                            Some(seed) => ui.add(egui::DragValue::new(seed)),
+                            None => ui.label(format!("{}", main_seed.wrapping_add(offset))),
+                        };
+                        ui.end_row();
+                    }
+                });
+            });
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("Update").clicked() {
+                    *error = settings.validate().err();
+                    update.0 = error.is_none();
+                }
+
+                if ui.button("Reset").clicked() {
+                    *settings = TerrainSettings::default();
+                    *error = None;
+                    update.0 = true;
+                }
+            });
+
+            if let Some(error) = &*error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+
+            ui.checkbox(&mut show_pending.0, "Show pending chunks");
+            ui.checkbox(&mut minimap.show, "Show minimap");
+
+            if worker_status.paused {
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    "Chunk workers are paused, resume them with chunkworker resume",
+                );
+            } else {
+                ui.label("Chunk workers are running");
+            }
+
+            ui.label(format!("Tick rate: {} ticks/s", CONFIG.server.tick_rate));
+        });
+    });
+
+    // Editing any setting switches to `Custom`, undoing the edit switches back.
+    *preset = TerrainPreset::detect(&settings);
+}
+
+/// Whether the window plotting the pending chunks is shown.
+#[derive(Resource, Default, Debug)]
+pub struct ShowPendingChunks(pub bool);
+
+/// A pending chunk in the plot of [`pending_chunks_ui`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PendingPoint {
+    /// Position of the chunk relative to the spawn chunk.
+    pub offset: (i32, i32),
+    pub color: egui::Color32,
+}
+
+/// Maps the pending chunks to points relative to `spawn`. Chunks that will be
+/// sent to the workers next are green, the ones sent last are red and chunks
+/// that were already sent are gray.
+#[must_use]
+pub fn pending_plot_points(
+    pending: &HashMap<ChunkPos, Option<Priority>>,
+    spawn: ChunkPos,
+) -> Vec<PendingPoint> {
+    let max_priority = pending.values().flatten().max().copied().unwrap_or(0);
+
+    pending
+        .iter()
+        .map(|(pos, priority)| PendingPoint {
+            offset: (pos.x - spawn.x, pos.z - spawn.z),
+            color: priority.map_or(egui::Color32::GRAY, |priority| {
+                priority_color(priority, max_priority)
+            }),
+        })
+        .collect()
+}
+
+/// Fades from green for the lowest to red for the highest priority.
+fn priority_color(priority: Priority, max_priority: Priority) -> egui::Color32 {
+    let t = if max_priority == 0 {
+        0.0
+    } else {
+        priority as f32 / max_priority as f32
+    };
+
+    egui::Color32::from_rgb((255.0 * t) as u8, (255.0 * (1.0 - t)) as u8, 0)
+}
+
+/// Plots the pending chunks around the spawn, toggled in the terrain settings.
+pub fn pending_chunks_ui(
+    mut egui_context: bevy_egui::EguiContexts,
+    mut show: ResMut<ShowPendingChunks>,
+    state: Res<WorldGenState>,
+    spawn: Res<SpawnPos>,
+) {
+    if !show.0 {
+        return;
+    }
+
+    let spawn = ChunkPos::new(
+        (spawn.0.x / 16.0).floor() as i32,
+        (spawn.0.z / 16.0).floor() as i32,
+    );
+    let points = pending_plot_points(&state.pending, spawn);
+
+    egui::Window::new("Pending Chunks")
+        .open(&mut show.0)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label(format!(
+                "{} pending, {} generating",
+                state.pending.values().flatten().count(),
+                state.in_flight
+            ));
+
+            let (response, painter) =
+                ui.allocate_painter(egui::Vec2::splat(300.0), egui::Sense::hover());
+            let rect = response.rect;
+            painter.rect_filled(rect, 0.0, egui::Color32::from_gray(20));
+
+            // Keep the spawn in the center, the plot grows with the furthest chunk.
+            let extent = points
+                .iter()
+                .map(|p| p.offset.0.abs().max(p.offset.1.abs()))
+                .max()
+                .unwrap_or(0)
+                .max(8) as f32
+                + 1.0;
+            let scale = rect.width() / (2.0 * extent);
+
+            for point in &points {
+                let center = rect.center()
+                    + egui::vec2(point.offset.0 as f32, point.offset.1 as f32) * scale;
+                painter.rect_filled(
+                    egui::Rect::from_center_size(center, egui::Vec2::splat(scale.max(1.0))),
+                    0.0,
+                    point.color,
+                );
+            }
+
+            painter.circle_stroke(
+                rect.center(),
+                3.0,
+                egui::Stroke::new(1.0, egui::Color32::WHITE),
+            );
+        });
+}
+
+/// Chunks per side of a minimap tile, see
+/// [`gen_chunk_at_scale`](chunk_worker::gen_chunk_at_scale).
+const MINIMAP_SCALE: u8 = 4;
+
+/// Tiles on every side of the spawn tile in the minimap.
+const MINIMAP_RADIUS: i32 = 2;
+
+/// Surface heights around the spawn, requested from the chunk workers while
+/// the minimap is shown. Toggled in the terrain settings.
+#[derive(Resource, Default, Debug)]
+pub struct Minimap {
+    pub show: bool,
+    /// Heights of every tile by the position of its first chunk.
+    tiles: HashMap<ChunkPos, Vec<Vec<u16>>>,
+    /// [`TerrainSettings::content_hash`] of the settings the tiles were
+    /// requested for.
+    settings_hash: Option<u64>,
+}
+
+/// Color of a column on the minimap, water is blue and gets darker the deeper
+/// it is, land fades from green to white.
+fn height_color(height: u16, water_height: i32) -> egui::Color32 {
+    let height = i32::from(height);
+
+    if height < water_height {
+        let depth = ((water_height - height) as f32 / 64.0).min(1.0);
+        return egui::Color32::from_rgb(20, 60, (220.0 - 140.0 * depth) as u8);
+    }
+
+    let t = ((height - water_height) as f32 / 128.0).min(1.0);
+    egui::Color32::from_rgb(
+        (60.0 + 195.0 * t) as u8,
+        (150.0 + 105.0 * t) as u8,
+        (50.0 + 205.0 * t) as u8,
+    )
+}
+
+/// Draws the surface around the spawn, the tiles are requested again when the
+/// terrain settings change.
+pub fn minimap_ui(
+    mut egui_context: bevy_egui::EguiContexts,
+    mut minimap: ResMut<Minimap>,
+    state: Res<WorldGenState>,
+    settings: Res<TerrainSettings>,
+    spawn: Res<SpawnPos>,
+) {
+    if !minimap.show {
+        return;
+    }
+
+    let tile_size = i32::from(MINIMAP_SCALE);
+    let spawn_tile = ChunkPos::new(
+        ((spawn.0.x / 16.0).floor() as i32).div_euclid(tile_size) * tile_size,
+        ((spawn.0.z / 16.0).floor() as i32).div_euclid(tile_size) * tile_size,
+    );
+    let tiles = iproduct!(
+        -MINIMAP_RADIUS..=MINIMAP_RADIUS,
+        -MINIMAP_RADIUS..=MINIMAP_RADIUS
+    )
+    .map(|(x, z)| ChunkPos::new(spawn_tile.x + x * tile_size, spawn_tile.z + z * tile_size))
+    .collect::<Vec<_>>();
+
+    if minimap.settings_hash != Some(state.settings_hash) {
+        minimap.tiles.clear();
+
+        // Sent again next frame if the queue is full.
+        let sent = tiles.iter().all(|pos| {
+            state
+                .sender
+                .try_send(WorkerMessage::HeightMap(*pos, MINIMAP_SCALE))
+                .is_ok()
+        });
+        if sent {
+            minimap.settings_hash = Some(state.settings_hash);
+        }
+    }
+
+    let minimap = minimap.into_inner();
+    egui::Window::new("Minimap")
+        .open(&mut minimap.show)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label(format!(
+                "{} blocks around the spawn",
+                (2 * MINIMAP_RADIUS + 1) * tile_size * 16
+            ));
+
+            let (response, painter) =
+                ui.allocate_painter(egui::Vec2::splat(320.0), egui::Sense::hover());
+            let rect = response.rect;
+            painter.rect_filled(rect, 0.0, egui::Color32::from_gray(20));
+
+            let cells = (2 * MINIMAP_RADIUS + 1) as f32 * 16.0;
+            let cell_size = rect.width() / cells;
+
+            for pos in &tiles {
+                let Some(heights) = minimap.tiles.get(pos) else {
+                    continue;
+                };
+
+                let origin = rect.min
+                    + egui::vec2(
+                        ((pos.x - spawn_tile.x) / tile_size + MINIMAP_RADIUS) as f32 * 16.0,
+                        ((pos.z - spawn_tile.z) / tile_size + MINIMAP_RADIUS) as f32 * 16.0,
+                    ) * cell_size;
+
+                for (z, row) in heights.iter().enumerate() {
+                    for (x, height) in row.iter().enumerate() {
+                        painter.rect_filled(
+                            egui::Rect::from_min_size(
+                                origin + egui::vec2(x as f32, z as f32) * cell_size,
+                                egui::Vec2::splat(cell_size),
+                            ),
+                            0.0,
+                            height_color(*height, settings.water_height),
+                        );
+                    }
+                }
+            }
+
+            let blocks = (2 * MINIMAP_RADIUS + 1) as f32 * f32::from(MINIMAP_SCALE) * 16.0;
+            let spawn_offset = egui::vec2(
+                (spawn.0.x as f32 - ((spawn_tile.x - MINIMAP_RADIUS * tile_size) * 16) as f32)
+                    / blocks,
+                (spawn.0.z as f32 - ((spawn_tile.z - MINIMAP_RADIUS * tile_size) * 16) as f32)
+                    / blocks,
+            );
+            painter.circle_stroke(
+                rect.min + spawn_offset * rect.width(),
+                3.0,
+                egui::Stroke::new(1.0, egui::Color32::RED),
+            );
+        });
+}
+
+/// Shows the [`CacheStats`], docked to the right side of the window.
+pub fn cache_stats_window(
+    mut egui_context: bevy_egui::EguiContexts,
+    mut stats: ResMut<CacheStats>,
+    state: Res<WorldGenState>,
+) {
+    stats.sample(Instant::now());
+
+    let ctx = egui_context.ctx_mut();
+    let right = ctx.screen_rect().right();
+
+    egui::Window::new("Chunk Cache")
+        .collapsible(true)
+        .default_pos(egui::pos2(right - 260.0, 10.0))
+        .show(ctx, |ui| {
+            ui.label(format!("Cached: {} / {} chunks", stats.len, stats.capacity));
+            ui.label(match stats.hit_ratio() {
+                Some(ratio) => format!(
+                    "Hits: {} / {} ({:.1}%)",
+                    stats.hits,
+                    stats.hits + stats.misses,
+                    ratio * 100.0
+                ),
+                None => "Hits: no chunks yet".to_string(),
+            });
+            ui.label(format!("Evictions: {}", stats.evictions));
+
+            // Occupancy of the last minute, the full height is the capacity.
+            let (response, painter) =
+                ui.allocate_painter(egui::vec2(240.0, 40.0), egui::Sense::hover());
+            let rect = response.rect;
+            painter.rect_filled(rect, 0.0, egui::Color32::from_gray(20));
+
+            let capacity = stats.capacity.max(1) as f32;
+            let step = rect.width() / (CACHE_HISTORY_SECONDS - 1) as f32;
+            let points = stats
+                .occupancy
+                .iter()
+                .enumerate()
+                .map(|(i, len)| {
+                    egui::pos2(
+                        rect.left() + i as f32 * step,
+                        rect.bottom() - (*len as f32 / capacity).min(1.0) * rect.height(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            painter.add(egui::Shape::line(
+                points,
+                egui::Stroke::new(1.0, egui::Color32::LIGHT_GREEN),
+            ));
+
+            if ui.button("Clear Cache").clicked() {
+                match state.sender.try_send(WorkerMessage::EmptyCache) {
+                    Ok(()) => {
+                        info!(target: "minecraft::world_gen", "Emptying the chunk cache");
+                    }
+                    Err(e) => {
+                        warn!(target: "minecraft::world_gen", "Unable to empty the chunk cache: {e}");
+                    }
+                }
+            }
+        });
+}
+
+/// Shows how long the phases of providing a chunk take.
+#[cfg(feature = "profiling")]
+pub fn timing_ui(mut egui_context: bevy_egui::EguiContexts, timings: Res<ChunkTimings>) {
+    egui::Window::new("Chunk Timing").show(egui_context.ctx_mut(), |ui| {
+        ui.label(format!("Chunks: {}", timings.count));
+
+        let count = timings.count.max(1);
+        let phases = [
+            (
+                "Cache lookup",
+                timings.last.cache_ns,
+                timings.total.cache_ns,
+            ),
+            ("Disk load", timings.last.disk_ns, timings.total.disk_ns),
+            ("Noise", timings.last.noise_ns, timings.total.noise_ns),
+            ("Send", timings.last.send_ns, timings.total.send_ns),
+        ];
+
+        egui::Grid::new("chunk_timing").show(ui, |ui| {
+            ui.label("Phase");
+            ui.label("Last");
+            ui.label("Average");
+            ui.end_row();
+
+            for (name, last, total) in phases {
+                ui.label(name);
+                ui.label(format!("{:.1} µs", last as f64 / 1000.0));
+                ui.label(format!("{:.1} µs", (total / count) as f64 / 1000.0));
+                ui.end_row();
+            }
+        });
+    });
+}
+
+/// Parses a seed entered in the inspector.
+///
+/// # Errors
+/// - `input` is not a number in the range of `u32`
+pub fn parse_seed(input: &str) -> Result<u32, String> {
+    input
+        .trim()
+        .parse()
+        .map_err(|_| format!("Seed must be a whole number between 0 and {}", u32::MAX))
+}
+
+/// Uses the seed entered in the inspector if it is valid.
+///
+/// # Errors
+/// - `input` is not a valid seed, see [`parse_seed`]
+fn apply_seed_input(input: &str, settings: &mut TerrainSettings) -> Result<(), String> {
+    settings.seed = parse_seed(input)?;
+    Ok(())
+}
+
+/// Shows `seed` in the seed input if the seed was changed somewhere else or
+/// the input is invalid.
+fn sync_seed_input(input: &mut String, seed: u32) {
+    if parse_seed(input) != Ok(seed) {
+        *input = seed.to_string();
+    }
+}
+
+fn randomize_seed(settings: &mut TerrainSettings, input: &mut String) {
+    settings.seed = rand::random();
+    *input = settings.seed.to_string();
+}
+
+fn ore_settings_ui(ui: &mut egui::Ui, name: &str, ore: &mut OreSettings) {
+    ui.collapsing(name, |ui| {
+        egui::Grid::new(format!("{name}_ore_settings")).show(ui, |ui| {
+            ui.checkbox(&mut ore.enabled, format!("Enable {}", name.to_lowercase()));
+            ui.end_row();
+
+            ui.label("Vein size");
+            ui.add(egui::DragValue::new(&mut ore.vein_size).speed(0.1));
+            ui.end_row();
+
+            ui.label("Frequency");
+            ui.add(
+                egui::DragValue::new(&mut ore.frequency)
+                    .speed(0.01)
+                    .clamp_range(0.0..=1.0),
+            );
+            ui.end_row();
+
+            ui.label("Min Y");
+            ui.add(egui::DragValue::new(&mut ore.min_y));
+            ui.end_row();
+
+            ui.label("Max Y");
+            ui.add(egui::DragValue::new(&mut ore.max_y));
+            ui.end_row();
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WorldConfig;
+
+    #[test]
+    fn unviewed_chunks_are_kept_for_the_delay() {
+        let mut unviewed = HashMap::new();
+        let pos = ChunkPos::new(1, 2);
+
+        for _ in 0..3 {
+            assert!(keep_unviewed(&mut unviewed, pos, false, 3));
+        }
+        assert!(!keep_unviewed(&mut unviewed, pos, false, 3));
+        assert!(unviewed.is_empty());
+    }
+
+    #[test]
+    fn viewing_a_chunk_resets_its_delay() {
+        let mut unviewed = HashMap::new();
+        let pos = ChunkPos::new(0, 0);
+
+        assert!(keep_unviewed(&mut unviewed, pos, false, 2));
+        assert!(keep_unviewed(&mut unviewed, pos, false, 2));
+        assert!(keep_unviewed(&mut unviewed, pos, true, 2));
+        assert!(!unviewed.contains_key(&pos));
+
+        assert!(keep_unviewed(&mut unviewed, pos, false, 2));
+        assert!(keep_unviewed(&mut unviewed, pos, false, 2));
+        assert!(!keep_unviewed(&mut unviewed, pos, false, 2));
+    }
+
+    #[test]
+    fn chunks_are_unloaded_right_away_without_a_delay() {
+        let mut unviewed = HashMap::new();
+
+        assert!(!keep_unviewed(&mut unviewed, ChunkPos::new(0, 0), false, 0));
+        assert_eq!(WorldConfig::default().chunk_unload_delay, 100);
+    }
+
+    /// Stone up to `height` in every column.
+    fn flat_chunk(height: usize) -> Chunk {
+        let mut chunk = Chunk::new(SECTION_COUNT);
+        for (x, y, z) in iproduct!(0..16, 0..=height, 0..16) {
+            chunk.set_block_state(x, y, z, BlockState::STONE);
+        }
+        chunk
+    }
+
+    #[test]
+    fn degenerate_columns_have_no_surface() {
+        let top = SECTION_COUNT * 16 - 1;
+
+        assert_eq!(surface_height(&Chunk::new(SECTION_COUNT), 0, 0), None);
+        assert_eq!(surface_height(&flat_chunk(top), 0, 0), None);
+        assert_eq!(surface_height(&flat_chunk(70), 0, 0), Some(70));
+    }
+
+    #[test]
+    fn spawn_skips_a_solid_column_at_the_origin() {
+        let mut chunk = flat_chunk(70);
+        for y in 71..SECTION_COUNT * 16 {
+            chunk.set_block_state(0, y, 0, BlockState::STONE);
+        }
+
+        let spawn = find_spawn(&chunk).unwrap();
+        assert_ne!((spawn.x, spawn.z), (0.0, 0.0));
+        assert_eq!(spawn.y, 70.0 - 50.0);
+
+        assert_eq!(find_spawn(&Chunk::new(SECTION_COUNT)), None);
+    }
+
+    #[test]
+    fn bad_seed_has_a_sane_spawn() {
+        // Generated a column that is solid up to the build limit at x:0 z:0.
+        let seed = 2_968_952_028;
+        let state = ChunkWorkerState::new(
+            seed,
+            TerrainSettings {
+                seed,
+                ..Default::default()
+            },
+        );
+        let chunk = gen_chunk(&state, ChunkPos::new(0, 0));
+
+        let spawn = find_spawn(&chunk).unwrap();
+        let (x, z) = (spawn.x as usize, spawn.z as usize);
+        let ground = surface_height(&chunk, x, z).unwrap();
+
+        assert!(!chunk.block_state(x, ground, z).is_liquid());
+        assert!(chunk.block_state(x, ground + 1, z).is_air());
+        assert!(chunk.block_state(x, ground + 2, z).is_air());
+    }
+
+    #[test]
+    fn pregen_shapes_have_the_expected_number_of_chunks() {
+        let count = |shape| pregen_positions(&shape).count();
+
+        assert_eq!(count(PregenShape::Square { radius: 0 }), 1);
+        assert_eq!(count(PregenShape::Square { radius: 2 }), 25);
+        assert_eq!(count(PregenShape::Square { radius: 22 }), 2025);
+
+        assert_eq!(count(PregenShape::Circle { radius: 0 }), 1);
+        assert_eq!(count(PregenShape::Circle { radius: 2 }), 13);
+        assert_eq!(count(PregenShape::Circle { radius: 22 }), 1517);
+    }
+
+    #[test]
+    fn pregen_circle_is_inside_the_square() {
+        let square = pregen_positions(&PregenShape::Square { radius: 5 }).collect::<HashSet<_>>();
+
+        for (x, z) in pregen_positions(&PregenShape::Circle { radius: 5 }) {
+            assert!(square.contains(&(x, z)));
+            assert!(x * x + z * z <= 25);
+        }
+        assert!(!PregenShape::Circle { radius: 5 }.contains(5, 5));
+        assert!(PregenShape::Circle { radius: 5 }.contains(0, -5));
+    }
+
+    #[test]
+    fn default_pregen_chunks_fit_into_the_cache() {
+        let config = WorldConfig::default();
+
+        assert_eq!(config.pregen_chunks, PregenShape::Square { radius: 22 });
+        assert!(pregen_positions(&config.pregen_chunks).count() <= config.chunks_cached);
+    }
+
+    #[test]
+    fn pregen_shapes_round_trip_through_the_config_formats() {
+        for shape in [
+            PregenShape::Square { radius: 3 },
+            PregenShape::Circle { radius: 7 },
+        ] {
+            let config = WorldConfig {
+                pregen_chunks: shape,
+                ..Default::default()
+            };
+
+            let toml = toml::to_string(&config).unwrap();
+            assert_eq!(
+                toml::from_str::<WorldConfig>(&toml).unwrap().pregen_chunks,
+                shape
+            );
+
+            let ron = ron::to_string(&config).unwrap();
+            assert_eq!(
+                ron::from_str::<WorldConfig>(&ron).unwrap().pregen_chunks,
+                shape
+            );
+        }
+    }
+
+    #[test]
+    fn regeneration_counts_the_remaining_chunks() {
+        let chunks = [
+            ChunkPos::new(0, 0),
+            ChunkPos::new(1, 0),
+            ChunkPos::new(0, 1),
+        ];
+        let mut regeneration = Regeneration::new(chunks.into_iter().chain(chunks));
+        assert_eq!(regeneration.total(), 3);
+        assert_eq!(regeneration.remaining(), 3);
+
+        regeneration.chunk_generated(ChunkPos::new(1, 0));
+        regeneration.chunk_generated(ChunkPos::new(1, 0));
+        regeneration.chunk_generated(ChunkPos::new(5, 5));
+        assert_eq!(regeneration.remaining(), 2);
+        assert_eq!(regeneration.total(), 3);
+    }
+
+    #[test]
+    fn regeneration_waits_for_the_new_settings() {
+        let mut regeneration = Regeneration::new([ChunkPos::new(0, 0)]);
+        regeneration.chunk_generated(ChunkPos::new(0, 0));
+        assert!(!regeneration.is_finished());
+
+        regeneration.settings_set = true;
+        assert!(regeneration.is_finished());
+        assert!(Regeneration {
+            settings_set: true,
+            ..Regeneration::new([])
+        }
+        .is_finished());
+    }
+
+    #[test]
+    fn regeneration_times_out() {
+        let timeout = Duration::from_secs(CONFIG.world.regeneration_timeout);
+        let mut regeneration = Regeneration::new([ChunkPos::new(0, 0)]);
+        assert!(!regeneration.timed_out(timeout));
+
+        regeneration.started = Instant::now() - timeout - Duration::from_secs(1);
+        assert!(regeneration.timed_out(timeout));
+        assert!(!regeneration.is_finished());
+        assert_eq!(WorldConfig::default().regeneration_timeout, 60);
+    }
+
+    fn test_state() -> (WorldGenState, Receiver<WorkerMessage>) {
+        let (sender, pending) = flume::unbounded();
+        let (_, receiver) = flume::unbounded();
+
+        let state = WorldGenState {
+            pending: HashMap::new(),
+            in_flight: 0,
+            chunk_hashes: HashMap::new(),
+            settings_hash: 0,
+            queue_full: false,
+            views_incomplete: false,
+            send_failed: false,
+            dimension_pending: HashSet::new(),
+            sender,
+            receiver,
+        };
+
+        (state, pending)
+    }
+
+    fn sent_batches(pending: &Receiver<WorkerMessage>) -> Vec<Vec<ChunkPos>> {
+        pending
+            .drain()
+            .map(|message| match message {
+                WorkerMessage::BatchChunks(batch) => batch,
+                _ => panic!("only batches should be sent"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn queued_chunks_are_limited_to_the_queue_depth() {
+        let (mut state, pending) = test_state();
+        for x in 0..10 {
+            state
+                .pending
+                .insert(ChunkPos::new(x, 0), Some(10 - x as u64));
+        }
+
+        send_pending_chunks(&mut state, 4);
+        // The chunks with the lowest priority value are sent first.
+        assert_eq!(
+            sent_batches(&pending),
+            [(6..10)
+                .rev()
+                .map(|x| ChunkPos::new(x, 0))
+                .collect::<Vec<_>>()]
+        );
+        assert_eq!(state.in_flight, 4);
+        assert!(state.queue_full);
+        assert_eq!(state.pending.values().filter(|p| p.is_some()).count(), 6);
+
+        // Nothing is sent until the workers finished some chunks.
+        send_pending_chunks(&mut state, 4);
+        assert!(sent_batches(&pending).is_empty());
+
+        state.in_flight = 1;
+        send_pending_chunks(&mut state, 4);
+        assert_eq!(sent_batches(&pending)[0].len(), 3);
+        assert_eq!(state.in_flight, 4);
+
+        state.in_flight = 0;
+        send_pending_chunks(&mut state, 4);
+        assert_eq!(sent_batches(&pending)[0].len(), 3);
+        assert!(!state.queue_full);
+        assert!(state.pending.values().all(Option::is_none));
+    }
+
+    #[test]
+    fn queue_depth_is_configurable() {
+        assert_eq!(WorldConfig::default().max_chunk_queue_depth, 256);
+
+        let config: WorldConfig = toml::from_str("max_chunk_queue_depth = 16").unwrap();
+        assert_eq!(config.max_chunk_queue_depth, 16);
+    }
+
+    #[test]
+    fn ctp_arguments_are_parsed() {
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        assert_eq!(
+            parse_chunk_pos(&args(&["3", "-7"])),
+            Some(ChunkPos::new(3, -7))
+        );
+        assert_eq!(parse_chunk_pos(&args(&[])), None);
+        assert_eq!(parse_chunk_pos(&args(&["3"])), None);
+        assert_eq!(parse_chunk_pos(&args(&["3", "-7", "1"])), None);
+        assert_eq!(parse_chunk_pos(&args(&["x", "1"])), None);
+        assert_eq!(parse_chunk_pos(&args(&["1.5", "1"])), None);
+        assert_eq!(parse_chunk_pos(&args(&["1", "99999999999"])), None);
+    }
+
+    #[test]
+    fn ctp_chunks_are_queued_first() {
+        let (mut state, _pending) = test_state();
+        state.pending.insert(ChunkPos::new(1, 1), Some(40));
+        state.pending.insert(ChunkPos::new(2, 2), None);
+
+        queue_first(&mut state, ChunkPos::new(0, 5));
+        queue_first(&mut state, ChunkPos::new(1, 1));
+        queue_first(&mut state, ChunkPos::new(2, 2));
+
+        assert_eq!(state.pending[&ChunkPos::new(0, 5)], Some(0));
+        assert_eq!(state.pending[&ChunkPos::new(1, 1)], Some(0));
+        // Already sent to the chunk workers.
+        assert_eq!(state.pending[&ChunkPos::new(2, 2)], None);
+    }
+
+    #[test]
+    fn ctp_needs_op_level_2() {
+        let mut app = App::new();
+        WorldGenPlugin.build(&mut app);
+
+        let ctp = app
+            .world
+            .resource::<crate::minecraft::command::CommandRegistry>()
+            .get("ctp")
+            .unwrap();
+        assert_eq!(ctp.op_level, 2);
+        assert_eq!(ctp.usage, CTP_USAGE);
+    }
+
+    #[test]
+    fn only_chunks_with_a_changed_hash_are_invalidated() {
+        let (mut state, _pending) = test_state();
+        state.settings_hash = 1;
+        state.chunk_hashes = HashMap::from([
+            (ChunkPos::new(0, 0), 1),
+            (ChunkPos::new(1, 0), 2),
+            (ChunkPos::new(2, 0), 2),
+            (ChunkPos::new(3, 0), 2),
+        ]);
+
+        // Chunk 3 was unloaded in the meantime.
+        invalidate_chunks(&mut state, 2, |pos| pos.x != 3);
+
+        assert_eq!(state.settings_hash, 2);
+        assert_eq!(
+            state.chunk_hashes,
+            HashMap::from([(ChunkPos::new(1, 0), 2), (ChunkPos::new(2, 0), 2)])
+        );
+
+        // Nothing changes if the hash stays the same.
+        invalidate_chunks(&mut state, 2, |_| true);
+        assert_eq!(state.chunk_hashes.len(), 2);
+
+        invalidate_chunks(&mut state, 3, |_| true);
+        assert!(state.chunk_hashes.is_empty());
+    }
+
+    #[test]
+    fn chunks_stay_pending_if_sending_fails() {
+        let (mut state, pending) = test_state();
+        drop(pending);
+        state.pending.insert(ChunkPos::new(0, 0), Some(3));
+        state.pending.insert(ChunkPos::new(1, 0), Some(1));
+
+        send_pending_chunks(&mut state, 16);
+
+        assert!(state.send_failed);
+        assert_eq!(state.in_flight, 0);
+        assert_eq!(state.pending[&ChunkPos::new(0, 0)], Some(3));
+        assert_eq!(state.pending[&ChunkPos::new(1, 0)], Some(1));
+
+        // The chunks are sent once the workers accept them again.
+        let (sender, pending) = flume::unbounded();
+        state.sender = sender;
+        send_pending_chunks(&mut state, 16);
+
+        assert!(!state.send_failed);
+        assert_eq!(state.in_flight, 2);
+        assert!(state.pending.values().all(Option::is_none));
+        assert_eq!(
+            sent_batches(&pending),
+            [[ChunkPos::new(1, 0), ChunkPos::new(0, 0)]]
+        );
+    }
+
+    #[test]
+    fn seeds_are_parsed() {
+        assert_eq!(parse_seed("42"), Ok(42));
+        assert_eq!(parse_seed(" 2968952028\n"), Ok(2_968_952_028));
+        assert_eq!(parse_seed("0"), Ok(0));
+        assert_eq!(parse_seed(&u32::MAX.to_string()), Ok(u32::MAX));
+
+        let error = Err(format!(
+            "Seed must be a whole number between 0 and {}",
+            u32::MAX
+        ));
+        for input in ["", "-1", "4294967296", "1.5", "seed", "0x10"] {
+            assert_eq!(parse_seed(input), error, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn cachesize_resizes_the_chunk_cache() {
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let (state, pending) = test_state();
+
+        assert_eq!(
+            resize_cache(&state, &args(&["4096"])),
+            Ok(NonZeroUsize::new(4096).unwrap())
+        );
+        assert!(matches!(
+            pending.try_recv(),
+            Ok(WorkerMessage::ResizeCache(capacity)) if capacity.get() == 4096
+        ));
+
+        for invalid in [vec![], vec!["0"], vec!["-5"], vec!["lots"]] {
+            assert_eq!(
+                resize_cache(&state, &args(&invalid)),
+                Err("Usage: cachesize <chunks>, the size has to be at least 1.".to_string())
+            );
+        }
+        assert!(pending.is_empty());
+
+        drop(pending);
+        assert!(resize_cache(&state, &args(&["16"]))
+            .unwrap_err()
+            .starts_with("Unable to resize the chunk cache"));
+    }
+
+    #[test]
+    fn cachesize_needs_op_level_3() {
+        let mut app = App::new();
+        WorldGenPlugin.build(&mut app);
+
+        let registry = app
+            .world
+            .resource::<crate::minecraft::command::CommandRegistry>();
+        assert_eq!(registry.get("cachesize").unwrap().op_level, 3);
+        assert_eq!(
+            registry.get("cachesize").unwrap().usage,
+            "cachesize <chunks>"
+        );
+    }
+
+    #[test]
+    fn chunks_in_the_look_direction_are_sent_first() {
+        let view = ChunkPos::new(5, -5);
+        let at = |dx, dz| ChunkPos::new(view.x + dx, view.z + dz);
+
+        // A yaw of 0 faces towards positive z.
+        for (yaw, front, back) in [
+            (0.0, at(0, 3), at(0, -3)),
+            (90.0, at(-3, 0), at(3, 0)),
+            (180.0, at(0, -3), at(0, 3)),
+            (-90.0, at(3, 0), at(-3, 0)),
+            (270.0, at(3, 0), at(-3, 0)),
+        ] {
+            assert_eq!(chunk_priority(view, front, yaw, true), 4, "yaw {yaw}");
+            assert_eq!(chunk_priority(view, back, yaw, true), 9, "yaw {yaw}");
+            assert_eq!(chunk_priority(view, front, yaw, false), 9, "yaw {yaw}");
+            assert_eq!(chunk_priority(view, back, yaw, false), 9, "yaw {yaw}");
+        }
+
+        // Chunks to the side and the chunk the player is in aren't preferred.
+        assert_eq!(chunk_priority(view, at(3, 0), 0.0, true), 9);
+        assert_eq!(chunk_priority(view, view, 0.0, true), 0);
+    }
+
+    #[test]
+    fn look_bias_orders_the_view_by_direction_then_distance() {
+        let view = ChunkPos::new(0, 0);
+        let mut chunks = vec![
+            ChunkPos::new(0, -2),
+            ChunkPos::new(0, 2),
+            ChunkPos::new(0, -1),
+            ChunkPos::new(0, 1),
+            ChunkPos::new(0, 4),
+        ];
+
+        chunks.sort_by_key(|pos| chunk_priority(view, *pos, 0.0, true));
+        assert_eq!(
+            chunks,
+            [
+                ChunkPos::new(0, 1),
+                ChunkPos::new(0, -1),
+                ChunkPos::new(0, 2),
+                ChunkPos::new(0, -2),
+                ChunkPos::new(0, 4),
+            ]
+        );
+
+        chunks.sort_by_key(|pos| chunk_priority(view, *pos, 180.0, true));
+        assert_eq!(
+            chunks,
+            [
+                ChunkPos::new(0, -1),
+                ChunkPos::new(0, 1),
+                ChunkPos::new(0, -2),
+                ChunkPos::new(0, 2),
+                ChunkPos::new(0, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn pregen_chunks_are_inserted_at_startup_by_default() {
+        let config = WorldConfig {
+            pregen_chunks: PregenShape::Square { radius: 2 },
+            ..Default::default()
+        };
+        assert!(config.load_pregen_chunks);
+
+        assert!(pending_pregen_chunks(&config).is_empty());
+        assert!(is_kept_loaded(&config, ChunkPos::new(0, 0)));
+        assert!(is_kept_loaded(&config, ChunkPos::new(-2, 2)));
+        assert!(!is_kept_loaded(&config, ChunkPos::new(3, 0)));
+    }
+
+    #[test]
+    fn pregen_chunks_are_queued_if_not_inserted_at_startup() {
+        let config: WorldConfig = toml::from_str(
+            r#"
+            load_pregen_chunks = false
+            pregen_chunks = { Square = { radius = 2 } }
+            "#,
+        )
+        .unwrap();
+        assert!(!config.load_pregen_chunks);
+
+        let pending = pending_pregen_chunks(&config);
+        assert_eq!(pending.len(), 25);
+        assert!(pregen_positions(&config.pregen_chunks)
+            .all(|(x, z)| pending[&ChunkPos::new(x, z)].is_some()));
+
+        // They are unloaded like any other chunk once nobody sees them.
+        assert!(!is_kept_loaded(&config, ChunkPos::new(0, 0)));
+    }
+
+    #[test]
+    fn view_chunks_are_rejected_above_the_pending_limit() {
+        let (mut state, _pending) = test_state();
+        state.pending.insert(ChunkPos::new(9, 9), None);
+
+        queue_view_chunk(&mut state, ChunkPos::new(0, 0), 5, 3);
+        queue_view_chunk(&mut state, ChunkPos::new(1, 0), 6, 3);
+        assert!(!state.views_incomplete);
+
+        queue_view_chunk(&mut state, ChunkPos::new(2, 0), 1, 3);
+        assert!(state.views_incomplete);
+        assert_eq!(state.pending.len(), 3);
+        assert!(!state.pending.contains_key(&ChunkPos::new(2, 0)));
+
+        // Chunks that are already pending are still updated.
+        queue_view_chunk(&mut state, ChunkPos::new(1, 0), 2, 3);
+        queue_view_chunk(&mut state, ChunkPos::new(9, 9), 0, 3);
+        assert_eq!(state.pending[&ChunkPos::new(1, 0)], Some(2));
+        assert_eq!(state.pending[&ChunkPos::new(9, 9)], None);
+
+        // The views are queued again once there is room.
+        assert!(!rescan_views(&mut state, 3));
+        assert!(state.views_incomplete);
+        state.pending.remove(&ChunkPos::new(9, 9));
+        assert!(rescan_views(&mut state, 3));
+        assert!(!state.views_incomplete);
+        assert!(!rescan_views(&mut state, 3));
+    }
+
+    #[test]
+    fn pending_limit_is_configurable() {
+        assert_eq!(WorldConfig::default().max_pending_chunks, 2048);
+
+        let config: WorldConfig = toml::from_str("max_pending_chunks = 64").unwrap();
+        assert_eq!(config.max_pending_chunks, 64);
+    }
+
+    #[test]
+    fn manually_entered_seeds_are_preserved() {
+        let mut settings = TerrainSettings::default();
+
+        assert_eq!(apply_seed_input(" 1234 ", &mut settings), Ok(()));
+        assert_eq!(settings.seed, 1234);
+
+        // The input isn't replaced while it matches the seed.
+        let mut input = " 1234 ".to_string();
+        sync_seed_input(&mut input, settings.seed);
+        assert_eq!(input, " 1234 ");
+
+        assert!(apply_seed_input("12a", &mut settings).is_err());
+        assert_eq!(settings.seed, 1234);
+        let mut input = "12a".to_string();
+        sync_seed_input(&mut input, settings.seed);
+        assert_eq!(input, "1234");
+    }
+
+    #[test]
+    fn randomized_seeds_are_valid() {
+        let mut settings = TerrainSettings::default();
+        let mut input = String::new();
+
+        for _ in 0..32 {
+            randomize_seed(&mut settings, &mut input);
+            assert_eq!(parse_seed(&input), Ok(settings.seed));
+            assert_eq!(settings.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn invalid_inspector_input_is_explained() {
+        assert_eq!(
+            parse_seed("-1"),
+            Err(format!(
+                "Seed must be a whole number between 0 and {}",
+                u32::MAX
+            ))
+        );
+
+        let mut settings = TerrainSettings::default();
+        settings.gravel_height.lacunarity = f64::INFINITY;
+        assert_eq!(
+            settings.validate(),
+            Err("Gravel: lacunarity must be a finite non-zero number".to_string())
+        );
+    }
+
+    fn chunk_info(worker: &str, cached: bool, saved: bool) -> ChunkInfo {
+        ChunkInfo {
+            worker: worker.to_string(),
+            duration: Duration::from_millis(3),
+            cached,
+            saved,
+        }
+    }
+
+    #[test]
+    fn chunkinfo_shows_where_a_chunk_came_from() {
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let mut infos = ChunkInfos::default();
+        infos.record(
+            ChunkPos::new(0, 0),
+            chunk_info("ChunkWorker_0", false, false),
+        );
+        infos.record(
+            ChunkPos::new(1, 0),
+            chunk_info("ChunkWorker_1", false, true),
+        );
+        infos.record(ChunkPos::new(2, 0), chunk_info("ChunkWorker_2", true, true));
+
+        let current = ChunkPos::new(0, 0);
+        assert_eq!(
+            chunk_info_message(&infos, &[], current),
+            Ok("Chunk 0 0: provided by ChunkWorker_0 from the generator in 3ms".to_string())
+        );
+        assert_eq!(
+            chunk_info_message(&infos, &args(&["1", "0"]), current),
+            Ok("Chunk 1 0: provided by ChunkWorker_1 from the region file in 3ms".to_string())
+        );
+        assert_eq!(
+            chunk_info_message(&infos, &args(&["2", "0"]), current),
+            Ok("Chunk 2 0: provided by ChunkWorker_2 from the cache in 3ms".to_string())
+        );
+        assert_eq!(
+            chunk_info_message(&infos, &args(&["-4", "7"]), current),
+            Ok("No information about chunk -4 7.".to_string())
+        );
+    }
+
+    #[test]
+    fn chunkinfo_rejects_invalid_positions() {
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let infos = ChunkInfos::default();
+
+        for invalid in [vec!["1"], vec!["1", "x"], vec!["1", "2", "3"]] {
+            assert_eq!(
+                chunk_info_message(&infos, &args(&invalid), ChunkPos::new(0, 0)),
+                Err("Usage: chunkinfo [<chunk x> <chunk z>]".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn chunkinfo_only_remembers_the_latest_chunks() {
+        let mut infos = ChunkInfos::default();
+        for x in 0..=CHUNK_INFO_CAPACITY as i32 {
+            infos.record(
+                ChunkPos::new(x, 0),
+                chunk_info("ChunkWorker_0", false, false),
+            );
+        }
+
+        assert!(infos.get(ChunkPos::new(0, 0)).is_none());
+        assert!(infos.get(ChunkPos::new(1, 0)).is_some());
+        assert!(infos
+            .get(ChunkPos::new(CHUNK_INFO_CAPACITY as i32, 0))
+            .is_some());
+    }
+
+    #[test]
+    fn chunkinfo_needs_op_level_2() {
+        let mut app = App::new();
+        WorldGenPlugin.build(&mut app);
+
+        let registry = app
+            .world
+            .resource::<crate::minecraft::command::CommandRegistry>();
+        assert_eq!(
+            registry.parse("chunkinfo", 1).unwrap_err(),
+            "Not enough permissions to use chunkinfo command."
+        );
+        assert!(registry.parse("chunkinfo 1 2", 2).is_ok());
+    }
+
+    #[test]
+    fn chunks_without_a_standable_column_are_empty() {
+        assert!(is_empty_terrain(&Chunk::new(SECTION_COUNT)));
+        assert!(is_empty_terrain(&flat_chunk(SECTION_COUNT * 16 - 1)));
+        assert!(!is_empty_terrain(&flat_chunk(64)));
+
+        let mut water = Chunk::new(SECTION_COUNT);
+        for (x, y, z) in iproduct!(0..16, 0..=62, 0..16) {
+            water.set_block_state(x, y, z, BlockState::WATER);
+        }
+        assert!(is_empty_terrain(&water));
+
+        // A single column is enough to stand on.
+        water.set_block_state(3, 63, 12, BlockState::STONE);
+        assert!(!is_empty_terrain(&water));
+    }
+
+    #[test]
+    fn terrain_without_blocks_is_empty() {
+        let settings = TerrainSettings {
+            enable_gravel: false,
+            enable_sand: false,
+            enable_stone: false,
+            enable_grass: false,
+            enable_water: false,
+            ..Default::default()
+        };
+        let state = ChunkWorkerState::new(settings.seed, settings);
+        assert!(is_empty_terrain(&gen_chunk(&state, ChunkPos::new(0, 0))));
+
+        // Flat terrain above the water.
+        let settings = TerrainSettings {
+            height_scale: 0.0,
+            water_height: 0,
+            ..Default::default()
+        };
+        let state = ChunkWorkerState::new(settings.seed, settings);
+        assert!(!is_empty_terrain(&gen_chunk(&state, ChunkPos::new(0, 0))));
+    }
+
+    #[test]
+    fn empty_terrain_is_reverted_to_the_defaults_with_the_same_seed() {
+        assert!(!WorldConfig::default().revert_empty_terrain);
+        let config: WorldConfig = toml::from_str("revert_empty_terrain = true").unwrap();
+        assert!(config.revert_empty_terrain);
+
+        let settings = TerrainSettings {
+            seed: 1234,
+            enable_stone: false,
+            height_scale: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            reverted_settings(&settings),
+            TerrainSettings {
+                seed: 1234,
+                ..Default::default()
+            }
+        );
+    }
+
+    /// Stone up to `ground`, then `liquid` up to `surface` in every column.
+    fn flooded_chunk(ground: usize, surface: usize, liquid: BlockState) -> Chunk {
+        let mut chunk = flat_chunk(ground);
+        for (x, y, z) in iproduct!(0..16, ground + 1..=surface, 0..16) {
+            chunk.set_block_state(x, y, z, liquid);
+        }
+        chunk
+    }
+
+    /// Raises the column at `x` `z` to `height`.
+    fn raise_column(chunk: &mut Chunk, x: usize, z: usize, height: usize) {
+        for y in 0..=height {
+            chunk.set_block_state(x, y, z, BlockState::STONE);
+        }
+    }
+
+    #[test]
+    fn spawn_is_never_on_or_below_liquids() {
+        for liquid in [BlockState::WATER, BlockState::LAVA] {
+            let mut chunk = flooded_chunk(60, 70, liquid);
+            assert_eq!(find_spawn(&chunk), None);
+
+            raise_column(&mut chunk, 5, 3, 72);
+            assert_eq!(find_spawn(&chunk), Some(DVec3::new(5.0, 72.0 - 50.0, 3.0)));
+        }
+    }
+
+    #[test]
+    fn landing_is_never_on_or_below_liquids() {
+        for liquid in [BlockState::WATER, BlockState::LAVA] {
+            let mut chunk = flooded_chunk(60, 70, liquid);
+            assert_eq!(safe_landing(&chunk, 8, 8), None);
+
+            raise_column(&mut chunk, 2, 14, 71);
+            raise_column(&mut chunk, 10, 9, 75);
+            // The closest dry column is picked.
+            assert_eq!(safe_landing(&chunk, 8, 8), Some((10, 9, 75)));
+            assert_eq!(safe_landing(&chunk, 0, 15), Some((2, 14, 71)));
+        }
+    }
+
+    #[test]
+    fn landing_avoids_unsafe_ground() {
+        let mut chunk = flooded_chunk(60, 70, BlockState::LAVA);
+        raise_column(&mut chunk, 8, 8, 71);
+        chunk.set_block_state(8, 71, 8, BlockState::MAGMA_BLOCK);
+        assert_eq!(safe_landing(&chunk, 8, 8), None);
+
+        raise_column(&mut chunk, 0, 0, 71);
+        assert_eq!(safe_landing(&chunk, 8, 8), Some((0, 0, 71)));
+    }
+
+    #[test]
+    fn random_teleport_cooldowns_expire() {
+        let mut cooldowns = RandomTeleportCooldowns::default();
+        let (player, other) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let cooldown = Duration::from_secs(30);
+        let now = Instant::now();
+
+        assert_eq!(cooldowns.remaining(player, now, cooldown), None);
+
+        cooldowns.start(player, now);
+        assert_eq!(cooldowns.remaining(player, now, cooldown), Some(cooldown));
+        assert_eq!(
+            cooldowns.remaining(player, now + Duration::from_secs(10), cooldown),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(cooldowns.remaining(player, now + cooldown, cooldown), None);
+        assert_eq!(
+            cooldowns.remaining(player, now + Duration::from_secs(60), cooldown),
+            None
+        );
+        assert_eq!(cooldowns.remaining(other, now, cooldown), None);
+
+        // Using it again restarts the cooldown.
+        cooldowns.start(player, now + Duration::from_secs(60));
+        assert_eq!(
+            cooldowns.remaining(player, now + Duration::from_secs(70), cooldown),
+            Some(Duration::from_secs(20))
+        );
+    }
+
+    #[test]
+    fn pending_points_are_relative_to_the_spawn_and_colored_by_priority() {
+        let pending = HashMap::from([
+            (ChunkPos::new(3, 4), Some(0)),
+            (ChunkPos::new(5, 4), Some(5)),
+            (ChunkPos::new(3, 2), Some(10)),
+            (ChunkPos::new(-1, 4), None),
+        ]);
+
+        let mut points = pending_plot_points(&pending, ChunkPos::new(3, 4));
+        points.sort_by_key(|point| point.offset);
+
+        assert_eq!(
+            points,
+            [
+                PendingPoint {
+                    offset: (-4, 0),
+                    color: egui::Color32::GRAY,
+                },
+                PendingPoint {
+                    offset: (0, -2),
+                    color: egui::Color32::from_rgb(255, 0, 0),
+                },
+                PendingPoint {
+                    offset: (0, 0),
+                    color: egui::Color32::from_rgb(0, 255, 0),
+                },
+                PendingPoint {
+                    offset: (2, 0),
+                    color: egui::Color32::from_rgb(127, 127, 0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pending_points_with_a_single_priority_are_green() {
+        let pending = HashMap::from([
+            (ChunkPos::new(0, 0), Some(0)),
+            (ChunkPos::new(1, 0), Some(0)),
+            (ChunkPos::new(2, 0), None),
+        ]);
+
+        let points = pending_plot_points(&pending, ChunkPos::new(0, 0));
+        assert_eq!(points.len(), 3);
+        for point in points {
+            let expected = if point.offset == (2, 0) {
+                egui::Color32::GRAY
+            } else {
+                egui::Color32::from_rgb(0, 255, 0)
+            };
+            assert_eq!(point.color, expected);
+        }
+
+        assert!(pending_plot_points(&HashMap::new(), ChunkPos::new(0, 0)).is_empty());
+    }
+
+    #[test]
+    fn chunkworker_pauses_and_resumes_the_workers() {
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let (state, pending) = test_state();
+        let mut status = ChunkWorkerStatus::default();
+
+        assert_eq!(set_paused(&state, &mut status, &args(&["pause"])), Ok(true));
+        assert!(status.paused);
+        assert!(matches!(pending.try_recv(), Ok(WorkerMessage::Pause)));
+
+        assert_eq!(
+            set_paused(&state, &mut status, &args(&["resume"])),
+            Ok(false)
+        );
+        assert!(!status.paused);
+        assert!(matches!(pending.try_recv(), Ok(WorkerMessage::Resume)));
+
+        status.paused = true;
+        for invalid in [vec![], vec!["stop"], vec!["PAUSE"]] {
+            assert_eq!(
+                set_paused(&state, &mut status, &args(&invalid)),
+                Err("Usage: chunkworker pause | chunkworker resume".to_string())
+            );
+        }
+        assert!(status.paused);
+        assert!(pending.is_empty());
+
+        // The status only changes if the workers got the message.
+        drop(pending);
+        assert!(set_paused(&state, &mut status, &args(&["resume"]))
+            .unwrap_err()
+            .starts_with("Unable to send the message to the chunk workers"));
+        assert!(status.paused);
+    }
+
+    #[test]
+    fn view_chunks_are_split_by_priority() {
+        let chunks = vec![
+            (3, ChunkPos::new(3, 0)),
+            (1, ChunkPos::new(1, 0)),
+            (2, ChunkPos::new(2, 0)),
+            (0, ChunkPos::new(0, 0)),
+        ];
+
+        assert_eq!(
+            split_view_chunks(chunks.clone(), 3),
+            (
+                vec![
+                    ChunkPos::new(0, 0),
+                    ChunkPos::new(1, 0),
+                    ChunkPos::new(2, 0)
+                ],
+                vec![ChunkPos::new(3, 0)]
+            )
+        );
+        assert_eq!(split_view_chunks(chunks.clone(), 10).1, []);
+        assert_eq!(split_view_chunks(chunks, 0).0, []);
+    }
+
+    /// Queues the chunks of a view like `update_client_views`, returns the
+    /// chunks queued every tick until the backlog is empty.
+    fn queue_view_ticks(
+        view: ChunkView,
+        old_view: ChunkView,
+        backlog: &mut Vec<ChunkPos>,
+        whole_view: bool,
+        limit: usize,
+    ) -> Vec<Vec<ChunkPos>> {
+        let priority = |pos: ChunkPos| {
+            let (dx, dz) = (pos.x - view.pos.x, pos.z - view.pos.z);
+            (dx * dx + dz * dz) as Priority
+        };
+
+        let mut ticks = vec![];
+        let mut chunks = new_view_chunks(view, old_view, Some(backlog.as_slice()), whole_view);
+        loop {
+            let (now, later) = split_view_chunks(
+                chunks.into_iter().map(|pos| (priority(pos), pos)).collect(),
+                limit,
+            );
+            *backlog = later;
+            ticks.push(now);
+
+            if backlog.is_empty() {
+                return ticks;
+            }
+            // The view doesn't change in the following ticks.
+            chunks = new_view_chunks(view, view, Some(backlog.as_slice()), false);
+        }
+    }
+
+    #[test]
+    fn view_chunks_over_the_cap_are_queued_in_the_following_ticks() {
+        let view = ChunkView::new(ChunkPos::new(0, 0), 4);
+        let mut backlog = vec![];
+
+        let ticks = queue_view_ticks(view, view, &mut backlog, true, 10);
+
+        let total = view.iter().count();
+        assert_eq!(ticks.len(), total / 10 + usize::from(total % 10 != 0));
+        assert!(ticks.iter().all(|tick| tick.len() <= 10));
+
+        // The closest chunks are queued first.
+        assert_eq!(ticks[0][0], ChunkPos::new(0, 0));
+
+        let queued = ticks.concat();
+        assert_eq!(queued.len(), total);
+        assert_eq!(
+            queued.into_iter().collect::<HashSet<_>>(),
+            view.iter().collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn backlog_outside_the_new_view_is_dropped() {
+        let old_view = ChunkView::new(ChunkPos::new(0, 0), 4);
+        let (_, mut backlog) = split_view_chunks(old_view.iter().map(|pos| (0, pos)).collect(), 10);
+        assert!(!backlog.is_empty());
+
+        // The player moves far enough that the views don't overlap.
+        let view = ChunkView::new(ChunkPos::new(100, 0), 4);
+        let queued = queue_view_ticks(view, old_view, &mut backlog, false, 10).concat();
+
+        assert_eq!(queued.len(), view.iter().count());
+        assert!(queued.iter().all(|pos| view.contains(*pos)));
+        assert!(backlog.is_empty());
+    }
+
+    #[test]
+    fn backlog_inside_the_new_view_is_kept() {
+        let old_view = ChunkView::new(ChunkPos::new(0, 0), 4);
+        let view = ChunkView::new(ChunkPos::new(1, 0), 4);
+        let backlog = vec![ChunkPos::new(1, 1), ChunkPos::new(-20, 0)];
+
+        let chunks = new_view_chunks(view, old_view, Some(&backlog), false);
+
+        // The chunk at -20 0 isn't in the view anymore.
+        assert!(chunks.contains(&ChunkPos::new(1, 1)));
+        assert!(!chunks.contains(&ChunkPos::new(-20, 0)));
+        assert_eq!(chunks.len(), 1 + view.diff(old_view).count());
+        assert_eq!(new_view_chunks(view, view, None, false), []);
+    }
+
+    fn refresh_caller() -> Caller {
+        Caller {
+            username: "Alice".to_string(),
+            op_level: 2,
+            position: DVec3::new(8.5, 70.0, -3.5),
+            instance: Entity::from_raw(4),
+        }
+    }
+
+    #[test]
+    fn refresh_returns_the_caller_to_their_current_view() {
+        assert_eq!(
+            start_refresh(&refresh_caller(), false, &[]),
+            Ok(Refresh {
+                instance: Entity::from_raw(4),
+                position: DVec3::new(8.5, 70.0, -3.5),
+            })
+        );
+    }
+
+    #[test]
+    fn refresh_errors_are_sent_to_the_caller() {
+        assert_eq!(
+            start_refresh(&refresh_caller(), false, &["all".to_string()]),
+            Err("Usage: refresh".to_string())
+        );
+        // The position of the first refresh is kept.
+        assert_eq!(
+            start_refresh(&refresh_caller(), true, &[]),
+            Err("Your chunks are already being refreshed.".to_string())
+        );
+    }
+
+    #[test]
+    fn refresh_needs_op_level_2() {
+        let mut app = App::new();
+        WorldGenPlugin.build(&mut app);
+
+        let registry = app
+            .world
+            .resource::<crate::minecraft::command::CommandRegistry>();
+        assert_eq!(
+            registry.parse("refresh", 1).unwrap_err(),
+            "Not enough permissions to use refresh command."
+        );
+        assert!(registry.parse("refresh", 2).is_ok());
+    }
 }
@@ -0,0 +1,300 @@
+use bevy::prelude::{Reflect, Resource};
+use valence::{
+    biome::{Biome, BiomeId, BiomePrecipitation},
+    prelude::*,
+    protocol::ident::Ident,
+    server::Server,
+};
+
+use super::chunk_worker::{ChunkWorkerState, FBMSettings};
+
+/// Biomes generated by the terrain generator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerrainBiome {
+    Plains,
+    Desert,
+    SnowyPlains,
+}
+
+impl TerrainBiome {
+    pub const ALL: [TerrainBiome; 3] = [
+        TerrainBiome::Plains,
+        TerrainBiome::Desert,
+        TerrainBiome::SnowyPlains,
+    ];
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            TerrainBiome::Plains => "minecraft:plains",
+            TerrainBiome::Desert => "minecraft:desert",
+            TerrainBiome::SnowyPlains => "minecraft:snowy_plains",
+        }
+    }
+
+    /// Topmost block of a terrain layer, replaces grass blocks.
+    #[must_use]
+    pub fn top_block(self) -> BlockState {
+        match self {
+            TerrainBiome::Plains => BlockState::GRASS_BLOCK,
+            TerrainBiome::Desert => BlockState::SAND,
+            TerrainBiome::SnowyPlains => BlockState::SNOW_BLOCK,
+        }
+    }
+
+    /// Blocks below the top block, replaces dirt.
+    #[must_use]
+    pub fn filler_block(self) -> BlockState {
+        match self {
+            TerrainBiome::Plains | TerrainBiome::SnowyPlains => BlockState::DIRT,
+            TerrainBiome::Desert => BlockState::SAND,
+        }
+    }
+
+    #[must_use]
+    pub fn biome(self) -> Biome {
+        let name = Ident::new(self.name().to_string()).unwrap();
+
+        match self {
+            TerrainBiome::Plains => Biome {
+                name,
+                ..Default::default()
+            },
+            TerrainBiome::Desert => Biome {
+                name,
+                precipitation: BiomePrecipitation::None,
+                grass_color: Some(0xbfb755),
+                foliage_color: Some(0xaea42a),
+                ..Default::default()
+            },
+            TerrainBiome::SnowyPlains => Biome {
+                name,
+                precipitation: BiomePrecipitation::Snow,
+                grass_color: Some(0x80b497),
+                foliage_color: Some(0x60a17b),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// All biomes of the terrain generator, registered with the server.
+#[must_use]
+pub fn biomes() -> Vec<Biome> { TerrainBiome::ALL.map(TerrainBiome::biome).to_vec() }
+
+/// Ids the server assigned to the [`TerrainBiome`]s.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BiomeIds([BiomeId; TerrainBiome::ALL.len()]);
+
+impl BiomeIds {
+    /// Looks up the ids of the registered biomes, biomes that aren't
+    /// registered use the default biome.
+    #[must_use]
+    pub fn from_server(server: &Server) -> Self {
+        let mut ids = [BiomeId::default(); TerrainBiome::ALL.len()];
+
+        for (id, biome) in server.biomes() {
+            if let Some(i) = TerrainBiome::ALL
+                .iter()
+                .position(|b| b.name() == biome.name.as_str())
+            {
+                ids[i] = id;
+            }
+        }
+
+        Self(ids)
+    }
+
+    #[must_use]
+    pub fn get(&self, biome: TerrainBiome) -> BiomeId {
+        let i = TerrainBiome::ALL.iter().position(|b| *b == biome).unwrap();
+        self.0[i]
+    }
+}
+
+#[derive(Debug, Clone, Resource, Reflect, serde::Deserialize, serde::Serialize, PartialEq)]
+#[reflect(Resource)]
+pub struct BiomeSettings {
+    pub enable_biomes: bool,
+    pub temperature: FBMSettings,
+    pub humidity: FBMSettings,
+    /// Columns colder than this are snowy plains.
+    pub snow_temperature: f64,
+    /// Columns hotter than this and dryer than `desert_humidity` are deserts.
+    pub desert_temperature: f64,
+    pub desert_humidity: f64,
+}
+
+impl Default for BiomeSettings {
+    fn default() -> Self {
+        let climate = FBMSettings {
+            point_scaleing: 600.0,
+            octaves: 3,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        };
+
+        Self {
+            enable_biomes: true,
+            temperature: climate.clone(),
+            humidity: climate,
+            snow_temperature: 0.35,
+            desert_temperature: 0.6,
+            desert_humidity: 0.5,
+        }
+    }
+}
+
+impl BiomeSettings {
+    #[must_use]
+    pub fn summary(&self) -> String {
+        format!(
+            "Biomes: {}, snow below {}, desert above {} (humidity below {})",
+            if self.enable_biomes { "on" } else { "off" },
+            self.snow_temperature,
+            self.desert_temperature,
+            self.desert_humidity
+        )
+    }
+}
+
+/// Biome of the column at `x` `z`, the climate is sampled once per 4x4 biome
+/// cell so the surface blocks match the biome sent to the client.
+#[must_use]
+pub fn column_biome(state: &ChunkWorkerState, x: i32, z: i32) -> TerrainBiome {
+    let settings = &state.settings.biomes;
+    if !settings.enable_biomes {
+        return TerrainBiome::Plains;
+    }
+
+    let p = DVec3::new(
+        f64::from(x.div_euclid(4) * 4),
+        0.0,
+        f64::from(z.div_euclid(4) * 4),
+    );
+    let temperature = settings.temperature.call(&state.temperature, p);
+    let humidity = settings.humidity.call(&state.humidity, p);
+
+    if temperature < settings.snow_temperature {
+        TerrainBiome::SnowyPlains
+    } else if temperature > settings.desert_temperature && humidity < settings.desert_humidity {
+        TerrainBiome::Desert
+    } else {
+        TerrainBiome::Plains
+    }
+}
+
+/// Sets the biome of every biome cell of the chunk, does nothing if biomes are
+/// disabled.
+pub fn assign_biomes(state: &ChunkWorkerState, chunk: &mut Chunk, pos: ChunkPos) {
+    if !state.settings.biomes.enable_biomes {
+        return;
+    }
+
+    for cell_z in 0..4 {
+        for cell_x in 0..4 {
+            let x = pos.x * 16 + cell_x as i32 * 4;
+            let z = pos.z * 16 + cell_z as i32 * 4;
+            let biome = state.biome_ids.get(column_biome(state, x, z));
+
+            for cell_y in 0..chunk.section_count() * 4 {
+                chunk.set_biome(cell_x, cell_y, cell_z, biome);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::iproduct;
+
+    use super::*;
+    use crate::minecraft::world_gen::chunk_worker::TerrainSettings;
+
+    fn state(seed: u32, biomes: BiomeSettings) -> ChunkWorkerState {
+        ChunkWorkerState::new(
+            seed,
+            TerrainSettings {
+                seed,
+                biomes,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn biomes_are_deterministic() {
+        let a = state(5, BiomeSettings::default());
+        let b = state(5, BiomeSettings::default());
+
+        for (x, z) in iproduct!((-2000..2000).step_by(97), (-2000..2000).step_by(89)) {
+            assert_eq!(column_biome(&a, x, z), column_biome(&b, x, z), "{x} {z}");
+        }
+    }
+
+    #[test]
+    fn biomes_are_the_same_within_a_cell() {
+        let state = state(5, BiomeSettings::default());
+
+        for (cell_x, cell_z) in iproduct!(-20..20, -20..20) {
+            let biome = column_biome(&state, cell_x * 4, cell_z * 4);
+            for (dx, dz) in iproduct!(0..4, 0..4) {
+                assert_eq!(
+                    column_biome(&state, cell_x * 4 + dx, cell_z * 4 + dz),
+                    biome
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn disabled_biomes_are_plains() {
+        let state = state(
+            5,
+            BiomeSettings {
+                enable_biomes: false,
+                snow_temperature: 2.0,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(column_biome(&state, 100, -100), TerrainBiome::Plains);
+    }
+
+    #[test]
+    fn climate_thresholds_pick_the_biome() {
+        let snowy = state(
+            5,
+            BiomeSettings {
+                snow_temperature: 2.0,
+                ..Default::default()
+            },
+        );
+        let desert = state(
+            5,
+            BiomeSettings {
+                snow_temperature: -1.0,
+                desert_temperature: -1.0,
+                desert_humidity: 2.0,
+                ..Default::default()
+            },
+        );
+
+        for (x, z) in iproduct!((-500..500).step_by(50), (-500..500).step_by(50)) {
+            assert_eq!(column_biome(&snowy, x, z), TerrainBiome::SnowyPlains);
+            assert_eq!(column_biome(&desert, x, z), TerrainBiome::Desert);
+        }
+    }
+
+    #[test]
+    fn biomes_replace_the_surface_blocks() {
+        assert_eq!(TerrainBiome::Plains.top_block(), BlockState::GRASS_BLOCK);
+        assert_eq!(TerrainBiome::Desert.top_block(), BlockState::SAND);
+        assert_eq!(TerrainBiome::Desert.filler_block(), BlockState::SAND);
+        assert_eq!(
+            TerrainBiome::SnowyPlains.top_block(),
+            BlockState::SNOW_BLOCK
+        );
+        assert_eq!(TerrainBiome::SnowyPlains.filler_block(), BlockState::DIRT);
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use lru::LruCache;
+use valence::{prelude::Chunk, view::ChunkPos};
+
+use super::chunk_worker::TerrainSettings;
+use crate::{minecraft::save::save_chunk_to_region, util::LockResultExt};
+
+/// A chunk in the [`TrackedLruCache`].
+#[derive(Clone)]
+pub struct CachedChunk {
+    pub chunk: Chunk,
+    /// [`TerrainSettings::content_hash`] of the settings the chunk was
+    /// generated with.
+    pub hash: u64,
+    /// Incremented every time players modified the chunk since it was last
+    /// saved, `0` for unmodified chunks.
+    pub generation: u64,
+}
+
+impl CachedChunk {
+    #[must_use]
+    pub fn new(chunk: Chunk, hash: u64) -> Self {
+        Self {
+            chunk,
+            hash,
+            generation: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn is_dirty(&self) -> bool { self.generation > 0 }
+}
+
+/// Chunk cache of the chunk workers. Modified chunks that are evicted are
+/// saved, instead of being dropped silently like by [`LruCache`].
+pub struct TrackedLruCache {
+    cache: LruCache<ChunkPos, CachedChunk>,
+    /// Whether evicted chunks get saved to disk.
+    saving: bool,
+    /// Number of chunks evicted to make room, clearing the cache doesn't count.
+    evictions: u64,
+    /// Modified chunks that were evicted and wait to be saved. The lock is held
+    /// while they are written, so the cache never falls back to an older
+    /// version in the region file, see [`TrackedLruCache::get_current`].
+    pending: Arc<Mutex<HashMap<ChunkPos, CachedChunk>>>,
+    /// Saves an evicted chunk, [`save_chunk_to_region`] outside of tests.
+    save: fn(Chunk, ChunkPos, TerrainSettings) -> Result<()>,
+}
+
+impl TrackedLruCache {
+    #[must_use]
+    pub fn new(capacity: NonZeroUsize, saving: bool) -> Self {
+        Self {
+            cache: LruCache::new(capacity),
+            saving,
+            evictions: 0,
+            pending: Arc::default(),
+            save: save_chunk_to_region,
+        }
+    }
+
+    pub fn get(&mut self, pos: &ChunkPos) -> Option<&CachedChunk> { self.cache.get(pos) }
+
+    /// The chunk if it is cached with the settings of `hash`. A modified chunk
+    /// that was evicted but isn't saved yet is put back into the cache, if it
+    /// is being saved this waits until it is written.
+    pub fn get_current(
+        &mut self,
+        pos: &ChunkPos,
+        hash: u64,
+        settings: &TerrainSettings,
+    ) -> Option<Chunk> {
+        if let Some(cached) = self.cache.get(pos) {
+            return (cached.hash == hash).then(|| cached.chunk.clone());
+        }
+
+        let pending = self
+            .pending
+            .lock()
+            .ignore_poison()
+            .get(pos)
+            .filter(|cached| cached.hash == hash)
+            .cloned()?;
+        let chunk = pending.chunk.clone();
+        self.insert(*pos, pending, settings);
+
+        Some(chunk)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&ChunkPos, &CachedChunk)> { self.cache.iter() }
+
+    #[must_use]
+    pub fn len(&self) -> usize { self.cache.len() }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool { self.cache.is_empty() }
+
+    #[must_use]
+    pub fn capacity(&self) -> usize { self.cache.cap().get() }
+
+    #[must_use]
+    pub fn evictions(&self) -> u64 { self.evictions }
+
+    /// Caches an unmodified chunk.
+    pub fn push(&mut self, pos: ChunkPos, chunk: Chunk, hash: u64, settings: &TerrainSettings) {
+        self.insert(pos, CachedChunk::new(chunk, hash), settings);
+    }
+
+    /// Caches a chunk players modified, it is saved once it gets evicted.
+    pub fn push_modified(
+        &mut self,
+        pos: ChunkPos,
+        chunk: Chunk,
+        hash: u64,
+        settings: &TerrainSettings,
+    ) {
+        let generation = self.cache.peek(&pos).map_or(0, |c| c.generation) + 1;
+
+        self.insert(
+            pos,
+            CachedChunk {
+                chunk,
+                hash,
+                generation,
+            },
+            settings,
+        );
+    }
+
+    /// Changes the capacity, evicting the least recently used chunks if it
+    /// shrinks.
+    pub fn resize(&mut self, capacity: NonZeroUsize, settings: &TerrainSettings) {
+        while self.cache.len() > capacity.get() {
+            if let Some((pos, evicted)) = self.cache.pop_lru() {
+                self.evictions += 1;
+                self.evicted(pos, evicted, settings);
+            }
+        }
+
+        self.cache.resize(capacity);
+    }
+
+    /// Removes all chunks, modified chunks are saved.
+    pub fn clear(&mut self, settings: &TerrainSettings) {
+        while let Some((pos, evicted)) = self.cache.pop_lru() {
+            self.evicted(pos, evicted, settings);
+        }
+    }
+
+    /// Marks all chunks as saved.
+    pub fn mark_clean(&mut self) {
+        for (_, cached) in self.cache.iter_mut() {
+            cached.generation = 0;
+        }
+    }
+
+    fn insert(&mut self, pos: ChunkPos, cached: CachedChunk, settings: &TerrainSettings) {
+        // `push` also returns the old value if the chunk was already cached.
+        if let Some((evicted_pos, evicted)) = self.cache.push(pos, cached) {
+            if evicted_pos != pos {
+                self.evictions += 1;
+                self.evicted(evicted_pos, evicted, settings);
+            }
+        }
+    }
+
+    /// Saves the evicted chunk on a blocking task if it was modified with the
+    /// current settings. If the chunk is evicted again before the task runs,
+    /// only the newest version is saved.
+    fn evicted(&self, pos: ChunkPos, evicted: CachedChunk, settings: &TerrainSettings) {
+        if !self.queue_save(pos, evicted, settings) {
+            return;
+        }
+
+        let pending = self.pending.clone();
+        let settings = settings.clone();
+        let save = self.save;
+        tokio::task::spawn_blocking(move || {
+            let mut pending = pending.lock().ignore_poison();
+            let Some(evicted) = pending.remove(&pos) else {
+                return;
+            };
+
+            if let Err(e) = save(evicted.chunk, pos, settings) {
+                error!(target: "minecraft::save", "Failed to save evicted chunk {pos:?}: {e}");
+            }
+        });
+    }
+
+    /// Adds the evicted chunk to the pending chunks if it has to be saved,
+    /// returns whether it was added.
+    fn queue_save(&self, pos: ChunkPos, evicted: CachedChunk, settings: &TerrainSettings) -> bool {
+        if !self.saving || !evicted.is_dirty() || evicted.hash != settings.content_hash() {
+            return false;
+        }
+
+        self.pending.lock().ignore_poison().insert(pos, evicted);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use valence::prelude::BlockState;
+
+    use super::*;
+    use crate::SECTION_COUNT;
+
+    /// Positions of the chunks saved by [`record_save`].
+    static SAVED: Mutex<Vec<ChunkPos>> = Mutex::new(Vec::new());
+
+    fn record_save(_: Chunk, pos: ChunkPos, _: TerrainSettings) -> Result<()> {
+        SAVED.lock().ignore_poison().push(pos);
+        Ok(())
+    }
+
+    fn cache(capacity: usize, saving: bool) -> TrackedLruCache {
+        TrackedLruCache {
+            save: record_save,
+            ..TrackedLruCache::new(NonZeroUsize::new(capacity).unwrap(), saving)
+        }
+    }
+
+    fn chunk() -> Chunk { Chunk::new(SECTION_COUNT) }
+
+    fn is_saved(pos: ChunkPos) -> bool { SAVED.lock().ignore_poison().contains(&pos) }
+
+    /// Waits until the blocking task saved the chunk.
+    async fn wait_until_saved(pos: ChunkPos) {
+        let start = Instant::now();
+        while !is_saved(pos) {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "{pos:?} wasn't saved"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn dirty_chunks_are_saved_when_evicted() {
+        let settings = TerrainSettings::default();
+        let hash = settings.content_hash();
+        let mut cache = cache(1, true);
+        let (clean, dirty, outdated) = (
+            ChunkPos::new(100, 0),
+            ChunkPos::new(101, 0),
+            ChunkPos::new(102, 0),
+        );
+
+        cache.push(clean, chunk(), hash, &settings);
+        cache.push_modified(dirty, chunk(), hash, &settings);
+        // Chunks of other settings are outdated, they aren't saved either.
+        cache.push_modified(outdated, chunk(), hash.wrapping_add(1), &settings);
+        cache.push(ChunkPos::new(103, 0), chunk(), hash, &settings);
+
+        assert_eq!(cache.evictions(), 3);
+        wait_until_saved(dirty).await;
+        assert!(!is_saved(clean));
+        assert!(!is_saved(outdated));
+        assert!(cache.pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn chunks_are_not_saved_if_saving_is_disabled() {
+        let settings = TerrainSettings::default();
+        let mut cache = cache(1, false);
+        let pos = ChunkPos::new(110, 0);
+
+        cache.push_modified(pos, chunk(), settings.content_hash(), &settings);
+        cache.push(
+            ChunkPos::new(111, 0),
+            chunk(),
+            settings.content_hash(),
+            &settings,
+        );
+
+        assert_eq!(cache.evictions(), 1);
+        assert!(cache.pending.lock().unwrap().is_empty());
+        assert!(!is_saved(pos));
+    }
+
+    #[test]
+    fn pending_chunks_are_put_back_into_the_cache() {
+        let settings = TerrainSettings::default();
+        let hash = settings.content_hash();
+        let mut cache = cache(2, true);
+        let pos = ChunkPos::new(120, 0);
+
+        let mut modified = chunk();
+        modified.set_block_state(1, 2, 3, BlockState::STONE);
+        let evicted = CachedChunk {
+            chunk: modified,
+            hash,
+            generation: 2,
+        };
+        assert!(cache.queue_save(pos, evicted, &settings));
+        assert!(cache.pending.lock().unwrap().contains_key(&pos));
+
+        // Pending chunks of other settings are outdated.
+        assert!(cache
+            .get_current(&pos, hash.wrapping_add(1), &settings)
+            .is_none());
+        assert!(cache.is_empty());
+
+        let current = cache.get_current(&pos, hash, &settings).unwrap();
+        assert_eq!(current.block_state(1, 2, 3), BlockState::STONE);
+        // It stays modified until the save, so it isn't lost if it's evicted again.
+        assert_eq!(cache.get(&pos).unwrap().generation, 2);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn clean_chunks_are_not_queued() {
+        let settings = TerrainSettings::default();
+        let cache = cache(1, true);
+        let pos = ChunkPos::new(130, 0);
+
+        assert!(!cache.queue_save(
+            pos,
+            CachedChunk::new(chunk(), settings.content_hash()),
+            &settings
+        ));
+        assert!(cache.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn shrinking_evicts_the_least_recently_used_chunks() {
+        let settings = TerrainSettings::default();
+        let hash = settings.content_hash();
+        let mut cache = cache(4, true);
+        let positions = (140..144).map(|x| ChunkPos::new(x, 0)).collect::<Vec<_>>();
+
+        cache.push_modified(positions[0], chunk(), hash, &settings);
+        for pos in &positions[1..] {
+            cache.push(*pos, chunk(), hash, &settings);
+        }
+        // Used most recently, so it is kept.
+        cache.get(&positions[1]);
+
+        cache.resize(NonZeroUsize::new(2).unwrap(), &settings);
+
+        assert_eq!(cache.capacity(), 2);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.evictions(), 2);
+        assert!(cache.get(&positions[1]).is_some());
+        assert!(cache.get(&positions[3]).is_some());
+        wait_until_saved(positions[0]).await;
+        assert!(!is_saved(positions[2]));
+
+        // Growing keeps every chunk.
+        cache.resize(NonZeroUsize::new(8).unwrap(), &settings);
+        assert_eq!((cache.capacity(), cache.len()), (8, 2));
+    }
+
+    #[test]
+    fn marked_clean_chunks_are_not_saved() {
+        let settings = TerrainSettings::default();
+        let hash = settings.content_hash();
+        let mut cache = cache(1, true);
+        let pos = ChunkPos::new(150, 0);
+
+        cache.push_modified(pos, chunk(), hash, &settings);
+        cache.push_modified(pos, chunk(), hash, &settings);
+        assert_eq!(cache.get(&pos).unwrap().generation, 2);
+
+        cache.mark_clean();
+        assert!(!cache.get(&pos).unwrap().is_dirty());
+
+        // Evicting clean chunks doesn't spawn a save task, so no runtime is needed.
+        cache.push(ChunkPos::new(151, 0), chunk(), hash, &settings);
+        assert_eq!(cache.evictions(), 1);
+        assert!(cache.pending.lock().unwrap().is_empty());
+    }
+}
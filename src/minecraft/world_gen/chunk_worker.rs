@@ -1,18 +1,34 @@
 use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    path::PathBuf,
     sync::{Arc, Mutex, MutexGuard},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bevy::prelude::{Reflect, Resource};
+use cfg_if::cfg_if;
 use flume::{Receiver, Sender};
-use itertools::Itertools;
-use lru::LruCache;
+use itertools::{iproduct, Itertools};
 use noise::{NoiseFn, SuperSimplex};
+use rayon::prelude::*;
 use valence::{prelude::*, view::ChunkPos};
 
+use super::{
+    biome::{assign_biomes, column_biome, BiomeIds, BiomeSettings},
+    chunk_cache::TrackedLruCache,
+};
 use crate::{
-    minecraft::save::{chunkpos_to_regionpos, load_region, save_chunk_to_region},
+    config::{StaleRegions, WorldConfig},
+    minecraft::{
+        maprender::{export_map, in_map_area},
+        metrics,
+        save::{
+            chunkpos_to_regionpos, clean_stale_regions, load_region_async,
+            save_chunk_to_region_async, save_chunks_to_regions,
+        },
+    },
     util::LockResultExt,
     CONFIG, SECTION_COUNT,
 };
@@ -26,16 +42,125 @@ type CWReceiver = Receiver<WorkerMessage>;
 #[derive(Debug, Clone)]
 pub enum WorkerMessage {
     Chunk(ChunkPos),
+    /// Multiple chunks at once, processed in order.
+    BatchChunks(Vec<ChunkPos>),
     EmptyCache,
+    /// Changes the capacity of the chunk cache, evicting the least recently
+    /// used chunks if it shrinks.
+    ResizeCache(NonZeroUsize),
     GetTerrainSettings,
     SetTerrainSettings(TerrainSettings),
+    /// A chunk players modified, it is cached and saved once it gets evicted.
+    ChunkModified(ChunkPos, Chunk),
+    /// Saves all cached chunks of the current settings, answered with
+    /// [`WorkerResponse::FlushComplete`].
+    FlushAll,
+    /// Stops generating and saving chunks, requested chunks are kept until
+    /// [`WorkerMessage::Resume`].
+    Pause,
+    Resume,
+    /// Samples the surface of the area starting at the chunk, see
+    /// [`gen_chunk_at_scale`].
+    HeightMap(ChunkPos, u8),
+    /// Loads all chunks of the region file at the region position into the
+    /// cache, without sending them. Answered with
+    /// [`WorkerResponse::PrewarmComplete`].
+    PrewarmRegion(i64, i64),
+    /// Renders the cached chunks of the current settings around the chunk
+    /// position to a PNG file, answered with [`WorkerResponse::MapExported`].
+    ExportMap(PathBuf, ChunkPos),
+    /// A chunk of the additional dimension with the name, answered with
+    /// [`WorkerResponse::DimensionChunk`].
+    DimensionChunk(String, ChunkPos),
+    /// Deletes or archives the region files of other settings, depending on
+    /// `stale_regions`. Answered with [`WorkerResponse::StaleRegionsCleaned`].
+    CleanStaleRegions,
 }
 
 #[derive(Debug, Clone)]
 pub enum WorkerResponse {
-    Chunk(ChunkPos, Chunk),
+    /// A chunk and the [`TerrainSettings::content_hash`] of the settings it was
+    /// generated with.
+    Chunk(ChunkPos, Chunk, u64),
+    /// Sent after every chunk, see [`ChunkInfo`].
+    ChunkInfo(ChunkPos, ChunkInfo),
+    /// Sent after every chunk, see [`ChunkGenTiming`].
+    #[cfg(feature = "profiling")]
+    Timing(ChunkGenTiming),
     GetTerrainSettings(TerrainSettings),
     TerrainSettingsSet,
+    /// Number of chunks saved by [`WorkerMessage::FlushAll`].
+    FlushComplete {
+        count: usize,
+    },
+    /// The [`HeightMap`] requested by [`WorkerMessage::HeightMap`], indexed by
+    /// `[z][x]`.
+    HeightMap(ChunkPos, Vec<Vec<u16>>),
+    /// State of the chunk cache, sent after every chunk and every change of the
+    /// cache.
+    CacheStats {
+        len: usize,
+        capacity: usize,
+        evictions: u64,
+    },
+    /// Number of chunks [`WorkerMessage::PrewarmRegion`] put into the cache,
+    /// `0` if the region was already prewarmed.
+    PrewarmComplete(i64, i64, usize),
+    /// The size of the image written by [`WorkerMessage::ExportMap`] or why it
+    /// couldn't be written.
+    MapExported(PathBuf, Result<(u32, u32), String>),
+    /// A chunk requested by [`WorkerMessage::DimensionChunk`].
+    DimensionChunk(String, ChunkPos, Chunk),
+    /// Number of region files [`WorkerMessage::CleanStaleRegions`] deleted or
+    /// archived, or why they couldn't be read.
+    StaleRegionsCleaned(Result<usize, String>),
+}
+
+/// How a chunk was provided by the chunk workers.
+#[derive(Debug, Clone)]
+pub struct ChunkInfo {
+    /// Name of the worker that handled the chunk.
+    pub worker: String,
+    pub duration: Duration,
+    /// Whether the chunk was taken from the cache.
+    pub cached: bool,
+    /// Whether the chunk was loaded from a region file (or the cache).
+    pub saved: bool,
+}
+
+/// Time spent in each phase of providing a chunk, only measured with the
+/// `profiling` feature.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChunkGenTiming {
+    pub cache_ns: u64,
+    pub disk_ns: u64,
+    pub noise_ns: u64,
+    pub send_ns: u64,
+}
+
+impl ChunkGenTiming {
+    pub fn add(&mut self, other: &ChunkGenTiming) {
+        self.cache_ns += other.cache_ns;
+        self.disk_ns += other.disk_ns;
+        self.noise_ns += other.noise_ns;
+        self.send_ns += other.send_ns;
+    }
+}
+
+/// Adds the time `f` takes to `ns`, only calls `f` without the `profiling`
+/// feature.
+#[allow(unused_variables)]
+fn measure<T>(ns: &mut u64, f: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "profiling")]
+    {
+        let start = Instant::now();
+        let result = f();
+        *ns += start.elapsed().as_nanos() as u64;
+        result
+    }
+
+    #[cfg(not(feature = "profiling"))]
+    f()
 }
 
 #[derive(Debug, Clone, Resource, Reflect, serde::Deserialize, serde::Serialize, PartialEq)]
@@ -49,12 +174,52 @@ pub struct TerrainSettings {
     pub enable_stone: bool,
     pub stone_point_scaleing: f64,
     pub enable_grass: bool,
+    /// Grass density thresholds on land.
+    pub grass_thresholds: VegetationThresholds,
+    /// Seagrass density thresholds on the gravel under water.
+    pub seagrass_thresholds: VegetationThresholds,
     pub enable_water: bool,
+    pub enable_trees: bool,
+    /// Fraction of grass blocks (`0.0..=1.0`) considered for a tree.
+    pub tree_density: f64,
+    pub enable_ores: bool,
+    pub coal_ore: OreSettings,
+    pub iron_ore: OreSettings,
+    pub gold_ore: OreSettings,
+    pub redstone_ore: OreSettings,
+    pub diamond_ore: OreSettings,
+    pub enable_caves: bool,
+    /// Blocks with a cave noise value above this threshold (`0.0..=1.0`) are
+    /// carved out, lower values result in wider caves.
+    pub cave_threshold: f64,
+    /// Air below this height is filled with water.
+    pub water_height: i32,
+    /// Multiplies the height of hills and mountains (`0.0..=1.5`), `0.0`
+    /// generates flat terrain.
+    pub height_scale: f64,
+    pub biomes: BiomeSettings,
     pub seed: u32,
+    pub seeds: FeatureSeeds,
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Continues the FNV-1a `hash` with the bytes.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
 }
 
 impl Default for TerrainSettings {
-    fn default() -> Self {
+    fn default() -> Self { Self::from_config(&CONFIG.world) }
+}
+
+impl TerrainSettings {
+    /// The default settings with the seed and water height of the world
+    /// config.
+    #[must_use]
+    pub fn from_config(config: &WorldConfig) -> Self {
         Self {
             enable_gravel: true,
             gravel_height: FBMSettings::default_gravel(),
@@ -64,8 +229,390 @@ impl Default for TerrainSettings {
             enable_stone: true,
             stone_point_scaleing: 15.0,
             enable_grass: true,
+            grass_thresholds: VegetationThresholds::default(),
+            seagrass_thresholds: VegetationThresholds::default(),
             enable_water: true,
-            seed: CONFIG.world.seed.into(),
+            enable_trees: true,
+            tree_density: 0.02,
+            enable_ores: true,
+            coal_ore: OreSettings::default_coal(),
+            iron_ore: OreSettings::default_iron(),
+            gold_ore: OreSettings::default_gold(),
+            redstone_ore: OreSettings::default_redstone(),
+            diamond_ore: OreSettings::default_diamond(),
+            enable_caves: true,
+            cave_threshold: 0.9,
+            water_height: config.water_height,
+            height_scale: 1.0,
+            biomes: BiomeSettings::default(),
+            seed: config.seed.into(),
+            seeds: FeatureSeeds::default(),
+        }
+    }
+
+    /// Validates all noise settings, the error names the invalid setting.
+    ///
+    /// # Errors
+    /// - one of the [`FBMSettings`] is invalid, see [`FBMSettings::validate`]
+    /// - one of the [`VegetationThresholds`] is invalid, see
+    ///   [`VegetationThresholds::validate`]
+    /// - the height scale is outside of `0.0..=1.5`
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=MAX_HEIGHT_SCALE).contains(&self.height_scale) {
+            return Err(format!(
+                "Height scale: has to be between 0 and {MAX_HEIGHT_SCALE}"
+            ));
+        }
+
+        [
+            ("Grass", &self.grass_thresholds),
+            ("Seagrass", &self.seagrass_thresholds),
+        ]
+        .into_iter()
+        .try_for_each(|(name, t)| t.validate().map_err(|e| format!("{name}: {e}")))?;
+
+        [
+            ("Gravel", &self.gravel_height),
+            ("Sand", &self.sand_height),
+            ("Temperature", &self.biomes.temperature),
+            ("Humidity", &self.biomes.humidity),
+        ]
+        .into_iter()
+        .try_for_each(|(name, fbm)| fbm.validate().map_err(|e| format!("{name}: {e}")))
+    }
+
+    /// Hash of all settings that affect the generated blocks, chunks generated
+    /// with settings of a different hash are outdated.
+    ///
+    /// The hash is stored in region files, so it only changes if the settings
+    /// or their format change.
+    #[must_use]
+    pub fn content_hash(&self) -> u64 {
+        // Floats aren't `Hash`, so the serialized settings are hashed instead.
+        fnv1a(
+            FNV_OFFSET_BASIS,
+            &bincode::serialize(self).expect("terrain settings should be serializable"),
+        )
+    }
+
+    /// [`TerrainSettings::content_hash`] combined with the version of the
+    /// generator and the world height, worlds with the same fingerprint are
+    /// generated identically.
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        let hash = fnv1a(FNV_OFFSET_BASIS, &self.content_hash().to_le_bytes());
+        let hash = fnv1a(hash, env!("CARGO_PKG_VERSION").as_bytes());
+        fnv1a(hash, &(SECTION_COUNT as u64).to_le_bytes())
+    }
+
+    /// Human readable summary of the settings, one entry per line.
+    #[must_use]
+    pub fn summary(&self) -> Vec<String> {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+
+        vec![
+            format!("Seed: {}", self.seed),
+            self.seeds.summary(),
+            format!(
+                "Features: gravel {}, sand {}, stone {}, grass {}, water {}, trees {}, ores {}, \
+                 caves {}",
+                on_off(self.enable_gravel),
+                on_off(self.enable_sand),
+                on_off(self.enable_stone),
+                on_off(self.enable_grass),
+                on_off(self.enable_water),
+                on_off(self.enable_trees),
+                on_off(self.enable_ores),
+                on_off(self.enable_caves),
+            ),
+            format!("Gravel: {}", self.gravel_height.summary()),
+            format!(
+                "Sand: offset {}, {}",
+                self.sand_offset,
+                self.sand_height.summary()
+            ),
+            format!("Stone: scaling {}", self.stone_point_scaleing),
+            format!("Caves: threshold {}", self.cave_threshold),
+            format!("Water: height {}", self.water_height),
+            format!("Height scale: {}", self.height_scale),
+            format!(
+                "Vegetation: grass {}, seagrass {}",
+                self.grass_thresholds.summary(),
+                self.seagrass_thresholds.summary()
+            ),
+            self.biomes.summary(),
+        ]
+    }
+}
+
+/// Noise values above which grass (or seagrass) grows, and above which it
+/// grows two blocks tall.
+#[derive(Debug, Clone, Copy, Reflect, serde::Deserialize, serde::Serialize, PartialEq)]
+pub struct VegetationThresholds {
+    pub short: f64,
+    pub tall: f64,
+}
+
+impl Default for VegetationThresholds {
+    fn default() -> Self {
+        Self {
+            short: 0.55,
+            tall: 0.7,
+        }
+    }
+}
+
+impl VegetationThresholds {
+    /// # Errors
+    /// - a threshold is outside of `-1.0..=1.0`
+    /// - `tall` is lower than `short`
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-1.0..=1.0).contains(&self.short) || !(-1.0..=1.0).contains(&self.tall) {
+            return Err("thresholds have to be between -1 and 1".to_string());
+        }
+
+        if self.tall < self.short {
+            return Err("the tall threshold can't be lower than the short one".to_string());
+        }
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn summary(&self) -> String { format!("{}/{}", self.short, self.tall) }
+}
+
+/// Seeds of the individual noise functions, features without a seed use the
+/// main seed plus a fixed offset.
+#[derive(
+    Debug, Default, Clone, Copy, Reflect, serde::Deserialize, serde::Serialize, PartialEq, Eq,
+)]
+pub struct FeatureSeeds {
+    pub density: Option<u32>,
+    pub hilly: Option<u32>,
+    pub stone: Option<u32>,
+    pub gravel: Option<u32>,
+    pub grass: Option<u32>,
+    pub coal: Option<u32>,
+    pub iron: Option<u32>,
+    pub gold: Option<u32>,
+    pub redstone: Option<u32>,
+    pub diamond: Option<u32>,
+    pub temperature: Option<u32>,
+    pub humidity: Option<u32>,
+    pub caves: Option<u32>,
+    /// Seagrass uses the grass noise if it has no seed of its own.
+    pub seagrass: Option<u32>,
+}
+
+impl FeatureSeeds {
+    /// The name, seed and offset to the main seed of every feature.
+    pub fn fields_mut(&mut self) -> [(&'static str, &mut Option<u32>, u32); 14] {
+        [
+            ("Density", &mut self.density, 0),
+            ("Hilly", &mut self.hilly, 1),
+            ("Stone", &mut self.stone, 2),
+            ("Gravel", &mut self.gravel, 3),
+            ("Grass", &mut self.grass, 4),
+            ("Coal", &mut self.coal, 5),
+            ("Iron", &mut self.iron, 6),
+            ("Gold", &mut self.gold, 7),
+            ("Redstone", &mut self.redstone, 8),
+            ("Diamond", &mut self.diamond, 9),
+            ("Temperature", &mut self.temperature, 10),
+            ("Humidity", &mut self.humidity, 11),
+            ("Caves", &mut self.caves, 12),
+            ("Seagrass", &mut self.seagrass, 4),
+        ]
+    }
+
+    /// The seed of a feature, derived from the main seed if it isn't set.
+    #[must_use]
+    pub fn resolve(seed: Option<u32>, main_seed: u32, offset: u32) -> u32 {
+        seed.unwrap_or_else(|| main_seed.wrapping_add(offset))
+    }
+
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut seeds = *self;
+        let set = seeds
+            .fields_mut()
+            .into_iter()
+            .filter_map(|(name, seed, _)| seed.map(|seed| format!("{name} {seed}")))
+            .collect::<Vec<_>>();
+
+        if set.is_empty() {
+            "Feature seeds: derived from the seed".to_string()
+        } else {
+            format!("Feature seeds: {}", set.join(", "))
+        }
+    }
+}
+
+/// Highest [`TerrainSettings::height_scale`], the terrain stays below the top
+/// of the world.
+pub const MAX_HEIGHT_SCALE: f64 = 1.5;
+
+/// Complete terrain settings for a kind of world.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum TerrainPreset {
+    /// Flat land at `y=64` without water and caves.
+    Flat,
+    #[default]
+    Default,
+    /// Higher mountains and stretched out features.
+    Amplified,
+    /// Small islands in a raised ocean.
+    Archipelago,
+    /// Settings that don't match any preset.
+    Custom,
+}
+
+impl TerrainPreset {
+    pub const ALL: [Self; 5] = [
+        Self::Flat,
+        Self::Default,
+        Self::Amplified,
+        Self::Archipelago,
+        Self::Custom,
+    ];
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Flat => "Flat",
+            Self::Default => "Default",
+            Self::Amplified => "Amplified",
+            Self::Archipelago => "Archipelago",
+            Self::Custom => "Custom",
+        }
+    }
+
+    /// The settings of the preset, with the seeds of `current`. `None` for
+    /// [`TerrainPreset::Custom`].
+    #[must_use]
+    pub fn settings(self, current: &TerrainSettings) -> Option<TerrainSettings> {
+        let defaults = TerrainSettings {
+            seed: current.seed,
+            seeds: current.seeds,
+            ..TerrainSettings::default()
+        };
+
+        let settings = match self {
+            Self::Flat => TerrainSettings {
+                enable_water: false,
+                enable_caves: false,
+                height_scale: 0.0,
+                ..defaults
+            },
+            Self::Default => defaults,
+            Self::Amplified => {
+                let stretch = |fbm: FBMSettings| FBMSettings {
+                    point_scaleing: fbm.point_scaleing * 2.0,
+                    ..fbm
+                };
+
+                TerrainSettings {
+                    gravel_height: stretch(defaults.gravel_height),
+                    sand_height: stretch(defaults.sand_height),
+                    stone_point_scaleing: defaults.stone_point_scaleing * 2.0,
+                    height_scale: MAX_HEIGHT_SCALE,
+                    biomes: BiomeSettings {
+                        temperature: stretch(defaults.biomes.temperature),
+                        humidity: stretch(defaults.biomes.humidity),
+                        ..defaults.biomes
+                    },
+                    ..defaults
+                }
+            }
+            Self::Archipelago => TerrainSettings {
+                sand_offset: 8,
+                tree_density: 0.04,
+                water_height: 150,
+                height_scale: 0.6,
+                ..defaults
+            },
+            Self::Custom => return None,
+        };
+
+        Some(settings)
+    }
+
+    /// The preset `settings` belong to, [`TerrainPreset::Custom`] if they were
+    /// changed after a preset was loaded.
+    #[must_use]
+    pub fn detect(settings: &TerrainSettings) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.settings(settings).as_ref() == Some(settings))
+            .unwrap_or(Self::Custom)
+    }
+}
+
+/// Named groups of gravel, sand and stone settings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GeologyPreset {
+    #[default]
+    Balanced,
+    /// Thin sand and dirt layers, a lot of stone.
+    Rocky,
+    /// Wide beaches and a lot of gravel under water.
+    Sandy,
+}
+
+impl GeologyPreset {
+    pub const ALL: [Self; 3] = [Self::Balanced, Self::Rocky, Self::Sandy];
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Balanced => "Balanced",
+            Self::Rocky => "Rocky",
+            Self::Sandy => "Sandy",
+        }
+    }
+
+    /// Overwrites the gravel, sand and stone settings, everything else is kept.
+    pub fn apply(self, settings: &mut TerrainSettings) {
+        let defaults = TerrainSettings::default();
+
+        settings.enable_gravel = true;
+        settings.enable_sand = true;
+        settings.enable_stone = true;
+
+        match self {
+            Self::Balanced => {
+                settings.gravel_height = defaults.gravel_height;
+                settings.sand_offset = defaults.sand_offset;
+                settings.sand_height = defaults.sand_height;
+                settings.stone_point_scaleing = defaults.stone_point_scaleing;
+            }
+            Self::Rocky => {
+                settings.gravel_height = FBMSettings {
+                    point_scaleing: 20.0,
+                    ..FBMSettings::default_gravel()
+                };
+                settings.sand_offset = 1;
+                settings.sand_height = FBMSettings {
+                    point_scaleing: 5.0,
+                    ..FBMSettings::default_sand()
+                };
+                settings.stone_point_scaleing = 5.0;
+            }
+            Self::Sandy => {
+                settings.gravel_height = FBMSettings {
+                    point_scaleing: 5.0,
+                    octaves: 4,
+                    ..FBMSettings::default_gravel()
+                };
+                settings.sand_offset = 12;
+                settings.sand_height = FBMSettings {
+                    point_scaleing: 25.0,
+                    octaves: 2,
+                    ..FBMSettings::default_sand()
+                };
+                settings.stone_point_scaleing = 30.0;
+            }
         }
     }
 }
@@ -83,16 +630,50 @@ pub struct FBMSettings {
 
 #[allow(clippy::must_use_candidate)]
 impl FBMSettings {
-    pub fn call(&self, noise: &SuperSimplex, p: DVec3) -> f64 {
-        fbm(
+    pub fn call(&self, noise: &SuperSimplex, p: DVec3) -> f64 { self.call_raw(noise, p.to_array()) }
+
+    /// Same as [`call`](Self::call), but takes a plain array instead of a
+    /// [`DVec3`].
+    pub fn call_raw(&self, noise: &SuperSimplex, p: [f64; 3]) -> f64 {
+        fbm_raw(
             noise,
-            p / self.point_scaleing,
+            p.map(|c| c / self.point_scaleing),
             self.octaves,
             self.lacunarity,
             self.persistence,
         )
     }
 
+    /// Checks for settings that would result in degenerate noise.
+    ///
+    /// # Errors
+    /// - `octaves` is 0
+    /// - `lacunarity` or `point_scaleing` is 0 or not finite
+    /// - `persistence` is not finite
+    pub fn validate(&self) -> Result<(), String> {
+        if self.octaves == 0 {
+            return Err("octaves must be at least 1".to_string());
+        }
+        if self.lacunarity == 0.0 || !self.lacunarity.is_finite() {
+            return Err("lacunarity must be a finite non-zero number".to_string());
+        }
+        if self.point_scaleing == 0.0 || !self.point_scaleing.is_finite() {
+            return Err("point scaling must not be 0".to_string());
+        }
+        if !self.persistence.is_finite() {
+            return Err("persistence must be a number".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "scaling {}, octaves {}, lacunarity {}, persistence {}",
+            self.point_scaleing, self.octaves, self.lacunarity, self.persistence
+        )
+    }
+
     pub fn default_gravel() -> Self {
         Self {
             point_scaleing: 10.0,
@@ -112,11 +693,106 @@ impl FBMSettings {
     }
 }
 
+#[derive(
+    Debug, Default, Clone, Resource, Reflect, serde::Deserialize, serde::Serialize, PartialEq,
+)]
+#[reflect(Resource)]
+pub struct OreSettings {
+    pub enabled: bool,
+    /// Point scaling of the ore noise, bigger values result in bigger veins.
+    pub vein_size: f64,
+    /// Fraction of stone blocks (`0.0..=1.0`) replaced by the ore.
+    pub frequency: f64,
+    pub min_y: i32,
+    pub max_y: i32,
+}
+
+#[allow(clippy::must_use_candidate)]
+impl OreSettings {
+    pub fn contains(&self, noise: &SuperSimplex, p: DVec3) -> bool {
+        let y = p.y as i32;
+
+        self.enabled
+            && y >= self.min_y
+            && y <= self.max_y
+            && noise01(noise, p / self.vein_size) > 1.0 - self.frequency
+    }
+
+    pub fn default_coal() -> Self {
+        Self {
+            enabled: true,
+            vein_size: 4.0,
+            frequency: 0.12,
+            min_y: 40,
+            max_y: 200,
+        }
+    }
+
+    pub fn default_iron() -> Self {
+        Self {
+            enabled: true,
+            vein_size: 3.0,
+            frequency: 0.08,
+            min_y: 10,
+            max_y: 140,
+        }
+    }
+
+    pub fn default_gold() -> Self {
+        Self {
+            enabled: true,
+            vein_size: 2.5,
+            frequency: 0.05,
+            min_y: 0,
+            max_y: 60,
+        }
+    }
+
+    pub fn default_redstone() -> Self {
+        Self {
+            enabled: true,
+            vein_size: 2.5,
+            frequency: 0.06,
+            min_y: 0,
+            max_y: 40,
+        }
+    }
+
+    pub fn default_diamond() -> Self {
+        Self {
+            enabled: true,
+            vein_size: 2.0,
+            frequency: 0.03,
+            min_y: 0,
+            max_y: 25,
+        }
+    }
+}
+
 pub struct ChunkWorker {
     pub sender: CWSender,
     pub receiver: CWReceiver,
-    pub cache: LruCache<ChunkPos, Chunk>,
+    pub cache: TrackedLruCache,
     pub state: ChunkWorkerState,
+    /// [`TerrainSettings::content_hash`] of the current settings.
+    pub settings_hash: u64,
+    /// Whether generated chunks get saved to disk.
+    pub saving: bool,
+    /// Whether the worker is paused, see [`WorkerMessage::Pause`].
+    pub paused: bool,
+    /// Chunks requested while the worker was paused.
+    pub deferred: Vec<ChunkPos>,
+    /// Chunks of additional dimensions requested while the worker was paused.
+    pub deferred_dimensions: Vec<(String, ChunkPos)>,
+    /// Whether a [`WorkerMessage::FlushAll`] was received while the worker was
+    /// paused.
+    pub deferred_flush: bool,
+    /// Regions loaded into the cache by [`WorkerMessage::PrewarmRegion`] since
+    /// the cache was last emptied or the settings changed.
+    pub prewarmed: HashSet<(i64, i64)>,
+    /// Terrain generators of the additional dimensions by name, their chunks
+    /// aren't cached or saved.
+    pub dimensions: HashMap<String, ChunkWorkerState>,
 }
 
 #[derive(Clone)]
@@ -128,6 +804,70 @@ pub struct ChunkWorkerState {
     pub stone: SuperSimplex,
     pub gravel: SuperSimplex,
     pub grass: SuperSimplex,
+    pub seagrass: SuperSimplex,
+    pub coal: SuperSimplex,
+    pub iron: SuperSimplex,
+    pub gold: SuperSimplex,
+    pub redstone: SuperSimplex,
+    pub diamond: SuperSimplex,
+    pub temperature: SuperSimplex,
+    pub humidity: SuperSimplex,
+    pub cave_density: SuperSimplex,
+    /// Ids of the biomes registered with the server.
+    pub biome_ids: BiomeIds,
+}
+
+impl ChunkWorkerState {
+    #[must_use]
+    pub fn new(seed: u32, settings: TerrainSettings) -> Self {
+        let seeds = settings.seeds;
+        let noise = |feature: Option<u32>, offset: u32| {
+            SuperSimplex::new(FeatureSeeds::resolve(feature, seed, offset))
+        };
+
+        Self {
+            density: noise(seeds.density, 0),
+            hilly: noise(seeds.hilly, 1),
+            stone: noise(seeds.stone, 2),
+            gravel: noise(seeds.gravel, 3),
+            grass: noise(seeds.grass, 4),
+            seagrass: noise(seeds.seagrass.or(seeds.grass), 4),
+            coal: noise(seeds.coal, 5),
+            iron: noise(seeds.iron, 6),
+            gold: noise(seeds.gold, 7),
+            redstone: noise(seeds.redstone, 8),
+            diamond: noise(seeds.diamond, 9),
+            temperature: noise(seeds.temperature, 10),
+            humidity: noise(seeds.humidity, 11),
+            cave_density: noise(seeds.caves, 12),
+            settings,
+            biome_ids: BiomeIds::default(),
+        }
+    }
+
+    /// Recreates all noise functions from the given seed and the feature seeds
+    /// of the settings.
+    pub fn reseed(&mut self, seed: u32) {
+        *self = Self {
+            biome_ids: self.biome_ids,
+            ..Self::new(seed, self.settings.clone())
+        };
+    }
+}
+
+/// Number of chunk workers to spawn. Every worker occupies a thread of the
+/// runtime while it waits for chunks, so there are never more workers than
+/// runtime threads or cores. A `configured` count is clamped into
+/// `1..=` that limit.
+#[must_use]
+pub fn chunk_worker_count(
+    runtime_workers: usize,
+    available_parallelism: usize,
+    configured: Option<usize>,
+) -> usize {
+    let limit = runtime_workers.min(available_parallelism).max(1);
+
+    configured.map_or(limit, |count| count.clamp(1, limit))
 }
 
 /// # Panics
@@ -137,9 +877,16 @@ pub fn chunk_worker(worker: Arc<Mutex<ChunkWorker>>, worker_name: String) -> Res
 
     while let Ok(msg) = w.receiver.recv() {
         match msg {
+            WorkerMessage::Chunk(pos) if w.paused => w.deferred.push(pos),
             WorkerMessage::Chunk(pos) => {
                 handle_chunk(&mut w, &worker_name, pos)?;
             }
+            WorkerMessage::BatchChunks(positions) if w.paused => w.deferred.extend(positions),
+            WorkerMessage::BatchChunks(positions) => {
+                for pos in positions {
+                    handle_chunk(&mut w, &worker_name, pos)?;
+                }
+            }
             WorkerMessage::GetTerrainSettings => {
                 let settings = w.state.settings.clone();
                 let _ = w
@@ -149,24 +896,117 @@ pub fn chunk_worker(worker: Arc<Mutex<ChunkWorker>>, worker_name: String) -> Res
             WorkerMessage::SetTerrainSettings(new_settings) => {
                 debug!(target: "minecraft::world_gen::worker", "Updated terrain settings: {new_settings:?}");
 
-                if new_settings.seed != w.state.settings.seed {
-                    let seed = new_settings.seed;
-                    w.state.density = SuperSimplex::new(seed);
-                    w.state.hilly = SuperSimplex::new(seed.wrapping_add(1));
-                    w.state.stone = SuperSimplex::new(seed.wrapping_add(2));
-                    w.state.gravel = SuperSimplex::new(seed.wrapping_add(3));
-                    w.state.grass = SuperSimplex::new(seed.wrapping_add(4));
-                }
+                let reseed = new_settings.seed != w.state.settings.seed
+                    || new_settings.seeds != w.state.settings.seeds;
 
+                // Cached chunks are kept, chunks of other settings are regenerated when they
+                // are requested.
+                w.settings_hash = new_settings.content_hash();
                 w.state.settings = new_settings;
-                w.cache.clear();
-                debug!(target: "minecraft::world_gen::worker", "Cache emptied");
+                w.prewarmed.clear();
+
+                if reseed {
+                    let seed = w.state.settings.seed;
+                    w.state.reseed(seed);
+                }
 
                 let _ = w.sender.send(WorkerResponse::TerrainSettingsSet);
             }
+            WorkerMessage::ChunkModified(pos, mut chunk) => {
+                let ChunkWorker {
+                    cache,
+                    state,
+                    settings_hash,
+                    ..
+                } = &mut *w;
+
+                assign_biomes(state, &mut chunk, pos);
+                cache.push_modified(pos, chunk, *settings_hash, &state.settings);
+            }
             WorkerMessage::EmptyCache => {
-                w.cache.clear();
+                let ChunkWorker { cache, state, .. } = &mut *w;
+                cache.clear(&state.settings);
+                w.prewarmed.clear();
                 debug!(target: "minecraft::world_gen::worker", "Cache emptied");
+                send_cache_stats(&w);
+            }
+            WorkerMessage::ResizeCache(capacity) => {
+                let ChunkWorker { cache, state, .. } = &mut *w;
+                cache.resize(capacity, &state.settings);
+                debug!(target: "minecraft::world_gen::worker", "Cache resized to {capacity} chunks");
+                send_cache_stats(&w);
+            }
+            WorkerMessage::FlushAll if w.paused => w.deferred_flush = true,
+            WorkerMessage::FlushAll => {
+                let count = flush_cache(&mut w);
+                debug!(target: "minecraft::world_gen::worker", "Flushed {count} chunks");
+
+                let _ = w.sender.send(WorkerResponse::FlushComplete { count });
+            }
+            WorkerMessage::Pause => {
+                w.paused = true;
+                debug!(target: "minecraft::world_gen::worker", "Paused");
+            }
+            WorkerMessage::Resume => {
+                w.paused = false;
+                debug!(target: "minecraft::world_gen::worker",
+                    "Resumed, {} chunks were requested while paused", w.deferred.len()
+                );
+
+                for pos in std::mem::take(&mut w.deferred) {
+                    handle_chunk(&mut w, &worker_name, pos)?;
+                }
+                for (name, pos) in std::mem::take(&mut w.deferred_dimensions) {
+                    handle_dimension_chunk(&w, name, pos);
+                }
+
+                if std::mem::take(&mut w.deferred_flush) {
+                    let count = flush_cache(&mut w);
+                    let _ = w.sender.send(WorkerResponse::FlushComplete { count });
+                }
+            }
+            WorkerMessage::PrewarmRegion(x, z) => {
+                let count = if w.prewarmed.insert((x, z)) {
+                    prewarm_region(&mut w, (x, z))
+                } else {
+                    0
+                };
+                debug!(target: "minecraft::world_gen::worker",
+                    "Prewarmed {count} chunks of region {x} {z}"
+                );
+
+                let _ = w.sender.send(WorkerResponse::PrewarmComplete(x, z, count));
+                send_cache_stats(&w);
+            }
+            WorkerMessage::ExportMap(path, center) => {
+                // The chunks are copied so the other workers aren't blocked while the map is
+                // rendered.
+                let hash = w.settings_hash;
+                let chunks = w
+                    .cache
+                    .iter()
+                    .filter(|(pos, cached)| cached.hash == hash && in_map_area(center, **pos))
+                    .map(|(pos, cached)| (*pos, cached.chunk.clone()))
+                    .collect::<Vec<_>>();
+
+                let sender = w.sender.clone();
+                tokio::task::spawn_blocking(move || {
+                    let chunks = chunks.iter().map(|(pos, chunk)| (*pos, chunk));
+                    let result = export_map(chunks, center, &path).map_err(|e| e.to_string());
+                    let _ = sender.send(WorkerResponse::MapExported(path, result));
+                });
+            }
+            WorkerMessage::DimensionChunk(name, pos) if w.paused => {
+                w.deferred_dimensions.push((name, pos));
+            }
+            WorkerMessage::DimensionChunk(name, pos) => handle_dimension_chunk(&w, name, pos),
+            WorkerMessage::CleanStaleRegions => clean_stale_regions_in_background(&w),
+            WorkerMessage::HeightMap(pos, scale) => {
+                let heights = gen_chunk_at_scale(&w.state, pos, scale)
+                    .iter()
+                    .map(|row| row.to_vec())
+                    .collect();
+                let _ = w.sender.try_send(WorkerResponse::HeightMap(pos, heights));
             }
         }
     }
@@ -183,52 +1023,97 @@ fn handle_chunk(
     let cached;
     let saved;
     let start = Instant::now();
+    let hash = worker.settings_hash;
+    let mut timing = ChunkGenTiming::default();
+
+    let cached_chunk = measure(&mut timing.cache_ns, || {
+        let ChunkWorker { cache, state, .. } = &mut **worker;
+        cache.get_current(&pos, hash, &state.settings)
+    });
 
-    if worker.cache.contains(&pos) {
-        chunk = worker.cache.get_mut(&pos).unwrap().clone();
+    if let Some(cached_chunk) = cached_chunk {
+        chunk = cached_chunk;
         cached = true;
         saved = true;
     } else {
         chunk = {
-            if let Ok(region) = load_region(chunkpos_to_regionpos(&pos), &worker.state.settings) {
+            // The worker runs on a tokio worker thread, `block_in_place` lets the
+            // runtime move its other tasks to another thread while waiting.
+            let region = measure(&mut timing.disk_ns, || {
+                tokio::task::block_in_place(|| {
+                    tokio::runtime::Handle::current().block_on(load_region_async(
+                        chunkpos_to_regionpos(&pos),
+                        &worker.state.settings,
+                    ))
+                })
+            });
+
+            if let Ok(region) = region {
                 match region.chunk(pos) {
                     Some(c) => {
                         saved = true;
-                        c.into()
+                        let mut chunk = c.to_chunk(|| gen_chunk(&worker.state, pos));
+                        assign_biomes(&worker.state, &mut chunk, pos);
+                        chunk
                     }
                     None => {
                         saved = false;
-                        let chunk = gen_chunk(&worker.state, pos);
-                        let chunk_clone = chunk.clone();
-                        let pos_clone = pos;
-                        let settings = worker.state.settings.clone();
-                        let _ = tokio::task::Builder::new().spawn_blocking(move || {
-                            save_chunk_to_region(chunk_clone, pos_clone, settings).unwrap()
-                        });
+                        let chunk = measure(&mut timing.noise_ns, || gen_chunk(&worker.state, pos));
+                        save_in_background(worker, pos, &chunk);
                         chunk
                     }
                 }
             } else {
                 saved = false;
-                let chunk = gen_chunk(&worker.state, pos);
-                let chunk_clone = chunk.clone();
-                let pos_clone = pos;
-                let settings = worker.state.settings.clone();
-                let _ = tokio::task::Builder::new().spawn_blocking(move || {
-                    save_chunk_to_region(chunk_clone, pos_clone, settings).unwrap()
-                });
+                let chunk = measure(&mut timing.noise_ns, || gen_chunk(&worker.state, pos));
+                save_in_background(worker, pos, &chunk);
                 chunk
             }
         };
 
         // chunk = gen_chunk(&worker.state, pos);
-        worker.cache.push(pos, chunk.clone());
+        let ChunkWorker { cache, state, .. } = &mut **worker;
+        cache.push(pos, chunk.clone(), hash, &state.settings);
         cached = false;
     }
 
-    let _ = worker.sender.try_send(WorkerResponse::Chunk(pos, chunk));
+    // The receiver is only dropped when the server shuts down, so there is
+    // nobody left to generate chunks for.
+    measure(&mut timing.send_ns, || {
+        worker
+            .sender
+            .try_send(WorkerResponse::Chunk(pos, chunk, hash))
+    })
+    .map_err(|e| anyhow!("Unable to send chunk {pos:?}: {e}"))?;
 
     let duration = start.elapsed();
+    metrics::record_chunk(cached, saved, duration);
+
+    let _ = worker.sender.try_send(WorkerResponse::ChunkInfo(
+        pos,
+        ChunkInfo {
+            worker: worker_name.to_string(),
+            duration,
+            cached,
+            saved,
+        },
+    ));
+    send_cache_stats(worker);
+
+    #[cfg(feature = "profiling")]
+    {
+        trace!(
+            target: "minecraft::world_gen::worker::timing",
+            cache_ns = timing.cache_ns,
+            disk_ns = timing.disk_ns,
+            noise_ns = timing.noise_ns,
+            send_ns = timing.send_ns,
+            worker = worker_name,
+            "Timing of chunk {pos:?}"
+        );
+        let _ = worker.sender.try_send(WorkerResponse::Timing(timing));
+    }
+
     let settings = &worker.state.settings;
     trace!(
         target: "minecraft::world_gen::worker",
@@ -241,10 +1126,146 @@ fn handle_chunk(
     anyhow::Ok(())
 }
 
+/// Generates a chunk of an additional dimension.
+fn handle_dimension_chunk(worker: &ChunkWorker, name: String, pos: ChunkPos) {
+    let Some(state) = worker.dimensions.get(&name) else {
+        warn!(target: "minecraft::world_gen::worker", "Chunk {pos:?} of unknown dimension {name} requested");
+        return;
+    };
+
+    let chunk = gen_chunk(state, pos);
+    let _ = worker
+        .sender
+        .send(WorkerResponse::DimensionChunk(name, pos, chunk));
+}
+
+/// Puts the chunks of the region into the cache, chunks that are already cached
+/// with the current settings are kept as they might be modified. Returns the
+/// number of cached chunks.
+fn prewarm_region(worker: &mut ChunkWorker, rpos: (i64, i64)) -> usize {
+    let region = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(load_region_async(rpos, &worker.state.settings))
+    });
+    let region = match region {
+        Ok(region) => region,
+        Err(e) => {
+            debug!(target: "minecraft::world_gen::worker", "Unable to prewarm region {rpos:?}: {e}");
+            return 0;
+        }
+    };
+
+    let ChunkWorker {
+        cache,
+        state,
+        settings_hash,
+        ..
+    } = worker;
+    let mut count = 0;
+
+    for save_chunk in region {
+        let pos = ChunkPos::new(save_chunk.pos.0, save_chunk.pos.1);
+        if cache
+            .get_current(&pos, *settings_hash, &state.settings)
+            .is_some()
+        {
+            continue;
+        }
+
+        let mut chunk = save_chunk.to_chunk(|| gen_chunk(state, pos));
+        assign_biomes(state, &mut chunk, pos);
+        cache.push(pos, chunk, *settings_hash, &state.settings);
+        count += 1;
+    }
+
+    count
+}
+
+fn send_cache_stats(worker: &ChunkWorker) {
+    let _ = worker.sender.try_send(WorkerResponse::CacheStats {
+        len: worker.cache.len(),
+        capacity: worker.cache.capacity(),
+        evictions: worker.cache.evictions(),
+    });
+}
+
+/// Saves all cached chunks generated with the current settings, returns the
+/// number of saved chunks.
+fn flush_cache(worker: &mut ChunkWorker) -> usize {
+    if !worker.saving {
+        return 0;
+    }
+
+    let chunks = worker
+        .cache
+        .iter()
+        .filter(|(_, cached)| cached.hash == worker.settings_hash)
+        .map(|(pos, cached)| (*pos, cached.chunk.clone()))
+        .collect::<Vec<_>>();
+
+    let saved = tokio::task::block_in_place(|| {
+        save_chunks_to_regions(&chunks, worker.state.settings.clone())
+    });
+    worker.cache.mark_clean();
+
+    saved
+}
+
+/// Deletes or archives the region files of other settings on a separate task,
+/// depending on `stale_regions`. Nothing is touched if saving is disabled.
+fn clean_stale_regions_in_background(worker: &ChunkWorker) {
+    let handling = CONFIG.world.stale_regions;
+    if !worker.saving || handling == StaleRegions::Keep {
+        let _ = worker
+            .sender
+            .send(WorkerResponse::StaleRegionsCleaned(Ok(0)));
+        return;
+    }
+
+    let settings_hash = worker.settings_hash;
+    let sender = worker.sender.clone();
+    tokio::task::spawn_blocking(move || {
+        let result = clean_stale_regions(settings_hash, handling).map_err(|e| e.to_string());
+        let _ = sender.send(WorkerResponse::StaleRegionsCleaned(result));
+    });
+}
+
+/// Saves the chunk on a separate task, does nothing if saving is disabled.
+fn save_in_background(worker: &ChunkWorker, pos: ChunkPos, chunk: &Chunk) {
+    if !worker.saving {
+        return;
+    }
+
+    let chunk = chunk.clone();
+    let settings = worker.state.settings.clone();
+    let _ = tokio::task::Builder::new().spawn(async move {
+        if let Err(e) = save_chunk_to_region_async(chunk, pos, settings).await {
+            error!(target: "minecraft::save", "Failed to save chunk {pos:?}: {e}");
+        }
+    });
+}
+
+/// Blocks of a single column, from the bottom to the top of the chunk.
+pub type BlockColumn = Vec<BlockState>;
+
+/// Generates a chunk, the columns are generated in parallel with the
+/// `parallel` feature, see [`gen_chunk_parallel`].
 #[inline]
 #[must_use]
 pub fn gen_chunk(state: &ChunkWorkerState, pos: ChunkPos) -> Chunk {
+    cfg_if! {
+        if #[cfg(feature = "parallel")] {
+            gen_chunk_parallel(state, pos)
+        } else {
+            gen_chunk_sequential(state, pos)
+        }
+    }
+}
+
+#[inline]
+#[must_use]
+pub fn gen_chunk_sequential(state: &ChunkWorkerState, pos: ChunkPos) -> Chunk {
     let mut chunk = Chunk::new(SECTION_COUNT);
+    let heights = terrain_heights(state, pos, SECTION_COUNT * 16);
 
     let range = 0..16;
     let range_2 = 0..16;
@@ -253,9 +1274,63 @@ pub fn gen_chunk(state: &ChunkWorkerState, pos: ChunkPos) -> Chunk {
         let x = offset_x as i32 + pos.x * 16;
         let z = offset_z as i32 + pos.z * 16;
 
-        gen_block(state, &mut chunk, x, z, offset_x, offset_z);
+        gen_block_with_height(
+            state,
+            &mut chunk,
+            x,
+            z,
+            offset_x,
+            offset_z,
+            heights[offset_z][offset_x],
+        );
+    }
+
+    gen_caves(state, &mut chunk, pos);
+    gen_trees(state, &mut chunk, pos);
+    assign_biomes(state, &mut chunk, pos);
+
+    chunk
+}
+
+/// Like [`gen_chunk_sequential`], but the columns are generated on the rayon
+/// thread pool into a buffer, which is then copied into the chunk.
+#[must_use]
+pub fn gen_chunk_parallel(state: &ChunkWorkerState, pos: ChunkPos) -> Chunk {
+    let mut chunk = Chunk::new(SECTION_COUNT);
+    let heights = terrain_heights(state, pos, SECTION_COUNT * 16);
+
+    let mut columns: [[Option<BlockColumn>; 16]; 16] = Default::default();
+    columns
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(offset_z, row)| {
+            for (offset_x, column) in row.iter_mut().enumerate() {
+                let x = offset_x as i32 + pos.x * 16;
+                let z = offset_z as i32 + pos.z * 16;
+
+                let mut blocks = vec![BlockState::AIR; SECTION_COUNT * 16];
+                fill_column(state, &mut blocks, x, z, Some(heights[offset_z][offset_x]));
+                *column = Some(blocks);
+            }
+        });
+
+    for (offset_z, row) in columns.into_iter().enumerate() {
+        for (offset_x, blocks) in row.into_iter().enumerate() {
+            let Some(blocks) = blocks else {
+                continue;
+            };
+
+            // New chunks only contain air.
+            for (y, block) in blocks.into_iter().enumerate().filter(|(_, b)| !b.is_air()) {
+                chunk.set_block_state(offset_x, y, offset_z, block);
+            }
+        }
     }
 
+    gen_caves(state, &mut chunk, pos);
+    gen_trees(state, &mut chunk, pos);
+    assign_biomes(state, &mut chunk, pos);
+
     chunk
 }
 
@@ -263,39 +1338,246 @@ pub fn gen_chunk(state: &ChunkWorkerState, pos: ChunkPos) -> Chunk {
 #[must_use]
 pub fn gen_chunk_fors(state: &ChunkWorkerState, pos: ChunkPos) -> Chunk {
     let mut chunk = Chunk::new(SECTION_COUNT);
+    let heights = terrain_heights(state, pos, SECTION_COUNT * 16);
 
     for offset_z in 0..16 {
         for offset_x in 0..16 {
             let x = offset_x as i32 + pos.x * 16;
             let z = offset_z as i32 + pos.z * 16;
 
-            gen_block(state, &mut chunk, x, z, offset_x, offset_z);
+            gen_block_with_height(
+                state,
+                &mut chunk,
+                x,
+                z,
+                offset_x,
+                offset_z,
+                heights[offset_z][offset_x],
+            );
         }
     }
 
+    gen_caves(state, &mut chunk, pos);
+    gen_trees(state, &mut chunk, pos);
+    assign_biomes(state, &mut chunk, pos);
+
     chunk
 }
 
-pub fn gen_block(
-    state: &ChunkWorkerState,
-    chunk: &mut Chunk,
-    x: i32,
-    z: i32,
-    offset_x: usize,
-    offset_z: usize,
-) {
-    let mut in_terrain = false;
-    let mut depth = 0;
+/// Which horizontal layer of a chunk [`gen_slice`] returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceHeight {
+    /// The blocks at this height.
+    Y(usize),
+    /// The topmost block of every column that isn't air.
+    Surface,
+}
 
-    // Fill in the terrain column.
-    for y in (0..chunk.section_count() as i32 * 16).rev() {
-        const WATER_HEIGHT: i32 = 120;
+/// Generates a single horizontal layer of a chunk without allocating the whole
+/// chunk, for maps and previews. The blocks are indexed by
+/// `offset_z * 16 + offset_x`.
+///
+/// The layer matches the same layer of [`gen_chunk`], except for trees, which
+/// depend on the neighbouring columns.
+#[must_use]
+pub fn gen_slice(state: &ChunkWorkerState, pos: ChunkPos, slice: SliceHeight) -> Vec<BlockState> {
+    let mut column = vec![BlockState::AIR; SECTION_COUNT * 16];
 
-        let p = DVec3::new(f64::from(x), f64::from(y), f64::from(z));
+    iproduct!(0..16, 0..16)
+        .map(|(offset_z, offset_x)| {
+            let x = offset_x + pos.x * 16;
+            let z = offset_z + pos.z * 16;
 
-        let block = if has_terrain_at(state, p) {
+            // Every block of the column is overwritten, no need to clear it.
+            let terrain_height = terrain_height(state, x, z, column.len());
+            fill_column(state, &mut column, x, z, Some(terrain_height));
+            if state.settings.enable_caves {
+                carve_column(state, &mut column, x, z);
+            }
+
+            match slice {
+                SliceHeight::Y(y) => column.get(y).copied().unwrap_or(BlockState::AIR),
+                SliceHeight::Surface => column
+                    .iter()
+                    .rev()
+                    .find(|block| !block.is_air())
+                    .copied()
+                    .unwrap_or(BlockState::AIR),
+            }
+        })
+        .collect()
+}
+
+/// A vertical column of blocks the terrain is generated in.
+trait Column {
+    fn height(&self) -> usize;
+    fn block(&self, y: usize) -> BlockState;
+    fn set_block(&mut self, y: usize, block: BlockState);
+}
+
+impl Column for Vec<BlockState> {
+    fn height(&self) -> usize { self.len() }
+
+    fn block(&self, y: usize) -> BlockState { self[y] }
+
+    fn set_block(&mut self, y: usize, block: BlockState) { self[y] = block; }
+}
+
+/// A column of a [`Chunk`].
+struct ChunkColumn<'a> {
+    chunk: &'a mut Chunk,
+    offset_x: usize,
+    offset_z: usize,
+}
+
+impl<'a> ChunkColumn<'a> {
+    fn new(chunk: &'a mut Chunk, offset_x: usize, offset_z: usize) -> Self {
+        Self {
+            chunk,
+            offset_x,
+            offset_z,
+        }
+    }
+}
+
+impl Column for ChunkColumn<'_> {
+    fn height(&self) -> usize { self.chunk.section_count() * 16 }
+
+    fn block(&self, y: usize) -> BlockState {
+        self.chunk.block_state(self.offset_x, y, self.offset_z)
+    }
+
+    fn set_block(&mut self, y: usize, block: BlockState) {
+        self.chunk
+            .set_block_state(self.offset_x, y, self.offset_z, block);
+    }
+}
+
+/// [`has_terrain_at`] is always `true` at or below this height, `lower` is at
+/// least 64 as `hilly` is a square.
+const ALWAYS_TERRAIN_HEIGHT: i32 = 64;
+
+/// [`has_terrain_at`] is always `false` at or above this height, `upper` is at
+/// most `64 + 2 * 100 * height_scale` as the noise is at most 1.
+fn never_terrain_height(settings: &TerrainSettings) -> i32 {
+    ALWAYS_TERRAIN_HEIGHT + (200.0 * settings.height_scale).ceil() as i32
+}
+
+/// Height of the topmost block of every column of the chunk that
+/// [`has_terrain_at`], indexed by `[offset_z][offset_x]`.
+#[must_use]
+pub fn terrain_heights(state: &ChunkWorkerState, pos: ChunkPos, height: usize) -> [[i32; 16]; 16] {
+    let mut heights = [[0; 16]; 16];
+
+    for (offset_z, offset_x) in iproduct!(0..16, 0..16) {
+        let x = offset_x as i32 + pos.x * 16;
+        let z = offset_z as i32 + pos.z * 16;
+
+        heights[offset_z][offset_x] = terrain_height(state, x, z, height);
+    }
+
+    heights
+}
+
+/// Surface height of 16x16 columns, indexed by `[z][x]`.
+pub type HeightMap = [[u16; 16]; 16];
+
+/// Surface heights of the `16 * scale` blocks wide area starting at the corner
+/// of the chunk, for minimaps. Every cell covers `scale` x `scale` columns and
+/// only the column in its center is generated, so a `scale` of 4 summarizes
+/// 4x4 chunks for the cost of one. A `scale` of 1 is the same as
+/// [`terrain_heights`].
+///
+/// The heights don't include the water above the terrain.
+#[must_use]
+pub fn gen_chunk_at_scale(state: &ChunkWorkerState, pos: ChunkPos, scale: u8) -> HeightMap {
+    let scale = i32::from(scale.max(1));
+    let mut heights = [[0; 16]; 16];
+
+    for (cell_z, cell_x) in iproduct!(0..16, 0..16) {
+        let x = pos.x * 16 + cell_x as i32 * scale + scale / 2;
+        let z = pos.z * 16 + cell_z as i32 * scale + scale / 2;
+
+        heights[cell_z][cell_x] = terrain_height(state, x, z, SECTION_COUNT * 16) as u16;
+    }
+
+    heights
+}
+
+/// Scans the column from the top until [`has_terrain_at`] is `true`, only the
+/// heights where the result isn't known in advance are sampled.
+fn terrain_height(state: &ChunkWorkerState, x: i32, z: i32, height: usize) -> i32 {
+    let top = (height as i32 - 1).min(never_terrain_height(&state.settings) - 1);
+
+    (ALWAYS_TERRAIN_HEIGHT + 1..=top)
+        .rev()
+        .find(|y| has_terrain_at(state, DVec3::new(f64::from(x), f64::from(*y), f64::from(z))))
+        .unwrap_or(ALWAYS_TERRAIN_HEIGHT)
+}
+
+pub fn gen_block(
+    state: &ChunkWorkerState,
+    chunk: &mut Chunk,
+    x: i32,
+    z: i32,
+    offset_x: usize,
+    offset_z: usize,
+) {
+    fill_column(
+        state,
+        &mut ChunkColumn::new(chunk, offset_x, offset_z),
+        x,
+        z,
+        None,
+    );
+}
+
+/// Like [`gen_block`], but skips sampling the noise above `terrain_height`, the
+/// height of the topmost terrain block returned by [`terrain_heights`].
+pub fn gen_block_with_height(
+    state: &ChunkWorkerState,
+    chunk: &mut Chunk,
+    x: i32,
+    z: i32,
+    offset_x: usize,
+    offset_z: usize,
+    terrain_height: i32,
+) {
+    fill_column(
+        state,
+        &mut ChunkColumn::new(chunk, offset_x, offset_z),
+        x,
+        z,
+        Some(terrain_height),
+    );
+}
+
+/// Generates the terrain of a single column, including grass and seagrass.
+fn fill_column(
+    state: &ChunkWorkerState,
+    column: &mut impl Column,
+    x: i32,
+    z: i32,
+    terrain_height: Option<i32>,
+) {
+    let mut in_terrain = false;
+    let mut depth = 0;
+    let mut surfaces = Vec::new();
+    let biome = column_biome(state, x, z);
+
+    // Fill in the terrain column.
+    for y in (0..column.height() as i32).rev() {
+        let p = DVec3::new(f64::from(x), f64::from(y), f64::from(z));
+
+        let has_terrain = match terrain_height {
+            Some(terrain_height) if y > terrain_height => false,
+            Some(terrain_height) if y == terrain_height || y <= ALWAYS_TERRAIN_HEIGHT => true,
+            _ => has_terrain_at(state, p),
+        };
+
+        let block = if has_terrain {
             let gravel_fbm = state.settings.gravel_height.call(&state.gravel, p);
-            let gravel_height = WATER_HEIGHT - 1 - (gravel_fbm * 6.0).floor() as i32;
+            let gravel_height = state.settings.water_height - 1 - (gravel_fbm * 6.0).floor() as i32;
 
             let sand_fbm = state.settings.sand_height.call(&state.gravel, p);
             let sand_height =
@@ -307,17 +1589,18 @@ pub fn gen_block(
                     if y < gravel_height && state.settings.enable_gravel {
                         BlockState::GRAVEL
                     } else if state.settings.enable_grass {
-                        BlockState::DIRT
+                        biome.filler_block()
                     } else {
                         BlockState::AIR
                     }
                 } else if state.settings.enable_stone {
-                    BlockState::STONE
+                    stone_or_ore(state, p)
                 } else {
                     BlockState::AIR
                 }
             } else {
                 in_terrain = true;
+                surfaces.push(y as usize);
                 let n = noise01(&state.stone, p / state.settings.stone_point_scaleing);
 
                 depth = (n * 5.0).round() as u64;
@@ -327,7 +1610,7 @@ pub fn gen_block(
                 } else if y >= gravel_height && y < sand_height && state.settings.enable_sand {
                     BlockState::SAND
                 } else if state.settings.enable_grass {
-                    BlockState::GRASS_BLOCK
+                    biome.top_block()
                 } else {
                     BlockState::AIR
                 }
@@ -335,66 +1618,286 @@ pub fn gen_block(
         } else {
             in_terrain = false;
             depth = 0;
-            if y < WATER_HEIGHT && state.settings.enable_water {
+            if y < state.settings.water_height && state.settings.enable_water {
                 BlockState::WATER
             } else {
                 BlockState::AIR
             }
         };
 
-        chunk.set_block_state(offset_x, y as usize, offset_z, block);
+        // The bottom of the world is always bedrock.
+        let block = if y == 0 && !block.is_air() && !block.is_liquid() {
+            BlockState::BEDROCK
+        } else {
+            block
+        };
+
+        column.set_block(y as usize, block);
     }
 
     // Add grass on top of grass blocks.
     if (state.settings.enable_water && state.settings.enable_gravel) || state.settings.enable_grass
     {
-        for y in (0..chunk.section_count() * 16).rev() {
-            if chunk.block_state(offset_x, y, offset_z).is_air()
-                && chunk.block_state(offset_x, y - 1, offset_z) == BlockState::GRASS_BLOCK
-            {
-                let p = DVec3::new(f64::from(x), y as f64, f64::from(z));
-                let density = fbm(&state.grass, p / 5.0, 4, 2.0, 0.7);
+        decorate(state, column, x, z, surfaces);
+    }
+}
 
-                if density > 0.55 {
-                    if density > 0.7 && chunk.block_state(offset_x, y + 1, offset_z).is_air() {
-                        let upper = BlockState::TALL_GRASS.set(PropName::Half, PropValue::Upper);
-                        let lower = BlockState::TALL_GRASS.set(PropName::Half, PropValue::Lower);
+/// Adds grass and seagrass to a column, `surfaces` are the heights of the
+/// topmost blocks of every terrain layer in the column, from top to bottom.
+///
+/// Vegetation only grows directly on top of a terrain layer, so only the block
+/// above every surface is checked instead of scanning the whole column.
+pub fn decorate_column(
+    state: &ChunkWorkerState,
+    chunk: &mut Chunk,
+    x: i32,
+    z: i32,
+    offset_x: usize,
+    offset_z: usize,
+    surfaces: impl IntoIterator<Item = usize>,
+) {
+    decorate(
+        state,
+        &mut ChunkColumn::new(chunk, offset_x, offset_z),
+        x,
+        z,
+        surfaces,
+    );
+}
 
-                        chunk.set_block_state(offset_x, y + 1, offset_z, upper);
-                        chunk.set_block_state(offset_x, y, offset_z, lower);
-                    } else {
-                        chunk.set_block_state(offset_x, y, offset_z, BlockState::GRASS);
-                    }
+fn decorate(
+    state: &ChunkWorkerState,
+    column: &mut impl Column,
+    x: i32,
+    z: i32,
+    surfaces: impl IntoIterator<Item = usize>,
+) {
+    let height = column.height();
+
+    for y in surfaces.into_iter().map(|s| s + 1).filter(|y| *y < height) {
+        if column.block(y).is_air() && column.block(y - 1) == BlockState::GRASS_BLOCK {
+            let p = DVec3::new(f64::from(x), y as f64, f64::from(z));
+            let density = fbm(&state.grass, p / 5.0, 4, 2.0, 0.7);
+            let thresholds = state.settings.grass_thresholds;
+
+            if density > thresholds.short {
+                if density > thresholds.tall && y + 1 < height && column.block(y + 1).is_air() {
+                    let upper = BlockState::TALL_GRASS.set(PropName::Half, PropValue::Upper);
+                    let lower = BlockState::TALL_GRASS.set(PropName::Half, PropValue::Lower);
+
+                    column.set_block(y + 1, upper);
+                    column.set_block(y, lower);
+                } else {
+                    column.set_block(y, BlockState::GRASS);
                 }
-            } else if chunk.block_state(offset_x, y, offset_z).is_liquid()
-                && chunk.block_state(offset_x, y - 1, offset_z) == BlockState::GRAVEL
-                && state.settings.enable_water
-                && state.settings.enable_gravel
-            {
-                let p = DVec3::new(f64::from(x), y as f64, f64::from(z));
-                let density = fbm(&state.grass, p / 5.0, 4, 2.0, 0.7);
+            }
+        } else if column.block(y).is_liquid()
+            && column.block(y - 1) == BlockState::GRAVEL
+            && state.settings.enable_water
+            && state.settings.enable_gravel
+        {
+            let p = DVec3::new(f64::from(x), y as f64, f64::from(z));
+            let density = fbm(&state.seagrass, p / 5.0, 4, 2.0, 0.7);
+            let thresholds = state.settings.seagrass_thresholds;
 
-                if density > 0.55 {
-                    if density > 0.7 && chunk.block_state(offset_x, y + 1, offset_z).is_liquid() {
-                        let upper = BlockState::TALL_SEAGRASS.set(PropName::Half, PropValue::Upper);
-                        let lower = BlockState::TALL_SEAGRASS.set(PropName::Half, PropValue::Lower);
+            if density > thresholds.short {
+                if density > thresholds.tall && y + 1 < height && column.block(y + 1).is_liquid() {
+                    let upper = BlockState::TALL_SEAGRASS.set(PropName::Half, PropValue::Upper);
+                    let lower = BlockState::TALL_SEAGRASS.set(PropName::Half, PropValue::Lower);
 
-                        chunk.set_block_state(offset_x, y + 1, offset_z, upper);
-                        chunk.set_block_state(offset_x, y, offset_z, lower);
-                    } else {
-                        chunk.set_block_state(offset_x, y, offset_z, BlockState::SEAGRASS);
-                    }
+                    column.set_block(y + 1, upper);
+                    column.set_block(y, lower);
+                } else {
+                    column.set_block(y, BlockState::SEAGRASS);
                 }
             }
         }
     }
 }
 
+/// Carves caves out of the generated terrain, runs after all columns of the
+/// chunk are generated and before trees are placed.
+///
+/// Bedrock is never carved and blocks below liquids are kept, so oceans don't
+/// leak into caves. Floors of caves that would be made out of dirt, sand or
+/// gravel are turned into stone.
+pub fn gen_caves(state: &ChunkWorkerState, chunk: &mut Chunk, pos: ChunkPos) {
+    if !state.settings.enable_caves {
+        return;
+    }
+
+    for (offset_z, offset_x) in iproduct!(0..16, 0..16) {
+        let x = offset_x as i32 + pos.x * 16;
+        let z = offset_z as i32 + pos.z * 16;
+
+        carve_column(
+            state,
+            &mut ChunkColumn::new(chunk, offset_x, offset_z),
+            x,
+            z,
+        );
+    }
+}
+
+fn carve_column(state: &ChunkWorkerState, column: &mut impl Column, x: i32, z: i32) {
+    let (x, z) = (f64::from(x), f64::from(z));
+
+    for y in (1..column.height() - 1).rev() {
+        let block = column.block(y);
+
+        if block.is_air()
+            || block.is_liquid()
+            || block == BlockState::BEDROCK
+            || column.block(y + 1).is_liquid()
+            || !is_cave_at(state, DVec3::new(x, y as f64, z))
+        {
+            continue;
+        }
+
+        column.set_block(y, BlockState::AIR);
+
+        let floor = column.block(y - 1);
+        if [
+            BlockState::DIRT,
+            BlockState::GRASS_BLOCK,
+            BlockState::SAND,
+            BlockState::GRAVEL,
+        ]
+        .contains(&floor)
+        {
+            column.set_block(y - 1, BlockState::STONE);
+        }
+    }
+}
+
+/// Caves are worm-like tunnels along the intersection of two ridges of the
+/// cave noise, the second one sampled far away from the first.
+fn is_cave_at(state: &ChunkWorkerState, p: DVec3) -> bool {
+    // Caves are stretched horizontally.
+    let p = p / DVec3::new(32.0, 16.0, 32.0);
+
+    let ridge = |p: DVec3| 1.0 - state.cave_density.get(p.to_array()).abs();
+
+    ridge(p).min(ridge(p + 1000.0)) > state.settings.cave_threshold
+}
+
+/// Radius of the leaves around the top of a tree trunk.
+const TREE_CANOPY_RADIUS: usize = 2;
+
+/// Places oak trees on grass blocks, runs after all columns of the chunk are
+/// generated.
+///
+/// Trees are only placed if their canopy fits into the chunk, so there are no
+/// partial canopies cut off at chunk borders. Trees crossing the border would
+/// require the neighbouring chunks to already be generated, which isn't
+/// guaranteed as chunks are generated independently of each other.
+pub fn gen_trees(state: &ChunkWorkerState, chunk: &mut Chunk, pos: ChunkPos) {
+    if !state.settings.enable_trees {
+        return;
+    }
+
+    let margin = TREE_CANOPY_RADIUS;
+    let mut trees: Vec<(usize, usize)> = vec![];
+
+    for (offset_z, offset_x) in iproduct!(margin..16 - margin, margin..16 - margin) {
+        // Keep some space between trees.
+        if trees
+            .iter()
+            .any(|(tx, tz)| tx.abs_diff(offset_x) <= margin && tz.abs_diff(offset_z) <= margin)
+        {
+            continue;
+        }
+
+        let x = f64::from(offset_x as i32 + pos.x * 16);
+        let z = f64::from(offset_z as i32 + pos.z * 16);
+
+        let n = noise01(&state.grass, DVec3::new(x, 0.0, z) * 0.9);
+        if n <= 1.0 - state.settings.tree_density {
+            continue;
+        }
+
+        let Some(ground) = tree_ground(chunk, offset_x, offset_z) else {
+            continue;
+        };
+
+        let height = 3 + (noise01(&state.grass, DVec3::new(x, 64.0, z)) * 4.0).floor() as usize;
+        let top = ground + height;
+
+        if top + margin >= chunk.section_count() * 16 {
+            continue;
+        }
+
+        // Canopy
+        for (dx, dy, dz) in iproduct!(0..=margin * 2, 0..=margin * 2, 0..=margin * 2) {
+            let (cx, cy, cz) = (
+                offset_x + dx - margin,
+                top + dy - margin,
+                offset_z + dz - margin,
+            );
+            let distance = (dx.abs_diff(margin).pow(2)
+                + dy.abs_diff(margin).pow(2)
+                + dz.abs_diff(margin).pow(2)) as f64;
+
+            if distance.sqrt() <= margin as f64 + 0.5
+                && is_replaceable(chunk.block_state(cx, cy, cz))
+            {
+                chunk.set_block_state(cx, cy, cz, BlockState::OAK_LEAVES);
+            }
+        }
+
+        // Trunk
+        for y in ground + 1..=top {
+            chunk.set_block_state(offset_x, y, offset_z, BlockState::OAK_LOG);
+        }
+        chunk.set_block_state(offset_x, ground, offset_z, BlockState::DIRT);
+
+        trees.push((offset_x, offset_z));
+    }
+}
+
+/// Returns the height of the highest grass block in the column that only has
+/// replaceable blocks above it.
+fn tree_ground(chunk: &Chunk, x: usize, z: usize) -> Option<usize> {
+    (0..chunk.section_count() * 16 - 1)
+        .rev()
+        .take_while(|&y| is_replaceable(chunk.block_state(x, y + 1, z)))
+        .find(|&y| chunk.block_state(x, y, z) == BlockState::GRASS_BLOCK)
+}
+
+fn is_replaceable(block: BlockState) -> bool {
+    block.is_air() || block.to_kind() == BlockKind::Grass || block.to_kind() == BlockKind::TallGrass
+}
+
+/// Returns the ore at the given position, or stone if there is none.
+fn stone_or_ore(state: &ChunkWorkerState, p: DVec3) -> BlockState {
+    let settings = &state.settings;
+
+    if !settings.enable_ores {
+        return BlockState::STONE;
+    }
+
+    // Rarer ores are checked first so they don't get overwritten by common ones.
+    if settings.diamond_ore.contains(&state.diamond, p) {
+        BlockState::DIAMOND_ORE
+    } else if settings.gold_ore.contains(&state.gold, p) {
+        BlockState::GOLD_ORE
+    } else if settings.redstone_ore.contains(&state.redstone, p) {
+        BlockState::REDSTONE_ORE
+    } else if settings.iron_ore.contains(&state.iron, p) {
+        BlockState::IRON_ORE
+    } else if settings.coal_ore.contains(&state.coal, p) {
+        BlockState::COAL_ORE
+    } else {
+        BlockState::STONE
+    }
+}
+
 fn has_terrain_at(state: &ChunkWorkerState, p: DVec3) -> bool {
     let hilly = lerp(0.1, 1.0, noise01(&state.hilly, p / 400.0)).powi(2);
+    let scale = 100.0 * state.settings.height_scale;
 
-    let lower = 64.0 + 100.0 * hilly;
-    let upper = lower + 100.0 * hilly;
+    let lower = 64.0 + scale * hilly;
+    let upper = lower + scale * hilly;
 
     if p.y <= lower {
         return true;
@@ -422,13 +1925,28 @@ fn lerpstep(edge0: f64, edge1: f64, x: f64) -> f64 {
 }
 
 fn fbm(noise: &SuperSimplex, p: DVec3, octaves: u32, lacunarity: f64, persistence: f64) -> f64 {
+    fbm_raw(noise, p.to_array(), octaves, lacunarity, persistence)
+}
+
+/// Fractal brownian motion of the noise, the average of the octaves weighted
+/// by their amplitude. Usually in `[0, 1]`, but a negative `persistence` can
+/// go beyond it. Takes a plain array, so it can be used without depending on
+/// [`DVec3`].
+#[must_use]
+pub fn fbm_raw(
+    noise: &SuperSimplex,
+    p: [f64; 3],
+    octaves: u32,
+    lacunarity: f64,
+    persistence: f64,
+) -> f64 {
     let mut freq = 1.0;
     let mut amp = 1.0;
     let mut amp_sum = 0.0;
     let mut sum = 0.0;
 
     for _ in 0..octaves {
-        let n = noise01(noise, p * freq);
+        let n = (noise.get(p.map(|c| c * freq)) + 1.0) / 2.0;
         sum += n * amp;
         amp_sum += amp;
 
@@ -436,8 +1954,1022 @@ fn fbm(noise: &SuperSimplex, p: DVec3, octaves: u32, lacunarity: f64, persistenc
         amp *= persistence;
     }
 
-    // Scale the output to [0, 1]
-    sum / amp_sum
+    // Invalid settings (e.g. no octaves) would otherwise result in NaN.
+    let n = sum / amp_sum;
+    if n.is_finite() {
+        n
+    } else {
+        0.5
+    }
 }
 
 fn noise01(noise: &SuperSimplex, p: DVec3) -> f64 { (noise.get(p.to_array()) + 1.0) / 2.0 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(seed: u32) -> ChunkWorkerState {
+        ChunkWorkerState::new(
+            seed,
+            TerrainSettings {
+                seed,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Every block of the chunk with its position.
+    fn blocks(chunk: &Chunk) -> impl Iterator<Item = ((usize, usize, usize), BlockState)> + '_ {
+        iproduct!(0..16, 0..chunk.section_count() * 16, 0..16)
+            .map(move |(x, y, z)| ((x, y, z), chunk.block_state(x, y, z)))
+    }
+
+    #[test]
+    fn ores_are_only_placed_between_their_heights() {
+        let mut settings = TerrainSettings::default();
+        settings.coal_ore = OreSettings {
+            frequency: 1.0,
+            min_y: 50,
+            max_y: 60,
+            ..OreSettings::default_coal()
+        };
+        for ore in [
+            &mut settings.iron_ore,
+            &mut settings.gold_ore,
+            &mut settings.redstone_ore,
+            &mut settings.diamond_ore,
+        ] {
+            ore.enabled = false;
+        }
+        let state = ChunkWorkerState::new(1, settings);
+
+        for y in -10..300 {
+            let block = stone_or_ore(&state, DVec3::new(3.0, f64::from(y), 7.0));
+            if (50..=60).contains(&y) {
+                assert_eq!(block, BlockState::COAL_ORE, "y {y}");
+            } else {
+                assert_eq!(block, BlockState::STONE, "y {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn generated_ores_stay_inside_their_heights() {
+        for seed in [1, 42] {
+            let state = state(seed);
+            let settings = &state.settings;
+            let ores = [
+                (BlockState::COAL_ORE, &settings.coal_ore),
+                (BlockState::IRON_ORE, &settings.iron_ore),
+                (BlockState::GOLD_ORE, &settings.gold_ore),
+                (BlockState::REDSTONE_ORE, &settings.redstone_ore),
+                (BlockState::DIAMOND_ORE, &settings.diamond_ore),
+            ];
+
+            for pos in [ChunkPos::new(0, 0), ChunkPos::new(-3, 5)] {
+                let chunk = gen_chunk(&state, pos);
+
+                for ((_, y, _), block) in blocks(&chunk) {
+                    if let Some((_, ore)) = ores.iter().find(|(ore, _)| *ore == block) {
+                        assert!(
+                            (ore.min_y..=ore.max_y).contains(&(y as i32)),
+                            "{block:?} at y {y}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn disabled_ores_are_stone() {
+        let state = ChunkWorkerState::new(
+            1,
+            TerrainSettings {
+                enable_ores: false,
+                ..Default::default()
+            },
+        );
+
+        for y in 0..100 {
+            let block = stone_or_ore(&state, DVec3::new(0.0, f64::from(y), 0.0));
+            assert_eq!(block, BlockState::STONE);
+        }
+    }
+
+    #[test]
+    fn summary_lists_the_settings_line_by_line() {
+        let settings = TerrainSettings {
+            seed: 7,
+            enable_trees: false,
+            enable_caves: false,
+            water_height: 100,
+            height_scale: 0.5,
+            cave_threshold: 0.8,
+            seeds: FeatureSeeds {
+                coal: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let summary = settings.summary();
+
+        assert_eq!(summary[0], "Seed: 7");
+        assert_eq!(summary[1], "Feature seeds: Coal 3");
+        assert_eq!(
+            summary[2],
+            "Features: gravel on, sand on, stone on, grass on, water on, trees off, ores on, \
+             caves off"
+        );
+        assert_eq!(
+            summary[3],
+            "Gravel: scaling 10, octaves 3, lacunarity 2, persistence -1.5"
+        );
+        assert_eq!(
+            summary[4],
+            "Sand: offset 5, scaling 10, octaves 1, lacunarity 2, persistence 0.5"
+        );
+        assert!(summary.contains(&"Caves: threshold 0.8".to_string()));
+        assert!(summary.contains(&"Water: height 100".to_string()));
+        assert!(summary.contains(&"Height scale: 0.5".to_string()));
+        assert!(summary.iter().all(|line| !line.contains('\n')));
+    }
+
+    #[test]
+    fn feature_seeds_summary_without_seeds() {
+        assert_eq!(
+            FeatureSeeds::default().summary(),
+            "Feature seeds: derived from the seed"
+        );
+    }
+
+    /// Flat grass at y 64 with trees wherever they fit.
+    fn forest(seed: u32) -> ChunkWorkerState {
+        ChunkWorkerState::new(
+            seed,
+            TerrainSettings {
+                seed,
+                height_scale: 0.0,
+                water_height: 0,
+                enable_caves: false,
+                tree_density: 1.0,
+                biomes: BiomeSettings {
+                    enable_biomes: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Position of the top of every tree trunk.
+    fn tree_tops(chunk: &Chunk) -> Vec<(usize, usize, usize)> {
+        iproduct!(0..16, 0..16)
+            .filter_map(|(x, z)| {
+                (0..chunk.section_count() * 16)
+                    .rev()
+                    .find(|y| chunk.block_state(x, *y, z) == BlockState::OAK_LOG)
+                    .map(|y| (x, y, z))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tree_canopies_stay_inside_the_chunk() {
+        let margin = TREE_CANOPY_RADIUS;
+
+        for x in -2..2 {
+            let chunk = gen_chunk(&forest(x as u32), ChunkPos::new(x, 3));
+            let tops = tree_tops(&chunk);
+            assert!(!tops.is_empty());
+
+            for (x, _, z) in &tops {
+                assert!((margin..16 - margin).contains(x), "trunk at {x} {z}");
+                assert!((margin..16 - margin).contains(z), "trunk at {x} {z}");
+            }
+
+            for ((x, _, z), _) in blocks(&chunk).filter(|(_, b)| *b == BlockState::OAK_LEAVES) {
+                assert!(
+                    tops.iter()
+                        .any(|(tx, _, tz)| tx.abs_diff(x) <= margin && tz.abs_diff(z) <= margin),
+                    "leaves at {x} {z} without a tree"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tree_canopies_are_complete() {
+        let margin = TREE_CANOPY_RADIUS;
+        let chunk = gen_chunk(&forest(7), ChunkPos::new(0, 0));
+
+        for (x, top, z) in tree_tops(&chunk) {
+            let leaves = iproduct!(
+                x - margin..=x + margin,
+                top - margin..=top + margin,
+                z - margin..=z + margin
+            )
+            .filter(|(lx, ly, lz)| chunk.block_state(*lx, *ly, *lz) == BlockState::OAK_LEAVES)
+            .count();
+
+            // 81 blocks within 2.5 blocks of the top, minus the trunk.
+            assert_eq!(leaves, 78, "tree at {x} {z}");
+        }
+    }
+
+    #[test]
+    fn disabled_trees_are_not_generated() {
+        let mut state = forest(1);
+        state.settings.enable_trees = false;
+
+        assert!(tree_tops(&gen_chunk(&state, ChunkPos::new(0, 0))).is_empty());
+    }
+
+    /// Like `state`, but no grass or seagrass ever grows.
+    fn without_vegetation(state: &ChunkWorkerState) -> ChunkWorkerState {
+        let never = VegetationThresholds {
+            short: 2.0,
+            tall: 2.0,
+        };
+        let mut state = state.clone();
+        state.settings.grass_thresholds = never;
+        state.settings.seagrass_thresholds = never;
+        state
+    }
+
+    #[test]
+    fn decorating_surfaces_matches_scanning_the_whole_column() {
+        let state = state(3);
+        let bare = without_vegetation(&state);
+        let height = SECTION_COUNT * 16;
+        let mut decorated_columns = 0;
+
+        for (x, z) in iproduct!(0..16, 0..16).chain(iproduct!(400..416, -300..-284)) {
+            let mut decorated = vec![BlockState::AIR; height];
+            fill_column(&state, &mut decorated, x, z, None);
+
+            // Decorated like before the decoration pass was limited to the
+            // blocks above the surfaces.
+            let mut scanned = vec![BlockState::AIR; height];
+            fill_column(&bare, &mut scanned, x, z, None);
+            if scanned != decorated {
+                decorated_columns += 1;
+            }
+            decorate(&state, &mut scanned, x, z, (0..height - 1).rev());
+
+            assert_eq!(scanned, decorated, "column {x} {z}");
+        }
+
+        assert!(decorated_columns > 0);
+    }
+
+    #[test]
+    fn decoration_only_changes_the_blocks_above_surfaces() {
+        let state = state(3);
+        let bare = without_vegetation(&state);
+        let height = SECTION_COUNT * 16;
+
+        for (x, z) in iproduct!(0..16, 0..16) {
+            let mut column = vec![BlockState::AIR; height];
+            fill_column(&bare, &mut column, x, z, None);
+
+            let mut undecorated = column.clone();
+            decorate(&state, &mut undecorated, x, z, []);
+            assert_eq!(undecorated, column);
+
+            let surface = (0..height - 1)
+                .rev()
+                .find(|y| !column[*y].is_air() && !column[*y].is_liquid())
+                .unwrap();
+            let mut decorated = column.clone();
+            decorate(&state, &mut decorated, x, z, [surface]);
+
+            for y in (0..height).filter(|y| *y != surface + 1 && *y != surface + 2) {
+                assert_eq!(decorated[y], column[y], "column {x} {z} at y {y}");
+            }
+        }
+    }
+
+    fn caves_everywhere(seed: u32) -> ChunkWorkerState {
+        ChunkWorkerState::new(
+            seed,
+            TerrainSettings {
+                seed,
+                // The ridges are never below 0, so every block is a cave.
+                cave_threshold: -1.0,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn bedrock_and_blocks_below_liquids_are_not_carved() {
+        let state = caves_everywhere(1);
+        let mut column = vec![BlockState::AIR; 128];
+        column[..3].fill(BlockState::BEDROCK);
+        column[3..60].fill(BlockState::STONE);
+        column[31] = BlockState::BEDROCK;
+        column[20..23].fill(BlockState::LAVA);
+        column[10] = BlockState::DIRT;
+        column[60..70].fill(BlockState::WATER);
+
+        let before = column.clone();
+        carve_column(&state, &mut column, 0, 0);
+
+        for y in [0, 1, 2, 31] {
+            assert_eq!(column[y], BlockState::BEDROCK, "y {y}");
+        }
+        assert_eq!(column[20..23], before[20..23]);
+        assert_eq!(column[60..70], before[60..70]);
+        // Directly below the lava and the water.
+        assert_eq!(column[19], BlockState::STONE);
+        assert_eq!(column[59], BlockState::STONE);
+        // Everything else is carved.
+        assert!(column[3..19].iter().all(|b| b.is_air()));
+        assert!(column[32..59].iter().all(|b| b.is_air()));
+    }
+
+    #[test]
+    fn cave_floors_are_turned_into_stone() {
+        let state = caves_everywhere(1);
+
+        for floor in [
+            BlockState::DIRT,
+            BlockState::GRASS_BLOCK,
+            BlockState::SAND,
+            BlockState::GRAVEL,
+        ] {
+            let mut column = vec![floor, BlockState::STONE, BlockState::AIR];
+            carve_column(&state, &mut column, 0, 0);
+            assert_eq!(column[..2], [BlockState::STONE, BlockState::AIR]);
+        }
+
+        for floor in [BlockState::BEDROCK, BlockState::COAL_ORE] {
+            let mut column = vec![floor, BlockState::STONE, BlockState::AIR];
+            carve_column(&state, &mut column, 0, 0);
+            assert_eq!(column[..2], [floor, BlockState::AIR]);
+        }
+    }
+
+    #[test]
+    fn generated_caves_keep_bedrock_and_never_open_below_liquids() {
+        for seed in [1, 42, 1337] {
+            let state = ChunkWorkerState::new(
+                seed,
+                TerrainSettings {
+                    seed,
+                    cave_threshold: 0.6,
+                    ..Default::default()
+                },
+            );
+
+            for (x, z) in iproduct!((-64..64).step_by(9), (-64..64).step_by(9)) {
+                let mut column = vec![BlockState::AIR; SECTION_COUNT * 16];
+                fill_column(&state, &mut column, x, z, None);
+                let before = column.clone();
+                carve_column(&state, &mut column, x, z);
+
+                for y in 0..column.len() {
+                    if before[y] == BlockState::BEDROCK || before[y].is_liquid() {
+                        assert_eq!(column[y], before[y], "seed {seed} at {x} {y} {z}");
+                    }
+                    if y > 0 && column[y].is_liquid() {
+                        assert!(!column[y - 1].is_air() || before[y - 1].is_air());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Settings that differ from the defaults in the geology and elsewhere.
+    fn custom_settings() -> TerrainSettings {
+        TerrainSettings {
+            seed: 7,
+            water_height: 40,
+            enable_gravel: false,
+            enable_sand: false,
+            enable_stone: false,
+            sand_offset: -3,
+            stone_point_scaleing: 99.0,
+            tree_density: 0.5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn balanced_geology_restores_the_defaults() {
+        let mut settings = custom_settings();
+        GeologyPreset::Balanced.apply(&mut settings);
+
+        let defaults = TerrainSettings::default();
+        assert!(settings.enable_gravel && settings.enable_sand && settings.enable_stone);
+        assert_eq!(settings.gravel_height, defaults.gravel_height);
+        assert_eq!(settings.sand_height, defaults.sand_height);
+        assert_eq!(settings.sand_offset, defaults.sand_offset);
+        assert_eq!(settings.stone_point_scaleing, defaults.stone_point_scaleing);
+
+        let mut balanced_default = TerrainSettings::default();
+        GeologyPreset::Balanced.apply(&mut balanced_default);
+        assert_eq!(balanced_default, defaults);
+    }
+
+    #[test]
+    fn geology_presets_only_change_the_geology() {
+        for preset in GeologyPreset::ALL {
+            let original = custom_settings();
+            let mut settings = original.clone();
+            preset.apply(&mut settings);
+
+            let applied_twice = {
+                let mut settings = settings.clone();
+                preset.apply(&mut settings);
+                settings
+            };
+            assert_eq!(applied_twice, settings, "{}", preset.name());
+
+            // Putting the old geology back gives the original settings.
+            settings.enable_gravel = original.enable_gravel;
+            settings.enable_sand = original.enable_sand;
+            settings.enable_stone = original.enable_stone;
+            settings.gravel_height = original.gravel_height.clone();
+            settings.sand_height = original.sand_height.clone();
+            settings.sand_offset = original.sand_offset;
+            settings.stone_point_scaleing = original.stone_point_scaleing;
+            assert_eq!(settings, original, "{}", preset.name());
+        }
+    }
+
+    #[test]
+    fn geology_presets_are_distinct() {
+        let applied = GeologyPreset::ALL.map(|preset| {
+            let mut settings = TerrainSettings::default();
+            preset.apply(&mut settings);
+            settings
+        });
+
+        for (i, j) in iproduct!(0..3, 0..3).filter(|(i, j)| i < j) {
+            assert_ne!(applied[i], applied[j]);
+            assert_ne!(GeologyPreset::ALL[i].name(), GeologyPreset::ALL[j].name());
+        }
+
+        let [_, rocky, sandy] = applied;
+        assert!(sandy.sand_offset > rocky.sand_offset);
+        assert!(sandy.stone_point_scaleing > rocky.stone_point_scaleing);
+        assert_eq!(GeologyPreset::default(), GeologyPreset::Balanced);
+    }
+
+    #[test]
+    fn fbm_is_not_clamped_but_always_finite() {
+        let noise = SuperSimplex::new(1);
+        let points = || (0..100).map(|i| [f64::from(i) * 0.37, 0.0, f64::from(i) * 0.11]);
+
+        assert!(points()
+            .map(|p| fbm_raw(&noise, p, 2, 2.0, -0.9))
+            .any(|n| !(0.0..=1.0).contains(&n)));
+        for p in points() {
+            // No octaves and amplitudes that cancel out fall back to the middle.
+            assert_eq!(fbm_raw(&noise, p, 0, 2.0, 0.5), 0.5);
+            assert_eq!(fbm_raw(&noise, p, 2, 2.0, -1.0), 0.5);
+        }
+    }
+
+    #[test]
+    fn fbm_settings_are_validated() {
+        let valid = FBMSettings::default_gravel();
+        assert_eq!(valid.validate(), Ok(()));
+
+        let invalid = [
+            (
+                FBMSettings {
+                    octaves: 0,
+                    ..valid.clone()
+                },
+                "octaves must be at least 1",
+            ),
+            (
+                FBMSettings {
+                    lacunarity: 0.0,
+                    ..valid.clone()
+                },
+                "lacunarity must be a finite non-zero number",
+            ),
+            (
+                FBMSettings {
+                    lacunarity: f64::NAN,
+                    ..valid.clone()
+                },
+                "lacunarity must be a finite non-zero number",
+            ),
+            (
+                FBMSettings {
+                    point_scaleing: 0.0,
+                    ..valid.clone()
+                },
+                "point scaling must not be 0",
+            ),
+            (
+                FBMSettings {
+                    point_scaleing: f64::INFINITY,
+                    ..valid.clone()
+                },
+                "point scaling must not be 0",
+            ),
+            (
+                FBMSettings {
+                    persistence: f64::NAN,
+                    ..valid.clone()
+                },
+                "persistence must be a number",
+            ),
+        ];
+        for (settings, error) in invalid {
+            assert_eq!(settings.validate(), Err(error.to_string()), "{settings:?}");
+        }
+
+        // Negative values are fine.
+        let negative = FBMSettings {
+            lacunarity: -2.0,
+            persistence: -1.5,
+            ..valid
+        };
+        assert_eq!(negative.validate(), Ok(()));
+    }
+
+    #[test]
+    fn terrain_settings_name_the_invalid_noise() {
+        assert_eq!(TerrainSettings::default().validate(), Ok(()));
+
+        let mut settings = TerrainSettings::default();
+        settings.biomes.humidity.octaves = 0;
+        assert_eq!(
+            settings.validate(),
+            Err("Humidity: octaves must be at least 1".to_string())
+        );
+
+        let mut settings = TerrainSettings::default();
+        settings.sand_height.point_scaleing = 0.0;
+        assert_eq!(
+            settings.validate(),
+            Err("Sand: point scaling must not be 0".to_string())
+        );
+
+        let settings = TerrainSettings {
+            height_scale: -0.1,
+            ..Default::default()
+        };
+        assert!(settings.validate().unwrap_err().starts_with("Height scale"));
+    }
+
+    fn without_trees(seed: u32) -> ChunkWorkerState {
+        let mut state = state(seed);
+        state.settings.enable_trees = false;
+        state
+    }
+
+    #[test]
+    fn slices_match_the_layers_of_the_chunk_without_trees() {
+        for (seed, pos) in [(1, ChunkPos::new(0, 0)), (42, ChunkPos::new(-3, 5))] {
+            let state = without_trees(seed);
+            let chunk = gen_chunk(&state, pos);
+
+            for y in [0, 1, 30, 63, 64, 65, 80, 120, SECTION_COUNT * 16 - 1] {
+                let slice = gen_slice(&state, pos, SliceHeight::Y(y));
+                assert_eq!(slice.len(), 16 * 16);
+
+                for (offset_z, offset_x) in iproduct!(0..16, 0..16) {
+                    assert_eq!(
+                        slice[offset_z * 16 + offset_x],
+                        chunk.block_state(offset_x, y, offset_z),
+                        "seed {seed}, {pos:?}, block ({offset_x}, {y}, {offset_z})"
+                    );
+                }
+            }
+
+            let surface = gen_slice(&state, pos, SliceHeight::Surface);
+            for (offset_z, offset_x) in iproduct!(0..16, 0..16) {
+                let top = (0..SECTION_COUNT * 16)
+                    .rev()
+                    .map(|y| chunk.block_state(offset_x, y, offset_z))
+                    .find(|block| !block.is_air())
+                    .unwrap_or(BlockState::AIR);
+                assert_eq!(surface[offset_z * 16 + offset_x], top);
+            }
+        }
+    }
+
+    #[test]
+    fn slices_above_the_chunk_are_air() {
+        let state = without_trees(1);
+        let slice = gen_slice(
+            &state,
+            ChunkPos::new(0, 0),
+            SliceHeight::Y(SECTION_COUNT * 16),
+        );
+
+        assert!(slice.iter().all(|block| block.is_air()));
+    }
+
+    #[test]
+    fn height_map_at_scale_one_is_the_terrain_heights() {
+        for (seed, pos) in [(1, ChunkPos::new(0, 0)), (7, ChunkPos::new(4, -2))] {
+            let state = state(seed);
+            let heights = terrain_heights(&state, pos, SECTION_COUNT * 16);
+            let expected = heights.map(|row| row.map(|height| height as u16));
+
+            assert_eq!(gen_chunk_at_scale(&state, pos, 1), expected);
+            // A scale of 0 is treated as 1.
+            assert_eq!(gen_chunk_at_scale(&state, pos, 0), expected);
+        }
+    }
+
+    #[test]
+    fn height_map_samples_the_center_of_every_cell() {
+        let state = state(3);
+        let pos = ChunkPos::new(1, -1);
+        let map = gen_chunk_at_scale(&state, pos, 4);
+
+        for (cell_z, cell_x) in iproduct!(0..16, 0..16) {
+            let x = pos.x * 16 + cell_x as i32 * 4 + 2;
+            let z = pos.z * 16 + cell_z as i32 * 4 + 2;
+
+            assert_eq!(
+                i32::from(map[cell_z][cell_x]),
+                terrain_height(&state, x, z, SECTION_COUNT * 16)
+            );
+        }
+    }
+
+    /// Seeds and height scales the generation paths are compared with.
+    fn terrain_variants() -> impl Iterator<Item = ChunkWorkerState> {
+        iproduct!([0, 42, u32::MAX], [0.0, 0.5, 1.0, 2.0]).map(|(seed, height_scale)| {
+            ChunkWorkerState::new(
+                seed,
+                TerrainSettings {
+                    seed,
+                    height_scale,
+                    ..Default::default()
+                },
+            )
+        })
+    }
+
+    fn assert_same_blocks(a: &Chunk, b: &Chunk, settings: &TerrainSettings) {
+        for ((pos, a), (_, b)) in blocks(a).zip(blocks(b)) {
+            assert_eq!(
+                a, b,
+                "block {pos:?}, seed {}, height scale {}",
+                settings.seed, settings.height_scale
+            );
+        }
+    }
+
+    #[test]
+    fn cached_heights_match_sampling_every_block() {
+        let pos = ChunkPos::new(-1, 2);
+
+        for state in terrain_variants() {
+            let mut per_block = Chunk::new(SECTION_COUNT);
+            for (offset_z, offset_x) in iproduct!(0..16, 0..16) {
+                let x = offset_x as i32 + pos.x * 16;
+                let z = offset_z as i32 + pos.z * 16;
+                gen_block(&state, &mut per_block, x, z, offset_x, offset_z);
+            }
+            gen_caves(&state, &mut per_block, pos);
+            gen_trees(&state, &mut per_block, pos);
+            assign_biomes(&state, &mut per_block, pos);
+
+            assert_same_blocks(
+                &gen_chunk_sequential(&state, pos),
+                &per_block,
+                &state.settings,
+            );
+        }
+    }
+
+    #[test]
+    fn parallel_generation_matches_sequential_generation() {
+        for pos in [ChunkPos::new(0, 0), ChunkPos::new(-1, 2)] {
+            for state in terrain_variants() {
+                assert_same_blocks(
+                    &gen_chunk_parallel(&state, pos),
+                    &gen_chunk_sequential(&state, pos),
+                    &state.settings,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unset_feature_seeds_are_offsets_of_the_main_seed() {
+        assert_eq!(FeatureSeeds::resolve(None, 10, 3), 13);
+        assert_eq!(FeatureSeeds::resolve(None, u32::MAX, 2), 1);
+        assert_eq!(FeatureSeeds::resolve(Some(7), 10, 3), 7);
+    }
+
+    #[test]
+    fn feature_seed_fields_are_named_and_writable() {
+        let mut seeds = FeatureSeeds::default();
+        for (i, (_, seed, _)) in seeds.fields_mut().into_iter().enumerate() {
+            *seed = Some(i as u32);
+        }
+
+        let expected = FeatureSeeds {
+            density: Some(0),
+            hilly: Some(1),
+            stone: Some(2),
+            gravel: Some(3),
+            grass: Some(4),
+            coal: Some(5),
+            iron: Some(6),
+            gold: Some(7),
+            redstone: Some(8),
+            diamond: Some(9),
+            temperature: Some(10),
+            humidity: Some(11),
+            caves: Some(12),
+            seagrass: Some(13),
+        };
+        assert_eq!(seeds, expected);
+
+        let names = seeds
+            .fields_mut()
+            .into_iter()
+            .map(|(name, ..)| name)
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(names.len(), 14);
+    }
+
+    #[test]
+    fn feature_seeds_matching_the_derived_seeds_change_nothing() {
+        let seed = 1234;
+        let mut derived = TerrainSettings {
+            seed,
+            ..Default::default()
+        };
+        for (_, feature, offset) in derived.seeds.fields_mut() {
+            *feature = Some(FeatureSeeds::resolve(None, seed, offset));
+        }
+
+        let pos = ChunkPos::new(2, -1);
+        let chunk = gen_chunk(&state(seed), pos);
+        let with_seeds = gen_chunk(&ChunkWorkerState::new(seed, derived), pos);
+
+        for ((pos, a), (_, b)) in blocks(&chunk).zip(blocks(&with_seeds)) {
+            assert_eq!(a, b, "block {pos:?}");
+        }
+    }
+
+    #[test]
+    fn paused_workers_defer_requests() {
+        let (message_sender, receiver) = flume::unbounded();
+        let (sender, responses) = flume::unbounded();
+        let worker = Arc::new(Mutex::new(ChunkWorker {
+            sender,
+            receiver,
+            cache: TrackedLruCache::new(NonZeroUsize::new(16).unwrap(), false),
+            state: state(1),
+            settings_hash: 0,
+            saving: false,
+            paused: false,
+            deferred: vec![],
+            deferred_dimensions: vec![],
+            deferred_flush: false,
+            prewarmed: HashSet::new(),
+            dimensions: HashMap::new(),
+        }));
+
+        for message in [
+            WorkerMessage::Pause,
+            WorkerMessage::Chunk(ChunkPos::new(1, 2)),
+            WorkerMessage::BatchChunks(vec![ChunkPos::new(3, 4), ChunkPos::new(5, 6)]),
+            WorkerMessage::DimensionChunk("nether".to_string(), ChunkPos::new(7, 8)),
+            WorkerMessage::FlushAll,
+        ] {
+            message_sender.send(message).unwrap();
+        }
+        // The worker stops once every message was handled.
+        drop(message_sender);
+        chunk_worker(worker.clone(), "test".to_string()).unwrap();
+
+        let w = worker.lock().unwrap();
+        assert!(w.paused);
+        assert_eq!(
+            w.deferred,
+            [
+                ChunkPos::new(1, 2),
+                ChunkPos::new(3, 4),
+                ChunkPos::new(5, 6)
+            ]
+        );
+        assert_eq!(
+            w.deferred_dimensions,
+            [("nether".to_string(), ChunkPos::new(7, 8))]
+        );
+        assert!(w.deferred_flush);
+        assert!(responses.is_empty());
+    }
+
+    #[test]
+    fn water_height_defaults_to_the_world_config() {
+        assert_eq!(WorldConfig::default().water_height, 120);
+
+        let config = WorldConfig {
+            water_height: 64,
+            ..Default::default()
+        };
+        assert_eq!(TerrainSettings::from_config(&config).water_height, 64);
+    }
+
+    #[test]
+    fn chunks_below_water_height_zero_contain_no_water() {
+        let has_water = |water_height| {
+            let settings = TerrainSettings {
+                water_height,
+                ..Default::default()
+            };
+            let chunk = gen_chunk(&ChunkWorkerState::new(1, settings), ChunkPos::new(0, 0));
+
+            iproduct!(0..16, 0..chunk.section_count() * 16, 0..16)
+                .any(|(x, y, z)| chunk.block_state(x, y, z) == BlockState::WATER)
+        };
+
+        assert!(!has_water(0));
+        // Everything that isn't terrain is flooded.
+        assert!(has_water(383));
+    }
+
+    #[test]
+    fn equal_settings_hash_equally() {
+        let settings = TerrainSettings::default();
+
+        assert_eq!(settings.content_hash(), settings.clone().content_hash());
+        assert_eq!(
+            settings.content_hash(),
+            TerrainSettings::default().content_hash()
+        );
+        assert_eq!(settings.fingerprint(), settings.clone().fingerprint());
+    }
+
+    #[test]
+    fn changed_settings_change_the_hash() {
+        let settings = TerrainSettings::default();
+        let changed = [
+            TerrainSettings {
+                seed: settings.seed.wrapping_add(1),
+                ..settings.clone()
+            },
+            TerrainSettings {
+                water_height: settings.water_height + 1,
+                ..settings.clone()
+            },
+            TerrainSettings {
+                enable_caves: !settings.enable_caves,
+                ..settings.clone()
+            },
+            TerrainSettings {
+                height_scale: settings.height_scale + 0.01,
+                ..settings.clone()
+            },
+            TerrainSettings {
+                diamond_ore: OreSettings {
+                    max_y: settings.diamond_ore.max_y + 1,
+                    ..settings.diamond_ore.clone()
+                },
+                ..settings.clone()
+            },
+            TerrainSettings {
+                seeds: FeatureSeeds {
+                    coal: Some(3),
+                    ..Default::default()
+                },
+                ..settings.clone()
+            },
+        ];
+
+        let mut hashes = changed
+            .iter()
+            .map(TerrainSettings::content_hash)
+            .collect::<Vec<_>>();
+        hashes.push(settings.content_hash());
+        hashes.sort_unstable();
+        hashes.dedup();
+        assert_eq!(hashes.len(), changed.len() + 1);
+
+        for other in &changed {
+            assert_ne!(other.fingerprint(), settings.fingerprint());
+        }
+    }
+
+    /// Number of grass and seagrass blocks on the surfaces of a patch of
+    /// columns.
+    fn vegetation(state: &ChunkWorkerState) -> (usize, usize) {
+        let is = |block: BlockState, kinds: [BlockState; 2]| {
+            kinds.iter().any(|kind| kind.to_kind() == block.to_kind())
+        };
+        let (mut grass, mut seagrass) = (0, 0);
+
+        for (x, z) in iproduct!((0..512).step_by(16), (0..512).step_by(16)) {
+            let mut column = vec![BlockState::AIR; SECTION_COUNT * 16];
+            fill_column(state, &mut column, x, z, None);
+
+            for block in column {
+                if is(block, [BlockState::GRASS, BlockState::TALL_GRASS]) {
+                    grass += 1;
+                } else if is(block, [BlockState::SEAGRASS, BlockState::TALL_SEAGRASS]) {
+                    seagrass += 1;
+                }
+            }
+        }
+
+        (grass, seagrass)
+    }
+
+    #[test]
+    fn seagrass_thresholds_do_not_affect_grass() {
+        let state = state(5);
+        let (grass, seagrass) = vegetation(&state);
+        assert!(grass > 0);
+
+        let mut dense = state.clone();
+        dense.settings.seagrass_thresholds = VegetationThresholds {
+            short: -1.0,
+            tall: 1.0,
+        };
+        let (dense_grass, dense_seagrass) = vegetation(&dense);
+        assert_eq!(dense_grass, grass);
+        assert!(dense_seagrass > seagrass);
+
+        let mut bare = state.clone();
+        bare.settings.seagrass_thresholds = VegetationThresholds {
+            short: 2.0,
+            tall: 2.0,
+        };
+        assert_eq!(vegetation(&bare), (grass, 0));
+    }
+
+    #[test]
+    fn grass_thresholds_do_not_affect_seagrass() {
+        let mut state = state(5);
+        state.settings.seagrass_thresholds.short = -1.0;
+        let (_, seagrass) = vegetation(&state);
+
+        state.settings.grass_thresholds = VegetationThresholds {
+            short: 2.0,
+            tall: 2.0,
+        };
+        assert_eq!(vegetation(&state), (0, seagrass));
+    }
+
+    #[test]
+    fn invalid_vegetation_thresholds_are_rejected() {
+        assert_eq!(VegetationThresholds::default().validate(), Ok(()));
+        assert_eq!(
+            VegetationThresholds {
+                short: -1.5,
+                tall: 0.5
+            }
+            .validate(),
+            Err("thresholds have to be between -1 and 1".to_string())
+        );
+        assert_eq!(
+            VegetationThresholds {
+                short: 0.8,
+                tall: 0.5
+            }
+            .validate(),
+            Err("the tall threshold can't be lower than the short one".to_string())
+        );
+
+        let settings = TerrainSettings {
+            seagrass_thresholds: VegetationThresholds {
+                short: 0.8,
+                tall: 0.5,
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.validate(),
+            Err("Seagrass: the tall threshold can't be lower than the short one".to_string())
+        );
+    }
+
+    #[test]
+    fn chunk_worker_count_defaults_to_the_limit() {
+        assert_eq!(chunk_worker_count(8, 4, None), 4);
+        assert_eq!(chunk_worker_count(2, 16, None), 2);
+        // There is always at least one worker.
+        assert_eq!(chunk_worker_count(0, 4, None), 1);
+        assert_eq!(chunk_worker_count(8, 0, None), 1);
+    }
+
+    #[test]
+    fn configured_chunk_worker_count_is_clamped() {
+        assert_eq!(chunk_worker_count(8, 4, Some(0)), 1);
+        assert_eq!(chunk_worker_count(8, 4, Some(3)), 3);
+        assert_eq!(chunk_worker_count(8, 4, Some(4)), 4);
+        assert_eq!(chunk_worker_count(8, 4, Some(64)), 4);
+        assert_eq!(chunk_worker_count(2, 16, Some(usize::MAX)), 2);
+        assert_eq!(chunk_worker_count(0, 0, Some(5)), 1);
+    }
+}
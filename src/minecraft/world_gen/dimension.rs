@@ -0,0 +1,97 @@
+use valence::{
+    dimension::{Dimension, DimensionEffects, DimensionId},
+    server::Server,
+};
+
+use super::{biome::BiomeSettings, chunk_worker::TerrainSettings};
+use crate::config::DimensionType;
+
+impl DimensionType {
+    pub const ALL: [DimensionType; 3] = [
+        DimensionType::Overworld,
+        DimensionType::Nether,
+        DimensionType::End,
+    ];
+
+    #[must_use]
+    pub fn dimension(self) -> Dimension {
+        match self {
+            DimensionType::Overworld => Dimension::default(),
+            DimensionType::Nether => Dimension {
+                natural: false,
+                ambient_light: 0.1,
+                fixed_time: Some(18000),
+                effects: DimensionEffects::TheNether,
+                ..Default::default()
+            },
+            DimensionType::End => Dimension {
+                natural: false,
+                fixed_time: Some(6000),
+                effects: DimensionEffects::TheEnd,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Terrain of the dimension type with the given seed. The nether is bare
+    /// stone with wide caves and the end low stone islands without water, the
+    /// overworld uses the default settings.
+    #[must_use]
+    pub fn terrain_settings(self, seed: u32) -> TerrainSettings {
+        let defaults = TerrainSettings {
+            seed,
+            ..Default::default()
+        };
+
+        match self {
+            DimensionType::Overworld => defaults,
+            DimensionType::Nether => TerrainSettings {
+                enable_grass: false,
+                enable_water: false,
+                enable_trees: false,
+                enable_caves: true,
+                cave_threshold: 0.8,
+                biomes: BiomeSettings {
+                    enable_biomes: false,
+                    ..defaults.biomes
+                },
+                ..defaults
+            },
+            DimensionType::End => TerrainSettings {
+                enable_gravel: false,
+                enable_sand: false,
+                enable_grass: false,
+                enable_water: false,
+                enable_trees: false,
+                enable_ores: false,
+                enable_caves: false,
+                height_scale: 0.4,
+                biomes: BiomeSettings {
+                    enable_biomes: false,
+                    ..defaults.biomes
+                },
+                ..defaults
+            },
+        }
+    }
+}
+
+/// All dimension types, registered with the server in the order of
+/// [`DimensionType::ALL`].
+#[must_use]
+pub fn dimensions() -> Vec<Dimension> { DimensionType::ALL.map(DimensionType::dimension).to_vec() }
+
+/// Id the server assigned to the dimension type, the default dimension if it
+/// isn't registered.
+#[must_use]
+pub fn dimension_id(server: &Server, dimension: DimensionType) -> DimensionId {
+    let index = DimensionType::ALL
+        .iter()
+        .position(|d| *d == dimension)
+        .unwrap();
+
+    server
+        .dimensions()
+        .nth(index)
+        .map_or_else(DimensionId::default, |(id, _)| id)
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use valence::prelude::*;
+
+use super::{
+    chunk_worker::{gen_chunk, ChunkWorkerState, TerrainSettings},
+    find_spawn, pregen_positions,
+};
+use crate::config::PregenShape;
+
+/// A small generated world that only lives in memory.
+///
+/// Unlike the world created by the [`WorldGenPlugin`](super::WorldGenPlugin)
+/// nothing is loaded from or saved to disk and no chunk workers are started,
+/// chunks are generated on the calling thread. Useful to test systems that
+/// need some terrain without waiting for a full pregeneration.
+pub struct TestWorld {
+    pub state: ChunkWorkerState,
+    pub chunks: HashMap<ChunkPos, Chunk>,
+    pub spawn: DVec3,
+}
+
+impl TestWorld {
+    /// Generates all chunks within `radius` chunks of the origin with the
+    /// default terrain settings.
+    #[must_use]
+    pub fn new(seed: u32, radius: i32) -> Self {
+        let settings = TerrainSettings {
+            seed,
+            ..Default::default()
+        };
+
+        Self::with_settings(settings, radius)
+    }
+
+    #[must_use]
+    pub fn with_settings(settings: TerrainSettings, radius: i32) -> Self {
+        let state = ChunkWorkerState::new(settings.seed, settings);
+
+        let chunks: HashMap<_, _> = pregen_positions(&PregenShape::Square { radius })
+            .map(|(x, z)| {
+                let pos = ChunkPos::new(x, z);
+                (pos, gen_chunk(&state, pos))
+            })
+            .collect();
+
+        let spawn = chunks
+            .get(&ChunkPos::new(0, 0))
+            .and_then(find_spawn)
+            .unwrap_or(DVec3::new(0.0, 200.0, 0.0));
+
+        Self {
+            state,
+            chunks,
+            spawn,
+        }
+    }
+
+    /// Returns the chunk at `pos`, generating it if it isn't part of the world
+    /// yet.
+    pub fn chunk(&mut self, pos: ChunkPos) -> &Chunk {
+        let state = &self.state;
+        self.chunks
+            .entry(pos)
+            .or_insert_with(|| gen_chunk(state, pos))
+    }
+
+    /// Inserts all chunks of the world into the instance.
+    pub fn insert_into(&self, instance: &mut Instance) {
+        for (pos, chunk) in &self.chunks {
+            instance.insert_chunk(*pos, chunk.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::iproduct;
+
+    use super::*;
+    use crate::SECTION_COUNT;
+
+    fn same_blocks(a: &Chunk, b: &Chunk) -> bool {
+        iproduct!(0..16, 0..SECTION_COUNT * 16, 0..16)
+            .all(|(x, y, z)| a.block_state(x, y, z) == b.block_state(x, y, z))
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_world() {
+        let a = TestWorld::new(42, 1);
+        let b = TestWorld::new(42, 1);
+
+        assert_eq!(a.chunks.len(), 9);
+        assert_eq!(a.chunks.len(), b.chunks.len());
+        assert_eq!(a.spawn, b.spawn);
+        for (pos, chunk) in &a.chunks {
+            assert!(same_blocks(chunk, &b.chunks[pos]), "chunk {pos:?} differs");
+        }
+    }
+
+    #[test]
+    fn chunks_outside_the_radius_are_generated_on_demand() {
+        let mut world = TestWorld::new(42, 0);
+        let pos = ChunkPos::new(3, -2);
+        assert!(!world.chunks.contains_key(&pos));
+
+        let expected = gen_chunk(&world.state, pos);
+        assert!(same_blocks(world.chunk(pos), &expected));
+        assert!(world.chunks.contains_key(&pos));
+    }
+
+    #[test]
+    fn different_seeds_generate_different_worlds() {
+        let a = TestWorld::new(1, 0);
+        let b = TestWorld::new(2, 0);
+        let origin = ChunkPos::new(0, 0);
+
+        assert!(!same_blocks(&a.chunks[&origin], &b.chunks[&origin]));
+    }
+}
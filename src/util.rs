@@ -1,4 +1,7 @@
-use std::sync::{LockResult};
+use std::{fs, path::Path, sync::LockResult, time::Duration};
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
 
 /// Extension methods for [`LockResult`].
 ///
@@ -17,3 +20,100 @@ impl<Guard> LockResultExt for LockResult<Guard> {
 
     fn ignore_poison(self) -> Guard { self.unwrap_or_else(std::sync::PoisonError::into_inner) }
 }
+
+/// Formats a duration as a human readable string, e.g. `1h 2m 3s`.
+#[must_use]
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes, seconds) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+
+    let mut parts = vec![];
+    if days > 0 {
+        parts.push(format!("{days}d"));
+    }
+    if hours > 0 || !parts.is_empty() {
+        parts.push(format!("{hours}h"));
+    }
+    if minutes > 0 || !parts.is_empty() {
+        parts.push(format!("{minutes}m"));
+    }
+    parts.push(format!("{seconds}s"));
+
+    parts.join(" ")
+}
+
+/// Reads a json file, returns `None` if it doesn't exist.
+///
+/// # Errors
+/// - the file can't be read
+/// - the file isn't valid json for `T`
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+}
+
+/// Writes `value` as pretty printed json, creates the parent directories if
+/// they don't exist.
+///
+/// # Errors
+/// - the directories or the file can't be written
+pub fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_are_formatted_from_the_largest_unit() {
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h 2m 3s");
+        assert_eq!(format_duration(Duration::from_secs(93784)), "1d 2h 3m 4s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m 5s");
+    }
+
+    #[test]
+    fn zero_units_are_only_skipped_at_the_start() {
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1h 0m 0s");
+        assert_eq!(format_duration(Duration::from_secs(86405)), "1d 0h 0m 5s");
+    }
+
+    #[test]
+    fn fractions_of_seconds_are_dropped() {
+        assert_eq!(format_duration(Duration::from_millis(59_999)), "59s");
+    }
+
+    #[test]
+    fn json_files_round_trip() {
+        let dir = std::env::temp_dir().join(format!("vp-util-json-{}", std::process::id()));
+        let path = dir.join("nested").join("values.json");
+
+        assert_eq!(read_json::<Vec<u32>>(&path).unwrap(), None);
+
+        write_json(&path, &[1, 2, 3][..]).unwrap();
+        assert_eq!(read_json::<Vec<u32>>(&path).unwrap(), Some(vec![1, 2, 3]));
+
+        // Writing again replaces the whole file.
+        write_json(&path, &[4][..]).unwrap();
+        assert_eq!(read_json::<Vec<u32>>(&path).unwrap(), Some(vec![4]));
+
+        fs::write(&path, "not json").unwrap();
+        assert!(read_json::<Vec<u32>>(&path).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
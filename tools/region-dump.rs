@@ -0,0 +1,22 @@
+//! Prints a `.region` file as JSON, see `Region::to_json_writer`.
+//!
+//! Usage: `cargo r --bin region-dump -- world/0_0.region > 0_0.json`
+
+use std::{io, path::PathBuf, process};
+
+use valence_playground::minecraft::save::read_region_file;
+
+fn main() {
+    let Some(path) = std::env::args_os().nth(1).map(PathBuf::from) else {
+        eprintln!("Usage: region-dump <file.region>");
+        process::exit(2);
+    };
+
+    let result = read_region_file(&path)
+        .and_then(|region| region.to_json_writer(io::BufWriter::new(io::stdout().lock())));
+
+    if let Err(e) = result {
+        eprintln!("Unable to dump {}: {e}", path.display());
+        process::exit(1);
+    }
+}